@@ -1,15 +1,37 @@
 extern crate cbindgen;
-use std::env;
+use std::{env, fs};
+
+// P_ABI_VERSION is defined once, in src/core/version.rs,
+// and read from there for the SONAME
+fn abi_version (crate_dir: &str) -> u32 {
+    let source = fs::read_to_string(format!("{}/src/core/version.rs", crate_dir))
+        .expect("Unable to read src/core/version.rs");
+    source
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("pub const P_ABI_VERSION: u32 ="))
+        .and_then(|value| value.trim().trim_end_matches(';').trim().parse().ok())
+        .expect("Unable to find P_ABI_VERSION in src/core/version.rs")
+}
 
 fn main() {
     let crate_dir = env!("CARGO_MANIFEST_DIR");
+    let abi_version = abi_version(crate_dir);
 
     println!("{}", crate_dir);
 
+    // Give libprocessing.so a versioned SONAME so that programs linked
+    // against one ABI version refuse to load an incompatible one.
+    // Symbols coming from dependencies (wgpu exports a few C functions of its own)
+    // are hidden, so only our #[no_mangle] functions end up in the dynamic symbol table
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("linux") {
+        println!("cargo:rustc-cdylib-link-arg=-Wl,-soname,libprocessing.so.{}", abi_version);
+        println!("cargo:rustc-cdylib-link-arg=-Wl,--exclude-libs,ALL");
+    }
+
     cbindgen::Builder::new()
       .with_crate(crate_dir)
       .with_language(cbindgen::Language::C)
       .generate()
       .expect("Unable to generate bindings")
       .write_to_file("../test-c/bindings.h");
-}
\ No newline at end of file
+}
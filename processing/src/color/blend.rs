@@ -0,0 +1,150 @@
+use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+
+use crate::color::PColor;
+
+// How shapes are combined with what is already in the canvas.
+// Set with blendMode(), the modes match Processing's
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub enum PBlendMode {
+    // Linear interpolation of colors by the alpha of the shape
+    #[default]
+    BlendBlend,
+    // Additive blending with white clip
+    BlendAdd,
+    // Subtractive blending with black clip
+    BlendSubtract,
+    // Darkens by multiplying the colors, mixed in by the alpha of the
+    // shape. Custom shaders have to output colors multiplied by alpha
+    BlendMultiply,
+    BlendScreen,
+    // Only the lightest color succeeds
+    BlendLightest,
+    // Only the darkest color succeeds
+    BlendDarkest,
+    // The pixels are replaced by the shape's, alpha included
+    BlendReplace
+}
+
+impl PBlendMode {
+    pub const ALL: [PBlendMode; 8] = [
+        PBlendMode::BlendBlend,
+        PBlendMode::BlendAdd,
+        PBlendMode::BlendSubtract,
+        PBlendMode::BlendMultiply,
+        PBlendMode::BlendScreen,
+        PBlendMode::BlendLightest,
+        PBlendMode::BlendDarkest,
+        PBlendMode::BlendReplace
+    ];
+
+    // Whether the shape shader multiplies colors by their alpha for this
+    // mode. Multiply needs it to mix in the product by the shape's alpha
+    pub fn premultiplied (self) -> bool {
+        self == PBlendMode::BlendMultiply
+    }
+
+    // Colors coming out of the shape shader aren't premultiplied, apart
+    // from the modes that ask for it (see premultiplied()). Apart from Replace,
+    // alpha is always composited the same way, so an opaque canvas stays
+    // opaque whatever the mode
+    pub fn blend_state (self) -> BlendState {
+        let color = |src_factor, dst_factor, operation| BlendComponent { src_factor, dst_factor, operation };
+        let alpha = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::OneMinusSrcAlpha,
+            operation: BlendOperation::Add
+        };
+
+        let color = match self {
+            PBlendMode::BlendBlend => color(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha, BlendOperation::Add),
+            PBlendMode::BlendAdd => color(BlendFactor::SrcAlpha, BlendFactor::One, BlendOperation::Add),
+            PBlendMode::BlendSubtract => color(BlendFactor::SrcAlpha, BlendFactor::One, BlendOperation::ReverseSubtract),
+            PBlendMode::BlendMultiply => color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha, BlendOperation::Add),
+            PBlendMode::BlendScreen => color(BlendFactor::OneMinusDst, BlendFactor::One, BlendOperation::Add),
+            // Min and max ignore the factors, but they have to be One
+            PBlendMode::BlendLightest => color(BlendFactor::One, BlendFactor::One, BlendOperation::Max),
+            PBlendMode::BlendDarkest => color(BlendFactor::One, BlendFactor::One, BlendOperation::Min),
+            PBlendMode::BlendReplace => return BlendState::REPLACE
+        };
+
+        BlendState { color, alpha }
+    }
+
+    // Blends two colors on the CPU the way blend_state() does on the GPU,
+    // for blend() between images. source is drawn over destination
+    pub fn blend_colors (self, source: PColor, destination: PColor) -> PColor {
+        if self == PBlendMode::BlendReplace { return source; }
+
+        let (s, d, a) = (source, destination, source.a);
+        let channel = |s: f32, d: f32| match self {
+            PBlendMode::BlendBlend => s * a + d * (1.0 - a),
+            PBlendMode::BlendAdd => d + s * a,
+            PBlendMode::BlendSubtract => d - s * a,
+            PBlendMode::BlendMultiply => d * s * a + d * (1.0 - a),
+            PBlendMode::BlendScreen => s * (1.0 - d) + d,
+            PBlendMode::BlendLightest => s.max(d),
+            PBlendMode::BlendDarkest => s.min(d),
+            PBlendMode::BlendReplace => s
+        }.clamp(0.0, 1.0);
+
+        PColor {
+            r: channel(s.r, d.r),
+            g: channel(s.g, d.g),
+            b: channel(s.b, d.b),
+            a: (a + d.a * (1.0 - a)).clamp(0.0, 1.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What the GPU makes of blend_state() for one color channel
+    fn gpu_channel (mode: PBlendMode, s: f32, d: f32, a: f32) -> f32 {
+        let state = mode.blend_state();
+        let s = if mode.premultiplied() { s * a } else { s };
+        let factor = |factor| match factor {
+            BlendFactor::Zero => 0.0,
+            BlendFactor::One => 1.0,
+            BlendFactor::Src => s,
+            BlendFactor::SrcAlpha => a,
+            BlendFactor::OneMinusSrcAlpha => 1.0 - a,
+            BlendFactor::Dst => d,
+            BlendFactor::OneMinusDst => 1.0 - d,
+            factor => panic!("{:?} isn't simulated", factor)
+        };
+        let (source, destination) = (s * factor(state.color.src_factor), d * factor(state.color.dst_factor));
+        match state.color.operation {
+            BlendOperation::Add => source + destination,
+            BlendOperation::ReverseSubtract => destination - source,
+            BlendOperation::Min => s.min(d),
+            BlendOperation::Max => s.max(d),
+            operation => panic!("{:?} isn't simulated", operation)
+        }.clamp(0.0, 1.0)
+    }
+
+    #[test]
+    fn cpu_blending_matches_the_gpu () {
+        let destination = PColor { r: 0.8, g: 0.5, b: 0.2, a: 1.0 };
+        for mode in PBlendMode::ALL.into_iter().filter(|mode| *mode != PBlendMode::BlendReplace) {
+            for a in [0.0, 0.25, 1.0] {
+                let source = PColor { r: 0.5, g: 1.0, b: 0.0, a };
+                let blended = mode.blend_colors(source, destination);
+                for (s, d, channel) in [(source.r, destination.r, blended.r), (source.g, destination.g, blended.g), (source.b, destination.b, blended.b)] {
+                    assert!((gpu_channel(mode, s, d, a) - channel).abs() < 1e-6, "{:?} with alpha {}", mode, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multiply_is_mixed_in_by_alpha () {
+        let destination = PColor { r: 0.8, g: 0.8, b: 0.8, a: 1.0 };
+        let multiply = |a| PBlendMode::BlendMultiply.blend_colors(PColor { r: 0.5, g: 0.5, b: 0.5, a }, destination).r;
+        assert_eq!(multiply(0.0), 0.8);
+        assert!((multiply(0.5) - 0.6).abs() < 1e-6);
+        assert!((multiply(1.0) - 0.4).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,47 @@
+use lazy_static::lazy_static;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::color::{blend::PBlendMode, PColor};
+
+// Colors used by the drawing functions. None means
+// noFill() / noStroke() has been called
+pub struct ColorState {
+    pub fill: Option<PColor>,
+    pub stroke: Option<PColor>,
+    pub blend_mode: PBlendMode,
+}
+
+impl Default for ColorState {
+    fn default () -> Self {
+        Self {
+            fill: Some(PColor::WHITE),
+            stroke: Some(PColor::BLACK),
+            blend_mode: PBlendMode::BlendBlend
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref COLOR_STATE: Arc<RwLock<ColorState>> = Arc::new(RwLock::new(ColorState::default()));
+}
+
+pub fn get_color_state () -> RwLockReadGuard<'static, ColorState> {
+    COLOR_STATE.try_read().unwrap()
+}
+
+macro_rules! set_color_state {
+
+    // base cases
+    ($var:ident$(.$var2:ident)* = $value:expr;) => {
+        {
+            crate::color::color_state::COLOR_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)* = $value;
+        }
+    };
+
+    // expr - expr
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_color_state!{ $var$(.$var2)* = $value; };
+        set_color_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+}
+pub(crate) use set_color_state;
@@ -0,0 +1,120 @@
+use crate::{color::{blend::PBlendMode, color_state::set_color_state}, record::record_background, renderer::{format::PCanvasFormat, state::set_renderer_state}};
+
+pub mod blend;
+pub mod color_state;
+
+// A color with components between 0 and 1.
+// Components are sRGB encoded, like the values passed to fill()
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct PColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+pub(crate) fn srgb_to_linear (value: f32) -> f32 {
+    if value <= 0.04045 { value / 12.92 }
+    else { ((value + 0.055) / 1.055).powf(2.4) }
+}
+
+pub(crate) fn linear_to_srgb (value: f32) -> f32 {
+    if value <= 0.0031308 { value * 12.92 }
+    else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+impl PColor {
+    pub const WHITE: PColor = PColor { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    pub const BLACK: PColor = PColor { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const TRANSPARENT: PColor = PColor { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+    // Processing's default background
+    pub const DEFAULT_BACKGROUND: PColor = PColor { r: 0.8, g: 0.8, b: 0.8, a: 1.0 };
+
+    // Takes components between 0 and 255, like the C functions
+    pub fn from_rgba (r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self {
+            r: (r / 255.0).clamp(0.0, 1.0),
+            g: (g / 255.0).clamp(0.0, 1.0),
+            b: (b / 255.0).clamp(0.0, 1.0),
+            a: (a / 255.0).clamp(0.0, 1.0)
+        }
+    }
+
+    // Colors packed as 0xAARRGGBB, like Processing's color
+    // type and the values in pixels[]
+    pub fn from_argb (argb: u32) -> Self {
+        let [a, r, g, b] = argb.to_be_bytes();
+        Self::from_rgba(r as f32, g as f32, b as f32, a as f32)
+    }
+
+    pub fn to_argb (self) -> u32 {
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+        u32::from_be_bytes([channel(self.a), channel(self.r), channel(self.g), channel(self.b)])
+    }
+
+    // Values as they have to be written out by shaders drawing
+    // into a canvas of the given format. Linear canvases blend in
+    // linear space, so the sRGB encoded components are decoded first
+    pub fn to_shader_color (self, format: PCanvasFormat) -> [f32; 4] {
+        if format.is_linear() {
+            [srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b), self.a]
+        }
+        else { [self.r, self.g, self.b, self.a] }
+    }
+
+    pub fn to_wgpu_color (self, format: PCanvasFormat) -> wgpu::Color {
+        let [r, g, b, a] = self.to_shader_color(format);
+        wgpu::Color { r: r as f64, g: g as f64, b: b as f64, a: a as f64 }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn fill (r: f32, g: f32, b: f32, a: f32) {
+    set_color_state! {
+        fill = Some(PColor::from_rgba(r, g, b, a));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn noFill () {
+    set_color_state! {
+        fill = None;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn stroke (r: f32, g: f32, b: f32, a: f32) {
+    set_color_state! {
+        stroke = Some(PColor::from_rgba(r, g, b, a));
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn noStroke () {
+    set_color_state! {
+        stroke = None;
+    }
+}
+
+// Applies to everything drawn after it, shapes drawn
+// earlier in the frame keep the mode they were drawn with
+#[no_mangle]
+pub extern "C" fn blendMode (mode: PBlendMode) {
+    set_color_state! {
+        blend_mode = mode;
+    }
+}
+
+// Clears the canvas. Anything drawn earlier in
+// the frame is thrown away, as it would be covered anyway
+#[no_mangle]
+pub extern "C" fn background (r: f32, g: f32, b: f32, a: f32) {
+    let color = PColor::from_rgba(r, g, b, a);
+    record_background(color);
+    set_renderer_state! {
+        draw_list.clear();
+        clear_color = Some(color);
+        clear_depth = true;
+    }
+}
@@ -1,44 +1,96 @@
 use std::time::{Instant, Duration};
 
-use wgpu::{Instance, InstanceDescriptor, Backends, RequestAdapterOptions, DeviceDescriptor, SurfaceConfiguration, TextureUsages, PresentMode, Color, IndexFormat};
-use winit::{event_loop::{EventLoopBuilder, ControlFlow}, window::WindowBuilder, dpi::LogicalSize, monitor::MonitorHandle, event::{Event, WindowEvent}};
+use wgpu::{Instance, InstanceDescriptor, Backends, RequestAdapterOptions, DeviceDescriptor, Features, SurfaceConfiguration, TextureUsages, PresentMode};
+use winit::{event_loop::{EventLoopBuilder, ControlFlow}, window::{Fullscreen, WindowBuilder}, dpi::{LogicalPosition, LogicalSize}, monitor::MonitorHandle, event::{Event, WindowEvent}};
 
-use crate::{renderer::{state::{get_renderer_state, set_renderer_state}, shader::ShaderBuilder, vertex::Vertex}, event::state::get_event_state, event::handle_event, renderer::vertex::normalized_vtx};
+use crate::{renderer::{camera::PRenderer, state::{get_renderer_state, set_renderer_state}, format::{create_canvas, negotiate_sample_count, negotiate_surface_format}, frame::{build_shape_shader, flush, present}, blit::Blitter, lighting::LightingBindings, projection::Projection, texture::TextureBindings}, color::{blend::PBlendMode, PColor}, event::state::get_event_state, event::handle_event, image::graphics::prepare_graphics, light::reset_lights, shape::transform::reset_transforms, shader::{reload::reload_shaders, update_standard_uniforms}};
 
 pub async fn start_event_loop () {
-    let renderer_state = get_renderer_state();
+    // Create the event loop first, as it is needed
+    // to look up displays for fullScreen()
+    let event_loop = EventLoopBuilder::new().build();
+
+    let (title, resizable, position, full_screen, display, cursor_visible, cursor) = {
+        let renderer_state = get_renderer_state();
+        (
+            renderer_state.title.clone(),
+            renderer_state.resizable,
+            renderer_state.position,
+            renderer_state.full_screen,
+            renderer_state.display,
+            renderer_state.cursor_visible,
+            renderer_state.cursor
+        )
+    };
+
+    // Displays are numbered from 1 in fullScreen(),
+    // 0 (or a display that doesn't exist) means the primary one
+    let full_screen_monitor =
+        if full_screen {
+            let monitor =
+                display
+                .checked_sub(1)
+                .and_then(|index| event_loop.available_monitors().nth(index as usize))
+                .or_else(|| event_loop.primary_monitor())
+                .or_else(|| event_loop.available_monitors().next())
+                .expect("Could not find a display to go full screen on.");
+
+            // In full screen mode the window size comes from the display
+            let size = monitor.size().to_logical::<f32>(monitor.scale_factor());
+            set_renderer_state! {
+                width = Some(size.width);
+                height = Some(size.height);
+            }
+            Some(monitor)
+        }
+        else { None };
 
     // Get dimensions for window from renderer state
     // as specified by createWindow call
-    let width =
-        renderer_state
-        .width
-        .expect("No width has been set. Call the createWindow() function to set a width.");
+    let (width, height) = {
+        let renderer_state = get_renderer_state();
 
-    let height =
-        renderer_state
-        .height
-        .expect("No height has been set. Call the createWindow() function to set a height.");
+        let width =
+            renderer_state
+            .width
+            .expect("No width has been set. Call the createWindow() function to set a width.");
 
+        let height =
+            renderer_state
+            .height
+            .expect("No height has been set. Call the createWindow() function to set a height.");
 
-    // Create the event loop and window instances
-    let event_loop = EventLoopBuilder::new().build();
-    let window =
+        (width, height)
+    };
+
+
+    // Create the window instance
+    let mut window_builder =
         WindowBuilder::new()
-        .with_title("processing") // get this title from renderer state later on
+        .with_title(title)
+        .with_resizable(resizable)
         .with_inner_size(LogicalSize::new(width, height))
+        .with_fullscreen(full_screen_monitor.map(|monitor| Fullscreen::Borderless(Some(monitor))));
+
+    if let Some((x, y)) = position {
+        window_builder = window_builder.with_position(LogicalPosition::new(x, y));
+    }
+
+    let window =
+        window_builder
         .build(&event_loop)
         .expect("Error while creating window.");
 
-    drop(renderer_state);
+    window.set_cursor_icon(cursor.to_cursor_icon());
+    window.set_cursor_visible(cursor_visible);
 
     // Get the maximum frame rate of monitor
     // to prevent user from setting a higher frame rate
     let monitors: Vec<MonitorHandle> = window.available_monitors().collect();
-    if monitors.len() > 0 {
+    if !monitors.is_empty() {
         let first_monitor =
             monitors
-            .get(0)
+            .first()
             .expect("Could not get handle to monitor to check for frame rate.");
 
         if let Some(max_fps) = first_monitor.refresh_rate_millihertz() {
@@ -68,19 +120,22 @@ pub async fn start_event_loop () {
         .await
         .expect("Errpr while gettnig adapter from the GPU");
 
+    // Without this feature only 1 and 4 samples can be
+    // used for multisampling, whatever the adapter supports
+    let features = adapter.features() & Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES;
+
     let (device, queue) =
         adapter
-        .request_device(&DeviceDescriptor::default(), None)
+        .request_device(&DeviceDescriptor {
+            features,
+            ..Default::default()
+        }, None)
         .await
         .expect("Could not get device and queue handle from the GPU");
 
     let surface_capabilities = surface.get_capabilities(&adapter);
 
-    let format =
-        *surface_capabilities
-        .formats
-        .get(0)
-        .expect("Could not get texture format from the surface");
+    let format = negotiate_surface_format(&surface_capabilities);
 
     let config = SurfaceConfiguration {
         usage: TextureUsages::RENDER_ATTACHMENT,
@@ -88,39 +143,63 @@ pub async fn start_event_loop () {
         width: size.width,
         height: size.height,
         present_mode: PresentMode::Fifo,
-        alpha_mode: *surface_capabilities.alpha_modes.get(0).unwrap(),
+        alpha_mode: *surface_capabilities.alpha_modes.first().unwrap(),
         view_formats: vec![]
     };
 
     surface.configure(&device, &config);
 
+    // The canvas has pixelDensity() pixels per point,
+    // independent of the scale factor of the display
+    let (pixel_density, canvas_format, smooth, depth) = {
+        let renderer_state = get_renderer_state();
+        (renderer_state.pixel_density as f32, renderer_state.canvas_format, renderer_state.smooth, renderer_state.renderer == PRenderer::P3D)
+    };
+    let sample_count = negotiate_sample_count(&adapter, device.features(), canvas_format, smooth);
+    let canvas = create_canvas(
+        &device,
+        (width * pixel_density) as u32,
+        (height * pixel_density) as u32,
+        canvas_format,
+        sample_count,
+        depth
+    );
+    let blitter = Blitter::new(&device, &canvas.sample_view, format);
+    let projection = {
+        let renderer_state = get_renderer_state();
+        Projection::new(&device, width, height, renderer_state.camera.matrix(renderer_state.renderer, width, height))
+    };
+    let texture_bindings = TextureBindings::new(&device, &queue);
+    let lighting = LightingBindings::new(&device);
+
     set_renderer_state! {
         device = Some(device);
         queue = Some(queue);
         window = Some(window);
         surface = Some(surface);
+        surface_config = Some(config);
+        canvas = Some(canvas);
+        sample_count = sample_count;
+        blitter = Some(blitter);
+        projection = Some(projection);
+        texture_bindings = Some(texture_bindings);
+        lighting = Some(lighting);
+        clear_color = Some(PColor::DEFAULT_BACKGROUND);
         last_redraw_time = Some(Instant::now());
+        start_time = Some(Instant::now());
     }
 
 
-    let tgl_shader = ShaderBuilder::new()
-        .with_content(include_str!("../shaders/rect.wgsl"))
-        .with_label("Rectangle Shader")
-        .with_vertex_buffer(vec![
-            normalized_vtx!(0, 0),
-            normalized_vtx!(100, 0),
-            normalized_vtx!(0, 100),
-            normalized_vtx!(100, 100),
-
-        ])
-        .with_index_buffer(vec![0, 1, 2, 2, 1, 3])
-        .build();
-
+    // The default blend mode is used by nearly every sketch,
+    // so it is built upfront instead of on the first frame
+    let shape_shader = build_shape_shader(PBlendMode::BlendBlend);
 
     set_renderer_state! {
-        shaders.push(tgl_shader);
+        shape_shaders.insert(PBlendMode::BlendBlend, shape_shader);
     }
-    
+
+    prepare_graphics();
+
 
     event_loop.run(move |event, _, control_flow| {
         
@@ -139,61 +218,17 @@ pub async fn start_event_loop () {
                 get_event_state()
                 .draw
                 .expect("No draw function specified. Call the p_init() function to set a draw function.");
+            reload_shaders();
+            update_standard_uniforms();
+            set_renderer_state! {
+                clear_depth = true;
+            }
+            reset_lights();
+            reset_transforms();
             draw();
 
-            let renderer_state = get_renderer_state();
-            let device = renderer_state.device.as_ref().expect("No device specified");
-            let queue = renderer_state.queue.as_ref().expect("No queue specified");
-            let surface = renderer_state.surface.as_ref().expect("No surface specified");
-
-            let frame = surface.get_current_texture().unwrap();                
-            let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
-            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-            {
-                let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: None,
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(Color {
-                                r: 0.1,
-                                g: 0.2,
-                                b: 0.3,
-                                a: 1.0,
-                            }),
-                            store: wgpu::StoreOp::Store,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                    occlusion_query_set: None,
-                    timestamp_writes: None
-                });
-
-                // loop over shaders and draw them here
-                for shader in &renderer_state.shaders {
-                    rpass.set_pipeline(&shader.pipeline);
-                    if let Some(vertex_buffer) = &shader.vertex_buffer {
-                        rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
-                    }
-                    if let Some(index_buffer) = &shader.index_buffer {
-                        rpass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
-                    }
-
-                    if shader.has_index_buffer {
-                        rpass.draw_indexed(0..shader.draw_count, 0, 0..1)
-                    }
-                    else {
-                        rpass.draw(0..shader.draw_count, 0..1);
-                    }
-                }
-
-            }
-        
-            queue.submit(Some(encoder.finish()));
-            frame.present();
-            drop(renderer_state);
+            flush();
+            present(control_flow);
 
             set_renderer_state! {
                 last_redraw_time = Some(current_time);
@@ -212,4 +247,4 @@ pub async fn start_event_loop () {
         }
 
     });
-}
\ No newline at end of file
+}
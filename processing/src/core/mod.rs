@@ -1,8 +1,18 @@
+use std::ffi::{c_char, CStr};
+
 use crate::event::state::{PEvent, PEventCallback, set_event_state, get_event_state};
 
 pub mod window;
 pub mod event_loop;
+pub mod version;
+
+// A string passed in from C, null gives an empty string
+pub(crate) unsafe fn string_from (string: *const c_char) -> String {
+    if string.is_null() { return String::new(); }
+    CStr::from_ptr(string).to_string_lossy().into_owned()
+}
 
+#[no_mangle]
 pub extern "C" fn p_init (setup: PEventCallback, draw: PEventCallback) {
     set_event_state! {
         setup = Some(setup);
@@ -10,12 +20,14 @@ pub extern "C" fn p_init (setup: PEventCallback, draw: PEventCallback) {
     }
 }
 
+#[no_mangle]
 pub extern "C" fn p_on (event: PEvent, callback: PEventCallback) {
     set_event_state! {
         events.insert(event, callback);
     }
 }
 
+#[no_mangle]
 pub extern "C" fn p_run () {
     let setup = get_event_state().setup.expect("No setup function specified. Call the p_init() function to set a setup function.");
     setup();
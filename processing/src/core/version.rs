@@ -0,0 +1,13 @@
+// ABI version of the exported C API. Bump this whenever a change
+// breaks existing callers (a function signature changes, an enum
+// gets reordered, etc). It is also used as the SONAME suffix of
+// libprocessing.so, which build.rs reads from this line
+pub const P_ABI_VERSION: u32 = 1;
+
+// Returns the ABI version the library was built with.
+// C programs can compare this with the P_ABI_VERSION macro
+// from bindings.h to detect a mismatched shared library at runtime
+#[no_mangle]
+pub extern "C" fn p_version () -> u32 {
+    P_ABI_VERSION
+}
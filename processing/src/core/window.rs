@@ -1,11 +1,63 @@
-use crate::renderer::state::{set_renderer_state, get_renderer_state};
+use std::ffi::c_char;
 
-#[no_mangle]
-pub extern "C" fn createWindow (width: f32, height: f32) {
+use winit::{dpi::LogicalPosition, window::{CursorIcon, Window}};
+
+use crate::{
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    renderer::{format::PCanvasFormat, state::{set_renderer_state, get_renderer_state}}
+};
+
+// Cursor shapes that can be passed to cursor()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PCursor {
+    #[default]
+    CursorArrow,
+    CursorCross,
+    CursorHand,
+    CursorMove,
+    CursorText,
+    CursorWait
+}
+
+impl PCursor {
+    pub fn to_cursor_icon (self) -> CursorIcon {
+        match self {
+            PCursor::CursorArrow => CursorIcon::Default,
+            PCursor::CursorCross => CursorIcon::Crosshair,
+            PCursor::CursorHand => CursorIcon::Hand,
+            PCursor::CursorMove => CursorIcon::Move,
+            PCursor::CursorText => CursorIcon::Text,
+            PCursor::CursorWait => CursorIcon::Wait
+        }
+    }
+}
+
+// Most of these can be called in setup(), before the window exists.
+// In that case the values are only stored and get applied when
+// the window is created in start_event_loop
+fn with_window (callback: impl FnOnce(&Window)) {
+    let state = get_renderer_state();
+    if let Some(window) = state.window.as_ref() {
+        callback(window);
+    }
+}
+
+pub(crate) fn create_window (width: f32, height: f32) -> PResult<()> {
+    if width <= 0.0 || height <= 0.0 {
+        return Err(PError::InvalidWindowSize { width, height });
+    }
     set_renderer_state! {
         width = Some(width);
         height = Some(height);
     }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn createWindow (width: f32, height: f32) {
+    create_window(width, height).or_panic()
 }
 
 #[no_mangle]
@@ -18,4 +70,142 @@ pub extern "C" fn width () -> f32 {
 pub extern "C" fn height () -> f32 {
     let state = get_renderer_state();
     state.height.expect("No height has been set in createWindow()")
-}
\ No newline at end of file
+}
+
+/// Sets the title of the window
+///
+/// # Safety
+/// `title` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn windowTitle (title: *const c_char) {
+    if title.is_null() { return; }
+    set_window_title(string_from(title));
+}
+
+pub(crate) fn set_window_title (title: String) {
+    with_window(|window| window.set_title(&title));
+    set_renderer_state! {
+        title = title;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn windowResizable (resizable: bool) {
+    with_window(|window| window.set_resizable(resizable));
+    set_renderer_state! {
+        resizable = resizable;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn windowMove (x: i32, y: i32) {
+    with_window(|window| window.set_outer_position(LogicalPosition::new(x, y)));
+    set_renderer_state! {
+        position = Some((x, y));
+    }
+}
+
+// Makes the window cover the given display (1 is the first display).
+// Passing 0 uses the primary display. Has to be called in setup(),
+// createWindow() is not needed as the size is taken from the display
+#[no_mangle]
+pub extern "C" fn fullScreen (display: u32) {
+    set_renderer_state! {
+        full_screen = true;
+        display = display;
+    }
+}
+
+// Number of canvas pixels per window point. Only 1 and 2 are
+// supported, like in Processing. Use displayDensity() to match
+// the density of the display the window is on
+pub(crate) fn set_pixel_density (density: u32) -> PResult<()> {
+    if density != 1 && density != 2 {
+        return Err(PError::InvalidPixelDensity(density));
+    }
+    set_renderer_state! {
+        pixel_density = density;
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn pixelDensity (density: u32) {
+    set_pixel_density(density).or_panic()
+}
+
+// Turns on anti-aliasing with the given number of samples per pixel
+// (1, 2, 4 or 8). The canvas is multisampled if the adapter supports
+// it, otherwise strokes get their edges smoothed in the shader.
+// Smoothing is on with 4 samples by default.
+// Only has an effect in setup(), like pixelDensity()
+pub(crate) fn set_smooth (level: u32) -> PResult<()> {
+    if !matches!(level, 1 | 2 | 4 | 8) {
+        return Err(PError::InvalidSmoothLevel(level));
+    }
+    set_renderer_state! {
+        smooth = level;
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn smooth (level: u32) {
+    set_smooth(level).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn noSmooth () {
+    set_renderer_state! {
+        smooth = 1;
+    }
+}
+
+// Sets how colors are stored in the canvas (see PCanvasFormat).
+// Only has an effect in setup(), as all pipelines are built against it
+#[no_mangle]
+pub extern "C" fn canvasFormat (format: PCanvasFormat) {
+    set_renderer_state! {
+        canvas_format = format;
+    }
+}
+
+// Scale factor of the display the window is on,
+// or 1 if the window hasn't been created yet
+#[no_mangle]
+pub extern "C" fn displayDensity () -> f32 {
+    let state = get_renderer_state();
+    state.window.as_ref().map_or(1.0, |window| window.scale_factor() as f32)
+}
+
+#[no_mangle]
+pub extern "C" fn pixelWidth () -> u32 {
+    let state = get_renderer_state();
+    (state.width.expect("No width has been set in createWindow()") * state.pixel_density as f32) as u32
+}
+
+#[no_mangle]
+pub extern "C" fn pixelHeight () -> u32 {
+    let state = get_renderer_state();
+    (state.height.expect("No height has been set in createWindow()") * state.pixel_density as f32) as u32
+}
+
+#[no_mangle]
+pub extern "C" fn noCursor () {
+    with_window(|window| window.set_cursor_visible(false));
+    set_renderer_state! {
+        cursor_visible = false;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn cursor (cursor: PCursor) {
+    with_window(|window| {
+        window.set_cursor_icon(cursor.to_cursor_icon());
+        window.set_cursor_visible(true);
+    });
+    set_renderer_state! {
+        cursor = cursor;
+        cursor_visible = true;
+    }
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+
+use crate::shape::retained::PShapePrimitive;
+
+// Errors returned by the Rust API. The C API keeps
+// panicking with a message instead, as there is no
+// good way to hand a Rust error across the FFI boundary
+#[derive(Debug, Clone, PartialEq)]
+pub enum PError {
+    WindowNotCreated,
+    InvalidWindowSize { width: f32, height: f32 },
+    InvalidPixelDensity(u32),
+    InvalidSmoothLevel(u32),
+    ShapeNotStarted,
+    ShapeAlreadyStarted,
+    ContourOutsidePolygon,
+    ContourNotStarted,
+    ContourAlreadyStarted,
+    NoPreviousVertex,
+    FontNotFound(String),
+    InvalidFont(String),
+    UnknownFont(u32),
+    UnknownImage(u32),
+    InvalidPosterizeLevels(f32),
+    InvalidGraphicsSize { width: u32, height: u32 },
+    NotGraphics(u32),
+    GraphicsAlreadyStarted,
+    GraphicsNotStarted,
+    GraphicsInUse(u32),
+    InvalidImageSize { width: u32, height: u32 },
+    ImageNotFound(String),
+    InvalidImage(String),
+    GraphicsNotResizable(u32),
+    MaskSizeMismatch { width: u32, height: u32, mask_width: u32, mask_height: u32 },
+    ShaderNotFound(String),
+    InvalidShader { path: String, message: String },
+    UnknownShader(u32),
+    UnknownUniform(String),
+    InvalidUniformValue { name: String, expected: usize, given: usize },
+    Not3D,
+    TooManyLights,
+    MatrixStackFull,
+    MatrixStackEmpty,
+    UnknownShape(u32),
+    InvalidShapeParams { kind: PShapePrimitive, given: usize },
+    NotShapeGroup(u32),
+    ShapeContainsItself,
+    InvalidChildIndex { index: u32, count: u32 },
+    NotPathShape(u32),
+    ShapeNotRecording(u32),
+    InvalidVertexIndex { index: u32, count: u32 },
+    UnknownChild(String),
+    ShapeNotFound(String),
+    UnsupportedShapeFile(String),
+    InvalidShape { path: String, message: String },
+    RecordNot2D,
+    RecordAlreadyStarted,
+    RecordNotStarted,
+    RecordNotPdf,
+    RecordNotWritten { path: String, message: String },
+}
+
+impl fmt::Display for PError {
+    fn fmt (&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PError::WindowNotCreated =>
+                write!(f, "No window size has been set. Call create_window() in setup."),
+            PError::InvalidWindowSize { width, height } =>
+                write!(f, "Invalid window size {}x{}. Width and height must be positive.", width, height),
+            PError::InvalidPixelDensity(density) =>
+                write!(f, "Invalid pixel density {}. Only 1 and 2 are supported.", density),
+            PError::InvalidSmoothLevel(level) =>
+                write!(f, "Invalid smooth level {}. Only 1, 2, 4 and 8 are supported.", level),
+            PError::ShapeNotStarted =>
+                write!(f, "No shape has been started. Call beginShape() first."),
+            PError::ShapeAlreadyStarted =>
+                write!(f, "A shape has already been started. Call endShape() before starting a new one."),
+            PError::ContourOutsidePolygon =>
+                write!(f, "Contours can only be added to shapes started with beginShape(ShapePolygon)."),
+            PError::ContourNotStarted =>
+                write!(f, "No contour has been started. Call beginContour() first."),
+            PError::ContourAlreadyStarted =>
+                write!(f, "A contour has already been started. Call endContour() before starting a new one."),
+            PError::NoPreviousVertex =>
+                write!(f, "Curves need a starting point. Call vertex() at least once before bezierVertex() or quadraticVertex()."),
+            PError::FontNotFound(name) =>
+                write!(f, "Could not find the font \"{}\".", name),
+            PError::InvalidFont(name) =>
+                write!(f, "\"{}\" is not a TTF or OTF font.", name),
+            PError::UnknownFont(id) =>
+                write!(f, "No font with id {} has been loaded. Use a font returned by loadFont() or createFont().", id),
+            PError::UnknownImage(id) =>
+                write!(f, "No image with id {} exists.", id),
+            PError::InvalidPosterizeLevels(levels) =>
+                write!(f, "Invalid number of posterize levels {}. Pass between 2 and 255 levels with filterParam().", levels),
+            PError::InvalidGraphicsSize { width, height } =>
+                write!(f, "Invalid graphics size {}x{}. Width and height must be positive.", width, height),
+            PError::NotGraphics(id) =>
+                write!(f, "Image {} can't be drawn into. Only images made with createGraphics() can.", id),
+            PError::GraphicsAlreadyStarted =>
+                write!(f, "Already drawing into a graphics. Call endDraw() before beginDraw()."),
+            PError::GraphicsNotStarted =>
+                write!(f, "Not drawing into this graphics. Call beginDraw() first."),
+            PError::GraphicsInUse(id) =>
+                write!(f, "Graphics {} is being drawn into. Call endDraw() before freeing it.", id),
+            PError::InvalidImageSize { width, height } =>
+                write!(f, "Invalid image size {}x{}. Width and height must be positive, and small enough for the pixels to fit in memory.", width, height),
+            PError::ImageNotFound(path) =>
+                write!(f, "Could not find the image \"{}\".", path),
+            PError::InvalidImage(path) =>
+                write!(f, "\"{}\" is not a PNG or JPEG image.", path),
+            PError::GraphicsNotResizable(id) =>
+                write!(f, "Image {} is a graphics and can't be resized. Make a new one with createGraphics() instead.", id),
+            PError::MaskSizeMismatch { width, height, mask_width, mask_height } =>
+                write!(f, "The mask is {}x{} but the image is {}x{}. Both have to be the same size.", mask_width, mask_height, width, height),
+            PError::ShaderNotFound(path) =>
+                write!(f, "Could not find the shader \"{}\".", path),
+            PError::InvalidShader { path, message } =>
+                write!(f, "\"{}\" is not a shader that can be used.\n{}", path, message),
+            PError::UnknownShader(id) =>
+                write!(f, "No shader with id {} has been loaded. Use a shader returned by loadShader().", id),
+            PError::UnknownUniform(name) =>
+                write!(f, "The shader has no uniform called \"{}\".", name),
+            PError::InvalidUniformValue { name, expected, given } =>
+                write!(f, "Uniform \"{}\" takes {} values, {} were given.", name, expected, given),
+            PError::Not3D =>
+                write!(f, "Only available in 3D. Call createWindowWithRenderer() with P3D."),
+            PError::TooManyLights =>
+                write!(f, "Only 8 lights can be on at once. Call noLights() to turn them off first."),
+            PError::MatrixStackFull =>
+                write!(f, "Too many calls to pushMatrix(). Only 32 matrices can be pushed, call popMatrix() for each one."),
+            PError::MatrixStackEmpty =>
+                write!(f, "Too many calls to popMatrix(). There is no matrix left to pop, call pushMatrix() first."),
+            PError::UnknownShape(id) =>
+                write!(f, "No shape with id {} exists. Use a shape returned by createShape().", id),
+            PError::InvalidShapeParams { kind: PShapePrimitive::PrimitiveBox, given } =>
+                write!(f, "PrimitiveBox takes a size, or a width, height and depth. {} values were given.", given),
+            PError::InvalidShapeParams { kind: PShapePrimitive::PrimitiveSphere, given } =>
+                write!(f, "PrimitiveSphere takes a radius. {} values were given.", given),
+            PError::NotShapeGroup(id) =>
+                write!(f, "Shape {} is not a group. Children can only be added to shapes made with createShapeGroup().", id),
+            PError::ShapeContainsItself =>
+                write!(f, "A shape can't be added to itself or to one of its children."),
+            PError::InvalidChildIndex { index, count } =>
+                write!(f, "Child {} doesn't exist, the shape has {} children.", index, count),
+            PError::NotPathShape(id) =>
+                write!(f, "Shape {} can't be given vertices. Only shapes made with createShape() can.", id),
+            PError::ShapeNotRecording(id) =>
+                write!(f, "Shape {} is not being given vertices. Call PShape_beginShape() first.", id),
+            PError::InvalidVertexIndex { index, count } =>
+                write!(f, "Vertex {} doesn't exist, the shape has {} vertices.", index, count),
+            PError::UnknownChild(name) =>
+                write!(f, "The shape has no child called \"{}\".", name),
+            PError::ShapeNotFound(path) =>
+                write!(f, "Could not find the shape \"{}\".", path),
+            PError::UnsupportedShapeFile(path) =>
+                write!(f, "\"{}\" can't be loaded as a shape. Only SVG and OBJ files are supported.", path),
+            PError::InvalidShape { path, message } =>
+                write!(f, "\"{}\" is not a shape that can be loaded.\n{}", path, message),
+            PError::RecordNot2D =>
+                write!(f, "Only 2D drawing can be recorded to a file."),
+            PError::RecordAlreadyStarted =>
+                write!(f, "Already recording to a file. Call endRecord() before starting a new recording."),
+            PError::RecordNotStarted =>
+                write!(f, "Not recording to a file. Call beginRecord() first."),
+            PError::RecordNotPdf =>
+                write!(f, "Only PDF recordings have pages."),
+            PError::RecordNotWritten { path, message } =>
+                write!(f, "Could not write the recording to \"{}\".\n{}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for PError {}
+
+pub type PResult<T> = Result<T, PError>;
+
+// The C API has no way of returning errors,
+// so they end the program with their message instead
+pub(crate) trait OrPanic<T> {
+    fn or_panic (self) -> T;
+}
+
+impl<T> OrPanic<T> for PResult<T> {
+    fn or_panic (self) -> T {
+        self.unwrap_or_else(|err| panic!("{}", err))
+    }
+}
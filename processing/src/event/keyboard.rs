@@ -0,0 +1,99 @@
+use winit::event::{ElementState, KeyboardInput, ModifiersState, VirtualKeyCode};
+
+use crate::event::state::get_event_state;
+
+use super::state::{PEvent, set_event_state};
+
+// Key codes for keys that don't have a character
+// associated with them. Values match the ones used by Processing
+pub const BACKSPACE: u32 = 8;
+pub const TAB: u32 = 9;
+pub const ENTER: u32 = 10;
+pub const SHIFT: u32 = 16;
+pub const CONTROL: u32 = 17;
+pub const ALT: u32 = 18;
+pub const ESC: u32 = 27;
+pub const LEFT: u32 = 37;
+pub const UP: u32 = 38;
+pub const RIGHT: u32 = 39;
+pub const DOWN: u32 = 40;
+pub const DELETE: u32 = 127;
+
+// Converts a winit key code to the code returned by keyCode()
+// Letters and digits map to their (uppercase) ASCII value
+fn key_code_from (key: VirtualKeyCode) -> u32 {
+    let code = key as u32;
+
+    if (VirtualKeyCode::Key1 as u32..=VirtualKeyCode::Key9 as u32).contains(&code) {
+        return code - VirtualKeyCode::Key1 as u32 + '1' as u32;
+    }
+    if (VirtualKeyCode::A as u32..=VirtualKeyCode::Z as u32).contains(&code) {
+        return code - VirtualKeyCode::A as u32 + 'A' as u32;
+    }
+
+    match key {
+        VirtualKeyCode::Key0 => '0' as u32,
+        VirtualKeyCode::Space => ' ' as u32,
+        VirtualKeyCode::Back => BACKSPACE,
+        VirtualKeyCode::Tab => TAB,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => ENTER,
+        VirtualKeyCode::LShift | VirtualKeyCode::RShift => SHIFT,
+        VirtualKeyCode::LControl | VirtualKeyCode::RControl => CONTROL,
+        VirtualKeyCode::LAlt | VirtualKeyCode::RAlt => ALT,
+        VirtualKeyCode::Escape => ESC,
+        VirtualKeyCode::Left => LEFT,
+        VirtualKeyCode::Up => UP,
+        VirtualKeyCode::Right => RIGHT,
+        VirtualKeyCode::Down => DOWN,
+        VirtualKeyCode::Delete => DELETE,
+        _ => 0
+    }
+}
+
+pub fn handle_keyboard_event (input: KeyboardInput) {
+    if let Some(key) = input.virtual_keycode {
+        set_event_state! {
+            key_code = key_code_from(key);
+        }
+    }
+
+    match input.state {
+        ElementState::Pressed => {
+            if let Some(handler) = get_event_state().get_handler(PEvent::PKeyPressed) { handler() }
+        }
+
+        ElementState::Released => {
+            if let Some(handler) = get_event_state().get_handler(PEvent::PKeyReleased) { handler() }
+        }
+    }
+}
+
+// winit reports the typed character separately from the key press,
+// so key() is only up to date once the PKeyTyped event fires
+pub fn handle_received_character (character: char) {
+    set_event_state! {
+        key = character as u32;
+    }
+    if let Some(handler) = get_event_state().get_handler(PEvent::PKeyTyped) { handler() }
+}
+
+pub fn handle_modifiers_changed (modifiers: ModifiersState) {
+    set_event_state! {
+        ctrl_pressed = modifiers.ctrl();
+        alt_pressed = modifiers.alt();
+        shift_pressed = modifiers.shift();
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn keyCode () -> u32 {
+    let state = get_event_state();
+    state.key_code
+}
+
+// Returns the unicode code point of the last typed character
+#[no_mangle]
+pub extern "C" fn key () -> u32 {
+    let state = get_event_state();
+    state.key
+}
@@ -1,21 +1,30 @@
 use winit::{window::WindowId, event::WindowEvent};
-use crate::event::{mouse::handle_mouse_event, state::{get_event_state, PEvent}};
+use crate::event::{mouse::handle_mouse_event, keyboard::{handle_keyboard_event, handle_received_character, handle_modifiers_changed}, window::{handle_resize, scale_factor}, state::{get_event_state, PEvent}};
 
 use self::state::set_event_state;
 
 
 pub mod state;
 pub mod mouse;
+pub mod keyboard;
+pub mod window;
 
 pub fn handle_event (_id: WindowId, event: WindowEvent) {
     match event {
         WindowEvent::MouseInput { state, button, .. } => handle_mouse_event(state, button),
+        WindowEvent::KeyboardInput { input, .. } => handle_keyboard_event(input),
+        WindowEvent::ReceivedCharacter(character) => handle_received_character(character),
+        WindowEvent::ModifiersChanged(modifiers) => handle_modifiers_changed(modifiers),
 
 
+        WindowEvent::Resized(size) => handle_resize(size, scale_factor()),
+        WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => handle_resize(*new_inner_size, scale_factor),
+
         WindowEvent::CursorMoved { position, .. } => {
+            let position = position.to_logical::<f32>(scale_factor());
             set_event_state! {
-                mouse_x = position.x as f32;
-                mouse_y = position.y as f32;
+                mouse_x = position.x;
+                mouse_y = position.y;
             }
             if let Some(handler) = get_event_state().get_handler(PEvent::PMouseMoved){ handler() }
         }
@@ -12,7 +12,12 @@ pub enum PEvent {
     PMouseReleased,
     PMouseMoved,
     #[default]
-    NoEvent
+    NoEvent,
+    // Added after NoEvent so the values above stay the same
+    PKeyPressed,
+    PKeyReleased,
+    PKeyTyped,
+    PWindowResized
 }
 
 #[derive(Default, Copy, Clone)]
@@ -51,6 +56,7 @@ pub struct EventState {
     pub mouse_x: f32,
     pub mouse_y: f32,
     pub key_code: u32,
+    pub key: u32,
     pub mouse_button: PMouseButton,
 
     pub ctrl_pressed: bool,
@@ -0,0 +1,15 @@
+use winit::dpi::PhysicalSize;
+
+use crate::{event::state::{get_event_state, PEvent}, renderer::{resize::resize_renderer, state::get_renderer_state}};
+
+pub fn handle_resize (size: PhysicalSize<u32>, scale_factor: f64) {
+    resize_renderer(size, scale_factor);
+    if let Some(handler) = get_event_state().get_handler(PEvent::PWindowResized) { handler() }
+}
+
+// Scale factor of the window, used to convert the
+// physical sizes and positions winit reports into points
+pub fn scale_factor () -> f64 {
+    let state = get_renderer_state();
+    state.window.as_ref().map_or(1.0, |window| window.scale_factor())
+}
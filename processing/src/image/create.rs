@@ -0,0 +1,57 @@
+use std::{ffi::c_char, path::Path};
+
+use crate::{
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    image::{add_image, pixel_count, state::ImageData, PImage, PImageFormat}
+};
+
+// Width and height are in pixels, the image starts out transparent black.
+// Images made on the CPU have a pixel density of 1
+pub(crate) fn create_image (width: u32, height: u32, format: PImageFormat) -> PResult<PImage> {
+    if width == 0 || height == 0 {
+        return Err(PError::InvalidImageSize { width, height });
+    }
+
+    let pixels = vec![0; pixel_count(width, height)?];
+    Ok(add_image(ImageData::new(width, height, 1, format, pixels)))
+}
+
+// Loads a PNG or JPEG file. Images with an alpha channel are ARGB, others RGB
+pub(crate) fn load_image (path: &str) -> PResult<PImage> {
+    if !Path::new(path).is_file() {
+        return Err(PError::ImageNotFound(path.to_string()));
+    }
+
+    let decoded = ::image::io::Reader::open(path)
+        .and_then(|reader| reader.with_guessed_format())
+        .map_err(|_| PError::ImageNotFound(path.to_string()))?
+        .decode()
+        .map_err(|_| PError::InvalidImage(path.to_string()))?;
+
+    let format = if decoded.color().has_alpha() { PImageFormat::ImageArgb } else { PImageFormat::ImageRgb };
+    let rgba = decoded.to_rgba8();
+    let pixels = rgba
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            u32::from_be_bytes([a, r, g, b])
+        })
+        .collect();
+
+    Ok(add_image(ImageData::new(rgba.width(), rgba.height(), 1, format, pixels)))
+}
+
+#[no_mangle]
+pub extern "C" fn createImage (width: u32, height: u32, format: PImageFormat) -> PImage {
+    create_image(width, height, format).or_panic()
+}
+
+/// Loads a PNG or JPEG image
+///
+/// # Safety
+/// `path` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn loadImage (path: *const c_char) -> PImage {
+    load_image(&string_from(path)).or_panic()
+}
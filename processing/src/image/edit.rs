@@ -0,0 +1,330 @@
+use ::image::{imageops::{self, FilterType}, RgbaImage};
+
+use crate::{
+    color::{blend::PBlendMode, PColor},
+    error::{OrPanic, PError, PResult},
+    image::{edit_image, graphics::read_graphics_pixels, pixel_count, pixels::{canvas_pixel, set_canvas_pixels}, read_image, state::{get_image_state, IMAGE_STATE}, with_image, PImage, PImageFormat}
+};
+
+// A region of an image, in pixels
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct PRegion {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+// Resizes an image to width x height pixels. If one of them is 0,
+// it is computed from the other so the aspect ratio stays the same
+pub(crate) fn resize_image (image: PImage, width: u32, height: u32) -> PResult<()> {
+    let (old_width, old_height, is_graphics) = with_image(image, |data| (data.width, data.height, data.graphics.is_some()))?;
+    if is_graphics {
+        return Err(PError::GraphicsNotResizable(image.id));
+    }
+    if old_width == 0 || old_height == 0 {
+        return Err(PError::InvalidImageSize { width: old_width, height: old_height });
+    }
+
+    let (width, height) = match (width, height) {
+        (0, 0) => return Err(PError::InvalidImageSize { width, height }),
+        (0, height) => (((old_width as f32 * height as f32 / old_height as f32).round() as u32).max(1), height),
+        (width, 0) => (width, ((old_height as f32 * width as f32 / old_width as f32).round() as u32).max(1)),
+        size => size
+    };
+    pixel_count(width, height)?;
+
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let data = &mut state.images[image.id as usize];
+
+    let bytes = data.pixels.iter().flat_map(|pixel| {
+        let [a, r, g, b] = pixel.to_be_bytes();
+        [r, g, b, a]
+    }).collect();
+    let source = RgbaImage::from_raw(old_width, old_height, bytes).expect("Image has the wrong number of pixels");
+    let resized = imageops::resize(&source, width, height, FilterType::Triangle);
+
+    data.pixels = resized.pixels().map(|pixel| {
+        let [r, g, b, a] = pixel.0;
+        u32::from_be_bytes([a, r, g, b])
+    }).collect();
+    data.width = width;
+    data.height = height;
+    data.dirty = true;
+    Ok(())
+}
+
+// Samples a region of pixels at x, y (in pixels, from the top left of
+// the region's first pixel) with bilinear filtering. Samples never
+// reach outside of the region or the image
+fn sample (pixels: &[u32], width: u32, height: u32, region: PRegion, x: f32, y: f32) -> u32 {
+    let min_x = region.x.max(0);
+    let min_y = region.y.max(0);
+    let max_x = (region.x + region.width).min(width as i32) - 1;
+    let max_y = (region.y + region.height).min(height as i32) - 1;
+
+    let (x, y) = (x.clamp(min_x as f32, max_x as f32), y.clamp(min_y as f32, max_y as f32));
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let (x1, y1) = ((x0 + 1).min(max_x), (y0 + 1).min(max_y));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let at = |x: i32, y: i32| pixels[(y as u32 * width + x as u32) as usize].to_be_bytes().map(|channel| channel as f32);
+    let (top_left, top_right, bottom_left, bottom_right) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+
+    let mut result = [0; 4];
+    for channel in 0..4 {
+        let top = top_left[channel] + (top_right[channel] - top_left[channel]) * fx;
+        let bottom = bottom_left[channel] + (bottom_right[channel] - bottom_left[channel]) * fx;
+        result[channel] = (top + (bottom - top) * fy).round() as u8;
+    }
+    u32::from_be_bytes(result)
+}
+
+// Copies a region of source over a region of destination, scaling it to
+// fit. Regions are in pixels and cut off at the edges of their images.
+// Each pixel of the destination region is combined with its sample
+// of the source region by combine
+fn copy_region (
+    source: PImage,
+    source_region: PRegion,
+    destination: PImage,
+    destination_region: PRegion,
+    combine: impl Fn(u32, u32) -> u32
+) -> PResult<()> {
+    let (source_width, source_height, source_pixels) = read_image(source)?;
+
+    let (PRegion { width: sw, height: sh, .. }, PRegion { width: dw, height: dh, .. }) = (source_region, destination_region);
+    let source_visible =
+        sw > 0 && sh > 0
+        && source_region.x < source_width as i32 && source_region.y < source_height as i32
+        && source_region.x + sw > 0 && source_region.y + sh > 0;
+    if !source_visible || dw <= 0 || dh <= 0 { return Ok(()); }
+
+    let (scale_x, scale_y) = (sw as f32 / dw as f32, sh as f32 / dh as f32);
+    edit_image(destination, |pixels, width, height| {
+        for row in destination_region.y.max(0)..(destination_region.y + dh).min(height as i32) {
+            let y = source_region.y as f32 + ((row - destination_region.y) as f32 + 0.5) * scale_y - 0.5;
+            for column in destination_region.x.max(0)..(destination_region.x + dw).min(width as i32) {
+                let x = source_region.x as f32 + ((column - destination_region.x) as f32 + 0.5) * scale_x - 0.5;
+                let index = (row as u32 * width + column as u32) as usize;
+                let color = sample(&source_pixels, source_width, source_height, source_region, x, y);
+                pixels[index] = combine(color, pixels[index]);
+            }
+        }
+    })
+}
+
+// copy() in Processing. Pixels are replaced, alpha included
+pub(crate) fn copy_image (source: PImage, source_region: PRegion, destination: PImage, destination_region: PRegion) -> PResult<()> {
+    copy_region(source, source_region, destination, destination_region, |color, _| color)
+}
+
+// blend() in Processing. Like copy_image(), but pixels
+// are blended with the destination like blendMode() does
+pub(crate) fn blend_image (
+    source: PImage,
+    source_region: PRegion,
+    destination: PImage,
+    destination_region: PRegion,
+    mode: PBlendMode
+) -> PResult<()> {
+    copy_region(source, source_region, destination, destination_region, |color, destination| {
+        mode.blend_colors(PColor::from_argb(color), PColor::from_argb(destination)).to_argb()
+    })
+}
+
+// Uses the blue channel of mask as the alpha of image, like Processing
+// does with grayscale masks. Both have to be the same size in pixels
+pub(crate) fn mask_image (image: PImage, mask: PImage) -> PResult<()> {
+    let (mask_width, mask_height, mask_pixels) = read_image(mask)?;
+    let (width, height) = with_image(image, |data| (data.width, data.height))?;
+    if (width, height) != (mask_width, mask_height) {
+        return Err(PError::MaskSizeMismatch { width, height, mask_width, mask_height });
+    }
+
+    edit_image(image, |pixels, _, _| {
+        for (pixel, mask) in pixels.iter_mut().zip(mask_pixels) {
+            *pixel = (*pixel & 0x00ffffff) | ((mask & 0xff) << 24);
+        }
+    })?;
+
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    state.images[image.id as usize].format = PImageFormat::ImageArgb;
+    Ok(())
+}
+
+// Color of the pixel at x, y (in pixels), 0 outside of the image.
+// Graphics are only read back when they have changed since the last time
+pub(crate) fn get_image_pixel (image: PImage, x: i32, y: i32) -> PResult<u32> {
+    if get_image_state().drawing == Some(image) {
+        return Ok(canvas_pixel(x, y));
+    }
+    read_graphics_pixels(image)?;
+
+    let state = get_image_state();
+    let data = state.images.get(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+    if x < 0 || y < 0 || x >= data.width as i32 || y >= data.height as i32 { return Ok(0); }
+    Ok(data.pixels.get((y as u32 * data.width + x as u32) as usize).copied().unwrap_or(0))
+}
+
+// Sets the pixel at x, y (in pixels), nothing happens outside of the image.
+// Graphics get the pixel written to their canvas before they are next used
+pub(crate) fn set_image_pixel (image: PImage, x: i32, y: i32, argb: u32) -> PResult<()> {
+    if get_image_state().drawing == Some(image) {
+        set_canvas_pixels(x, y, 1, argb);
+        return Ok(());
+    }
+    read_graphics_pixels(image)?;
+
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let data = state.images.get_mut(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+    if x < 0 || y < 0 || x >= data.width as i32 || y >= data.height as i32 { return Ok(()); }
+
+    let index = (y as u32 * data.width + x as u32) as usize;
+    // Graphics have no pixels before the window exists
+    let Some(pixel) = data.pixels.get_mut(index) else { return Ok(()); };
+    *pixel = argb;
+    match data.graphics.as_mut() {
+        Some(graphics) => graphics.pixels_changed = true,
+        None => data.dirty = true
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn imageResize (image: PImage, width: u32, height: u32) {
+    resize_image(image, width, height).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageCopy (source: PImage, source_region: PRegion, destination: PImage, destination_region: PRegion) {
+    copy_image(source, source_region, destination, destination_region).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageBlend (source: PImage, source_region: PRegion, destination: PImage, destination_region: PRegion, mode: PBlendMode) {
+    blend_image(source, source_region, destination, destination_region, mode).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageMask (image: PImage, mask: PImage) {
+    mask_image(image, mask).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageGet (image: PImage, x: i32, y: i32) -> u32 {
+    get_image_pixel(image, x, y).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageSet (image: PImage, x: i32, y: i32, color: u32) {
+    set_image_pixel(image, x, y, color).or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::{create::create_image, free_image}, lock_state};
+
+    const RED: u32 = 0xffff0000;
+    const BLUE: u32 = 0xff0000ff;
+
+    // A CPU image with the given pixels
+    fn image (width: u32, height: u32, pixels: &[u32]) -> PImage {
+        let image = create_image(width, height, PImageFormat::ImageArgb).unwrap();
+        edit_image(image, |data, _, _| data.copy_from_slice(pixels)).unwrap();
+        image
+    }
+
+    fn pixels (image: PImage) -> Vec<u32> {
+        read_image(image).unwrap().2
+    }
+
+    fn region (x: i32, y: i32, width: i32, height: i32) -> PRegion {
+        PRegion { x, y, width, height }
+    }
+
+    #[test]
+    fn sizes_that_overflow_are_errors () {
+        let _state = lock_state();
+        assert_eq!(create_image(u32::MAX, u32::MAX, PImageFormat::ImageArgb), Err(PError::InvalidImageSize { width: u32::MAX, height: u32::MAX }));
+        assert_eq!(create_image(0, 1, PImageFormat::ImageArgb), Err(PError::InvalidImageSize { width: 0, height: 1 }));
+
+        let image = image(1, 1, &[RED]);
+        assert!(matches!(resize_image(image, u32::MAX, u32::MAX), Err(PError::InvalidImageSize { .. })));
+        free_image(image).unwrap();
+    }
+
+    #[test]
+    fn resize_keeps_the_aspect_ratio () {
+        let _state = lock_state();
+        let image = image(4, 2, &[RED; 8]);
+        resize_image(image, 2, 0).unwrap();
+        assert_eq!(read_image(image).unwrap(), (2, 1, vec![RED; 2]));
+        resize_image(image, 0, 3).unwrap();
+        assert_eq!(read_image(image).unwrap(), (6, 3, vec![RED; 18]));
+        assert_eq!(resize_image(image, 0, 0), Err(PError::InvalidImageSize { width: 0, height: 0 }));
+        free_image(image).unwrap();
+    }
+
+    #[test]
+    fn copy_scales_and_clips_regions () {
+        let _state = lock_state();
+        let source = image(2, 1, &[RED, BLUE]);
+        let destination = image(4, 2, &[0; 8]);
+
+        copy_image(source, region(0, 0, 2, 1), destination, region(1, 0, 2, 1)).unwrap();
+        assert_eq!(pixels(destination), [0, RED, BLUE, 0, 0, 0, 0, 0]);
+
+        // One pixel stretched over a region
+        copy_image(source, region(1, 0, 1, 1), destination, region(0, 1, 3, 1)).unwrap();
+        assert_eq!(pixels(destination), [0, RED, BLUE, 0, BLUE, BLUE, BLUE, 0]);
+
+        // Parts outside of the destination are left out
+        copy_image(source, region(0, 0, 1, 1), destination, region(-1, -1, 2, 2)).unwrap();
+        assert_eq!(pixels(destination), [RED, RED, BLUE, 0, BLUE, BLUE, BLUE, 0]);
+
+        // A source region outside of the source copies nothing
+        copy_image(source, region(5, 0, 1, 1), destination, region(0, 0, 4, 2)).unwrap();
+        assert_eq!(pixels(destination)[3], 0);
+
+        free_image(source).unwrap();
+        free_image(destination).unwrap();
+    }
+
+    #[test]
+    fn blend_combines_with_the_destination () {
+        let _state = lock_state();
+        let source = image(1, 1, &[0x800000ff]);
+        let destination = image(1, 1, &[RED]);
+
+        blend_image(source, region(0, 0, 1, 1), destination, region(0, 0, 1, 1), PBlendMode::BlendAdd).unwrap();
+        assert_eq!(pixels(destination), [0xffff0080]);
+
+        blend_image(source, region(0, 0, 1, 1), destination, region(0, 0, 1, 1), PBlendMode::BlendReplace).unwrap();
+        assert_eq!(pixels(destination), [0x800000ff]);
+
+        free_image(source).unwrap();
+        free_image(destination).unwrap();
+    }
+
+    #[test]
+    fn mask_sets_alpha_from_blue () {
+        let _state = lock_state();
+        let image = create_image(2, 1, PImageFormat::ImageRgb).unwrap();
+        edit_image(image, |data, _, _| data.copy_from_slice(&[RED, BLUE])).unwrap();
+        let mask = self::image(2, 1, &[0xff000080, 0xffffff00]);
+
+        mask_image(image, mask).unwrap();
+        assert_eq!(pixels(image), [0x80ff0000, 0x000000ff]);
+        assert_eq!(with_image(image, |data| data.format).unwrap(), PImageFormat::ImageArgb);
+
+        let small = create_image(1, 1, PImageFormat::ImageArgb).unwrap();
+        assert_eq!(mask_image(image, small), Err(PError::MaskSizeMismatch { width: 2, height: 1, mask_width: 1, mask_height: 1 }));
+
+        for image in [image, mask, small] {
+            free_image(image).unwrap();
+        }
+    }
+}
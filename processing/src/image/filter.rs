@@ -0,0 +1,299 @@
+use wgpu::TextureFormat;
+
+use crate::{
+    color::PColor,
+    error::{OrPanic, PError, PResult},
+    image::{graphics::{begin_draw, end_draw}, state::{get_image_state, IMAGE_STATE}, with_image, PImage},
+    renderer::{filter::FilterPass, frame::flush, readback::{read_pixels, write_pixels}, state::{get_renderer_state, set_renderer_state}, target::RenderTarget}
+};
+
+// Filters for filter(). The order matches the kinds in filter.wgsl
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PFilter {
+    // Black or white depending on whether the luminance is
+    // above the level (0 to 1, 0.5 by default)
+    FilterThreshold,
+    FilterGray,
+    FilterInvert,
+    // Limits each channel to a number of levels (2 to 255)
+    FilterPosterize,
+    // Blurs with the given radius (in points, 1 by default)
+    FilterBlur,
+    // Darkest and brightest of each pixel and its neighbours
+    FilterErode,
+    FilterDilate,
+    // Sets alpha to 1
+    FilterOpaque
+}
+
+// Turns the parameter given to filter() into the threshold or posterize
+// value and the blur radius in pixels, using Processing's blur kernel size
+fn filter_settings (filter: PFilter, param: Option<f32>, pixel_density: u32) -> PResult<(f32, i32)> {
+    match filter {
+        PFilter::FilterThreshold => Ok((param.unwrap_or(0.5).clamp(0.0, 1.0), 0)),
+        PFilter::FilterPosterize => {
+            let levels = param.unwrap_or(0.0).floor();
+            if !(2.0..=255.0).contains(&levels) {
+                return Err(PError::InvalidPosterizeLevels(param.unwrap_or(0.0)));
+            }
+            Ok((levels, 0))
+        }
+        PFilter::FilterBlur => {
+            let radius = (param.unwrap_or(1.0).max(0.0) * pixel_density as f32 * 3.5) as i32;
+            Ok((0.0, radius.clamp(1, 248)))
+        }
+        _ => Ok((0.0, 0))
+    }
+}
+
+// Filters pixels (0xAARRGGBB, row by row) on the CPU. This does the same as
+// filter.wgsl, it is used for images when there is no GPU yet and the tests
+// check the GPU against it. value and radius are as given to FilterPass::apply
+pub fn filter_pixels (pixels: &mut [u32], width: u32, height: u32, filter: PFilter, value: f32, radius: i32) {
+    let (width, height) = (width as i32, height as i32);
+    let luminance = |color: PColor| color.r * 0.299 + color.g * 0.587 + color.b * 0.114;
+    let with_rgb = |color: PColor, r: f32, g: f32, b: f32| PColor { r, g, b, a: color.a }.to_argb();
+
+    match filter {
+        PFilter::FilterBlur => {
+            // Each axis is rounded to 8 bits on its own, like the two GPU passes
+            let horizontal = blur_pixels(pixels, width, height, radius, (1, 0));
+            let vertical = blur_pixels(&horizontal, width, height, radius, (0, 1));
+            pixels.copy_from_slice(&vertical);
+        }
+        PFilter::FilterErode | PFilter::FilterDilate => {
+            let source = pixels.to_vec();
+            let at = |x: i32, y: i32| PColor::from_argb(source[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize]);
+            for y in 0..height {
+                for x in 0..width {
+                    let mut result = at(x, y);
+                    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                        let color = at(x + dx, y + dy);
+                        let replace =
+                            if filter == PFilter::FilterDilate { luminance(color) > luminance(result) }
+                            else { luminance(color) < luminance(result) };
+                        if replace { result = color; }
+                    }
+                    pixels[(y * width + x) as usize] = result.to_argb();
+                }
+            }
+        }
+        _ => {
+            for pixel in pixels.iter_mut() {
+                let color = PColor::from_argb(*pixel);
+                *pixel = match filter {
+                    PFilter::FilterThreshold => {
+                        let value = if luminance(color) >= value { 1.0 } else { 0.0 };
+                        with_rgb(color, value, value, value)
+                    }
+                    PFilter::FilterGray => {
+                        let value = luminance(color);
+                        with_rgb(color, value, value, value)
+                    }
+                    PFilter::FilterInvert => with_rgb(color, 1.0 - color.r, 1.0 - color.g, 1.0 - color.b),
+                    PFilter::FilterPosterize => {
+                        let posterize = |channel: f32| (channel * value).floor().min(value - 1.0) / (value - 1.0);
+                        with_rgb(color, posterize(color.r), posterize(color.g), posterize(color.b))
+                    }
+                    PFilter::FilterOpaque => PColor { a: 1.0, ..color }.to_argb(),
+                    _ => *pixel
+                };
+            }
+        }
+    }
+}
+
+// One axis of Processing's blur. Weights fall off with the square of
+// the distance, pixels outside of the image are left out
+fn blur_pixels (pixels: &[u32], width: i32, height: i32, radius: i32, direction: (i32, i32)) -> Vec<u32> {
+    let mut result = Vec::with_capacity(pixels.len());
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = [0.0; 4];
+            let mut total = 0.0;
+            for offset in (1 - radius)..radius {
+                let (sample_x, sample_y) = (x + direction.0 * offset, y + direction.1 * offset);
+                if sample_x < 0 || sample_y < 0 || sample_x >= width || sample_y >= height { continue; }
+
+                let color = PColor::from_argb(pixels[(sample_y * width + sample_x) as usize]);
+                let weight = ((radius - offset.abs()) * (radius - offset.abs())) as f32;
+                for (channel, value) in sum.iter_mut().zip([color.r, color.g, color.b, color.a]) {
+                    *channel += value * weight;
+                }
+                total += weight;
+            }
+            let [r, g, b, a] = sum.map(|channel| channel / total);
+            result.push(PColor { r, g, b, a }.to_argb());
+        }
+    }
+    result
+}
+
+// Builds the filter pipeline for a texture format the first time it is needed
+fn prepare_filter_pass (format: TextureFormat) {
+    let filter_pass = {
+        let renderer_state = get_renderer_state();
+        if renderer_state.filter_passes.contains_key(&format) { return; }
+        let Some(device) = renderer_state.device.as_ref() else { return; };
+        FilterPass::new(device, format)
+    };
+
+    set_renderer_state! {
+        filter_passes.insert(format, filter_pass);
+    }
+}
+
+// Filters the canvas. Everything drawn so far this frame
+// is rendered first, like with loadPixels()
+pub(crate) fn filter_canvas (filter: PFilter, param: Option<f32>) -> PResult<()> {
+    let (value, radius) = filter_settings(filter, param, get_renderer_state().pixel_density)?;
+    flush();
+
+    let Some(format) = get_renderer_state().canvas.as_ref().map(|canvas| canvas.format) else { return Ok(()); };
+    prepare_filter_pass(format);
+
+    let version = {
+        let renderer_state = get_renderer_state();
+        let (Some(device), Some(queue), Some(canvas), Some(filter_pass)) = (
+            renderer_state.device.as_ref(),
+            renderer_state.queue.as_ref(),
+            renderer_state.canvas.as_ref(),
+            renderer_state.filter_passes.get(&format)
+        ) else { return Ok(()); };
+
+        filter_pass.apply(device, queue, canvas, filter, value, radius);
+        renderer_state.canvas_version + 1
+    };
+
+    set_renderer_state! {
+        canvas_version = version;
+    }
+    Ok(())
+}
+
+// Filters an image. Runs on the GPU once the window has been created,
+// before that (or without a window at all) it runs on the CPU.
+// Graphics are filtered like the canvas, by drawing into them
+pub(crate) fn filter_image (image: PImage, filter: PFilter, param: Option<f32>) -> PResult<()> {
+    let (width, height, pixel_density, is_graphics) =
+        with_image(image, |data| (data.width, data.height, data.pixel_density, data.graphics.is_some()))?;
+
+    if is_graphics {
+        if get_image_state().drawing == Some(image) {
+            return filter_canvas(filter, param);
+        }
+        begin_draw(image)?;
+        let result = filter_canvas(filter, param);
+        end_draw(image)?;
+        return result;
+    }
+
+    let (value, radius) = filter_settings(filter, param, pixel_density)?;
+    let mut pixels = std::mem::take(&mut IMAGE_STATE.try_write().expect("Could not write to RwLock").images[image.id as usize].pixels);
+
+    prepare_filter_pass(TextureFormat::Rgba8Unorm);
+    {
+        let renderer_state = get_renderer_state();
+        match (
+            renderer_state.device.as_ref(),
+            renderer_state.queue.as_ref(),
+            renderer_state.filter_passes.get(&TextureFormat::Rgba8Unorm)
+        ) {
+            (Some(device), Some(queue), Some(filter_pass)) if width > 0 && height > 0 => {
+                let target = RenderTarget::new(device, "Filter Image", width, height, TextureFormat::Rgba8Unorm, TextureFormat::Rgba8Unorm, 1);
+                write_pixels(queue, &target, &pixels);
+                filter_pass.apply(device, queue, &target, filter, value, radius);
+                pixels = read_pixels(device, queue, &target);
+            }
+            _ => filter_pixels(&mut pixels, width, height, filter, value, radius)
+        }
+    }
+
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let data = &mut state.images[image.id as usize];
+    data.pixels = pixels;
+    data.dirty = true;
+    Ok(())
+}
+
+// filter() and filterParam() in Processing. Filters that take a
+// parameter use their default with filter(), except posterize
+// which needs the number of levels
+#[no_mangle]
+pub extern "C" fn filter (filter: PFilter) {
+    filter_canvas(filter, None).or_panic();
+}
+
+#[no_mangle]
+pub extern "C" fn filterParam (filter: PFilter, param: f32) {
+    filter_canvas(filter, Some(param)).or_panic();
+}
+
+#[no_mangle]
+pub extern "C" fn imageFilter (image: PImage, filter: PFilter) {
+    filter_image(image, filter, None).or_panic();
+}
+
+#[no_mangle]
+pub extern "C" fn imageFilterParam (image: PImage, filter: PFilter, param: f32) {
+    filter_image(image, filter, Some(param)).or_panic();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [(PFilter, Option<f32>); 8] = [
+        (PFilter::FilterThreshold, Some(0.5)),
+        (PFilter::FilterGray, None),
+        (PFilter::FilterInvert, None),
+        (PFilter::FilterPosterize, Some(4.0)),
+        (PFilter::FilterBlur, Some(1.0)),
+        (PFilter::FilterErode, None),
+        (PFilter::FilterDilate, None),
+        (PFilter::FilterOpaque, None)
+    ];
+
+    // Channels can be one step apart, from rounding on the GPU
+    fn assert_close (filter: PFilter, gpu: &[u32], cpu: &[u32]) {
+        for (i, (gpu, cpu)) in gpu.iter().zip(cpu).enumerate() {
+            let close = (0..4).all(|channel| ((gpu >> (channel * 8)) & 0xff).abs_diff((cpu >> (channel * 8)) & 0xff) <= 1);
+            assert!(close, "{:?} differs at pixel {}: GPU {:08x}, CPU {:08x}", filter, i, gpu, cpu);
+        }
+    }
+
+    // Runs every filter on a small image on the GPU and compares it with
+    // filter_pixels(). Skipped when there is no adapter to run on
+    #[test]
+    fn gpu_filters_match_filter_pixels () {
+        let instance = wgpu::Instance::default();
+        let Some(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+            .expect("Could not get a device");
+        let filter_pass = FilterPass::new(&device, TextureFormat::Rgba8Unorm);
+
+        // Colors that vary in every channel, with some transparency
+        let (width, height) = (9, 7);
+        let pixels: Vec<u32> = (0..width * height).map(|i| {
+            let [a, r, g, b] = [200 + i * 7 % 56, i * 37 % 256, i * 91 % 256, i * 53 % 256];
+            a << 24 | r << 16 | g << 8 | b
+        }).collect();
+
+        for (filter, param) in FILTERS {
+            let (value, radius) = filter_settings(filter, param, 1).unwrap();
+
+            let target = RenderTarget::new(&device, "Filter Test", width, height, TextureFormat::Rgba8Unorm, TextureFormat::Rgba8Unorm, 1);
+            write_pixels(&queue, &target, &pixels);
+            filter_pass.apply(&device, &queue, &target, filter, value, radius);
+            let gpu = read_pixels(&device, &queue, &target);
+
+            let mut cpu = pixels.clone();
+            filter_pixels(&mut cpu, width, height, filter, value, radius);
+            assert_close(filter, &gpu, &cpu);
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use std::{ffi::c_char, mem::swap};
+
+use crate::{
+    color::color_state::COLOR_STATE,
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    image::{add_image, pixel_count, state::{get_image_state, set_image_state, GraphicsData, ImageData, ImageState, IMAGE_STATE}, with_image, PImage, PImageFormat},
+    light::{reset_lights, state::LIGHT_STATE},
+    record::{new_recording, state::RECORD_STATE, write_recording, PRecordFormat},
+    renderer::{camera::PRenderer, frame::flush, projection::Projection, readback::{read_pixels, write_pixels}, state::{get_renderer_state, set_renderer_state, RENDERER_STATE}, target::RenderTarget, texture::{create_render_texture, GpuTexture}},
+    shape::{state::SHAPE_STATE, transform::reset_transforms},
+    text::state::TEXT_STATE
+};
+
+// Offscreen canvas made by createGraphics(). It is an image, so it can
+// be drawn with image() and passed to anything else that takes one
+pub type PGraphics = PImage;
+
+// Width and height are in points, the graphics
+// gets the pixel density of the sketch
+pub(crate) fn create_graphics (width: u32, height: u32) -> PResult<PGraphics> {
+    if width == 0 || height == 0 {
+        return Err(PError::InvalidGraphicsSize { width, height });
+    }
+
+    let pixel_density = get_renderer_state().pixel_density;
+    let (Some(pixel_width), Some(pixel_height)) = (width.checked_mul(pixel_density), height.checked_mul(pixel_density)) else {
+        return Err(PError::InvalidImageSize { width, height });
+    };
+    pixel_count(pixel_width, pixel_height)?;
+    let graphics = add_image(ImageData {
+        texture: Some(create_render_texture(pixel_width, pixel_height)),
+        graphics: Some(Box::new(GraphicsData::new(pixel_density))),
+        ..ImageData::new(pixel_width, pixel_height, pixel_density, PImageFormat::ImageArgb, vec![])
+    });
+
+    create_target(graphics);
+    Ok(graphics)
+}
+
+// Graphics that write what is drawn into them to a file instead of
+// drawing it, at every endDraw(). They have no texture to draw into,
+// and stay transparent when drawn with image()
+pub(crate) fn create_graphics_with_recorder (width: u32, height: u32, format: PRecordFormat, path: &str) -> PResult<PGraphics> {
+    if width == 0 || height == 0 {
+        return Err(PError::InvalidGraphicsSize { width, height });
+    }
+
+    let pixels = vec![0; pixel_count(width, height)?];
+    let mut data = GraphicsData::new(1);
+    data.record_state.recording = Some(new_recording(format, path, width as f32, height as f32)?);
+    Ok(add_image(ImageData {
+        graphics: Some(Box::new(data)),
+        ..ImageData::new(width, height, 1, PImageFormat::ImageArgb, pixels)
+    }))
+}
+
+// Graphics made with createGraphicsWithRecorder()
+fn only_records (graphics: PGraphics) -> bool {
+    with_image(graphics, |data| data.graphics.is_some() && data.texture.is_none()).unwrap_or(false)
+}
+
+// Makes the render target of a graphics and the texture image() draws
+// it with. Does nothing until the window (and with it the GPU) exists
+fn create_target (graphics: PGraphics) {
+    let Ok((width, height, pixel_density, Some(texture), Some(camera))) =
+        with_image(graphics, |data| (data.width, data.height, data.pixel_density, data.texture, data.graphics.as_ref().map(|graphics| graphics.camera)))
+    else { return; };
+
+    let (canvas, projection, gpu_texture) = {
+        let renderer_state = get_renderer_state();
+        let (Some(device), Some(texture_bindings)) = (
+            renderer_state.device.as_ref(),
+            renderer_state.texture_bindings.as_ref()
+        ) else { return; };
+
+        // Same format, sample count and depth buffer as the
+        // canvas, so the same pipelines can draw into both
+        let format = renderer_state.canvas_format;
+        let (points_width, points_height) = ((width / pixel_density) as f32, (height / pixel_density) as f32);
+        let canvas = RenderTarget::new(device, "Graphics", width, height, format.texture_format(), format.sample_format(), renderer_state.sample_count);
+        (
+            if renderer_state.renderer == PRenderer::P3D { canvas.with_depth(device) } else { canvas },
+            Projection::new(device, points_width, points_height, camera.matrix(renderer_state.renderer, points_width, points_height)),
+            GpuTexture::new(device, &texture_bindings.layout, &texture_bindings.samplers, width, height, format.texture_format())
+        )
+    };
+
+    RENDERER_STATE.try_write().expect("Could not write to RwLock").textures[texture].gpu = Some(gpu_texture);
+
+    let mut image_state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    if let Some(data) = image_state.images[graphics.id as usize].graphics.as_mut() {
+        data.canvas = Some(canvas);
+        data.projection = Some(projection);
+    }
+}
+
+// Swaps what drawing functions draw into and with between the sketch
+// and a graphics. Called once to start drawing into the graphics,
+// and once more to go back to the sketch
+fn swap_target (graphics: PGraphics) {
+    let mut image_state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let ImageState { images, pixels, pixels_version, pixels_drawn, .. } = &mut *image_state;
+    let image = &mut images[graphics.id as usize];
+    let data = image.graphics.as_mut().expect("Image is not a graphics");
+
+    let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    swap(&mut renderer_state.canvas, &mut data.canvas);
+    swap(&mut renderer_state.projection, &mut data.projection);
+    swap(&mut renderer_state.draw_list, &mut data.draw_list);
+    swap(&mut renderer_state.clear_color, &mut data.clear_color);
+    swap(&mut renderer_state.canvas_version, &mut data.canvas_version);
+    swap(&mut renderer_state.pixel_density, &mut data.pixel_density);
+    swap(&mut renderer_state.shader, &mut data.shader);
+    swap(&mut renderer_state.camera, &mut data.camera);
+    swap(&mut renderer_state.clear_depth, &mut data.clear_depth);
+
+    swap(pixels, &mut image.pixels);
+    swap(pixels_version, &mut data.pixels_version);
+    swap(pixels_drawn, &mut data.pixels_drawn);
+
+    swap(&mut *COLOR_STATE.try_write().expect("Could not write to RwLock"), &mut data.color_state);
+    swap(&mut *SHAPE_STATE.try_write().expect("Could not write to RwLock"), &mut data.shape_state);
+    swap(&mut *RECORD_STATE.try_write().expect("Could not write to RwLock"), &mut data.record_state);
+    swap(&mut *LIGHT_STATE.try_write().expect("Could not write to RwLock"), &mut data.light_state);
+
+    let mut text_state = TEXT_STATE.try_write().expect("Could not write to RwLock");
+    swap(&mut *text_state, &mut data.text_state);
+    swap(&mut text_state.fonts, &mut data.text_state.fonts);
+}
+
+// Copies the graphics being drawn into to the texture image() draws it with.
+// Drawing it with image() while drawing into it then works too
+fn update_texture (graphics: PGraphics) {
+    let Ok(Some(texture)) = with_image(graphics, |data| data.texture) else { return; };
+
+    let renderer_state = get_renderer_state();
+    let (Some(device), Some(queue), Some(canvas), Some(gpu_texture)) = (
+        renderer_state.device.as_ref(),
+        renderer_state.queue.as_ref(),
+        renderer_state.canvas.as_ref(),
+        renderer_state.textures.get(texture).and_then(|slot| slot.gpu.as_ref())
+    ) else { return; };
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Graphics Texture") });
+    encoder.copy_texture_to_texture(
+        canvas.texture.as_image_copy(),
+        gpu_texture.texture.as_image_copy(),
+        gpu_texture.texture.size()
+    );
+    queue.submit(Some(encoder.finish()));
+}
+
+// Loads the pixels of a graphics that isn't being drawn into, if its
+// canvas has changed since. Unlike beginDraw() and loadPixels(), this
+// also works while drawing into another graphics. Graphics have no
+// pixels before the window exists
+pub(crate) fn read_graphics_pixels (graphics: PGraphics) -> PResult<()> {
+    let mut image_state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let image = image_state.images.get_mut(graphics.id as usize).ok_or(PError::UnknownImage(graphics.id))?;
+    let Some(data) = image.graphics.as_mut() else { return Ok(()); };
+    if data.pixels_changed || data.pixels_version == Some(data.canvas_version) { return Ok(()); }
+
+    let renderer_state = get_renderer_state();
+    let (Some(device), Some(queue), Some(canvas)) = (
+        renderer_state.device.as_ref(),
+        renderer_state.queue.as_ref(),
+        data.canvas.as_ref()
+    ) else { return Ok(()); };
+
+    // Everything drawn is flushed at endDraw(), so a clear
+    // color that is left means nothing has been drawn yet
+    image.pixels = match data.clear_color {
+        Some(color) => vec![color.to_argb(); (canvas.width * canvas.height) as usize],
+        None => read_pixels(device, queue, canvas)
+    };
+    data.pixels_version = Some(data.canvas_version);
+    Ok(())
+}
+
+// Writes pixels changed while not drawing into a graphics to its canvas,
+// and to the texture image() draws it with. Done right before the
+// graphics is drawn into or drawn, so imageSet() stays cheap
+pub(crate) fn write_graphics_pixels (graphics: PGraphics) -> PResult<()> {
+    let changed = with_image(graphics, |data| data.graphics.as_ref().is_some_and(|data| data.pixels_changed))?;
+    if !changed { return Ok(()); }
+
+    // Anything drawn so far may draw the graphics
+    // with image(), so it has to be drawn before it changes
+    flush();
+
+    let mut image_state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let image = &mut image_state.images[graphics.id as usize];
+    let Some(data) = image.graphics.as_mut() else { return Ok(()); };
+
+    let renderer_state = get_renderer_state();
+    let (Some(device), Some(queue), Some(canvas)) = (
+        renderer_state.device.as_ref(),
+        renderer_state.queue.as_ref(),
+        data.canvas.as_ref()
+    ) else { return Ok(()); };
+    if image.pixels.len() != (canvas.width * canvas.height) as usize { return Ok(()); }
+
+    write_pixels(queue, canvas, &image.pixels);
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Graphics Pixels") });
+    canvas.reload_samples(&mut encoder);
+    if let Some(gpu_texture) = image.texture.and_then(|texture| renderer_state.textures.get(texture)).and_then(|slot| slot.gpu.as_ref()) {
+        encoder.copy_texture_to_texture(
+            canvas.texture.as_image_copy(),
+            gpu_texture.texture.as_image_copy(),
+            gpu_texture.texture.size()
+        );
+    }
+    queue.submit(Some(encoder.finish()));
+
+    // The pixels cover whatever clear was still to come
+    data.canvas_version += 1;
+    data.pixels_version = Some(data.canvas_version);
+    data.clear_color = None;
+    data.pixels_changed = false;
+    Ok(())
+}
+
+pub(crate) fn begin_draw (graphics: PGraphics) -> PResult<()> {
+    if !with_image(graphics, |data| data.graphics.is_some())? {
+        return Err(PError::NotGraphics(graphics.id));
+    }
+    if get_image_state().drawing.is_some() {
+        return Err(PError::GraphicsAlreadyStarted);
+    }
+
+    // What has been drawn so far may draw this graphics
+    // with image(), so it has to be drawn before it changes
+    flush();
+    write_graphics_pixels(graphics)?;
+    swap_target(graphics);
+    set_image_state! {
+        drawing = Some(graphics);
+    }
+    // Like the canvas at the start of a frame
+    set_renderer_state! {
+        clear_depth = true;
+    }
+    reset_lights();
+    reset_transforms();
+    Ok(())
+}
+
+pub(crate) fn end_draw (graphics: PGraphics) -> PResult<()> {
+    if get_image_state().drawing != Some(graphics) {
+        return Err(PError::GraphicsNotStarted);
+    }
+
+    flush();
+    // Nothing is drawn for graphics that only record
+    let only_records = only_records(graphics);
+    if only_records {
+        set_renderer_state! {
+            draw_list.clear();
+        }
+    }
+    update_texture(graphics);
+    swap_target(graphics);
+    set_image_state! {
+        drawing = None;
+    }
+
+    if !only_records { return Ok(()); }
+    let image_state = get_image_state();
+    let recording = image_state.images[graphics.id as usize].graphics.as_ref().and_then(|data| data.record_state.recording.as_ref());
+    recording.map_or(Ok(()), write_recording)
+}
+
+// Called once the window exists. Graphics made in setup() get their
+// render target, and what was drawn into them there is drawn for real
+pub(crate) fn prepare_graphics () {
+    let waiting: Vec<PGraphics> = {
+        let image_state = get_image_state();
+        (0..image_state.images.len() as u32)
+            .map(|id| PImage { id })
+            .filter(|image| image_state.drawing != Some(*image))
+            .filter(|image| image_state.images[image.id as usize].graphics.as_ref().is_some_and(|data| data.canvas.is_none()))
+            .filter(|image| image_state.images[image.id as usize].texture.is_some())
+            .collect()
+    };
+
+    for graphics in waiting {
+        create_target(graphics);
+
+        // The sketch's own drawing from setup() isn't flushed here,
+        // it may draw these graphics and has to wait for them
+        swap_target(graphics);
+        flush();
+        update_texture(graphics);
+        swap_target(graphics);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn createGraphics (width: u32, height: u32) -> PGraphics {
+    create_graphics(width, height).or_panic()
+}
+
+/// Graphics the size of width x height points, that write what is drawn
+/// into them to the file at path at every endDraw()
+///
+/// # Safety
+/// `path` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn createGraphicsWithRecorder (width: u32, height: u32, format: PRecordFormat, path: *const c_char) -> PGraphics {
+    let path = string_from(path);
+    create_graphics_with_recorder(width, height, format, &path).or_panic()
+}
+
+// Every drawing function draws into the graphics until endDraw(),
+// with the graphics' own styles. Graphics can't be nested
+#[no_mangle]
+pub extern "C" fn beginDraw (graphics: PGraphics) {
+    begin_draw(graphics).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn endDraw (graphics: PGraphics) {
+    end_draw(graphics).or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::PColor, image::free_image, light::{add_light, default_lights, state::{get_light_state, PLightKind}}, lock_state};
+
+    #[test]
+    fn graphics_have_their_own_lights () {
+        let _state = lock_state();
+        set_renderer_state! {
+            renderer = PRenderer::P3D;
+        }
+        default_lights().unwrap();
+        let sketch_lights = get_light_state().lights.len();
+
+        let graphics = create_graphics(4, 4).unwrap();
+        begin_draw(graphics).unwrap();
+        assert!(get_light_state().lights.is_empty());
+        add_light(PLightKind::Ambient, PColor::WHITE, [0.0; 3], [0.0; 3], 0.0, 0.0).unwrap();
+        end_draw(graphics).unwrap();
+        assert_eq!(get_light_state().lights.len(), sketch_lights);
+
+        // Lights start off every time, like at the start of a frame
+        begin_draw(graphics).unwrap();
+        assert!(get_light_state().lights.is_empty());
+        end_draw(graphics).unwrap();
+
+        free_image(graphics).unwrap();
+        reset_lights();
+    }
+}
@@ -0,0 +1,239 @@
+use crate::{
+    color::{color_state::get_color_state, PColor},
+    error::{OrPanic, PError, PResult},
+    math::matrix::IDENTITY,
+    record::record_image,
+    image::{graphics::write_graphics_pixels, pixels::{load_image_pixels, update_image_pixels}, state::{get_image_state, ImageData, ImageState, IMAGE_STATE}},
+    renderer::{state::{get_renderer_state, set_renderer_state}, texture::{create_texture, free_texture, update_texture, SampledTexture, TextureId}, vertex::Vertex},
+    shape::transform::model_matrix
+};
+
+pub mod create;
+pub mod edit;
+pub mod filter;
+pub mod graphics;
+pub mod pixels;
+pub mod state;
+
+// Handle to an image, returned by the functions that create one
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct PImage {
+    pub id: u32
+}
+
+// What the pixels of an image hold. Pixels are always 0xAARRGGBB,
+// the format decides which parts of them are used
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PImageFormat {
+    // Alpha is ignored, the image is drawn opaque
+    ImageRgb,
+    #[default]
+    ImageArgb,
+    // Only the lowest byte is used, as the alpha of a white image
+    ImageAlpha
+}
+
+pub(crate) fn add_image (image: ImageData) -> PImage {
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    match state.free_images.pop() {
+        Some(id) => {
+            state.images[id as usize] = image;
+            PImage { id }
+        }
+        None => {
+            state.images.push(image);
+            PImage { id: state.images.len() as u32 - 1 }
+        }
+    }
+}
+
+// Frees an image and its texture. Its id is given to the next
+// image made, so the handle can't be used anymore after this
+pub(crate) fn free_image (image: PImage) -> PResult<()> {
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    if image.id as usize >= state.images.len() || state.free_images.contains(&image.id) {
+        return Err(PError::UnknownImage(image.id));
+    }
+    if state.drawing == Some(image) {
+        return Err(PError::GraphicsInUse(image.id));
+    }
+
+    let data = std::mem::replace(&mut state.images[image.id as usize], ImageData::new(0, 0, 1, PImageFormat::ImageArgb, vec![]));
+    state.free_images.push(image.id);
+    drop(state);
+    if let Some(texture) = data.texture {
+        free_texture(texture);
+    }
+    Ok(())
+}
+
+// Number of pixels in a width x height image, an error
+// for sizes whose pixels can't be counted or held
+pub(crate) fn pixel_count (width: u32, height: u32) -> PResult<usize> {
+    (width as usize).checked_mul(height as usize)
+        .filter(|count| count.checked_mul(4).is_some())
+        .ok_or(PError::InvalidImageSize { width, height })
+}
+
+// Runs callback with the data of an image
+pub(crate) fn with_image<T> (image: PImage, callback: impl FnOnce(&ImageData) -> T) -> PResult<T> {
+    let state = get_image_state();
+    let data = state.images.get(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+    Ok(callback(data))
+}
+
+// Size in points, the number of pixels divided by the pixel density
+pub(crate) fn image_size (image: PImage) -> PResult<(u32, u32)> {
+    with_image(image, |data| (data.width / data.pixel_density, data.height / data.pixel_density))
+}
+
+// Width, height and a copy of the pixels of an image.
+// Graphics are read back from the GPU first
+pub(crate) fn read_image (image: PImage) -> PResult<(u32, u32, Vec<u32>)> {
+    load_image_pixels(image)?;
+    let state = get_image_state();
+    let data = state.images.get(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+    let pixels = if state.drawing == Some(image) { &state.pixels } else { &data.pixels };
+    Ok((data.width, data.height, pixels.clone()))
+}
+
+// Runs callback with the pixels of an image, its width and height.
+// Graphics are read back before and written back after
+pub(crate) fn edit_image<T> (image: PImage, callback: impl FnOnce(&mut Vec<u32>, u32, u32) -> T) -> PResult<T> {
+    load_image_pixels(image)?;
+    let result = {
+        let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+        let drawing = state.drawing == Some(image);
+        let ImageState { images, pixels, .. } = &mut *state;
+        let data = images.get_mut(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+
+        // The pixels of graphics being drawn into are in the canvas' buffer
+        let pixels = if drawing { pixels } else { &mut data.pixels };
+        // Graphics have no pixels before the window exists
+        pixels.resize((data.width * data.height) as usize, 0);
+        callback(pixels, data.width, data.height)
+    };
+    update_image_pixels(image)?;
+    Ok(result)
+}
+
+// Texture pixels are RGBA
+pub(crate) fn to_rgba (format: PImageFormat, pixels: &[u32]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|pixel| {
+            let [a, r, g, b] = pixel.to_be_bytes();
+            match format {
+                PImageFormat::ImageRgb => [r, g, b, 255],
+                PImageFormat::ImageArgb => [r, g, b, a],
+                PImageFormat::ImageAlpha => [255, 255, 255, b]
+            }
+        })
+        .collect()
+}
+
+// Texture an image is drawn with. Images get one the first time they are
+// drawn and it is updated when they are drawn after their pixels changed.
+// Graphics have theirs from the start. None for images without pixels
+pub(crate) fn image_texture (image: PImage) -> PResult<Option<TextureId>> {
+    write_graphics_pixels(image)?;
+    let (width, height, texture, dirty) = with_image(image, |data| (data.width, data.height, data.texture, data.dirty))?;
+    if width == 0 || height == 0 { return Ok(None); }
+    if texture.is_some() && !dirty { return Ok(texture); }
+
+    let pixels = with_image(image, |data| to_rgba(data.format, &data.pixels))?;
+    let texture = match texture {
+        Some(texture) => {
+            update_texture(texture, |slot| {
+                slot.width = width;
+                slot.height = height;
+                slot.pixels = pixels;
+            });
+            texture
+        }
+        None => create_texture(width, height, pixels)
+    };
+
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let data = &mut state.images[image.id as usize];
+    data.texture = Some(texture);
+    data.dirty = false;
+    Ok(Some(texture))
+}
+
+// Draws an image with its top left corner at x, y. size is in points
+// and defaults to the size of the image. The image is drawn with
+// the current blend mode, like the shapes drawn around it
+pub(crate) fn draw_image (image: PImage, x: f32, y: f32, size: Option<(f32, f32)>) -> PResult<()> {
+    let (width, height) = match size {
+        Some(size) => size,
+        None => image_size(image).map(|(width, height)| (width as f32, height as f32))?
+    };
+    let Some(texture) = image_texture(image)? else { return Ok(()); };
+    let model = model_matrix();
+    record_image(image, [x, y, width, height], (model != IDENTITY).then_some(&model))?;
+
+    let color = PColor::WHITE.to_shader_color(get_renderer_state().canvas_format);
+    let vertices = [
+        Vertex::new(x, y, 0.0).with_color(color).with_uv(0.0, 0.0),
+        Vertex::new(x + width, y, 0.0).with_color(color).with_uv(1.0, 0.0),
+        Vertex::new(x + width, y + height, 0.0).with_color(color).with_uv(1.0, 1.0),
+        Vertex::new(x, y + height, 0.0).with_color(color).with_uv(0.0, 1.0)
+    ].map(|vertex| vertex.transformed(&model));
+
+    let blend_mode = get_color_state().blend_mode;
+    let shader = get_renderer_state().shader;
+    set_renderer_state! {
+        draw_list.push(&vertices, &[0, 1, 2, 0, 2, 3], blend_mode, Some(SampledTexture::clamped(texture)), shader, false);
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "C" fn image (image: PImage, x: f32, y: f32) {
+    draw_image(image, x, y, None).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageRect (image: PImage, x: f32, y: f32, width: f32, height: f32) {
+    draw_image(image, x, y, Some((width, height))).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn imageWidth (image: PImage) -> u32 {
+    image_size(image).or_panic().0
+}
+
+#[no_mangle]
+pub extern "C" fn imageHeight (image: PImage) -> u32 {
+    image_size(image).or_panic().1
+}
+
+// Frees an image made by loadImage(), createImage(), get() or createGraphics().
+// Images aren't freed otherwise, so ones made every frame should be freed
+#[no_mangle]
+pub extern "C" fn freeImage (image: PImage) {
+    free_image(image).or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::create::create_image, lock_state};
+
+    #[test]
+    fn freed_images_are_reused () {
+        let _state = lock_state();
+        let image = create_image(4, 2, PImageFormat::ImageArgb).unwrap();
+        free_image(image).unwrap();
+        assert_eq!(free_image(image), Err(PError::UnknownImage(image.id)));
+        assert_eq!(image_size(image).unwrap(), (0, 0));
+
+        let reused = create_image(1, 1, PImageFormat::ImageArgb).unwrap();
+        assert_eq!(reused, image);
+        assert_eq!(image_size(reused).unwrap(), (1, 1));
+        free_image(reused).unwrap();
+    }
+}
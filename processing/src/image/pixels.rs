@@ -0,0 +1,295 @@
+use crate::{
+    color::{blend::PBlendMode, PColor},
+    error::{OrPanic, PError, PResult},
+    image::{add_image, graphics::{read_graphics_pixels, write_graphics_pixels}, state::{get_image_state, set_image_state, ImageData, IMAGE_STATE}, pixel_count, with_image, PImage, PImageFormat},
+    renderer::{frame::flush, readback::{read_pixels, write_pixels}, state::{get_renderer_state, set_renderer_state}, vertex::Vertex}
+};
+
+// Pixels of the canvas handed out to C by pixels().
+// data stays valid until the next call to loadPixels()
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct PPixels {
+    pub data: *mut u32,
+    pub length: usize,
+}
+
+// Copies the canvas into the pixel buffer. Everything drawn so far
+// this frame is rendered first. The buffer has pixelWidth() * pixelHeight()
+// entries, so with pixelDensity(2) there are four pixels per point.
+// Only works once the window has been created
+pub(crate) fn load_pixels () {
+    flush();
+
+    let (pixels, version) = {
+        let renderer_state = get_renderer_state();
+        let (Some(device), Some(queue), Some(canvas)) = (
+            renderer_state.device.as_ref(),
+            renderer_state.queue.as_ref(),
+            renderer_state.canvas.as_ref()
+        ) else { return; };
+
+        (read_pixels(device, queue, canvas), renderer_state.canvas_version)
+    };
+
+    set_image_state! {
+        pixels = pixels;
+        pixels_version = Some(version);
+        pixels_drawn = 0;
+    }
+}
+
+// Whether the pixel buffer still matches the canvas with everything
+// drawn this frame. Only set() keeps it up to date while drawing
+fn pixels_current () -> bool {
+    let renderer_state = get_renderer_state();
+    let image_state = get_image_state();
+    renderer_state.clear_color.is_none()
+        && renderer_state.draw_list.indices.len() == image_state.pixels_drawn
+        && image_state.pixels_version == Some(renderer_state.canvas_version)
+}
+
+// Loads the pixels again if the canvas has changed since they were loaded
+fn ensure_pixels () {
+    if !pixels_current() {
+        load_pixels();
+    }
+}
+
+// Writes the pixel buffer back into the canvas. Anything drawn
+// since loadPixels() is drawn first and then covered by the pixels
+pub(crate) fn update_pixels () {
+    flush();
+
+    let version = {
+        let renderer_state = get_renderer_state();
+        let image_state = get_image_state();
+        let (Some(device), Some(queue), Some(canvas)) = (
+            renderer_state.device.as_ref(),
+            renderer_state.queue.as_ref(),
+            renderer_state.canvas.as_ref()
+        ) else { return; };
+
+        // The buffer is from before a resize, it doesn't fit anymore
+        if image_state.pixels.len() != (canvas.width * canvas.height) as usize { return; }
+
+        write_pixels(queue, canvas, &image_state.pixels);
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Update Pixels") });
+        canvas.reload_samples(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+
+        renderer_state.canvas_version + 1
+    };
+
+    // The buffer is what is in the canvas now, so it stays loaded
+    set_renderer_state! {
+        canvas_version = version;
+    }
+    set_image_state! {
+        pixels_version = Some(version);
+        pixels_drawn = 0;
+    }
+}
+
+fn canvas_size () -> (u32, u32, u32) {
+    let renderer_state = get_renderer_state();
+    let density = renderer_state.pixel_density;
+    renderer_state.canvas.as_ref().map_or((0, 0, density), |canvas| (canvas.width, canvas.height, density))
+}
+
+// Color of the canvas pixel at x, y (in pixels), 0 outside of the canvas
+pub(crate) fn canvas_pixel (pixel_x: i32, pixel_y: i32) -> u32 {
+    ensure_pixels();
+
+    let (width, height, _) = canvas_size();
+    if pixel_x < 0 || pixel_y < 0 || pixel_x >= width as i32 || pixel_y >= height as i32 { return 0; }
+
+    get_image_state()
+        .pixels
+        .get((pixel_y as u32 * width + pixel_x as u32) as usize)
+        .copied()
+        .unwrap_or(0)
+}
+
+// Color of the pixel at x, y (in points), 0 outside of the canvas.
+// With pixelDensity(2) this is the top left of the four pixels
+pub(crate) fn get_pixel (x: i32, y: i32) -> u32 {
+    let density = canvas_size().2 as i32;
+    canvas_pixel(x * density, y * density)
+}
+
+// Copies a part of the canvas into a new image, at the canvas' pixel density.
+// Pixels outside of the canvas are transparent. Like every image, it is
+// kept until it is freed with freeImage()
+pub(crate) fn get_region (x: i32, y: i32, width: i32, height: i32) -> PResult<PImage> {
+    let (canvas_width, canvas_height, density) = canvas_size();
+    let density_i = density as i32;
+    let (Some(region_width), Some(region_height)) = (width.max(0).checked_mul(density_i), height.max(0).checked_mul(density_i)) else {
+        return Err(PError::InvalidImageSize { width: width as u32, height: height as u32 });
+    };
+    let count = pixel_count(region_width as u32, region_height as u32)?;
+    ensure_pixels();
+
+    let pixels = {
+        let state = get_image_state();
+        let mut pixels = Vec::with_capacity(count);
+        for row in 0..region_height {
+            let canvas_y = y * density_i + row;
+            for column in 0..region_width {
+                let canvas_x = x * density_i + column;
+                let inside = canvas_x >= 0 && canvas_y >= 0 && canvas_x < canvas_width as i32 && canvas_y < canvas_height as i32;
+                pixels.push(
+                    if inside { state.pixels.get((canvas_y as u32 * canvas_width + canvas_x as u32) as usize).copied().unwrap_or(0) }
+                    else { 0 }
+                );
+            }
+        }
+        pixels
+    };
+
+    Ok(add_image(ImageData::new(region_width as u32, region_height as u32, density, PImageFormat::ImageArgb, pixels)))
+}
+
+// Replaces size x size canvas pixels from x, y (in pixels) with color,
+// alpha included. They are drawn like a shape, so they keep their place
+// among everything else drawn this frame. They are drawn on the screen
+// projection at depth 0, so in P3D the camera and depth test don't move or
+// hide them. A loaded pixel buffer is changed too, reading them then
+// doesn't need the canvas to be read back
+pub(crate) fn set_canvas_pixels (pixel_x: i32, pixel_y: i32, size: i32, argb: u32) {
+    let current = pixels_current();
+    let (width, height, density) = canvas_size();
+    let color = PColor::from_argb(argb).to_shader_color(get_renderer_state().canvas_format);
+    let (x0, y0) = (pixel_x as f32 / density as f32, pixel_y as f32 / density as f32);
+    let (x1, y1) = (x0 + size as f32 / density as f32, y0 + size as f32 / density as f32);
+
+    let vertices = [
+        Vertex::new(x0, y0, 0.0).with_color(color),
+        Vertex::new(x1, y0, 0.0).with_color(color),
+        Vertex::new(x1, y1, 0.0).with_color(color),
+        Vertex::new(x0, y1, 0.0).with_color(color)
+    ];
+
+    set_renderer_state! {
+        draw_list.push(&vertices, &[0, 1, 2, 0, 2, 3], PBlendMode::BlendReplace, None, None, true);
+    }
+    if !current { return; }
+
+    let drawn = get_renderer_state().draw_list.indices.len();
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    state.pixels_drawn = drawn;
+    if state.pixels.len() != (width * height) as usize { return; }
+
+    for y in pixel_y..pixel_y + size {
+        for x in pixel_x..pixel_x + size {
+            if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 { continue; }
+            state.pixels[(y as u32 * width + x as u32) as usize] = argb;
+        }
+    }
+}
+
+// Sets the pixel at x, y (in points) to color, replacing it (alpha
+// included). With pixelDensity(2) that is four pixels.
+// Like in Processing, shader() doesn't apply to it
+pub(crate) fn set_pixel (x: i32, y: i32, argb: u32) {
+    let density = canvas_size().2 as i32;
+    set_canvas_pixels(x * density, y * density, density, argb);
+}
+
+// loadPixels() for an image. Images made on the CPU always have their
+// pixels, graphics copy theirs from the GPU like the canvas
+pub(crate) fn load_image_pixels (image: PImage) -> PResult<()> {
+    if !with_image(image, |data| data.graphics.is_some())? { return Ok(()); }
+
+    if get_image_state().drawing == Some(image) {
+        load_pixels();
+        return Ok(());
+    }
+    read_graphics_pixels(image)
+}
+
+// updatePixels() for an image. Images made on the CPU upload their
+// pixels the next time they are drawn, graphics write them back right away
+pub(crate) fn update_image_pixels (image: PImage) -> PResult<()> {
+    if !with_image(image, |data| data.graphics.is_some())? {
+        IMAGE_STATE.try_write().expect("Could not write to RwLock").images[image.id as usize].dirty = true;
+        return Ok(());
+    }
+
+    if get_image_state().drawing == Some(image) {
+        update_pixels();
+        return Ok(());
+    }
+    if let Some(data) = IMAGE_STATE.try_write().expect("Could not write to RwLock").images[image.id as usize].graphics.as_mut() {
+        data.pixels_changed = true;
+    }
+    write_graphics_pixels(image)
+}
+
+// Pixel buffer of an image. For graphics being drawn into, that is
+// the canvas' buffer
+fn image_pixels (image: PImage) -> PResult<PPixels> {
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    let drawing = state.drawing == Some(image);
+    let data = state.images.get_mut(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+    let pixels = if drawing { &mut state.pixels } else { &mut data.pixels };
+    Ok(PPixels {
+        data: pixels.as_mut_ptr(),
+        length: pixels.len()
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn loadPixels () {
+    load_pixels();
+}
+
+#[no_mangle]
+pub extern "C" fn updatePixels () {
+    update_pixels();
+}
+
+// The buffer filled by loadPixels(). Pixels are 0xAARRGGBB,
+// row by row from the top, pixelWidth() pixels per row
+#[no_mangle]
+pub extern "C" fn pixels () -> PPixels {
+    let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+    PPixels {
+        data: state.pixels.as_mut_ptr(),
+        length: state.pixels.len()
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn get (x: i32, y: i32) -> u32 {
+    get_pixel(x, y)
+}
+
+#[no_mangle]
+pub extern "C" fn getRegion (x: i32, y: i32, width: i32, height: i32) -> PImage {
+    get_region(x, y, width, height).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn set (x: i32, y: i32, color: u32) {
+    set_pixel(x, y, color);
+}
+
+#[no_mangle]
+pub extern "C" fn imageLoadPixels (image: PImage) {
+    load_image_pixels(image).or_panic();
+}
+
+// Changes made through imagePixels() show up once this is called
+#[no_mangle]
+pub extern "C" fn imageUpdatePixels (image: PImage) {
+    update_image_pixels(image).or_panic();
+}
+
+// Pixels of an image, laid out like pixels(). Stays valid
+// until the image is changed by anything but the pointer
+#[no_mangle]
+pub extern "C" fn imagePixels (image: PImage) -> PPixels {
+    image_pixels(image).or_panic()
+}
@@ -0,0 +1,141 @@
+use lazy_static::lazy_static;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::{
+    color::{color_state::ColorState, PColor},
+    image::{PImage, PImageFormat},
+    light::state::LightState,
+    record::state::RecordState,
+    renderer::{camera::Camera, custom_shader::ShaderId, draw_list::DrawList, projection::Projection, target::RenderTarget, texture::TextureId},
+    shape::state::ShapeState,
+    text::state::TextState
+};
+
+// Pixels of an image, 0xAARRGGBB row by row from the top.
+// width and height are in pixels, the image is pixel_density
+// times smaller when drawn, like the canvas
+pub struct ImageData {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_density: u32,
+    pub format: PImageFormat,
+    pub pixels: Vec<u32>,
+    // Texture image() draws with, made the first time the image is drawn.
+    // Set when pixels have changed since, they get uploaded the next time
+    pub texture: Option<TextureId>,
+    pub dirty: bool,
+    // Set for images made with createGraphics(). Their pixels
+    // are on the GPU, pixels is their loadPixels() buffer
+    pub graphics: Option<Box<GraphicsData>>,
+}
+
+impl ImageData {
+    pub fn new (width: u32, height: u32, pixel_density: u32, format: PImageFormat, pixels: Vec<u32>) -> Self {
+        Self {
+            width,
+            height,
+            pixel_density,
+            format,
+            pixels,
+            texture: None,
+            dirty: false,
+            graphics: None
+        }
+    }
+}
+
+// Everything that belongs to the target drawing functions draw into.
+// beginDraw() swaps it with the sketch's (see image::graphics),
+// so a graphics has its own canvas, projection and styles
+pub struct GraphicsData {
+    // Made once there is a GPU, draws from before are kept in draw_list
+    pub canvas: Option<RenderTarget>,
+    pub projection: Option<Projection>,
+    pub draw_list: DrawList,
+    pub clear_color: Option<PColor>,
+    pub canvas_version: u64,
+    pub pixel_density: u32,
+    pub pixels_version: Option<u64>,
+    pub pixels_drawn: usize,
+    // Set when pixels has been changed while not drawing into the
+    // graphics, they are written to its canvas before it is used
+    pub pixels_changed: bool,
+    pub shader: Option<ShaderId>,
+    pub camera: Camera,
+    pub clear_depth: bool,
+
+    pub color_state: ColorState,
+    pub shape_state: ShapeState,
+    // Only the text settings are swapped, fonts are shared
+    pub text_state: TextState,
+    pub record_state: RecordState,
+    pub light_state: LightState,
+}
+
+impl GraphicsData {
+    // Graphics start out transparent, with the default styles
+    pub fn new (pixel_density: u32) -> Self {
+        Self {
+            canvas: None,
+            projection: None,
+            draw_list: DrawList::default(),
+            clear_color: Some(PColor::TRANSPARENT),
+            canvas_version: 0,
+            pixel_density,
+            pixels_version: None,
+            pixels_drawn: 0,
+            pixels_changed: false,
+            shader: None,
+            camera: Camera::default(),
+            clear_depth: true,
+            color_state: ColorState::default(),
+            shape_state: ShapeState::default(),
+            text_state: TextState::default(),
+            record_state: RecordState::default(),
+            light_state: LightState::default()
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ImageState {
+    pub images: Vec<ImageData>,
+    // Ids of freed images, given to the next images made
+    pub free_images: Vec<u32>,
+
+    // Copy of the canvas made by loadPixels(), along with
+    // the canvas version it was made from (see RendererState)
+    pub pixels: Vec<u32>,
+    pub pixels_version: Option<u64>,
+    // Indices of the draw list the copy already includes. set() writes
+    // into the copy too, so get() doesn't read the canvas back after it
+    pub pixels_drawn: usize,
+
+    // Graphics being drawn into, between beginDraw() and endDraw()
+    pub drawing: Option<PImage>,
+}
+
+lazy_static! {
+    pub static ref IMAGE_STATE: Arc<RwLock<ImageState>> = Arc::new(RwLock::new(ImageState::default()));
+}
+
+pub fn get_image_state () -> RwLockReadGuard<'static, ImageState> {
+    IMAGE_STATE.try_read().unwrap()
+}
+
+macro_rules! set_image_state {
+
+    // base cases
+    ($var:ident$(.$var2:ident)* = $value:expr;) => {
+        {
+            crate::image::state::IMAGE_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)* = $value;
+        }
+    };
+
+    // expr - expr
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_image_state!{ $var$(.$var2)* = $value; };
+        set_image_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+}
+pub(crate) use set_image_state;
@@ -2,4 +2,25 @@ pub mod core;
 pub mod renderer;
 pub mod math;
 pub mod color;
-pub mod event;
\ No newline at end of file
+pub mod event;
+pub mod shape;
+pub mod light;
+pub mod text;
+pub mod image;
+pub mod shader;
+pub mod record;
+pub mod error;
+pub mod sketch;
+
+// The state is global, tests that use it take turns
+#[cfg(test)]
+pub(crate) fn lock_state () -> std::sync::MutexGuard<'static, ()> {
+    static STATE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    STATE.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+// Path of a file in tests/fixtures
+#[cfg(test)]
+pub(crate) fn fixture (name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
@@ -0,0 +1,183 @@
+use std::f32::consts::PI;
+
+use crate::{
+    color::PColor,
+    error::{OrPanic, PError, PResult},
+    light::state::{get_light_state, set_light_state, PLight, PLightKind, LIGHT_STATE},
+    renderer::{
+        camera::{check_3d, PRenderer},
+        lighting::{LightUniform, LightingUniform, AMBIENT, DIRECTIONAL, MAX_LIGHTS, POINT, SPOT},
+        state::get_renderer_state
+    }
+};
+
+pub mod state;
+
+// Turns on a light with the current falloff and specular color
+pub(crate) fn add_light (kind: PLightKind, color: PColor, position: [f32; 3], direction: [f32; 3], angle: f32, concentration: f32) -> PResult<()> {
+    check_3d()?;
+    let mut state = LIGHT_STATE.try_write().expect("Could not write to RwLock");
+    if state.lights.len() >= MAX_LIGHTS {
+        return Err(PError::TooManyLights);
+    }
+
+    let (falloff, specular) = (state.falloff, state.specular);
+    state.lights.push(PLight { kind, color, specular, position, direction, falloff, angle, concentration });
+    Ok(())
+}
+
+// Processing's default lights: a gray ambient light and a
+// gray directional light shining into the screen
+pub(crate) fn default_lights () -> PResult<()> {
+    check_3d()?;
+    set_light_state! {
+        lights.clear();
+        falloff = [1.0, 0.0, 0.0];
+        specular = PColor::BLACK;
+    }
+    let gray = PColor::from_rgba(128.0, 128.0, 128.0, 255.0);
+    add_light(PLightKind::Ambient, gray, [0.0; 3], [0.0; 3], 0.0, 0.0)?;
+    add_light(PLightKind::Directional, gray, [0.0; 3], [0.0, 0.0, -1.0], 0.0, 0.0)
+}
+
+// Called before every frame
+pub(crate) fn reset_lights () {
+    set_light_state! {
+        lights.clear();
+    }
+}
+
+// Lights and material for fills drawn now, None while
+// no lights are on (or in 2D, where there are none)
+pub(crate) fn lighting_uniform () -> Option<LightingUniform> {
+    let state = get_light_state();
+    let renderer_state = get_renderer_state();
+    if state.lights.is_empty() || renderer_state.renderer != PRenderer::P3D {
+        return None;
+    }
+
+    let format = renderer_state.canvas_format;
+    let (width, height) = renderer_state.target_size();
+    let [x, y, z] = renderer_state.camera.eye(width, height);
+    let rgb = |color: PColor| {
+        let [r, g, b, _] = color.to_shader_color(format);
+        [r, g, b, 1.0]
+    };
+
+    let mut lights = [LightUniform::default(); MAX_LIGHTS];
+    for (uniform, light) in lights.iter_mut().zip(&state.lights) {
+        let kind = match light.kind {
+            PLightKind::Ambient => AMBIENT,
+            PLightKind::Directional => DIRECTIONAL,
+            PLightKind::Point => POINT,
+            PLightKind::Spot => SPOT
+        };
+        let ([px, py, pz], [dx, dy, dz], [constant, linear, quadratic]) = (light.position, light.direction, light.falloff);
+        *uniform = LightUniform {
+            position: [px, py, pz, 1.0],
+            direction: [dx, dy, dz, 0.0],
+            diffuse: rgb(light.color),
+            specular: rgb(light.specular),
+            falloff: [constant, linear, quadratic, 0.0],
+            spot: [light.angle.cos(), light.concentration, kind, 0.0]
+        };
+    }
+
+    Some(LightingUniform {
+        ambient: rgb(state.ambient.unwrap_or_default()),
+        specular: rgb(state.material_specular),
+        emissive: rgb(state.emissive),
+        eye: [x, y, z, 1.0],
+        shininess: state.shininess,
+        count: state.lights.len() as u32,
+        lit: 1,
+        fill_ambient: state.ambient.is_none() as u32,
+        lights
+    })
+}
+
+// Gray ambient and directional lights, with no falloff or specular highlights.
+// Only in 3D, like the other light functions
+#[no_mangle]
+pub extern "C" fn lights () {
+    default_lights().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn noLights () {
+    reset_lights();
+}
+
+// Light coming from everywhere, it lights every side of a shape the same
+#[no_mangle]
+pub extern "C" fn ambientLight (r: f32, g: f32, b: f32) {
+    add_light(PLightKind::Ambient, PColor::from_rgba(r, g, b, 255.0), [0.0; 3], [0.0; 3], 0.0, 0.0).or_panic()
+}
+
+// Light shining from far away in the direction nx, ny, nz
+#[no_mangle]
+pub extern "C" fn directionalLight (r: f32, g: f32, b: f32, nx: f32, ny: f32, nz: f32) {
+    add_light(PLightKind::Directional, PColor::from_rgba(r, g, b, 255.0), [0.0; 3], [nx, ny, nz], 0.0, 0.0).or_panic()
+}
+
+// Light shining in every direction from x, y, z
+#[no_mangle]
+pub extern "C" fn pointLight (r: f32, g: f32, b: f32, x: f32, y: f32, z: f32) {
+    add_light(PLightKind::Point, PColor::from_rgba(r, g, b, 255.0), [x, y, z], [0.0; 3], 0.0, 0.0).or_panic()
+}
+
+// Cone of light from x, y, z in the direction nx, ny, nz. angle (in radians)
+// is how wide the cone is, concentration how much it fades away from its center
+#[no_mangle]
+pub extern "C" fn spotLight (r: f32, g: f32, b: f32, x: f32, y: f32, z: f32, nx: f32, ny: f32, nz: f32, angle: f32, concentration: f32) {
+    add_light(PLightKind::Spot, PColor::from_rgba(r, g, b, 255.0), [x, y, z], [nx, ny, nz], angle.clamp(0.0, PI), concentration).or_panic()
+}
+
+// How lights turned on after this fade with the distance d to what they
+// light: they are divided by constant + linear * d + quadratic * d * d
+#[no_mangle]
+pub extern "C" fn lightFalloff (constant: f32, linear: f32, quadratic: f32) {
+    set_light_state! {
+        falloff = [constant, linear, quadratic];
+    }
+}
+
+// Color of the highlights of lights turned on after this
+#[no_mangle]
+pub extern "C" fn lightSpecular (r: f32, g: f32, b: f32) {
+    set_light_state! {
+        specular = PColor::from_rgba(r, g, b, 255.0);
+    }
+}
+
+// How much ambient light fills reflect. They reflect their own color until this is called
+#[no_mangle]
+pub extern "C" fn ambient (r: f32, g: f32, b: f32) {
+    set_light_state! {
+        ambient = Some(PColor::from_rgba(r, g, b, 255.0));
+    }
+}
+
+// Color of the highlights on fills, with lightSpecular()
+#[no_mangle]
+pub extern "C" fn specular (r: f32, g: f32, b: f32) {
+    set_light_state! {
+        material_specular = PColor::from_rgba(r, g, b, 255.0);
+    }
+}
+
+// Color fills give off without any light
+#[no_mangle]
+pub extern "C" fn emissive (r: f32, g: f32, b: f32) {
+    set_light_state! {
+        emissive = PColor::from_rgba(r, g, b, 255.0);
+    }
+}
+
+// How sharp highlights are, higher is smaller and shinier
+#[no_mangle]
+pub extern "C" fn shininess (shine: f32) {
+    set_light_state! {
+        shininess = shine.max(0.0);
+    }
+}
@@ -0,0 +1,91 @@
+use lazy_static::lazy_static;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::color::PColor;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PLightKind {
+    Ambient,
+    Directional,
+    Point,
+    Spot
+}
+
+// A light turned on with one of the light functions. Falloff and
+// specular are the ones set when it was turned on, like in Processing
+#[derive(Debug, Copy, Clone)]
+pub struct PLight {
+    pub kind: PLightKind,
+    pub color: PColor,
+    pub specular: PColor,
+    pub position: [f32; 3],
+    pub direction: [f32; 3],
+    pub falloff: [f32; 3],
+    // Only used by spot lights, the angle is in radians
+    pub angle: f32,
+    pub concentration: f32,
+}
+
+// Lights are turned off at the start of every frame, so they have to be
+// turned on in draw(). The material is kept until it is changed
+pub struct LightState {
+    pub lights: Vec<PLight>,
+    pub falloff: [f32; 3],
+    pub specular: PColor,
+
+    // Set with ambient(), the fill color is used until it is called
+    pub ambient: Option<PColor>,
+    pub material_specular: PColor,
+    pub emissive: PColor,
+    pub shininess: f32,
+}
+
+impl Default for LightState {
+    fn default () -> Self {
+        Self {
+            lights: vec![],
+            falloff: [1.0, 0.0, 0.0],
+            specular: PColor::BLACK,
+            ambient: None,
+            material_specular: PColor::from_rgba(125.0, 125.0, 125.0, 255.0),
+            emissive: PColor::BLACK,
+            shininess: 1.0
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref LIGHT_STATE: Arc<RwLock<LightState>> = Arc::new(RwLock::new(LightState::default()));
+}
+
+pub fn get_light_state () -> RwLockReadGuard<'static, LightState> {
+    LIGHT_STATE.try_read().unwrap()
+}
+
+macro_rules! set_light_state {
+
+    // base cases
+    ($var:ident$(.$var2:ident)* = $value:expr;) => {
+        {
+            crate::light::state::LIGHT_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)* = $value;
+        }
+    };
+    ($var:ident$(.$var2:ident)*($($value:expr),*);) => {
+        {
+            crate::light::state::LIGHT_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)*($($value),*);
+        }
+    };
+
+    // expr - expr
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_light_state!{ $var$(.$var2)* = $value; };
+        set_light_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+
+    // fn - expr
+    ($var:ident$(.$var2:ident)*($($value:expr),*); $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_light_state!{ $var$(.$var2)*($($value),*); };
+        set_light_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+}
+pub(crate) use set_light_state;
@@ -0,0 +1,150 @@
+// Point and tangent math for bezier and Catmull-Rom curves.
+// Like in Processing, these work on one coordinate at a time,
+// so call them once for x and once for y
+
+// How far along its tangents the control points of a cubic bezier
+// are placed for it to follow a quarter of a circle or an ellipse
+pub(crate) const KAPPA: f32 = 0.552_284_8;
+
+pub fn bezier_point (a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    let mt = 1.0 - t;
+    mt * mt * mt * a + 3.0 * mt * mt * t * b + 3.0 * mt * t * t * c + t * t * t * d
+}
+
+pub fn bezier_tangent (a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    let mt = 1.0 - t;
+    3.0 * mt * mt * (b - a) + 6.0 * mt * t * (c - b) + 3.0 * t * t * (d - c)
+}
+
+// The segment of a Catmull-Rom curve between b and c, as the control
+// values of the bezier curve that traces the same path.
+// A tightness of 0 gives a Catmull-Rom spline, 1 gives straight lines
+pub fn curve_to_bezier (a: f32, b: f32, c: f32, d: f32, tightness: f32) -> [f32; 4] {
+    let scale = (1.0 - tightness) / 6.0;
+    [b, b + (c - a) * scale, c - (d - b) * scale, c]
+}
+
+pub fn curve_point (a: f32, b: f32, c: f32, d: f32, t: f32, tightness: f32) -> f32 {
+    let [a, b, c, d] = curve_to_bezier(a, b, c, d, tightness);
+    bezier_point(a, b, c, d, t)
+}
+
+pub fn curve_tangent (a: f32, b: f32, c: f32, d: f32, t: f32, tightness: f32) -> f32 {
+    let [a, b, c, d] = curve_to_bezier(a, b, c, d, tightness);
+    bezier_tangent(a, b, c, d, t)
+}
+
+// Control points of the cubic bezier tracing the same path as a quadratic one
+pub fn quadratic_to_cubic (start: [f32; 2], control: [f32; 2], end: [f32; 2]) -> [[f32; 2]; 4] {
+    let lerp = |from: [f32; 2], to: [f32; 2]| [
+        from[0] + (to[0] - from[0]) * 2.0 / 3.0,
+        from[1] + (to[1] - from[1]) * 2.0 / 3.0
+    ];
+    [start, lerp(start, control), lerp(end, control), end]
+}
+
+// Recursion limit for adaptive flattening, 2^16 segments is plenty
+const MAX_DEPTH: u32 = 16;
+
+fn distance_to_line (point: [f32; 2], start: [f32; 2], end: [f32; 2]) -> f32 {
+    let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length < f32::EPSILON {
+        let (px, py) = (point[0] - start[0], point[1] - start[1]);
+        return (px * px + py * py).sqrt();
+    }
+    ((point[0] - start[0]) * dy - (point[1] - start[1]) * dx).abs() / length
+}
+
+fn flatten_adaptive (points: [[f32; 2]; 4], tolerance: f32, depth: u32, output: &mut Vec<[f32; 2]>) {
+    let [p0, p1, p2, p3] = points;
+    let flat =
+        distance_to_line(p1, p0, p3) <= tolerance &&
+        distance_to_line(p2, p0, p3) <= tolerance;
+
+    if flat || depth >= MAX_DEPTH {
+        output.push(p3);
+        return;
+    }
+
+    // Split in half with de Casteljau's algorithm
+    let mid = |a: [f32; 2], b: [f32; 2]| [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0];
+    let (p01, p12, p23) = (mid(p0, p1), mid(p1, p2), mid(p2, p3));
+    let (p012, p123) = (mid(p01, p12), mid(p12, p23));
+    let center = mid(p012, p123);
+
+    flatten_adaptive([p0, p01, p012, center], tolerance, depth + 1, output);
+    flatten_adaptive([center, p123, p23, p3], tolerance, depth + 1, output);
+}
+
+// Turns a cubic bezier into line segments. The start point is not
+// included, as it is usually the end of the previous segment.
+// With a detail of 0 the curve is subdivided until it is within
+// tolerance of the real curve, otherwise it is split into detail segments
+pub fn flatten_bezier (points: [[f32; 2]; 4], detail: u32, tolerance: f32) -> Vec<[f32; 2]> {
+    let mut output = vec![];
+
+    if detail == 0 {
+        flatten_adaptive(points, tolerance, 0, &mut output);
+    }
+    else {
+        let [p0, p1, p2, p3] = points;
+        for i in 1..=detail {
+            let t = i as f32 / detail as f32;
+            output.push([
+                bezier_point(p0[0], p1[0], p2[0], p3[0], t),
+                bezier_point(p0[1], p1[1], p2[1], p3[1], t)
+            ]);
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close (actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-4, "expected {}, got {}", expected, actual);
+    }
+
+    // Processing's bezierPoint() reference example
+    #[test]
+    fn bezier_point_at_ends_and_middle () {
+        assert_close(bezier_point(85.0, 10.0, 90.0, 15.0, 0.0), 85.0);
+        assert_close(bezier_point(85.0, 10.0, 90.0, 15.0, 0.5), 50.0);
+        assert_close(bezier_point(85.0, 10.0, 90.0, 15.0, 1.0), 15.0);
+    }
+
+    #[test]
+    fn bezier_tangent_at_ends_and_middle () {
+        // 3 (b - a) at the start and 3 (d - c) at the end
+        assert_close(bezier_tangent(85.0, 10.0, 90.0, 15.0, 0.0), -225.0);
+        assert_close(bezier_tangent(85.0, 10.0, 90.0, 15.0, 0.5), 7.5);
+        assert_close(bezier_tangent(85.0, 10.0, 90.0, 15.0, 1.0), -225.0);
+    }
+
+    // Expected values come from the Catmull-Rom polynomial
+    // 0.5 (2b + (c - a) t + (2a - 5b + 4c - d) t² + (3b - a - 3c + d) t³)
+    #[test]
+    fn curve_point_at_ends_and_middle () {
+        assert_close(curve_point(0.0, 10.0, 30.0, 70.0, 0.0, 0.0), 10.0);
+        assert_close(curve_point(0.0, 10.0, 30.0, 70.0, 0.5, 0.0), 18.125);
+        assert_close(curve_point(0.0, 10.0, 30.0, 70.0, 1.0, 0.0), 30.0);
+    }
+
+    #[test]
+    fn curve_tangent_at_ends_and_middle () {
+        // (c - a) / 2 at the start and (d - b) / 2 at the end
+        assert_close(curve_tangent(0.0, 10.0, 30.0, 70.0, 0.0, 0.0), 15.0);
+        assert_close(curve_tangent(0.0, 10.0, 30.0, 70.0, 0.5, 0.0), 18.75);
+        assert_close(curve_tangent(0.0, 10.0, 30.0, 70.0, 1.0, 0.0), 30.0);
+    }
+
+    #[test]
+    fn curve_with_full_tightness_is_a_line () {
+        assert_close(curve_point(0.0, 10.0, 30.0, 70.0, 0.5, 1.0), 20.0);
+        assert_close(curve_tangent(0.0, 10.0, 30.0, 70.0, 0.5, 1.0), 30.0);
+    }
+}
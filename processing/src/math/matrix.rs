@@ -0,0 +1,193 @@
+// 4x4 matrices, column major like WGSL expects. Projections map to
+// wgpu's clip space (depth from 0 to 1) with y flipped, as y goes down
+// in Processing's coordinates
+pub type PMatrix = [[f32; 4]; 4];
+
+pub const IDENTITY: PMatrix = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// a * b, so b is applied first
+pub fn multiply (a: &PMatrix, b: &PMatrix) -> PMatrix {
+    let mut result = [[0.0; 4]; 4];
+    for (column, result_column) in result.iter_mut().enumerate() {
+        for (row, value) in result_column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][row] * b[column][k]).sum();
+        }
+    }
+    result
+}
+
+// Point x, y, z (with w = 1) transformed by matrix
+pub fn transform_point (matrix: &PMatrix, [x, y, z]: [f32; 3]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = matrix[0][row] * x + matrix[1][row] * y + matrix[2][row] * z + matrix[3][row];
+    }
+    result
+}
+
+fn subtract (a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross (a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot (a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub fn normalize (a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    if length == 0.0 { return a; }
+    [a[0] / length, a[1] / length, a[2] / length]
+}
+
+// Transforms normals the way model transforms the surfaces they are on:
+// the inverse transpose of its upper 3x3, as model alone would tilt
+// them with a non-uniform scale. Its columns are the cofactors of
+// model's, divided by the determinant
+pub fn normal_matrix (model: &PMatrix) -> PMatrix {
+    let column = |i: usize| [model[i][0], model[i][1], model[i][2]];
+    let (x, y, z) = (column(0), column(1), column(2));
+    let (x_normal, y_normal, z_normal) = (cross(y, z), cross(z, x), cross(x, y));
+    let determinant = dot(x, x_normal);
+    // Flattened, whatever is left can't be lit properly anyway
+    let scale = if determinant == 0.0 { 1.0 } else { 1.0 / determinant };
+    let scaled = |[x, y, z]: [f32; 3]| [x * scale, y * scale, z * scale, 0.0];
+    [scaled(x_normal), scaled(y_normal), scaled(z_normal), [0.0, 0.0, 0.0, 1.0]]
+}
+
+// View matrix of a camera at eye looking at center, with up pointing
+// up on the screen. The camera looks down its -z axis
+pub fn look_at (eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> PMatrix {
+    let forward = normalize(subtract(center, eye));
+    let side = normalize(cross(forward, up));
+    let up = cross(side, forward);
+    [
+        [side[0], up[0], -forward[0], 0.0],
+        [side[1], up[1], -forward[1], 0.0],
+        [side[2], up[2], -forward[2], 0.0],
+        [-dot(side, eye), -dot(up, eye), dot(forward, eye), 1.0],
+    ]
+}
+
+// Perspective projection of the part of the view between
+// left and right, bottom and top at distance near from the camera
+pub fn frustum (left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> PMatrix {
+    [
+        [2.0 * near / (right - left), 0.0, 0.0, 0.0],
+        [0.0, -2.0 * near / (top - bottom), 0.0, 0.0],
+        [(right + left) / (right - left), -(top + bottom) / (top - bottom), far / (near - far), -1.0],
+        [0.0, 0.0, near * far / (near - far), 0.0],
+    ]
+}
+
+// fov is the vertical field of view in radians
+pub fn perspective (fov: f32, aspect: f32, near: f32, far: f32) -> PMatrix {
+    let top = near * (fov / 2.0).tan();
+    frustum(-top * aspect, top * aspect, -top, top, near, far)
+}
+
+pub fn ortho (left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> PMatrix {
+    [
+        [2.0 / (right - left), 0.0, 0.0, 0.0],
+        [0.0, -2.0 / (top - bottom), 0.0, 0.0],
+        [0.0, 0.0, 1.0 / (near - far), 0.0],
+        [-(right + left) / (right - left), (top + bottom) / (top - bottom), near / (near - far), 1.0],
+    ]
+}
+
+pub fn translation (x: f32, y: f32, z: f32) -> PMatrix {
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [x, y, z, 1.0],
+    ]
+}
+
+pub fn scaling (x: f32, y: f32, z: f32) -> PMatrix {
+    [
+        [x, 0.0, 0.0, 0.0],
+        [0.0, y, 0.0, 0.0],
+        [0.0, 0.0, z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+// Rotations by angle radians around an axis. With y going
+// down, positive angles turn clockwise on the screen
+pub fn rotation_x (angle: f32) -> PMatrix {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, cos, sin, 0.0],
+        [0.0, -sin, cos, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn rotation_y (angle: f32) -> PMatrix {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, 0.0, -sin, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [sin, 0.0, cos, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+pub fn rotation_z (angle: f32) -> PMatrix {
+    let (sin, cos) = angle.sin_cos();
+    [
+        [cos, sin, 0.0, 0.0],
+        [-sin, cos, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close (a: [f32; 4], b: [f32; 4]) {
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn normal_matrix_undoes_non_uniform_scale () {
+        let normal = normal_matrix(&scaling(2.0, 4.0, 1.0));
+        assert_close(normal[0], [0.5, 0.0, 0.0, 0.0]);
+        assert_close(normal[1], [0.0, 0.25, 0.0, 0.0]);
+        assert_close(normal[2], [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn normal_matrix_keeps_rotations_and_ignores_translation () {
+        let model = multiply(&translation(10.0, 20.0, 30.0), &rotation_z(0.7));
+        let normal = normal_matrix(&model);
+        let rotation = rotation_z(0.7);
+        for i in 0..3 {
+            assert_close(normal[i], rotation[i]);
+        }
+        assert_close(normal[3], [0.0, 0.0, 0.0, 1.0]);
+    }
+
+    // Normals stay perpendicular to the surface after a shear
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular () {
+        let mut model = IDENTITY;
+        model[1][0] = 1.5;
+        let normal = normal_matrix(&model);
+        let tangent = transform_point(&model, [0.0, 1.0, 0.0]);
+        let transformed = transform_point(&normal, [1.0, 0.0, 0.0]);
+        assert!(dot([tangent[0], tangent[1], tangent[2]], [transformed[0], transformed[1], transformed[2]]).abs() < 1e-5);
+    }
+}
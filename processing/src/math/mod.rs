@@ -0,0 +1,3 @@
+pub mod curve;
+pub mod matrix;
+pub mod vector;
@@ -0,0 +1,8 @@
+// A point or direction, like Processing's PVector
+#[derive(Default, Debug, Copy, Clone, PartialEq)]
+#[repr(C)]
+pub struct PVector {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
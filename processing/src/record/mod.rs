@@ -0,0 +1,267 @@
+use std::ffi::c_char;
+
+use crate::{
+    color::PColor,
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    image::{read_image, to_rgba, with_image, PImage},
+    math::{curve::KAPPA, matrix::PMatrix},
+    record::state::{get_record_state, PathSegment, RecordedImage, RecordedItem, RecordedPath, Recording, RECORD_STATE},
+    renderer::{camera::PRenderer, state::get_renderer_state},
+    shape::{path_pieces, state::{PShapeClose, PShapeKind, ShapeCurve, ShapePath, ShapeStyle, ShapeVertex}}
+};
+
+pub mod pdf;
+pub mod state;
+pub mod svg;
+
+// Files beginRecord() and createGraphicsWithRecorder() can write
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PRecordFormat {
+    RecordSvg,
+    RecordPdf
+}
+
+// A recording of width x height points. Only 2D drawing can be recorded
+pub(crate) fn new_recording (format: PRecordFormat, path: &str, width: f32, height: f32) -> PResult<Recording> {
+    if get_renderer_state().renderer == PRenderer::P3D {
+        return Err(PError::RecordNot2D);
+    }
+    Ok(Recording { format, path: path.to_string(), width, height, pages: vec![], items: vec![] })
+}
+
+// Everything drawn after it is also written to a file, the size of the
+// window, at endRecord(). It is drawn to the window as usual too
+pub(crate) fn begin_record (format: PRecordFormat, path: &str) -> PResult<()> {
+    if get_record_state().recording.is_some() {
+        return Err(PError::RecordAlreadyStarted);
+    }
+    let (width, height) = {
+        let renderer_state = get_renderer_state();
+        renderer_state.width.zip(renderer_state.height).ok_or(PError::WindowNotCreated)?
+    };
+
+    let recording = new_recording(format, path, width, height)?;
+    RECORD_STATE.try_write().expect("Could not write to RwLock").recording = Some(recording);
+    Ok(())
+}
+
+pub(crate) fn end_record () -> PResult<()> {
+    let recording = RECORD_STATE.try_write().expect("Could not write to RwLock").recording.take().ok_or(PError::RecordNotStarted)?;
+    write_recording(&recording)
+}
+
+pub(crate) fn write_recording (recording: &Recording) -> PResult<()> {
+    let contents = match recording.format {
+        PRecordFormat::RecordSvg => svg::write_svg(recording)?.into_bytes(),
+        PRecordFormat::RecordPdf => pdf::write_pdf(recording)
+    };
+    std::fs::write(&recording.path, contents)
+        .map_err(|error| PError::RecordNotWritten { path: recording.path.clone(), message: error.to_string() })
+}
+
+// What is drawn after it goes on a new page
+pub(crate) fn next_page () -> PResult<()> {
+    let mut state = RECORD_STATE.try_write().expect("Could not write to RwLock");
+    let recording = state.recording.as_mut().ok_or(PError::RecordNotStarted)?;
+    if recording.format != PRecordFormat::RecordPdf {
+        return Err(PError::RecordNotPdf);
+    }
+    let page = std::mem::take(&mut recording.items);
+    recording.pages.push(page);
+    Ok(())
+}
+
+pub(crate) fn is_recording () -> bool {
+    get_record_state().recording.is_some()
+}
+
+fn push_items (items: impl IntoIterator<Item = RecordedItem>) {
+    let mut state = RECORD_STATE.try_write().expect("Could not write to RwLock");
+    if let Some(recording) = state.recording.as_mut() {
+        recording.items.extend(items);
+    }
+}
+
+// Lines between the vertices, with the curves they were flattened
+// from (starting and ending at vertices) in place of their lines
+fn outline (vertices: &[ShapeVertex], curves: &[&ShapeCurve], close: bool) -> Vec<PathSegment> {
+    let Some(first) = vertices.first() else { return vec![]; };
+    let mut segments = vec![PathSegment::MoveTo([first.x, first.y])];
+    let mut i = 0;
+    while i + 1 < vertices.len() {
+        match curves.iter().find(|curve| curve.start == i && curve.end > i && curve.end < vertices.len()) {
+            Some(curve) => {
+                segments.push(curve.segment);
+                i = curve.end;
+            }
+            None => {
+                i += 1;
+                segments.push(PathSegment::LineTo([vertices[i].x, vertices[i].y]));
+            }
+        }
+    }
+    if close {
+        segments.push(PathSegment::Close);
+    }
+    segments
+}
+
+// The curves of the outline (None) or of a contour
+fn curves_of (path: &ShapePath, contour: Option<usize>) -> Vec<&ShapeCurve> {
+    path.curves.iter().filter(|curve| curve.contour == contour).collect()
+}
+
+// Four quarter circles, like the round points strokes draw
+fn circle ([x, y]: [f32; 2], radius: f32) -> Vec<PathSegment> {
+    let handle = radius * KAPPA;
+    vec![
+        PathSegment::MoveTo([x + radius, y]),
+        PathSegment::CubicTo([x + radius, y + handle], [x + handle, y + radius], [x, y + radius]),
+        PathSegment::CubicTo([x - handle, y + radius], [x - radius, y + handle], [x - radius, y]),
+        PathSegment::CubicTo([x - radius, y - handle], [x - handle, y - radius], [x, y - radius]),
+        PathSegment::CubicTo([x + handle, y - radius], [x + radius, y - handle], [x + radius, y]),
+        PathSegment::Close
+    ]
+}
+
+// The 2D part of a matrix as SVG's matrix() takes it, None without one
+fn recorded_transform (matrix: Option<&PMatrix>) -> Option<[f32; 6]> {
+    matrix.map(|m| [m[0][0], m[0][1], m[1][0], m[1][1], m[3][0], m[3][1]])
+}
+
+// Mirrors a path that is being drawn, with the matrix it is drawn
+// with. Paths get the colors of their first vertex, curves of polygons
+// are written as curves and textured fills are recorded with the fill color
+pub(crate) fn record_path (path: &ShapePath, style: &ShapeStyle, matrix: Option<&PMatrix>) {
+    if !is_recording() { return; }
+
+    let transform = recorded_transform(matrix);
+    let item = |segments, first: &ShapeVertex, fill: bool, stroke: bool| RecordedItem::Path(RecordedPath {
+        segments,
+        fill: (fill && style.fill.is_some()).then_some(first.fill),
+        stroke: (stroke && style.stroke.is_some()).then_some(first.stroke),
+        stroke_weight: style.stroke_weight,
+        transform
+    });
+    let Some(first) = path.vertices.first() else { return; };
+
+    let items = match path.kind {
+        // Points are round dots filled with the stroke color
+        PShapeKind::ShapePoints => {
+            if style.stroke.is_none() { return; }
+            path.vertices.iter().map(|vertex| RecordedItem::Path(RecordedPath {
+                segments: circle([vertex.x, vertex.y], style.stroke_weight / 2.0),
+                fill: Some(vertex.stroke),
+                stroke: None,
+                stroke_weight: style.stroke_weight,
+                transform
+            })).collect()
+        }
+        PShapeKind::ShapeLines => vec![item(path.vertices.chunks_exact(2).flat_map(|line| outline(line, &[], false)).collect(), first, false, true)],
+        PShapeKind::ShapePolygon => {
+            let mut segments = outline(&path.vertices, &curves_of(path, None), path.close == PShapeClose::ShapeClose);
+            for (i, contour) in path.contours.iter().enumerate() {
+                segments.extend(outline(contour, &curves_of(path, Some(i)), true));
+            }
+            vec![item(segments, first, true, true)]
+        }
+        kind => path_pieces(kind, &path.vertices).iter().map(|piece| item(outline(piece, &[], true), &piece[0], true, true)).collect()
+    };
+    push_items(items);
+}
+
+// Glyph outlines of text that is being drawn, filled with fill
+pub(crate) fn record_text (segments: Vec<PathSegment>, fill: PColor, matrix: Option<&PMatrix>) {
+    if segments.is_empty() { return; }
+    push_items([RecordedItem::Path(RecordedPath { segments, fill: Some(fill), stroke: None, stroke_weight: 1.0, transform: recorded_transform(matrix) })]);
+}
+
+// Images are kept at their own size, bounds is x, y, width and height
+pub(crate) fn record_image (image: PImage, bounds: [f32; 4], matrix: Option<&PMatrix>) -> PResult<()> {
+    if !is_recording() { return Ok(()); }
+
+    let (width, height, pixels) = read_image(image)?;
+    let format = with_image(image, |data| data.format)?;
+    push_items([RecordedItem::Image(RecordedImage { width, height, rgba: to_rgba(format, &pixels), bounds, transform: recorded_transform(matrix) })]);
+    Ok(())
+}
+
+// Like the canvas, what was recorded on the page before is thrown away
+pub(crate) fn record_background (color: PColor) {
+    let mut state = RECORD_STATE.try_write().expect("Could not write to RwLock");
+    let Some(recording) = state.recording.as_mut() else { return; };
+
+    let (width, height) = (recording.width, recording.height);
+    recording.items.clear();
+    recording.items.push(RecordedItem::Path(RecordedPath {
+        segments: vec![
+            PathSegment::MoveTo([0.0, 0.0]),
+            PathSegment::LineTo([width, 0.0]),
+            PathSegment::LineTo([width, height]),
+            PathSegment::LineTo([0.0, height]),
+            PathSegment::Close
+        ],
+        fill: Some(color),
+        stroke: None,
+        stroke_weight: 1.0,
+        transform: None
+    }));
+}
+
+/// Starts writing everything that is drawn to a file as well, until endRecord()
+///
+/// # Safety
+/// `path` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn beginRecord (format: PRecordFormat, path: *const c_char) {
+    begin_record(format, &string_from(path)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn endRecord () {
+    end_record().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn nextPage () {
+    next_page().or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fixture,
+        lock_state,
+        renderer::state::set_renderer_state,
+        shape::{add_vertex, begin_shape, curve::{bezier_vertex, draw_curve, quadratic_vertex}, end_shape}
+    };
+
+    // Records a few shapes with curves without a window, like beginRecord()
+    // would, and compares the file with the one they should give
+    #[test]
+    fn svg_keeps_curves () {
+        let _state = lock_state();
+        set_renderer_state! {
+            renderer = PRenderer::P2D;
+        }
+        let path = std::env::temp_dir().join(format!("processing-record-{}.svg", std::process::id()));
+        let recording = new_recording(PRecordFormat::RecordSvg, &path.to_string_lossy(), 100.0, 100.0).unwrap();
+        RECORD_STATE.try_write().expect("Could not write to RwLock").recording = Some(recording);
+
+        begin_shape(PShapeKind::ShapePolygon).unwrap();
+        add_vertex(10.0, 10.0, 0.0).unwrap();
+        bezier_vertex(20.0, 0.0, 80.0, 0.0, 90.0, 10.0).unwrap();
+        add_vertex(90.0, 50.0, 0.0).unwrap();
+        quadratic_vertex(90.0, 90.0, 10.0, 90.0).unwrap();
+        end_shape(PShapeClose::ShapeClose).unwrap();
+        draw_curve([[0.0, 0.0], [10.0, 50.0], [50.0, 60.0], [100.0, 100.0]]).unwrap();
+        end_record().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(written, std::fs::read_to_string(fixture("curves.svg")).unwrap());
+    }
+}
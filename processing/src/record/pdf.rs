@@ -0,0 +1,260 @@
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use pdf_writer::{types::{LineCapStyle, LineJoinStyle}, Content, Filter, Finish, Name, Pdf, Rect, Ref};
+
+use crate::{
+    color::PColor,
+    math::curve::quadratic_to_cubic,
+    record::state::{PathSegment, RecordedImage, RecordedItem, RecordedPath, Recording}
+};
+
+const COMPRESSION_LEVEL: u8 = 6;
+
+// Objects are numbered in the order they are written
+struct Refs(i32);
+
+impl Refs {
+    fn next (&mut self) -> Ref {
+        self.0 += 1;
+        Ref::new(self.0)
+    }
+}
+
+// What a page's content stream refers to by name
+#[derive(Default)]
+struct Resources {
+    // Fill and stroke opacity of each graphics state
+    opacities: Vec<[f32; 2]>,
+    images: Vec<Ref>,
+}
+
+fn rgb (color: PColor) -> [f32; 3] {
+    [color.r, color.g, color.b].map(|channel| channel.clamp(0.0, 1.0))
+}
+
+fn write_path (content: &mut Content, path: &RecordedPath, resources: &mut Resources) {
+    if path.fill.is_none() && path.stroke.is_none() { return; }
+
+    content.save_state();
+    if let Some(transform) = path.transform {
+        content.transform(transform);
+    }
+    // Opacity can only be set through a graphics state
+    let opacity = [path.fill, path.stroke].map(|color| color.map_or(1.0, |color| color.a.clamp(0.0, 1.0)));
+    if opacity != [1.0, 1.0] {
+        let index = resources.opacities.iter().position(|&used| used == opacity).unwrap_or_else(|| {
+            resources.opacities.push(opacity);
+            resources.opacities.len() - 1
+        });
+        content.set_parameters(Name(format!("G{}", index).as_bytes()));
+    }
+    if let Some(fill) = path.fill {
+        let [r, g, b] = rgb(fill);
+        content.set_fill_rgb(r, g, b);
+    }
+    if let Some(stroke) = path.stroke {
+        let [r, g, b] = rgb(stroke);
+        content.set_stroke_rgb(r, g, b);
+        content.set_line_width(path.stroke_weight);
+    }
+
+    // PDF has no quadratic curves, they are written as the cubic one
+    // starting at the current point that traces the same path
+    let (mut current, mut subpath_start) = ([0.0, 0.0], [0.0, 0.0]);
+    for segment in &path.segments {
+        match *segment {
+            PathSegment::MoveTo([x, y]) => {
+                content.move_to(x, y);
+                (current, subpath_start) = ([x, y], [x, y]);
+            }
+            PathSegment::LineTo([x, y]) => {
+                content.line_to(x, y);
+                current = [x, y];
+            }
+            PathSegment::QuadTo(control, to) => {
+                let [_, [x1, y1], [x2, y2], [x, y]] = quadratic_to_cubic(current, control, to);
+                content.cubic_to(x1, y1, x2, y2, x, y);
+                current = to;
+            }
+            PathSegment::CubicTo([x1, y1], [x2, y2], [x, y]) => {
+                content.cubic_to(x1, y1, x2, y2, x, y);
+                current = [x, y];
+            }
+            PathSegment::Close => {
+                content.close_path();
+                current = subpath_start;
+            }
+        }
+    }
+    match (path.fill.is_some(), path.stroke.is_some()) {
+        (true, true) => content.fill_nonzero_and_stroke(),
+        (true, false) => content.fill_nonzero(),
+        _ => content.stroke()
+    };
+    content.restore_state();
+}
+
+// The colors of the image, with its alpha as a soft mask when
+// it isn't opaque, stretched over its bounds
+fn write_image (pdf: &mut Pdf, refs: &mut Refs, content: &mut Content, image: &RecordedImage, resources: &mut Resources) {
+    if image.width == 0 || image.height == 0 { return; }
+
+    let colors: Vec<u8> = image.rgba.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect();
+    let alpha: Vec<u8> = image.rgba.chunks_exact(4).map(|pixel| pixel[3]).collect();
+
+    let mask_id = alpha.iter().any(|&a| a < 255).then(|| {
+        let id = refs.next();
+        let samples = compress_to_vec_zlib(&alpha, COMPRESSION_LEVEL);
+        let mut mask = pdf.image_xobject(id, &samples);
+        mask.filter(Filter::FlateDecode);
+        mask.width(image.width as i32);
+        mask.height(image.height as i32);
+        mask.bits_per_component(8);
+        mask.color_space().device_gray();
+        mask.finish();
+        id
+    });
+
+    let id = refs.next();
+    let samples = compress_to_vec_zlib(&colors, COMPRESSION_LEVEL);
+    let mut xobject = pdf.image_xobject(id, &samples);
+    xobject.filter(Filter::FlateDecode);
+    xobject.width(image.width as i32);
+    xobject.height(image.height as i32);
+    xobject.bits_per_component(8);
+    xobject.color_space().device_rgb();
+    if let Some(mask_id) = mask_id {
+        xobject.s_mask(mask_id);
+    }
+    xobject.finish();
+
+    // Images fill the unit square, with their first row at the top
+    let [x, y, width, height] = image.bounds;
+    content.save_state();
+    if let Some(transform) = image.transform {
+        content.transform(transform);
+    }
+    content.transform([width, 0.0, 0.0, -height, x, y + height]);
+    content.x_object(Name(format!("I{}", resources.images.len()).as_bytes()));
+    content.restore_state();
+    resources.images.push(id);
+}
+
+// A PDF document with a page as big as the recording for every page
+// finished with nextPage() and the one being drawn, each with
+// everything on it in the order it was drawn
+pub(crate) fn write_pdf (recording: &Recording) -> Vec<u8> {
+    let mut pdf = Pdf::new();
+    let mut refs = Refs(0);
+    let catalog_id = refs.next();
+    let tree_id = refs.next();
+
+    let mut page_ids = vec![];
+    for items in recording.pages.iter().chain([&recording.items]) {
+        let mut content = Content::new();
+        let mut resources = Resources::default();
+        // Points go down from the top left corner, like on the canvas
+        content.transform([1.0, 0.0, 0.0, -1.0, 0.0, recording.height]);
+        content.set_line_cap(LineCapStyle::RoundCap);
+        content.set_line_join(LineJoinStyle::MiterJoin);
+        for item in items {
+            match item {
+                RecordedItem::Path(path) => write_path(&mut content, path, &mut resources),
+                RecordedItem::Image(image) => write_image(&mut pdf, &mut refs, &mut content, image, &mut resources)
+            }
+        }
+
+        let state_ids: Vec<Ref> = resources.opacities.iter().map(|&[fill, stroke]| {
+            let id = refs.next();
+            pdf.ext_graphics(id).non_stroking_alpha(fill).stroking_alpha(stroke);
+            id
+        }).collect();
+
+        let content_id = refs.next();
+        pdf.stream(content_id, &compress_to_vec_zlib(&content.finish(), COMPRESSION_LEVEL)).filter(Filter::FlateDecode);
+
+        let page_id = refs.next();
+        let mut page = pdf.page(page_id);
+        page.parent(tree_id)
+            .media_box(Rect::new(0.0, 0.0, recording.width, recording.height))
+            .contents(content_id);
+        let mut page_resources = page.resources();
+        if !resources.images.is_empty() {
+            let mut x_objects = page_resources.x_objects();
+            for (i, id) in resources.images.iter().enumerate() {
+                x_objects.pair(Name(format!("I{}", i).as_bytes()), *id);
+            }
+        }
+        if !state_ids.is_empty() {
+            let mut ext_g_states = page_resources.ext_g_states();
+            for (i, id) in state_ids.iter().enumerate() {
+                ext_g_states.pair(Name(format!("G{}", i).as_bytes()), *id);
+            }
+        }
+        page_resources.finish();
+        page.finish();
+        page_ids.push(page_id);
+    }
+
+    pdf.pages(tree_id).count(page_ids.len() as i32).kids(page_ids);
+    pdf.catalog(catalog_id).pages(tree_id);
+    pdf.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+    use crate::{
+        lock_state,
+        record::{end_record, new_recording, next_page, state::RECORD_STATE, PRecordFormat},
+        renderer::{camera::PRenderer, state::set_renderer_state},
+        shape::{add_vertex, begin_shape, curve::draw_bezier, end_shape, state::{PShapeClose, PShapeKind}}
+    };
+
+    // Every stream in the file, decompressed
+    fn streams (pdf: &[u8]) -> Vec<String> {
+        let find = |from: usize, needle: &[u8]| pdf[from..].windows(needle.len()).position(|window| window == needle).map(|i| from + i);
+        let mut streams = vec![];
+        let mut from = 0;
+        while let Some(start) = find(from, b"stream\n") {
+            let start = start + b"stream\n".len();
+            let end = find(start, b"\nendstream").expect("Stream is not ended");
+            streams.push(String::from_utf8(decompress_to_vec_zlib(&pdf[start..end]).expect("Stream is not zlib")).unwrap());
+            from = end + b"\nendstream".len();
+        }
+        streams
+    }
+
+    // Records a page, starts a second one with nextPage() and checks that
+    // both end up in the file, each with what was drawn on it
+    #[test]
+    fn next_page_adds_pages () {
+        let _state = lock_state();
+        set_renderer_state! {
+            renderer = PRenderer::P2D;
+        }
+        let path = std::env::temp_dir().join(format!("processing-record-{}.pdf", std::process::id()));
+        let recording = new_recording(PRecordFormat::RecordPdf, &path.to_string_lossy(), 100.0, 100.0).unwrap();
+        RECORD_STATE.try_write().expect("Could not write to RwLock").recording = Some(recording);
+
+        begin_shape(PShapeKind::ShapePolygon).unwrap();
+        for [x, y] in [[10.0, 10.0], [90.0, 10.0], [90.0, 90.0]] {
+            add_vertex(x, y, 0.0).unwrap();
+        }
+        end_shape(PShapeClose::ShapeClose).unwrap();
+        next_page().unwrap();
+        draw_bezier([[10.0, 50.0], [30.0, 0.0], [70.0, 100.0], [90.0, 50.0]]).unwrap();
+        end_record().unwrap();
+
+        let pdf = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(pdf.windows(8).any(|window| window == b"/Count 2"));
+
+        // Flipped so y goes down, round caps and miter joins, then the paths
+        let page = |path: &str| format!("1 0 0 -1 0 100 cm\n1 J\n0 j\nq\n1 1 1 rg\n0 0 0 RG\n1 w\n{}\nB\nQ", path);
+        assert_eq!(streams(&pdf), [
+            page("10 10 m\n90 10 l\n90 90 l\nh"),
+            page("10 50 m\n30 0 70 100 90 50 c")
+        ]);
+    }
+}
@@ -0,0 +1,72 @@
+use lazy_static::lazy_static;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::{color::PColor, record::PRecordFormat};
+
+// Path commands, in points on the canvas
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    MoveTo([f32; 2]),
+    LineTo([f32; 2]),
+    QuadTo([f32; 2], [f32; 2]),
+    CubicTo([f32; 2], [f32; 2], [f32; 2]),
+    Close,
+}
+
+// A path filled with the nonzero rule and stroked with round
+// caps and miter joins, like the renderer draws them
+#[derive(Debug, Clone)]
+pub struct RecordedPath {
+    pub segments: Vec<PathSegment>,
+    pub fill: Option<PColor>,
+    pub stroke: Option<PColor>,
+    pub stroke_weight: f32,
+    // The 2D part of the matrix the path was drawn with, as a, b, c, d,
+    // e, f like SVG's matrix(). Strokes are scaled along with the path
+    pub transform: Option<[f32; 6]>,
+}
+
+// Pixels of an image as RGBA rows from the top, and the
+// rectangle it was drawn into, moved with transform like paths
+#[derive(Debug, Clone)]
+pub struct RecordedImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+    pub bounds: [f32; 4],
+    pub transform: Option<[f32; 6]>,
+}
+
+#[derive(Debug, Clone)]
+pub enum RecordedItem {
+    Path(RecordedPath),
+    Image(RecordedImage),
+}
+
+// What has been drawn since beginRecord(), and where it is written
+pub struct Recording {
+    pub format: PRecordFormat,
+    pub path: String,
+    pub width: f32,
+    pub height: f32,
+    // Pages finished with nextPage(), only PDF recordings have more than one
+    pub pages: Vec<Vec<RecordedItem>>,
+    // What is on the page being drawn
+    pub items: Vec<RecordedItem>,
+}
+
+// Swapped along with the rest of the target when drawing into a
+// graphics, so the sketch and every graphics record on their own
+#[derive(Default)]
+pub struct RecordState {
+    // Some between beginRecord() and endRecord()
+    pub recording: Option<Recording>,
+}
+
+lazy_static! {
+    pub static ref RECORD_STATE: Arc<RwLock<RecordState>> = Arc::new(RwLock::new(RecordState::default()));
+}
+
+pub fn get_record_state () -> RwLockReadGuard<'static, RecordState> {
+    RECORD_STATE.try_read().unwrap()
+}
@@ -0,0 +1,96 @@
+use std::fmt::Write;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ::image::{codecs::png::PngEncoder, ColorType, ImageEncoder};
+
+use crate::{
+    color::PColor,
+    error::{PError, PResult},
+    record::state::{PathSegment, RecordedImage, RecordedItem, RecordedPath, Recording}
+};
+
+// Rounded to a thousandth of a point, so files stay small and
+// the same drawing always gives the same file
+fn number (value: f32) -> String {
+    let rounded = (value * 1000.0).round() / 1000.0;
+    format!("{}", rounded + 0.0)
+}
+
+fn point ([x, y]: [f32; 2]) -> String {
+    format!("{} {}", number(x), number(y))
+}
+
+fn path_data (segments: &[PathSegment]) -> String {
+    segments.iter().map(|segment| match *segment {
+        PathSegment::MoveTo(to) => format!("M{}", point(to)),
+        PathSegment::LineTo(to) => format!("L{}", point(to)),
+        PathSegment::QuadTo(control, to) => format!("Q{} {}", point(control), point(to)),
+        PathSegment::CubicTo(control1, control2, to) => format!("C{} {} {}", point(control1), point(control2), point(to)),
+        PathSegment::Close => "Z".to_string()
+    }).collect::<Vec<_>>().join(" ")
+}
+
+// A paint attribute and its opacity, left out when opaque
+fn paint (name: &str, color: Option<PColor>) -> String {
+    let Some(color) = color else { return format!(" {}=\"none\"", name); };
+    let [r, g, b] = [color.r, color.g, color.b].map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    let mut attributes = format!(" {}=\"#{:02x}{:02x}{:02x}\"", name, r, g, b);
+    if color.a < 1.0 {
+        let _ = write!(attributes, " {}-opacity=\"{}\"", name, number(color.a.max(0.0)));
+    }
+    attributes
+}
+
+fn write_path (svg: &mut String, path: &RecordedPath) {
+    let _ = write!(svg, "<path d=\"{}\"{}{}", path_data(&path.segments), paint("fill", path.fill), paint("stroke", path.stroke));
+    if path.stroke.is_some() {
+        let _ = write!(svg, " stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"miter\"", number(path.stroke_weight));
+    }
+    svg.push_str(&transform_attribute(path.transform));
+    svg.push_str("/>\n");
+}
+
+fn transform_attribute (transform: Option<[f32; 6]>) -> String {
+    transform.map_or(String::new(), |transform| format!(" transform=\"matrix({})\"", transform.map(number).join(" ")))
+}
+
+fn encode_png (image: &RecordedImage) -> Result<Vec<u8>, ::image::ImageError> {
+    let mut png = vec![];
+    PngEncoder::new(&mut png).write_image(&image.rgba, image.width, image.height, ColorType::Rgba8)?;
+    Ok(png)
+}
+
+// Embedded as a PNG, stretched over its bounds
+fn write_image (svg: &mut String, image: &RecordedImage, png: &[u8]) {
+    let [x, y, width, height] = image.bounds.map(number);
+    let _ = writeln!(
+        svg,
+        "<image x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"{} xlink:href=\"data:image/png;base64,{}\"/>",
+        x, y, width, height, transform_attribute(image.transform), STANDARD.encode(png)
+    );
+}
+
+// An SVG document as big as the recording, with an element
+// for everything in it in the order it was drawn
+pub(crate) fn write_svg (recording: &Recording) -> PResult<String> {
+    let (width, height) = (number(recording.width), number(recording.height));
+    let mut svg = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">",
+        width, height, width, height
+    );
+
+    for item in &recording.items {
+        match item {
+            RecordedItem::Path(path) => write_path(&mut svg, path),
+            RecordedItem::Image(image) => {
+                let png = encode_png(image)
+                    .map_err(|error| PError::RecordNotWritten { path: recording.path.clone(), message: error.to_string() })?;
+                write_image(&mut svg, image, &png);
+            }
+        }
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
@@ -0,0 +1,104 @@
+use wgpu::{BindGroup, CommandEncoder, Device, RenderPipeline, TextureFormat, TextureView};
+
+// Copies a render target onto another texture (usually the window surface),
+// scaling it with linear filtering if the sizes don't match.
+// This is what makes pixelDensity() work, the canvas can have
+// more or fewer pixels than the window it ends up on
+pub struct Blitter {
+    pipeline: RenderPipeline,
+    bind_group: BindGroup,
+}
+
+impl Blitter {
+    pub fn new (device: &Device, source: &TextureView, target_format: TextureFormat) -> Self {
+        // Sampling gives linear values. sRGB targets encode them on write,
+        // for anything else the shader has to do the encoding
+        let fragment_entry_point = if target_format.is_srgb() { "fs_main" } else { "fs_encode_srgb" };
+        Self::with_options(device, source, target_format, 1, fragment_entry_point)
+    }
+
+    // Copies a texture as is into another one with the same format,
+    // which can be multisampled. Used to put pixels written into
+    // a render target back into its multisampled texture
+    pub fn copy (device: &Device, source: &TextureView, target_format: TextureFormat, sample_count: u32) -> Self {
+        Self::with_options(device, source, target_format, sample_count, "fs_main")
+    }
+
+    fn with_options (device: &Device, source: &TextureView, target_format: TextureFormat, sample_count: u32, fragment_entry_point: &str) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Blit Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/blit.wgsl").into())
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Blit Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: fragment_entry_point,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            multiview: None,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Blit Sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Blit Bind Group"),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler)
+                }
+            ]
+        });
+
+        Self { pipeline, bind_group }
+    }
+
+    pub fn blit (&self, encoder: &mut CommandEncoder, target: &TextureView) {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Blit Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None
+        });
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &self.bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
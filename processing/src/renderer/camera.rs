@@ -0,0 +1,182 @@
+use std::f32::consts::PI;
+
+use crate::{
+    core::window::create_window,
+    error::{OrPanic, PError, PResult},
+    math::matrix::{self, look_at, multiply, PMatrix},
+    renderer::{frame::flush, projection::ortho_matrix, state::{get_renderer_state, set_renderer_state, RENDERER_STATE}}
+};
+
+// Renderers that can be passed to createWindowWithRenderer()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PRenderer {
+    // Everything is drawn flat onto the canvas, z is ignored
+    #[default]
+    P2D,
+    // Geometry is seen through a camera and hidden behind
+    // what is in front of it, with a depth buffer
+    P3D
+}
+
+// Set with camera() and perspective(), ortho() or frustum(). Left
+// unset, they follow the canvas size like in Processing: the camera
+// looks at the middle of the canvas from where z = 0 fills it exactly
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Camera {
+    pub view: Option<PMatrix>,
+    pub projection: Option<PMatrix>,
+}
+
+// Vertical field of view of the default perspective
+const DEFAULT_FOV: f32 = PI / 3.0;
+
+// Distance of the default camera from z = 0
+fn default_distance (height: f32) -> f32 {
+    (height / 2.0) / (DEFAULT_FOV / 2.0).tan()
+}
+
+fn default_view (width: f32, height: f32) -> PMatrix {
+    look_at([width / 2.0, height / 2.0, default_distance(height)], [width / 2.0, height / 2.0, 0.0], [0.0, 1.0, 0.0])
+}
+
+fn default_perspective (width: f32, height: f32) -> PMatrix {
+    let distance = default_distance(height);
+    matrix::perspective(DEFAULT_FOV, width / height, distance / 10.0, distance * 10.0)
+}
+
+impl Camera {
+    // Maps positions to clip space on a canvas
+    // of width x height points
+    pub fn matrix (&self, renderer: PRenderer, width: f32, height: f32) -> PMatrix {
+        match renderer {
+            PRenderer::P2D => ortho_matrix(width, height),
+            PRenderer::P3D => multiply(
+                &self.projection.unwrap_or_else(|| default_perspective(width, height)),
+                &self.view.unwrap_or_else(|| default_view(width, height))
+            )
+        }
+    }
+
+    // Where the camera is, worked out from the view
+    pub fn eye (&self, width: f32, height: f32) -> [f32; 3] {
+        let view = self.view.unwrap_or_else(|| default_view(width, height));
+        let translation = view[3];
+        [0, 1, 2].map(|axis| -(0..3).map(|row| view[axis][row] * translation[row]).sum::<f32>())
+    }
+}
+
+pub(crate) fn check_3d () -> PResult<()> {
+    if get_renderer_state().renderer != PRenderer::P3D {
+        return Err(PError::Not3D);
+    }
+    Ok(())
+}
+
+// Changes the camera of what is being drawn into. Geometry already
+// drawn is drawn first, as it was drawn with the old camera
+fn update_camera (change: impl FnOnce(&mut Camera, f32, f32)) -> PResult<()> {
+    check_3d()?;
+    if !get_renderer_state().draw_list.is_empty() {
+        flush();
+    }
+
+    let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let (width, height) = renderer_state.target_size();
+    let mut camera = renderer_state.camera;
+    change(&mut camera, width, height);
+    renderer_state.camera = camera;
+
+    let camera_matrix = camera.matrix(PRenderer::P3D, width, height);
+    if let (Some(queue), Some(projection)) = (renderer_state.queue.as_ref(), renderer_state.projection.as_ref()) {
+        projection.set_matrix(queue, camera_matrix);
+    }
+    Ok(())
+}
+
+pub(crate) fn set_renderer (renderer: PRenderer) {
+    set_renderer_state! {
+        renderer = renderer;
+    }
+}
+
+// Places the camera at eye, looking at center. up is the direction
+// that points up on the screen, usually (0, 1, 0)
+pub(crate) fn set_camera (eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> PResult<()> {
+    update_camera(|camera, _, _| camera.view = Some(look_at(eye, center, up)))
+}
+
+// Back to the default camera
+pub(crate) fn reset_camera () -> PResult<()> {
+    update_camera(|camera, _, _| camera.view = None)
+}
+
+// fov is the vertical field of view in radians, aspect width / height.
+// Nothing closer than near or further than far is drawn
+pub(crate) fn set_perspective (fov: f32, aspect: f32, near: f32, far: f32) -> PResult<()> {
+    update_camera(|camera, _, _| camera.projection = Some(matrix::perspective(fov, aspect, near, far)))
+}
+
+pub(crate) fn reset_perspective () -> PResult<()> {
+    update_camera(|camera, _, _| camera.projection = None)
+}
+
+// Orthographic projection, things don't get smaller further away.
+// Bounds are relative to the middle of the view
+pub(crate) fn set_ortho (left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> PResult<()> {
+    update_camera(|camera, _, _| camera.projection = Some(matrix::ortho(left, right, bottom, top, near, far)))
+}
+
+// Orthographic projection showing the whole canvas
+pub(crate) fn reset_ortho () -> PResult<()> {
+    update_camera(|camera, width, height| {
+        let far = default_distance(height) * 10.0;
+        camera.projection = Some(matrix::ortho(-width / 2.0, width / 2.0, -height / 2.0, height / 2.0, 0.0, far));
+    })
+}
+
+pub(crate) fn set_frustum (left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> PResult<()> {
+    update_camera(|camera, _, _| camera.projection = Some(matrix::frustum(left, right, bottom, top, near, far)))
+}
+
+// Like createWindow(), with P3D to draw in 3D
+#[no_mangle]
+pub extern "C" fn createWindowWithRenderer (width: f32, height: f32, renderer: PRenderer) {
+    create_window(width, height).or_panic();
+    set_renderer(renderer);
+}
+
+#[no_mangle]
+pub extern "C" fn camera (eye_x: f32, eye_y: f32, eye_z: f32, center_x: f32, center_y: f32, center_z: f32, up_x: f32, up_y: f32, up_z: f32) {
+    set_camera([eye_x, eye_y, eye_z], [center_x, center_y, center_z], [up_x, up_y, up_z]).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn resetCamera () {
+    reset_camera().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn perspective (fov: f32, aspect: f32, near: f32, far: f32) {
+    set_perspective(fov, aspect, near, far).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn resetPerspective () {
+    reset_perspective().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn ortho (left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+    set_ortho(left, right, bottom, top, near, far).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn resetOrtho () {
+    reset_ortho().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn frustum (left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) {
+    set_frustum(left, right, bottom, top, near, far).or_panic()
+}
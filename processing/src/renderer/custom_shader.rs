@@ -0,0 +1,262 @@
+use std::{collections::HashMap, time::SystemTime};
+
+use naga::ScalarKind;
+use wgpu::{util::{BufferInitDescriptor, DeviceExt}, BindGroup, BindGroupLayout, Buffer, BufferUsages, Device};
+
+use crate::{
+    color::blend::PBlendMode,
+    error::PResult,
+    renderer::{shader::{catch_gpu_errors, Shader, ShaderBuilder}, state::{get_renderer_state, RENDERER_STATE}},
+    shader::reflect::Reflection
+};
+
+// Index into the renderer's list of shaders loaded with loadShader()
+pub type ShaderId = usize;
+
+// WGSL source of one stage, and the function it starts at
+#[derive(Clone)]
+pub struct StageSource {
+    pub source: String,
+    pub entry_point: String,
+}
+
+// One number in a uniform block, at offset bytes from its start
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct UniformSlot {
+    pub offset: u32,
+    pub kind: ScalarKind,
+}
+
+// Every number in a uniform block, by the name of the field it is in
+pub type UniformFields = HashMap<String, Vec<UniformSlot>>;
+
+// Files a shader was loaded from, None for built-in stages,
+// and when they last changed. Checked by shaderHotReload()
+#[derive(Clone, Default)]
+pub struct ShaderFiles {
+    pub fragment: Option<String>,
+    pub vertex: Option<String>,
+    pub modified: Option<SystemTime>,
+}
+
+// A shader loaded with loadShader(). Like textures, it can be loaded before
+// the window exists, its pipelines are built the first time it is drawn with.
+// There is one pipeline per blend mode, like for the shape shader
+pub struct CustomShader {
+    pub label: String,
+    pub vertex: StageSource,
+    pub fragment: StageSource,
+    pub files: ShaderFiles,
+    pub uniform_fields: UniformFields,
+    // Contents of the uniform block, written to the GPU
+    // before the shader is next drawn with when dirty
+    pub uniforms: Vec<u8>,
+    pub dirty: bool,
+    pub gpu: Option<CustomShaderGpu>,
+}
+
+pub struct CustomShaderGpu {
+    pub uniforms: Option<UniformBlock>,
+    pub pipelines: HashMap<PBlendMode, Shader>,
+}
+
+// Bound at group 2 while the shader is drawn with
+pub struct UniformBlock {
+    pub buffer: Buffer,
+    pub layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl CustomShader {
+    // Sets the numbers of a uniform field. They are converted to
+    // the field's type, ints and uints are truncated
+    pub fn set_uniform (&mut self, slots: &[UniformSlot], values: &[f32]) {
+        for (slot, value) in slots.iter().zip(values) {
+            let bytes = match slot.kind {
+                ScalarKind::Sint => (*value as i32).to_ne_bytes(),
+                ScalarKind::Uint => (*value as u32).to_ne_bytes(),
+                ScalarKind::Bool => ((*value != 0.0) as u32).to_ne_bytes(),
+                ScalarKind::Float => value.to_ne_bytes()
+            };
+            let offset = slot.offset as usize;
+            self.uniforms[offset..offset + 4].copy_from_slice(&bytes);
+        }
+        self.dirty = true;
+    }
+
+    // Swaps in recompiled stages. Fields that are still there with the same
+    // type keep their values, the pipelines are built again when next drawn
+    pub fn reload (&mut self, vertex: StageSource, fragment: StageSource, uniform_size: u32, uniform_fields: UniformFields) {
+        let mut uniforms = vec![0; uniform_size as usize];
+        for (name, slots) in &uniform_fields {
+            let Some(old_slots) = self.uniform_fields.get(name) else { continue; };
+            let same_type = old_slots.len() == slots.len() && old_slots.iter().zip(slots).all(|(old, new)| old.kind == new.kind);
+            if !same_type { continue; }
+            for (old, new) in old_slots.iter().zip(slots) {
+                let (old, new) = (old.offset as usize, new.offset as usize);
+                uniforms[new..new + 4].copy_from_slice(&self.uniforms[old..old + 4]);
+            }
+        }
+
+        self.vertex = vertex;
+        self.fragment = fragment;
+        self.uniform_fields = uniform_fields;
+        self.uniforms = uniforms;
+        self.dirty = false;
+        self.gpu = None;
+    }
+}
+
+// Adds a shader and returns its id. The uniform block starts out zeroed
+pub fn add_custom_shader (
+    label: String,
+    vertex: StageSource,
+    fragment: StageSource,
+    files: ShaderFiles,
+    uniform_size: u32,
+    uniform_fields: UniformFields
+) -> ShaderId {
+    let mut state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    state.custom_shaders.push(CustomShader {
+        label,
+        vertex,
+        fragment,
+        files,
+        uniform_fields,
+        uniforms: vec![0; uniform_size as usize],
+        dirty: false,
+        gpu: None
+    });
+    state.custom_shaders.len() - 1
+}
+
+fn build_uniform_block (device: &Device, uniforms: &[u8]) -> Option<UniformBlock> {
+    if uniforms.is_empty() { return None; }
+
+    let buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Uniform Buffer"),
+        contents: uniforms,
+        usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+    });
+
+    let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Uniform Bind Group Layout"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None
+            },
+            count: None
+        }]
+    });
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Uniform Bind Group"),
+        layout: &layout,
+        entries: &[wgpu::BindGroupEntry {
+            binding: 0,
+            resource: buffer.as_entire_binding()
+        }]
+    });
+
+    Some(UniformBlock { buffer, layout, bind_group })
+}
+
+fn build_pipeline (label: &str, vertex: &StageSource, fragment: &StageSource, uniforms: Option<&UniformBlock>, blend_mode: PBlendMode) -> Shader {
+    let mut builder = ShaderBuilder::new();
+    builder
+        .with_label(format!("{} ({:?})", label, blend_mode))
+        .with_content(fragment.source.clone())
+        .with_vertex_content(vertex.source.clone())
+        .with_entry_points(&vertex.entry_point, &fragment.entry_point)
+        .with_blend(blend_mode.blend_state())
+        .with_vertex_layout()
+        .with_projection()
+        .with_texture();
+
+    if let Some(uniforms) = uniforms {
+        builder.with_bind_group_layout(&uniforms.layout);
+    }
+    builder.build()
+}
+
+// Swaps in recompiled stages. Pipelines the shader already had are built
+// again first, with GPU errors caught, so on error it keeps all it had
+pub fn reload_custom_shader (id: ShaderId, path: &str, reflection: Reflection) -> PResult<()> {
+    let gpu = {
+        let renderer_state = get_renderer_state();
+        let shader = &renderer_state.custom_shaders[id];
+        match (renderer_state.device.as_ref(), shader.gpu.as_ref()) {
+            (Some(device), Some(gpu)) => Some(catch_gpu_errors(device, path, || {
+                let uniforms = build_uniform_block(device, &vec![0; reflection.uniform_size as usize]);
+                let pipelines = gpu.pipelines.keys().map(|blend_mode| {
+                    (*blend_mode, build_pipeline(&shader.label, &reflection.vertex, &reflection.fragment, uniforms.as_ref(), *blend_mode))
+                }).collect();
+                CustomShaderGpu { uniforms, pipelines }
+            })?),
+            _ => None
+        }
+    };
+
+    let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let shader = &mut renderer_state.custom_shaders[id];
+    shader.reload(reflection.vertex, reflection.fragment, reflection.uniform_size, reflection.uniforms);
+    // The uniforms kept from before are written before it is next drawn with
+    shader.dirty = gpu.is_some();
+    shader.gpu = gpu;
+    Ok(())
+}
+
+// Builds what the draw list needs of the custom shaders it uses
+// and writes uniforms that changed since they were last drawn with
+pub fn prepare_custom_shaders () {
+    let used: Vec<(ShaderId, PBlendMode)> = {
+        let renderer_state = get_renderer_state();
+        let mut used = vec![];
+        for batch in &renderer_state.draw_list.batches {
+            let Some(id) = batch.shader else { continue; };
+            let built = renderer_state.custom_shaders[id].gpu.as_ref().is_some_and(|gpu| gpu.pipelines.contains_key(&batch.blend_mode));
+            if !built && !used.contains(&(id, batch.blend_mode)) {
+                used.push((id, batch.blend_mode));
+            }
+        }
+        used
+    };
+
+    for (id, blend_mode) in used {
+        if get_renderer_state().custom_shaders[id].gpu.is_none() {
+            let uniforms = {
+                let renderer_state = get_renderer_state();
+                let Some(device) = renderer_state.device.as_ref() else { continue; };
+                build_uniform_block(device, &renderer_state.custom_shaders[id].uniforms)
+            };
+            let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+            let shader = &mut renderer_state.custom_shaders[id];
+            shader.gpu = Some(CustomShaderGpu { uniforms, pipelines: HashMap::new() });
+            shader.dirty = false;
+        }
+
+        let pipeline = {
+            let renderer_state = get_renderer_state();
+            let shader = &renderer_state.custom_shaders[id];
+            let uniforms = shader.gpu.as_ref().and_then(|gpu| gpu.uniforms.as_ref());
+            build_pipeline(&shader.label, &shader.vertex, &shader.fragment, uniforms, blend_mode)
+        };
+        let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+        if let Some(gpu) = renderer_state.custom_shaders[id].gpu.as_mut() {
+            gpu.pipelines.insert(blend_mode, pipeline);
+        }
+    }
+
+    let mut guard = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let state = &mut *guard;
+    let Some(queue) = state.queue.as_ref() else { return; };
+    for shader in state.custom_shaders.iter_mut().filter(|shader| shader.dirty) {
+        let Some(uniforms) = shader.gpu.as_ref().and_then(|gpu| gpu.uniforms.as_ref()) else { continue; };
+        queue.write_buffer(&uniforms.buffer, 0, &shader.uniforms);
+        shader.dirty = false;
+    }
+}
@@ -0,0 +1,132 @@
+use std::{ops::Range, sync::{Arc, OnceLock}};
+
+use wgpu::{util::{BufferInitDescriptor, DeviceExt}, Buffer, BufferUsages, Device};
+
+use crate::{color::blend::PBlendMode, math::matrix::PMatrix, renderer::{custom_shader::ShaderId, lighting::LightingUniform, texture::SampledTexture, vertex::Vertex}};
+
+// Tessellated geometry kept by a retained shape (see shape()), drawn from
+// its own buffers. They are uploaded the first time it is drawn
+pub struct RetainedGeometry {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    buffers: OnceLock<(Buffer, Buffer)>,
+}
+
+impl RetainedGeometry {
+    pub fn new (vertices: Vec<Vertex>, indices: Vec<u32>) -> Self {
+        Self { vertices, indices, buffers: OnceLock::new() }
+    }
+
+    // Vertex and index buffer
+    pub fn buffers (&self, device: &Device) -> &(Buffer, Buffer) {
+        self.buffers.get_or_init(|| (
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Retained Vertex Buffer"),
+                contents: bytemuck::cast_slice(&self.vertices),
+                usage: BufferUsages::VERTEX
+            }),
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Retained Index Buffer"),
+                contents: bytemuck::cast_slice(&self.indices),
+                usage: BufferUsages::INDEX
+            })
+        ))
+    }
+}
+
+// Retained geometry and where it is drawn
+pub struct RetainedDraw {
+    pub geometry: Arc<RetainedGeometry>,
+    pub model: PMatrix,
+}
+
+// Consecutive indices drawn with the same blend mode, texture and shader
+pub struct DrawBatch {
+    pub blend_mode: PBlendMode,
+    pub texture: Option<SampledTexture>,
+    pub shader: Option<ShaderId>,
+    // Drawn with the screen projection, see Projection
+    pub screen: bool,
+    // Index of the lighting block the batch is lit with, None if it isn't
+    pub lighting: Option<usize>,
+    // Drawn from a retained shape's buffers instead of the draw list's
+    pub retained: Option<RetainedDraw>,
+    pub indices: Range<u32>,
+}
+
+// Geometry produced by the drawing functions during a frame.
+// It is uploaded and drawn into the canvas once draw() returns,
+// then cleared for the next frame. Batches keep the draw order,
+// so the pipeline can be switched whenever the blend mode changes
+// and the texture whenever a textured shape (like text) is drawn.
+// Batches with a shader are drawn with it instead of the shape shader.
+// Screen batches hold 3D strokes, which are already projected.
+// Retained batches index into the buffers of a retained shape
+#[derive(Default)]
+pub struct DrawList {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub batches: Vec<DrawBatch>,
+    pub lighting: Vec<LightingUniform>,
+}
+
+impl DrawList {
+    // Indices are relative to the given vertices
+    pub fn push (&mut self, vertices: &[Vertex], indices: &[u32], blend_mode: PBlendMode, texture: Option<SampledTexture>, shader: Option<ShaderId>, screen: bool) {
+        self.push_batch(vertices, indices, DrawBatch { blend_mode, texture, shader, screen, lighting: None, retained: None, indices: 0..0 });
+    }
+
+    // Like push(), for fills drawn while lights are on. Consecutive
+    // batches with the same lights and material share their block
+    pub fn push_lit (&mut self, vertices: &[Vertex], indices: &[u32], blend_mode: PBlendMode, texture: Option<SampledTexture>, shader: Option<ShaderId>, lighting: &LightingUniform) {
+        if indices.is_empty() { return; }
+
+        let lighting = Some(self.lighting_block(lighting));
+        self.push_batch(vertices, indices, DrawBatch { blend_mode, texture, shader, screen: false, lighting, retained: None, indices: 0..0 });
+    }
+
+    // Draws indices of retained geometry, lit if lighting is given. Never merged with other batches
+    pub fn push_retained (&mut self, retained: RetainedDraw, indices: Range<u32>, blend_mode: PBlendMode, texture: Option<SampledTexture>, shader: Option<ShaderId>, lighting: Option<&LightingUniform>) {
+        if indices.is_empty() { return; }
+
+        let lighting = lighting.map(|lighting| self.lighting_block(lighting));
+        self.batches.push(DrawBatch { blend_mode, texture, shader, screen: false, lighting, retained: Some(retained), indices });
+    }
+
+    fn lighting_block (&mut self, lighting: &LightingUniform) -> usize {
+        if self.lighting.last() != Some(lighting) {
+            self.lighting.push(*lighting);
+        }
+        self.lighting.len() - 1
+    }
+
+    // Adds the geometry to the last batch if it is drawn the same way as batch
+    fn push_batch (&mut self, vertices: &[Vertex], indices: &[u32], batch: DrawBatch) {
+        if indices.is_empty() { return; }
+
+        let offset = self.vertices.len() as u32;
+        let start = self.indices.len() as u32;
+        self.vertices.extend_from_slice(vertices);
+        self.indices.extend(indices.iter().map(|index| index + offset));
+        let end = self.indices.len() as u32;
+
+        match self.batches.last_mut() {
+            Some(last) if last.retained.is_none() && last.blend_mode == batch.blend_mode && last.texture == batch.texture && last.shader == batch.shader
+                && last.screen == batch.screen && last.lighting == batch.lighting => {
+                last.indices.end = end;
+            }
+            _ => self.batches.push(DrawBatch { indices: start..end, ..batch })
+        }
+    }
+
+    pub fn clear (&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+        self.batches.clear();
+        self.lighting.clear();
+    }
+
+    pub fn is_empty (&self) -> bool {
+        self.batches.is_empty()
+    }
+}
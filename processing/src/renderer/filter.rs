@@ -0,0 +1,156 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{util::{BufferInitDescriptor, DeviceExt}, BufferUsages, CommandEncoder, Device, Extent3d, Queue, RenderPipeline, Texture, TextureFormat, TextureUsages, TextureView};
+
+use crate::{image::filter::PFilter, renderer::target::RenderTarget};
+
+// Uniforms of filter.wgsl
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Pod, Zeroable)]
+struct FilterParams {
+    kind: u32,
+    linear: u32,
+    value: f32,
+    radius: i32,
+    direction: [i32; 2],
+}
+
+// Runs filter() over a render target on the GPU. Every pixel is
+// computed from a copy of the target, so a pass never reads
+// what it has already written. Blurs take two passes, one per axis
+pub struct FilterPass {
+    pipeline: RenderPipeline,
+    // Whether the format stores linear values, filters
+    // work on sRGB encoded ones like pixels[]
+    linear: bool,
+}
+
+impl FilterPass {
+    pub fn new (device: &Device, format: TextureFormat) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Filter Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/filter.wgsl").into())
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Filter Pipeline"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[]
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            linear: format.is_srgb() || format == TextureFormat::Rgba16Float
+        }
+    }
+
+    // value is the threshold level or the number of posterize levels,
+    // radius the blur kernel radius in pixels. The others ignore both
+    pub fn apply (&self, device: &Device, queue: &Queue, target: &RenderTarget, filter: PFilter, value: f32, radius: i32) {
+        let params = FilterParams {
+            kind: filter as u32,
+            linear: self.linear as u32,
+            value,
+            radius,
+            direction: [1, 0]
+        };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Filter") });
+        let (_source_texture, source) = Self::copy_of(device, &mut encoder, target);
+
+        if filter == PFilter::FilterBlur {
+            let (_horizontal_texture, horizontal) = Self::scratch_texture(device, target);
+            self.pass(device, &mut encoder, &source, &horizontal, params);
+            self.pass(device, &mut encoder, &horizontal, &target.view, FilterParams { direction: [0, 1], ..params });
+        }
+        else {
+            self.pass(device, &mut encoder, &source, &target.view, params);
+        }
+
+        target.reload_samples(&mut encoder);
+        queue.submit(Some(encoder.finish()));
+    }
+
+    fn scratch_texture (device: &Device, target: &RenderTarget) -> (Texture, TextureView) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Filter Texture"),
+            size: Extent3d { width: target.width, height: target.height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: target.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[]
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn copy_of (device: &Device, encoder: &mut CommandEncoder, target: &RenderTarget) -> (Texture, TextureView) {
+        let (texture, view) = Self::scratch_texture(device, target);
+        encoder.copy_texture_to_texture(
+            target.texture.as_image_copy(),
+            texture.as_image_copy(),
+            Extent3d { width: target.width, height: target.height, depth_or_array_layers: 1 }
+        );
+        (texture, view)
+    }
+
+    fn pass (&self, device: &Device, encoder: &mut CommandEncoder, source: &TextureView, destination: &TextureView, params: FilterParams) {
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Filter Params"),
+            contents: bytemuck::bytes_of(&params),
+            usage: BufferUsages::UNIFORM
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Filter Bind Group"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(source)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding()
+                }
+            ]
+        });
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Filter Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: destination,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            occlusion_query_set: None,
+            timestamp_writes: None
+        });
+
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
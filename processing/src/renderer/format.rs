@@ -0,0 +1,108 @@
+use wgpu::{Adapter, Device, Features, SurfaceCapabilities, TextureFormat, TextureFormatFeatureFlags};
+
+use crate::renderer::target::RenderTarget;
+
+// How colors are stored in the canvas, and so
+// in which space blending happens. Set with canvasFormat()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PCanvasFormat {
+    // 8 bits per channel, blending happens on sRGB encoded values.
+    // This is what Processing does, so it is the default
+    #[default]
+    CanvasSrgb,
+    // 8 bits per channel, blending happens on linear values
+    CanvasLinear,
+    // 16 bit floats per channel, linear. Values above 1.0
+    // are kept, which is useful for HDR work in offscreen passes
+    CanvasFloat
+}
+
+impl PCanvasFormat {
+    // Format of the canvas texture itself
+    pub fn texture_format (self) -> TextureFormat {
+        match self {
+            PCanvasFormat::CanvasSrgb => TextureFormat::Rgba8Unorm,
+            PCanvasFormat::CanvasLinear => TextureFormat::Rgba8UnormSrgb,
+            PCanvasFormat::CanvasFloat => TextureFormat::Rgba16Float
+        }
+    }
+
+    // Format pipelines that draw into the canvas are built against
+    pub fn render_format (self) -> TextureFormat {
+        self.texture_format()
+    }
+
+    // Format the canvas is viewed as when it is sampled.
+    // Sampling always gives back linear values, so the sRGB
+    // canvas is read through an sRGB view to decode it
+    pub fn sample_format (self) -> TextureFormat {
+        match self {
+            PCanvasFormat::CanvasSrgb => TextureFormat::Rgba8UnormSrgb,
+            _ => self.texture_format()
+        }
+    }
+
+    // Format textures drawn into the canvas are uploaded in. Their pixels
+    // are sRGB encoded, so linear canvases decode them when sampling
+    pub fn image_format (self) -> TextureFormat {
+        if self.is_linear() { TextureFormat::Rgba8UnormSrgb } else { TextureFormat::Rgba8Unorm }
+    }
+
+    // Whether colors given by the user (which are sRGB encoded)
+    // have to be converted to linear before being written out
+    pub fn is_linear (self) -> bool {
+        self != PCanvasFormat::CanvasSrgb
+    }
+}
+
+// Picks the format the window surface is configured with.
+// sRGB formats are preferred so that the blit pass can write the linear
+// values it samples as is. Other formats work too, the blit shader
+// then encodes to sRGB itself
+pub fn negotiate_surface_format (capabilities: &SurfaceCapabilities) -> TextureFormat {
+    let preferred = [TextureFormat::Bgra8UnormSrgb, TextureFormat::Rgba8UnormSrgb];
+
+    preferred
+        .iter()
+        .find(|format| capabilities.formats.contains(format))
+        .or_else(|| capabilities.formats.iter().find(|format| format.is_srgb()))
+        .or_else(|| capabilities.formats.first())
+        .copied()
+        .expect("Could not get texture format from the surface")
+}
+
+// Picks the number of samples the canvas is drawn with for smooth(level).
+// Counts other than 1 and 4 need an adapter specific feature, so the
+// requested count is only a hint: the closest lower count the adapter
+// supports is used, then the closest higher one. Returns 1 if the
+// canvas format can't be multisampled at all
+pub fn negotiate_sample_count (adapter: &Adapter, device_features: Features, format: PCanvasFormat, requested: u32) -> u32 {
+    if requested <= 1 { return 1; }
+
+    let format_features =
+        if device_features.contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+            adapter.get_texture_format_features(format.texture_format())
+        }
+        else {
+            format.texture_format().guaranteed_format_features(device_features)
+        };
+
+    if !format_features.flags.contains(TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE) {
+        return 1;
+    }
+
+    let counts = [8, 4, 2];
+    counts
+        .iter()
+        .filter(|count| **count <= requested)
+        .chain(counts.iter().rev().filter(|count| **count > requested))
+        .find(|count| format_features.flags.sample_count_supported(**count))
+        .copied()
+        .unwrap_or(1)
+}
+
+pub fn create_canvas (device: &Device, width: u32, height: u32, format: PCanvasFormat, sample_count: u32, depth: bool) -> RenderTarget {
+    let canvas = RenderTarget::new(device, "Canvas", width, height, format.texture_format(), format.sample_format(), sample_count);
+    if depth { canvas.with_depth(device) } else { canvas }
+}
@@ -0,0 +1,229 @@
+use wgpu::{util::{BufferInitDescriptor, DeviceExt}, BufferUsages, IndexFormat, SurfaceError};
+use winit::event_loop::ControlFlow;
+
+use crate::{color::blend::PBlendMode, renderer::{custom_shader::prepare_custom_shaders, resize::reconfigure_surface, shader::{Shader, ShaderBuilder}, state::{get_renderer_state, set_renderer_state}, texture::{release_textures, upload_textures, PTextureWrap}}};
+
+// Everything drawn through the draw list uses this shader,
+// with a pipeline for each blend mode
+pub fn build_shape_shader (blend_mode: PBlendMode) -> Shader {
+    ShaderBuilder::new()
+        .with_content(concat!(include_str!("../shaders/shape.wgsl"), include_str!("../shaders/lighting.wgsl")))
+        .with_entry_points("vs_lit", if blend_mode.premultiplied() { "fs_lit_premultiplied" } else { "fs_lit" })
+        .with_label(format!("Shape Shader ({:?})", blend_mode))
+        .with_blend(blend_mode.blend_state())
+        .with_vertex_layout()
+        .with_projection()
+        .with_texture()
+        .with_lighting()
+        .build()
+}
+
+// Builds the pipelines for blend modes used for the first time this frame
+fn prepare_shape_shaders () {
+    let missing: Vec<PBlendMode> = {
+        let renderer_state = get_renderer_state();
+        PBlendMode::ALL
+            .into_iter()
+            .filter(|mode| !renderer_state.shape_shaders.contains_key(mode))
+            .filter(|mode| renderer_state.draw_list.batches.iter().any(|batch| batch.blend_mode == *mode))
+            .collect()
+    };
+
+    for blend_mode in missing {
+        let shape_shader = build_shape_shader(blend_mode);
+        set_renderer_state! {
+            shape_shaders.insert(blend_mode, shape_shader);
+        }
+    }
+}
+
+// Draws everything drawn so far into the canvas and empties the draw list.
+// Called at the end of every frame, and by anything that needs
+// the canvas to be up to date in the middle of one (like loadPixels())
+pub fn flush () {
+    // Before the window exists (in setup()) everything stays in the draw list
+    if get_renderer_state().device.is_none() { return; }
+
+    prepare_shape_shaders();
+    prepare_custom_shaders();
+    upload_textures();
+
+    let changed = {
+        let renderer_state = get_renderer_state();
+        !renderer_state.draw_list.is_empty() || renderer_state.clear_color.is_some() || !renderer_state.shaders.is_empty()
+    };
+    if !changed {
+        release_textures();
+        return;
+    }
+
+    {
+        let renderer_state = get_renderer_state();
+        let (Some(device), Some(queue), Some(canvas), Some(projection), Some(texture_bindings), Some(lighting)) = (
+            renderer_state.device.as_ref(),
+            renderer_state.queue.as_ref(),
+            renderer_state.canvas.as_ref(),
+            renderer_state.projection.as_ref(),
+            renderer_state.texture_bindings.as_ref(),
+            renderer_state.lighting.as_ref()
+        ) else { return; };
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        // Buffers for this frame's draw list, and the
+        // projections retained shapes are drawn with
+        let draw_list = &renderer_state.draw_list;
+        let draw_list_buffers = (!draw_list.indices.is_empty()).then(|| (
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Draw List Vertex Buffer"),
+                contents: bytemuck::cast_slice(&draw_list.vertices),
+                usage: BufferUsages::VERTEX
+            }),
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Draw List Index Buffer"),
+                contents: bytemuck::cast_slice(&draw_list.indices),
+                usage: BufferUsages::INDEX
+            })
+        ));
+        let lighting_buffer = (!draw_list.is_empty()).then(|| lighting.upload(device, &draw_list.lighting));
+        let view_projection = renderer_state.view_projection();
+        let model_projections: Vec<_> = draw_list.batches.iter()
+            .map(|batch| batch.retained.as_ref().map(|retained| projection.with_model(device, view_projection, retained.model)))
+            .collect();
+
+        // The canvas is only cleared when background() has been called
+        let load = match renderer_state.clear_color {
+            Some(color) => wgpu::LoadOp::Clear(color.to_wgpu_color(renderer_state.canvas_format)),
+            None => wgpu::LoadOp::Load
+        };
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(canvas.color_attachment(load))],
+                depth_stencil_attachment: canvas.depth_attachment(renderer_state.clear_depth),
+                occlusion_query_set: None,
+                timestamp_writes: None
+            });
+
+            // loop over shaders and draw them here
+            for shader in &renderer_state.shaders {
+                rpass.set_pipeline(&shader.pipeline);
+                if shader.has_uniforms {
+                    rpass.set_bind_group(0, &projection.bind_group, &[]);
+                }
+                if let Some(vertex_buffer) = &shader.vertex_buffer {
+                    rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                }
+                if let Some(index_buffer) = &shader.index_buffer {
+                    rpass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+                }
+
+                if shader.has_index_buffer {
+                    rpass.draw_indexed(0..shader.draw_count, 0, 0..1)
+                }
+                else {
+                    rpass.draw(0..shader.draw_count, 0..1);
+                }
+            }
+
+            // Batches are drawn in order, switching pipelines when the blend mode
+            // or shader changes. The shape shader gets the batch's lighting block
+            // at group 2, custom shaders get their uniforms there instead
+            if let Some((_, lighting_bind_group)) = &lighting_buffer {
+                for (batch, model_projection) in draw_list.batches.iter().zip(&model_projections) {
+                    let (vertex_buffer, index_buffer) = match (&batch.retained, &draw_list_buffers) {
+                        (Some(retained), _) => retained.geometry.buffers(device),
+                        (None, Some(buffers)) => buffers,
+                        (None, None) => continue
+                    };
+
+                    let (pipeline, uniforms) = match batch.shader {
+                        Some(id) => {
+                            let Some(gpu) = renderer_state.custom_shaders[id].gpu.as_ref() else { continue; };
+                            let Some(shader) = gpu.pipelines.get(&batch.blend_mode) else { continue; };
+                            (&shader.pipeline, gpu.uniforms.as_ref())
+                        }
+                        None => {
+                            let Some(shader) = renderer_state.shape_shaders.get(&batch.blend_mode) else { continue; };
+                            (&shader.pipeline, None)
+                        }
+                    };
+                    let texture_bind_group =
+                        batch.texture
+                        .and_then(|texture| Some(renderer_state.textures.get(texture.id)?.gpu.as_ref()?.bind_group(texture.wrap)))
+                        .unwrap_or(texture_bindings.white.bind_group(PTextureWrap::TextureClamp));
+
+                    // Strokes in 3D are already projected, see Tessellator
+                    let projection_bind_group = match model_projection {
+                        Some((_, bind_group)) => bind_group,
+                        None if batch.screen => &projection.screen_bind_group,
+                        None => &projection.bind_group
+                    };
+
+                    rpass.set_pipeline(pipeline);
+                    rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                    rpass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+                    rpass.set_bind_group(0, projection_bind_group, &[]);
+                    rpass.set_bind_group(1, texture_bind_group, &[]);
+                    match (batch.shader, uniforms) {
+                        (Some(_), Some(uniforms)) => rpass.set_bind_group(2, &uniforms.bind_group, &[]),
+                        (Some(_), None) => {}
+                        (None, _) => rpass.set_bind_group(2, lighting_bind_group, &[lighting.offset(batch.lighting)])
+                    }
+                    rpass.draw_indexed(batch.indices.clone(), 0, 0..1);
+                }
+            }
+        }
+
+        queue.submit(Some(encoder.finish()));
+    }
+
+    let canvas_version = get_renderer_state().canvas_version + 1;
+    set_renderer_state! {
+        draw_list.clear();
+        clear_color = None;
+        clear_depth = false;
+        canvas_version = canvas_version;
+    }
+    release_textures();
+}
+
+// Blits the canvas onto the window
+pub fn present (control_flow: &mut ControlFlow) {
+    let renderer_state = get_renderer_state();
+    let (Some(device), Some(queue), Some(surface), Some(blitter)) = (
+        renderer_state.device.as_ref(),
+        renderer_state.queue.as_ref(),
+        renderer_state.surface.as_ref(),
+        renderer_state.blitter.as_ref()
+    ) else { return; };
+
+    let frame = match surface.get_current_texture() {
+        Ok(frame) => frame,
+
+        // The surface no longer matches the window (usually after a resize
+        // that hasn't been handled yet). Reconfigure and try again next frame.
+        // Nothing is lost, everything has already been drawn into the canvas
+        Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+            drop(renderer_state);
+            reconfigure_surface();
+            return;
+        }
+
+        Err(SurfaceError::Timeout) => return,
+
+        Err(SurfaceError::OutOfMemory) => {
+            eprintln!("The GPU ran out of memory while getting the next frame.");
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
+    };
+    let view = frame.texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    blitter.blit(&mut encoder, &view);
+
+    queue.submit(Some(encoder.finish()));
+    frame.present();
+}
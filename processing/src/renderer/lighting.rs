@@ -0,0 +1,108 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{util::{BufferInitDescriptor, DeviceExt}, BindGroup, BindGroupLayout, Buffer, BufferUsages, Device};
+
+// Processing's limit, the lighting block has room for this many
+pub(crate) const MAX_LIGHTS: usize = 8;
+
+// Kinds of light, as the shape shader tells them apart
+pub(crate) const AMBIENT: f32 = 0.0;
+pub(crate) const DIRECTIONAL: f32 = 1.0;
+pub(crate) const POINT: f32 = 2.0;
+pub(crate) const SPOT: f32 = 3.0;
+
+// One light, laid out like Light in lighting.wgsl. Colors
+// are in the canvas' color space, see PColor::to_shader_color
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Pod, Zeroable)]
+pub struct LightUniform {
+    pub position: [f32; 4],
+    pub direction: [f32; 4],
+    pub diffuse: [f32; 4],
+    pub specular: [f32; 4],
+    // Constant, linear and quadratic falloff
+    pub falloff: [f32; 4],
+    // Cosine of the spot angle, spot concentration and the kind of light
+    pub spot: [f32; 4],
+}
+
+// Lights and material fills are drawn with, laid out like Lighting in
+// lighting.wgsl. Geometry drawn without lights gets one with lit = 0
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Pod, Zeroable)]
+pub struct LightingUniform {
+    pub ambient: [f32; 4],
+    pub specular: [f32; 4],
+    pub emissive: [f32; 4],
+    // Where the camera is, for specular highlights
+    pub eye: [f32; 4],
+    pub shininess: f32,
+    pub count: u32,
+    pub lit: u32,
+    // The fill color is used as the ambient color until ambient() is called
+    pub fill_ambient: u32,
+    pub lights: [LightUniform; MAX_LIGHTS],
+}
+
+// Layout of the lighting block (group 2 of the shape shader). Every block used
+// in a frame goes into one buffer, batches pick theirs with a dynamic offset
+pub struct LightingBindings {
+    pub layout: BindGroupLayout,
+    // Distance between blocks in the buffer, as uniform offsets have to be aligned
+    pub stride: usize,
+}
+
+impl LightingBindings {
+    pub fn new (device: &Device) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lighting Bind Group Layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<LightingUniform>() as u64)
+                },
+                count: None
+            }]
+        });
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+        let stride = std::mem::size_of::<LightingUniform>().div_ceil(alignment) * alignment;
+
+        Self { layout, stride }
+    }
+
+    // Buffer with the unlit block first, then blocks, and a bind group for it
+    pub fn upload (&self, device: &Device, blocks: &[LightingUniform]) -> (Buffer, BindGroup) {
+        let mut contents = vec![0; self.stride * (blocks.len() + 1)];
+        for (index, block) in blocks.iter().enumerate() {
+            let offset = self.stride * (index + 1);
+            contents[offset..offset + std::mem::size_of::<LightingUniform>()].copy_from_slice(bytemuck::bytes_of(block));
+        }
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Lighting Buffer"),
+            contents: &contents,
+            usage: BufferUsages::UNIFORM
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Lighting Bind Group"),
+            layout: &self.layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<LightingUniform>() as u64)
+                })
+            }]
+        });
+        (buffer, bind_group)
+    }
+
+    // Dynamic offset of a batch's block, None for unlit batches
+    pub fn offset (&self, block: Option<usize>) -> u32 {
+        (block.map_or(0, |index| index + 1) * self.stride) as u32
+    }
+}
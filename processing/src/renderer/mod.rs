@@ -1,4 +1,17 @@
 pub mod bind_group;
+pub mod blit;
+pub mod camera;
+pub mod custom_shader;
+pub mod draw_list;
+pub mod filter;
+pub mod format;
+pub mod frame;
+pub mod lighting;
+pub mod projection;
+pub mod readback;
+pub mod resize;
 pub mod shader;
 pub mod state;
-pub mod vertex;
\ No newline at end of file
+pub mod target;
+pub mod texture;
+pub mod vertex;
@@ -0,0 +1,118 @@
+use wgpu::{BindGroup, BindGroupLayout, Buffer, BufferUsages, Device, Queue, util::{BufferInitDescriptor, DeviceExt}};
+
+use crate::math::matrix::{multiply, normal_matrix, PMatrix, IDENTITY};
+
+// Maps window coordinates (in points, origin at the top left)
+// to normalized device coordinates. Shaders built with
+// ShaderBuilder::with_projection get it at group 0, binding 0.
+// In 3D the matrix is the camera's (see Camera), and screen maps
+// strokes, which are projected on the CPU, like in 2D.
+// Binding 1 is the model matrix of retained shapes (identity for
+// everything else), which the matrix at binding 0 already includes.
+// The shape shader needs it on its own to light them, and binding 2,
+// its normal matrix (see normal_matrix()), to turn their normals
+pub struct Projection {
+    pub buffer: Buffer,
+    pub model_buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+    pub screen_buffer: Buffer,
+    pub screen_bind_group: BindGroup,
+    // Size of the canvas in points
+    pub width: f32,
+    pub height: f32,
+}
+
+// Column major, like WGSL expects. z is kept as is, it is the depth
+pub fn ortho_matrix (width: f32, height: f32) -> PMatrix {
+    [
+        [2.0 / width, 0.0, 0.0, 0.0],
+        [0.0, -2.0 / height, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [-1.0, 1.0, 0.0, 1.0],
+    ]
+}
+
+fn bind_group_for (device: &Device, layout: &BindGroupLayout, matrix: &Buffer, [model, normal]: [&Buffer; 2]) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("Projection Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: matrix.as_entire_binding()
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: model.as_entire_binding()
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: normal.as_entire_binding()
+            }
+        ]
+    })
+}
+
+impl Projection {
+    pub fn new (device: &Device, width: f32, height: f32, matrix: PMatrix) -> Self {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Projection Buffer"),
+            contents: bytemuck::cast_slice(&matrix),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        });
+        // The identity is its own normal matrix, so this is bound for both
+        let model_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Model Buffer"),
+            contents: bytemuck::cast_slice(&IDENTITY),
+            usage: BufferUsages::UNIFORM
+        });
+        let screen_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Screen Projection Buffer"),
+            contents: bytemuck::cast_slice(&ortho_matrix(width, height)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Projection Bind Group Layout"),
+            entries: &[0, 1, 2].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None
+                },
+                count: None
+            })
+        });
+
+        let bind_group = bind_group_for(device, &bind_group_layout, &buffer, [&model_buffer, &model_buffer]);
+        let screen_bind_group = bind_group_for(device, &bind_group_layout, &screen_buffer, [&model_buffer, &model_buffer]);
+
+        Self { buffer, model_buffer, bind_group_layout, bind_group, screen_buffer, screen_bind_group, width, height }
+    }
+
+    // Bind group for a retained shape drawn with model, under the camera matrix.
+    // The buffers have to be kept until the shape has been drawn
+    pub fn with_model (&self, device: &Device, matrix: PMatrix, model: PMatrix) -> ([Buffer; 3], BindGroup) {
+        let buffers = [multiply(&matrix, &model), model, normal_matrix(&model)].map(|matrix| device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Model Projection Buffer"),
+            contents: bytemuck::cast_slice(&matrix),
+            usage: BufferUsages::UNIFORM
+        }));
+        let bind_group = bind_group_for(device, &self.bind_group_layout, &buffers[0], [&buffers[1], &buffers[2]]);
+        (buffers, bind_group)
+    }
+
+    pub fn update (&mut self, queue: &Queue, width: f32, height: f32, matrix: PMatrix) {
+        self.width = width;
+        self.height = height;
+        self.set_matrix(queue, matrix);
+        queue.write_buffer(&self.screen_buffer, 0, bytemuck::cast_slice(&ortho_matrix(width, height)));
+    }
+
+    pub fn set_matrix (&self, queue: &Queue, matrix: PMatrix) {
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&matrix));
+    }
+}
@@ -0,0 +1,207 @@
+use wgpu::{BufferDescriptor, BufferUsages, Device, Extent3d, ImageCopyBuffer, ImageDataLayout, Maintain, MapMode, Queue, TextureFormat, COPY_BYTES_PER_ROW_ALIGNMENT};
+
+use crate::{color::{linear_to_srgb, srgb_to_linear, PColor}, renderer::target::RenderTarget};
+
+// Moves pixels of a render target between the GPU and the CPU.
+// On the CPU side pixels are 0xAARRGGBB with sRGB encoded components,
+// like pixels[] in Processing, whatever format the target has
+
+fn bytes_per_pixel (format: TextureFormat) -> u32 {
+    match format {
+        TextureFormat::Rgba16Float => 8,
+        _ => 4
+    }
+}
+
+// Reads every pixel of the target, row by row from the top.
+// Waits for the GPU to finish everything submitted before
+pub fn read_pixels (device: &Device, queue: &Queue, target: &RenderTarget) -> Vec<u32> {
+    let bytes_per_pixel = bytes_per_pixel(target.format);
+    let row_bytes = target.width * bytes_per_pixel;
+
+    // Rows copied into a buffer have to start at multiples of
+    // COPY_BYTES_PER_ROW_ALIGNMENT, so each row is padded
+    let padded_row_bytes = row_bytes.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_row_bytes * target.height) as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback") });
+    encoder.copy_texture_to_buffer(
+        target.texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_bytes),
+                rows_per_image: Some(target.height)
+            }
+        },
+        Extent3d { width: target.width, height: target.height, depth_or_array_layers: 1 }
+    );
+    queue.submit(Some(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| { let _ = sender.send(result); });
+    device.poll(Maintain::Wait);
+    receiver
+        .recv()
+        .expect("The GPU went away while reading pixels")
+        .expect("Could not read pixels back from the GPU");
+
+    let data = slice.get_mapped_range();
+    let pixels =
+        data
+        .chunks(padded_row_bytes as usize)
+        .flat_map(|row| row[..row_bytes as usize].chunks(bytes_per_pixel as usize))
+        .map(|pixel| to_argb(target.format, pixel))
+        .collect();
+
+    drop(data);
+    buffer.unmap();
+    pixels
+}
+
+// Replaces every pixel of the target. pixels has to have
+// width * height entries. Doesn't touch multisampled targets'
+// samples, see RenderTarget::reload_samples
+pub fn write_pixels (queue: &Queue, target: &RenderTarget, pixels: &[u32]) {
+    let bytes_per_pixel = bytes_per_pixel(target.format);
+    let mut data = Vec::with_capacity(pixels.len() * bytes_per_pixel as usize);
+    for pixel in pixels {
+        from_argb(target.format, *pixel, &mut data);
+    }
+
+    queue.write_texture(
+        target.texture.as_image_copy(),
+        &data,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(target.width * bytes_per_pixel),
+            rows_per_image: Some(target.height)
+        },
+        Extent3d { width: target.width, height: target.height, depth_or_array_layers: 1 }
+    );
+}
+
+// 8 bit formats (sRGB views included) store sRGB encoded bytes,
+// float canvases store linear values that have to be encoded
+fn to_argb (format: TextureFormat, pixel: &[u8]) -> u32 {
+    match format {
+        TextureFormat::Rgba16Float => {
+            let channel = |index: usize| f16_to_f32(u16::from_le_bytes([pixel[index * 2], pixel[index * 2 + 1]]));
+            PColor {
+                r: linear_to_srgb(channel(0).clamp(0.0, 1.0)),
+                g: linear_to_srgb(channel(1).clamp(0.0, 1.0)),
+                b: linear_to_srgb(channel(2).clamp(0.0, 1.0)),
+                a: channel(3)
+            }.to_argb()
+        }
+        _ => u32::from_be_bytes([pixel[3], pixel[0], pixel[1], pixel[2]])
+    }
+}
+
+fn from_argb (format: TextureFormat, argb: u32, data: &mut Vec<u8>) {
+    match format {
+        TextureFormat::Rgba16Float => {
+            let color = PColor::from_argb(argb);
+            for value in [srgb_to_linear(color.r), srgb_to_linear(color.g), srgb_to_linear(color.b), color.a] {
+                data.extend_from_slice(&f32_to_f16(value).to_le_bytes());
+            }
+        }
+        _ => {
+            let [a, r, g, b] = argb.to_be_bytes();
+            data.extend_from_slice(&[r, g, b, a]);
+        }
+    }
+}
+
+fn f16_to_f32 (bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = ((bits >> 10) & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f32;
+
+    match exponent {
+        0 => sign * mantissa * 2f32.powi(-24),
+        31 => if mantissa == 0.0 { sign * f32::INFINITY } else { f32::NAN },
+        _ => sign * (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15)
+    }
+}
+
+// Rounds to nearest, values closer to zero than
+// the smallest subnormal half float become zero
+fn f32_to_f16 (value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    if value.is_nan() { return 0x7e00; }
+
+    let magnitude = value.abs();
+    if magnitude >= 65520.0 { return sign | 0x7c00; }
+    if magnitude <= 2f32.powi(-25) { return sign; }
+
+    if magnitude < 2f32.powi(-14) {
+        return sign | (magnitude / 2f32.powi(-24)).round() as u16;
+    }
+
+    let exponent = magnitude.log2().floor() as i32;
+    let mantissa = ((magnitude / 2f32.powi(exponent) - 1.0) * 1024.0).round() as u32;
+    // Rounding up can carry into the exponent
+    let (exponent, mantissa) = if mantissa == 1024 { (exponent + 1, 0) } else { (exponent, mantissa) };
+    sign | (((exponent + 15) as u16) << 10) | mantissa as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_floats_round_trip () {
+        for bits in 0..=u16::MAX {
+            let value = f16_to_f32(bits);
+            if value.is_nan() { continue; }
+            assert_eq!(f32_to_f16(value), bits, "{:#06x} is {}", bits, value);
+        }
+    }
+
+    #[test]
+    fn half_float_special_values () {
+        assert_eq!(f16_to_f32(0x0000), 0.0);
+        assert!(f16_to_f32(0x8000).is_sign_negative());
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x03ff), 1023.0 * 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x0400), 2f32.powi(-14));
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(f32_to_f16(1e10), 0x7c00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn half_floats_round_to_nearest () {
+        let step = 2f32.powi(-10);
+        assert_eq!(f32_to_f16(1.0 + step * 0.4), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + step * 0.6), 0x3c01);
+        assert_eq!(f32_to_f16(2.0 - step * 0.1), 0x4000);
+        assert_eq!(f32_to_f16(65519.0), 0x7bff);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+
+        // Subnormals, and values below the smallest one
+        assert_eq!(f32_to_f16(2f32.powi(-24) * 0.4), 0x0000);
+        assert_eq!(f32_to_f16(2f32.powi(-24) * 0.6), 0x0001);
+        assert_eq!(f32_to_f16(-2f32.powi(-24) * 1.6), 0x8002);
+        assert_eq!(f32_to_f16(2f32.powi(-14) - 2f32.powi(-26)), 0x0400);
+    }
+}
@@ -0,0 +1,75 @@
+use winit::dpi::PhysicalSize;
+
+use crate::{color::PColor, renderer::{blit::Blitter, camera::PRenderer, format::create_canvas, state::{get_renderer_state, set_renderer_state, RENDERER_STATE}}};
+
+// Called when the window changes size or moves to a display
+// with a different scale factor. Everything that depends on the
+// window size (surface, canvas, projection) is recreated or updated
+pub fn resize_renderer (size: PhysicalSize<u32>, scale_factor: f64) {
+    // Minimized windows report a size of 0, which
+    // can't be used to configure a surface
+    if size.width == 0 || size.height == 0 { return; }
+
+    let logical = size.to_logical::<f32>(scale_factor);
+
+    let (config, canvas, blitter) = {
+        let state = get_renderer_state();
+        let (Some(device), Some(surface), Some(config)) = (
+            state.device.as_ref(),
+            state.surface.as_ref(),
+            state.surface_config.as_ref()
+        ) else { return; };
+
+        let mut config = config.clone();
+        config.width = size.width;
+        config.height = size.height;
+        surface.configure(device, &config);
+
+        let pixel_density = state.pixel_density as f32;
+        let canvas = create_canvas(
+            device,
+            (logical.width * pixel_density) as u32,
+            (logical.height * pixel_density) as u32,
+            state.canvas_format,
+            state.sample_count,
+            state.renderer == PRenderer::P3D
+        );
+        let blitter = Blitter::new(device, &canvas.sample_view, config.format);
+
+        (config, canvas, blitter)
+    };
+
+    {
+        let mut guard = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+        let state = &mut *guard;
+        let matrix = state.camera.matrix(state.renderer, logical.width, logical.height);
+        if let (Some(queue), Some(projection)) = (state.queue.as_ref(), state.projection.as_mut()) {
+            projection.update(queue, logical.width, logical.height, matrix);
+        }
+    }
+
+    let canvas_version = get_renderer_state().canvas_version + 1;
+    set_renderer_state! {
+        canvas_version = canvas_version;
+        width = Some(logical.width);
+        height = Some(logical.height);
+        surface_config = Some(config);
+        canvas = Some(canvas);
+        blitter = Some(blitter);
+        clear_color = Some(PColor::DEFAULT_BACKGROUND);
+        clear_depth = true;
+    }
+}
+
+// Reconfigures the surface with its current configuration.
+// Used when get_current_texture() reports the surface as lost or outdated
+pub fn reconfigure_surface () {
+    let state = get_renderer_state();
+    if let (Some(device), Some(surface), Some(config)) = (
+        state.device.as_ref(),
+        state.surface.as_ref(),
+        state.surface_config.as_ref()
+    ) {
+        surface.configure(device, config);
+    }
+}
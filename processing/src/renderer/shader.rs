@@ -1,6 +1,8 @@
-use wgpu::{Buffer, ShaderModule, RenderPipeline, util::{BufferInitDescriptor, DeviceExt}, BufferUsages, RenderPipelineDescriptor, VertexState, ShaderModuleDescriptor, ShaderSource, VertexBufferLayout, FragmentState};
+use std::time::SystemTime;
 
-use crate::renderer::vertex::Vertex;
+use wgpu::{BindGroupLayout, BlendState, Buffer, Device, ErrorFilter, ShaderModule, PipelineLayout, RenderPipeline, util::{BufferInitDescriptor, DeviceExt}, BufferUsages, RenderPipelineDescriptor, VertexState, ShaderModuleDescriptor, ShaderSource, VertexBufferLayout, FragmentState};
+
+use crate::{error::{PError, PResult}, renderer::{camera::PRenderer, state::RendererState, target::DEPTH_FORMAT, vertex::Vertex}, shader::{glsl::translate, reflect::parse}};
 
 use super::state::get_renderer_state;
 
@@ -13,22 +15,148 @@ pub struct Shader {
 
     pub has_vertex_buffer: bool,
     pub has_index_buffer: bool,
-    pub has_uniforms: bool
+    pub has_uniforms: bool,
+
+    // Set for shaders built with ShaderBuilder::from_source()
+    pub source: Option<ShaderSourceFile>,
+}
+
+// Everything about a pipeline but its shader modules and layout
+struct PipelineSettings {
+    label: String,
+    vertex_entry_point: String,
+    fragment_entry_point: String,
+    blend: BlendState,
+    has_vertices: bool,
+}
+
+// File a shader was built from, and what building its pipeline
+// again takes. Checked by shaderHotReload(), like loadShader()'s files
+pub struct ShaderSourceFile {
+    pub path: String,
+    pub modified: Option<SystemTime>,
+    vertex_content: Option<String>,
+    layout: Option<PipelineLayout>,
+    settings: PipelineSettings,
+}
+
+impl Shader {
+    // Builds the module and pipeline again from the source file. On
+    // error the shader keeps drawing with the ones it had
+    pub fn rebuild_from_source (&mut self) -> PResult<()> {
+        let Some(source) = self.source.as_ref() else { return Ok(()); };
+        let content = std::fs::read_to_string(&source.path).map_err(|_| PError::ShaderNotFound(source.path.clone()))?;
+        // naga's errors have the line, wgpu's don't
+        parse(&source.path, &content)?;
+
+        let state = get_renderer_state();
+        let Some(device) = state.device.as_ref() else { return Ok(()); };
+        let (module, pipeline) = catch_gpu_errors(device, &source.path, || {
+            let module = device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(source.settings.label.as_str()),
+                source: ShaderSource::Wgsl(content.into())
+            });
+            let vertex_module = source.vertex_content.as_ref().map(|content| device.create_shader_module(ShaderModuleDescriptor {
+                label: Some(source.settings.label.as_str()),
+                source: ShaderSource::Wgsl(content.into())
+            }));
+            let pipeline = create_pipeline(&state, &source.settings, &module, vertex_module.as_ref(), source.layout.as_ref());
+            (module, pipeline)
+        })?;
+
+        self.module = module;
+        self.pipeline = pipeline;
+        Ok(())
+    }
+}
+
+// Runs build with GPU validation errors caught instead of going
+// to the device's error handler, which ends the program
+pub fn catch_gpu_errors<T> (device: &Device, path: &str, build: impl FnOnce() -> T) -> PResult<T> {
+    device.push_error_scope(ErrorFilter::Validation);
+    let result = build();
+    match pollster::block_on(device.pop_error_scope()) {
+        Some(error) => Err(PError::InvalidShader { path: path.to_string(), message: error.to_string() }),
+        None => Ok(result)
+    }
+}
+
+fn create_pipeline (
+    state: &RendererState,
+    settings: &PipelineSettings,
+    module: &ShaderModule,
+    vertex_module: Option<&ShaderModule>,
+    layout: Option<&PipelineLayout>
+) -> RenderPipeline {
+    let device = state.device.as_ref().unwrap();
+
+    let mut buffers: Vec<VertexBufferLayout> = vec![];
+    if settings.has_vertices {
+        buffers.push(Vertex::layout());
+    }
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(settings.label.as_str()),
+
+        layout,
+
+        vertex: VertexState {
+            module: vertex_module.unwrap_or(module),
+            entry_point: &settings.vertex_entry_point,
+            buffers: &buffers
+        },
+
+        fragment: Some(FragmentState {
+            module,
+            entry_point: &settings.fragment_entry_point,
+            targets: &[Some(wgpu::ColorTargetState {
+                format: state.canvas_format.render_format(),
+                blend: Some(settings.blend),
+                write_mask: wgpu::ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        // Has to match the canvas too, which has a depth buffer in 3D.
+        // Equal depths pass so later geometry covers earlier, like in 2D
+        depth_stencil: (state.renderer == PRenderer::P3D).then(|| wgpu::DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default()
+        }),
+        // Has to match the canvas, which is multisampled with smooth()
+        multisample: wgpu::MultisampleState {
+            count: state.sample_count,
+            ..Default::default()
+        },
+        multiview: None,
+    })
 }
 
 #[derive(Default)]
-pub struct ShaderBuilder {
+pub struct ShaderBuilder<'a> {
     label: String,
     content: Option<String>,
+    source_path: Option<String>,
+    vertex_content: Option<String>,
+    vertex_entry_point: Option<String>,
+    fragment_entry_point: Option<String>,
     vertex_buffer: Option<Buffer>,
     index_buffer: Option<Buffer>,
     vertex_or_index_count: u32,
 
     has_vertex_buffer: bool,
     has_index_buffer: bool,
+    has_uniforms: bool,
+    has_vertex_layout: bool,
+    has_texture: bool,
+    has_lighting: bool,
+    bind_group_layouts: Vec<&'a BindGroupLayout>,
+    blend: Option<BlendState>,
 }
 
-impl ShaderBuilder {
+impl<'a> ShaderBuilder<'a> {
     pub fn new () -> Self {
         Self {
             label: String::from("Shader Builder"),
@@ -46,15 +174,35 @@ impl ShaderBuilder {
         self
     }
 
-    pub fn from_source (&mut self, source: impl Into<String>) -> &mut Self {
-        self.content = 
-            if let Ok(content_string) = 
-                std::fs::read_to_string(source.into()) { Some(content_string) }
-            else { None };
+    // Takes the vertex stage from its own source instead of the content's
+    pub fn with_vertex_content (&mut self, content: impl Into<String>) -> &mut Self {
+        self.vertex_content = Some(content.into());
+        self
+    }
 
+    // vs_main and fs_main by default
+    pub fn with_entry_points (&mut self, vertex: &str, fragment: &str) -> &mut Self {
+        self.vertex_entry_point = Some(vertex.to_string());
+        self.fragment_entry_point = Some(fragment.to_string());
         self
     }
 
+    // Takes both stages from GLSL, translated to WGSL by glsl::translate()
+    pub fn with_glsl (&mut self, vertex: &str, fragment: &str) -> PResult<&mut Self> {
+        let (fragment, vertex) = translate(Some(("fragment shader", fragment.to_string())), Some(("vertex shader", vertex.to_string())))?;
+        let ((_, fragment), (_, vertex)) = (fragment.unwrap_or_default(), vertex.unwrap_or_default());
+        Ok(self.with_content(fragment).with_vertex_content(vertex).with_entry_points("main", "main"))
+    }
+
+    // Takes the content from a WGSL file. The shader is built
+    // again when the file changes while shaderHotReload() is on
+    pub fn from_source (&mut self, source: impl Into<String>) -> PResult<&mut Self> {
+        let path = source.into();
+        let content = std::fs::read_to_string(&path).map_err(|_| PError::ShaderNotFound(path.clone()))?;
+        self.source_path = Some(path);
+        Ok(self.with_content(content))
+    }
+
     pub fn with_vertex_buffer (&mut self, vertices: Vec<Vertex>) -> &mut Self {
         let state = get_renderer_state();
         let device = state.device.as_ref().unwrap();
@@ -91,6 +239,46 @@ impl ShaderBuilder {
         self
     }
 
+    // Uses the Vertex layout without giving the shader a buffer.
+    // For shaders whose vertices are uploaded every frame, like the draw list
+    pub fn with_vertex_layout (&mut self) -> &mut Self {
+        self.has_vertex_layout = true;
+        self
+    }
+
+    // Binds the projection matrix at group 0, binding 0,
+    // so vertices can be given in window coordinates
+    pub fn with_projection (&mut self) -> &mut Self {
+        self.has_uniforms = true;
+        self
+    }
+
+    // Binds a texture and its sampler at group 1, see TextureBindings.
+    // Needs the projection as well
+    pub fn with_texture (&mut self) -> &mut Self {
+        self.has_texture = true;
+        self
+    }
+
+    // Binds the lighting block at group 2, see LightingBindings.
+    // Needs the texture as well
+    pub fn with_lighting (&mut self) -> &mut Self {
+        self.has_lighting = true;
+        self
+    }
+
+    // Adds a bind group after the projection, texture and lighting ones
+    pub fn with_bind_group_layout (&mut self, layout: &'a BindGroupLayout) -> &mut Self {
+        self.bind_group_layouts.push(layout);
+        self
+    }
+
+    // Replaces whatever is in the canvas by default
+    pub fn with_blend (&mut self, blend: BlendState) -> &mut Self {
+        self.blend = Some(blend);
+        self
+    }
+
     pub fn build (&mut self) -> Shader {
         // 
         let state = get_renderer_state();
@@ -101,48 +289,50 @@ impl ShaderBuilder {
             source: ShaderSource::Wgsl(self.content.as_ref().unwrap().into())
         });
 
-        // make pipeline layout
-
-        let mut buffers: Vec<VertexBufferLayout> = vec![];
-        if self.vertex_buffer.is_some() {
-            let x = VertexBufferLayout {
-                array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
-                step_mode: wgpu::VertexStepMode::Vertex,
-                attributes: &wgpu::vertex_attr_array![0 => Float32x3]
-            };
-            buffers.push(x);
-        }
-
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        let vertex_module = self.vertex_content.as_ref().map(|content| device.create_shader_module(ShaderModuleDescriptor {
             label: Some(self.label.as_str()),
+            source: ShaderSource::Wgsl(content.into())
+        }));
 
-            layout: None,
-
-            vertex: VertexState {
-                module: &module,
-                entry_point: "vs_main",
-                buffers: &buffers
-            },
-
-            fragment: Some(FragmentState {
-                module: &module,
-                entry_point: "fs_main",
-                targets: &[Some(wgpu::ColorTargetState {
-					format: wgpu::TextureFormat::Bgra8UnormSrgb,
-					blend: Some(wgpu::BlendState {
-						color: wgpu::BlendComponent::REPLACE,
-						alpha: wgpu::BlendComponent::REPLACE,
-					}),
-					write_mask: wgpu::ColorWrites::ALL,
-				})],
-            }),
-            primitive: wgpu::PrimitiveState::default(),
-            depth_stencil: None,
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
+        // make pipeline layout
+        let layout = if self.has_uniforms {
+            let projection = state.projection.as_ref().expect("No projection specified");
+            let mut bind_group_layouts = vec![&projection.bind_group_layout];
+            if self.has_texture {
+                let texture_bindings = state.texture_bindings.as_ref().expect("No texture bindings specified");
+                bind_group_layouts.push(&texture_bindings.layout);
+            }
+            if self.has_lighting {
+                let lighting = state.lighting.as_ref().expect("No lighting bindings specified");
+                bind_group_layouts.push(&lighting.layout);
+            }
+            bind_group_layouts.extend(self.bind_group_layouts.iter().copied());
+
+            Some(device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(self.label.as_str()),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[]
+            }))
+        }
+        else { None };
+
+        let settings = PipelineSettings {
+            label: self.label.clone(),
+            vertex_entry_point: self.vertex_entry_point.clone().unwrap_or_else(|| "vs_main".to_string()),
+            fragment_entry_point: self.fragment_entry_point.clone().unwrap_or_else(|| "fs_main".to_string()),
+            blend: self.blend.unwrap_or(BlendState::REPLACE),
+            has_vertices: self.vertex_buffer.is_some() || self.has_vertex_layout
+        };
+        let pipeline = create_pipeline(&state, &settings, &module, vertex_module.as_ref(), layout.as_ref());
+
+        let source = self.source_path.take().map(|path| ShaderSourceFile {
+            modified: std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok(),
+            path,
+            vertex_content: self.vertex_content.clone(),
+            layout,
+            settings
         });
 
-
         Shader {
             module,
             pipeline,
@@ -150,10 +340,36 @@ impl ShaderBuilder {
 
             has_vertex_buffer: self.has_vertex_buffer,
             has_index_buffer: self.has_index_buffer,
-            has_uniforms: false,
+            has_uniforms: self.has_uniforms,
 
             vertex_buffer: self.vertex_buffer.take(),
-            index_buffer: self.index_buffer.take()
+            index_buffer: self.index_buffer.take(),
+            source
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A module that doesn't validate gives an error rather than ending
+    // the program, and the device can still be used after it.
+    // Skipped when there is no adapter to run on
+    #[test]
+    fn gpu_errors_are_caught () {
+        let instance = wgpu::Instance::default();
+        let Some(adapter) = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default())) else {
+            eprintln!("No GPU adapter, skipping");
+            return;
+        };
+        let (device, _queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor::default(), None))
+            .expect("Could not get a device");
+
+        let build = |source: &'static str| catch_gpu_errors(&device, "test.wgsl", || {
+            device.create_shader_module(ShaderModuleDescriptor { label: None, source: ShaderSource::Wgsl(source.into()) })
+        });
+        let error = build("@fragment fn fs_main () -> @location(0) vec4<f32> { return 1.0; }").err();
+        assert!(matches!(error, Some(PError::InvalidShader { .. })), "{:?}", error);
+        assert!(build("@fragment fn fs_main () -> @location(0) vec4<f32> { return vec4<f32>(1.0); }").is_ok());
+    }
+}
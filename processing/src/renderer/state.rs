@@ -1,7 +1,9 @@
-use std::{sync::{RwLock, Arc, RwLockReadGuard}, time::Instant};
-use wgpu::{Device, Queue, Surface};
+use std::{collections::HashMap, sync::{RwLock, Arc, RwLockReadGuard}, time::Instant};
+use wgpu::{Device, Queue, Surface, SurfaceConfiguration, TextureFormat};
 use winit::window::Window;
-use crate::renderer::shader::Shader;
+use crate::{color::{blend::PBlendMode, PColor}, core::window::PCursor, renderer::{blit::Blitter, camera::{Camera, PRenderer}, custom_shader::{CustomShader, ShaderId}, draw_list::DrawList, filter::FilterPass, format::PCanvasFormat, lighting::LightingBindings, projection::Projection, shader::Shader, target::RenderTarget, texture::{TextureBindings, TextureId, TextureSlot}}};
+use crate::shader::reload::ShaderErrorHandler;
+use crate::math::matrix::PMatrix;
 use lazy_static::lazy_static;
 
 #[derive(Default)]
@@ -10,22 +12,105 @@ pub struct RendererState {
     pub queue: Option<Queue>,
     pub window: Option<Window>,
     pub surface: Option<Surface>,
+    pub surface_config: Option<SurfaceConfiguration>,
+
+    // Everything is drawn into the canvas, which
+    // is then blitted onto the surface every frame
+    pub canvas: Option<RenderTarget>,
+    pub canvas_format: PCanvasFormat,
+    pub blitter: Option<Blitter>,
+    pub projection: Option<Projection>,
+    // Set with createWindowWithRenderer(). In 3D every render
+    // target has a depth buffer, cleared at the start of each frame
+    pub renderer: PRenderer,
+    pub camera: Camera,
+    pub clear_depth: bool,
 
     pub last_redraw_time: Option<Instant>,
+    // When the window was created, for the time uniform of shaders
+    pub start_time: Option<Instant>,
     
     pub max_fps: u32,
     pub target_fps: u32,
     
     pub width: Option<f32>,
     pub height: Option<f32>,
+    pub pixel_density: u32,
+    // Samples per pixel asked for with smooth(), and the count the
+    // canvas actually got from the adapter when the window was created
+    pub smooth: u32,
+    pub sample_count: u32,
+
+    pub title: String,
+    pub resizable: bool,
+    pub position: Option<(i32, i32)>,
+    pub full_screen: bool,
+    pub display: u32,
+    pub cursor_visible: bool,
+    pub cursor: PCursor,
+
+    pub shaders: Vec<Shader>,
+
+    // Geometry drawn this frame, and the pipelines it is drawn with.
+    // There is one pipeline per blend mode, built the first time it is used
+    pub draw_list: DrawList,
+    pub shape_shaders: HashMap<PBlendMode, Shader>,
+    pub textures: Vec<TextureSlot>,
+    // Freed textures are released at the next flush, once nothing drawn
+    // before can still use them. Their slots are then given to new textures
+    pub released_textures: Vec<TextureId>,
+    pub free_textures: Vec<TextureId>,
+    pub texture_bindings: Option<TextureBindings>,
+    pub lighting: Option<LightingBindings>,
+    // Set by background(). The canvas keeps its contents
+    // between frames otherwise, like in Processing
+    pub clear_color: Option<PColor>,
+    // Goes up every time the canvas changes, so copies
+    // of its pixels can tell when they are out of date
+    pub canvas_version: u64,
+    // filter() pipelines, one per format filtered
+    // (the canvas' and the one images are filtered in)
+    pub filter_passes: HashMap<TextureFormat, FilterPass>,
+    // Shaders loaded with loadShader(), and the one set with shader().
+    // Geometry is drawn with the shape shader while none is set
+    pub custom_shaders: Vec<CustomShader>,
+    pub shader: Option<ShaderId>,
+    // Set with shaderHotReload() and onShaderError()
+    pub shader_hot_reload: bool,
+    pub shader_error_handler: Option<ShaderErrorHandler>,
+}
+
+impl RendererState {
+    // Strokes are anti-aliased in the shader when smooth() is on
+    // but the adapter couldn't give the canvas more than one sample
+    pub fn analytic_aa (&self) -> bool {
+        self.smooth > 1 && self.sample_count == 1
+    }
+
+    // Size in points of what is being drawn into
+    pub fn target_size (&self) -> (f32, f32) {
+        match &self.projection {
+            Some(projection) => (projection.width, projection.height),
+            None => (self.width.unwrap_or_default(), self.height.unwrap_or_default())
+        }
+    }
 
-    pub shaders: Vec<Shader>
+    // Maps positions to clip space, the same way the projection does
+    pub fn view_projection (&self) -> PMatrix {
+        let (width, height) = self.target_size();
+        self.camera.matrix(self.renderer, width, height)
+    }
 }
 
 lazy_static! {
     pub static ref RENDERER_STATE: Arc<RwLock<RendererState>> = Arc::new(RwLock::new(RendererState {
         target_fps: 60,
         max_fps: 60,
+        pixel_density: 1,
+        smooth: 4,
+        sample_count: 1,
+        title: String::from("processing"),
+        cursor_visible: true,
         ..Default::default()
     }));
 }
@@ -42,9 +127,9 @@ macro_rules! set_renderer_state {
             crate::renderer::state::RENDERER_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)* = $value;
         }
     };
-    ($var:ident$(.$var2:ident)*($value:expr$(,$other:expr)*);) => {
+    ($var:ident$(.$var2:ident)*($($value:expr),*);) => {
         {
-            crate::renderer::state::RENDERER_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)*($value$(,$other)*);
+            crate::renderer::state::RENDERER_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)*($($value),*);
         }
     };
 
@@ -55,21 +140,21 @@ macro_rules! set_renderer_state {
     };
 
     // fn - expr
-    ($var:ident$(.$var2:ident)*($value:expr$(,$other:expr)*); $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
-        set_renderer_state!{ $var$(.$var2)*($value$(,$other)*); };
+    ($var:ident$(.$var2:ident)*($($value:expr),*); $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_renderer_state!{ $var$(.$var2)*($($value),*); };
         set_renderer_state!{ $($var3$(.$var4)* = $value2;)* };
     };
 
     // expr - fn
-    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)*($value2:expr$(,$other:expr)*);)*) => {
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)*($($value2:expr),*);)*) => {
         set_renderer_state!{ $var$(.$var2)* = $value; };
-        set_renderer_state!{ $($var3$(.$var4)*($value2$(,$other)*);)* };
+        set_renderer_state!{ $($var3$(.$var4)*($($value2),*);)* };
     };  
 
     // fn - fn
-    ($var:ident$(.$var2:ident)*($value:expr$(,$other:expr)*); $($var3:ident$(.$var4:ident)*($value2:expr$(,$other2:expr)*);)*) => {
-        set_renderer_state!{ $var$(.$var2)*($value$(,$other)*); };
-        set_renderer_state!{ $($var3$(.$var4)*($value2$(,$other2)*);)* };
+    ($var:ident$(.$var2:ident)*($($value:expr),*); $($var3:ident$(.$var4:ident)*($($value2:expr),*);)*) => {
+        set_renderer_state!{ $var$(.$var2)*($($value),*); };
+        set_renderer_state!{ $($var3$(.$var4)*($($value2),*);)* };
     };  
 }
 pub(crate) use set_renderer_state;
\ No newline at end of file
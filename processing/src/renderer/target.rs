@@ -0,0 +1,159 @@
+use wgpu::{Color, CommandEncoder, Device, Extent3d, LoadOp, Operations, RenderPassColorAttachment, RenderPassDepthStencilAttachment, StoreOp, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView};
+
+use crate::renderer::blit::Blitter;
+
+// Format of the depth buffer of render targets in 3D
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+
+// A texture that can be drawn into and then sampled from.
+// The main canvas is one of these, everything gets drawn into
+// it first and is then blitted onto the window surface
+pub struct RenderTarget {
+    pub texture: Texture,
+    pub view: TextureView,
+    // Used when the target is sampled, can have a different
+    // format than view (an sRGB view of a linear texture)
+    pub sample_view: TextureView,
+    // Multisampled texture that is drawn into instead of view when
+    // smooth() is on. It is resolved into texture at the end of every pass
+    pub msaa_view: Option<TextureView>,
+    // Copies the texture back into msaa_view after
+    // its pixels have been written to directly
+    sample_loader: Option<Blitter>,
+    // Only in 3D, with as many samples as what is drawn into
+    pub depth_view: Option<TextureView>,
+    pub sample_count: u32,
+    pub format: TextureFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RenderTarget {
+    pub fn new (
+        device: &Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        sample_format: TextureFormat,
+        sample_count: u32
+    ) -> Self {
+        let view_formats = if sample_format != format { vec![sample_format] } else { vec![] };
+        let size = Extent3d {
+            width: width.max(1),
+            height: height.max(1),
+            depth_or_array_layers: 1
+        };
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage:
+                TextureUsages::RENDER_ATTACHMENT |
+                TextureUsages::TEXTURE_BINDING |
+                TextureUsages::COPY_SRC |
+                TextureUsages::COPY_DST,
+            view_formats: &view_formats
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sample_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            format: Some(sample_format),
+            ..Default::default()
+        });
+
+        // The multisampled texture is only ever rendered to and
+        // resolved, it doesn't need to be sampled or copied
+        let msaa_view = (sample_count > 1).then(|| {
+            device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: TextureDimension::D2,
+                format,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[]
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+
+        let sample_loader = msaa_view.as_ref().map(|_| Blitter::copy(device, &view, format, sample_count));
+
+        Self {
+            texture,
+            view,
+            sample_view,
+            msaa_view,
+            sample_loader,
+            depth_view: None,
+            sample_count,
+            format,
+            width: width.max(1),
+            height: height.max(1)
+        }
+    }
+
+    // Gives the target a depth buffer, for drawing in 3D
+    pub fn with_depth (mut self, device: &Device) -> Self {
+        self.depth_view = Some(
+            device.create_texture(&TextureDescriptor {
+                label: Some("Depth Buffer"),
+                size: Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: self.sample_count,
+                dimension: TextureDimension::D2,
+                format: DEPTH_FORMAT,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[]
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        );
+        self
+    }
+
+    // Draws with multisampling start from the samples, not from the texture.
+    // Anything that writes to the texture directly (updatePixels(), filters)
+    // has to call this afterwards, or the next pass would undo the changes
+    pub fn reload_samples (&self, encoder: &mut CommandEncoder) {
+        if let (Some(msaa_view), Some(sample_loader)) = (&self.msaa_view, &self.sample_loader) {
+            sample_loader.blit(encoder, msaa_view);
+        }
+    }
+
+    // Attachment for a render pass that draws into this target.
+    // With multisampling the samples are kept between passes (the canvas
+    // isn't cleared every frame) and resolved into the texture each time
+    pub fn color_attachment (&self, load: LoadOp<Color>) -> RenderPassColorAttachment<'_> {
+        let (view, resolve_target) = match &self.msaa_view {
+            Some(msaa_view) => (msaa_view, Some(&self.view)),
+            None => (&self.view, None)
+        };
+
+        RenderPassColorAttachment {
+            view,
+            resolve_target,
+            ops: Operations {
+                load,
+                store: StoreOp::Store
+            }
+        }
+    }
+
+    // Depth attachment for a render pass, None in 2D. The depth
+    // buffer is kept between passes unless clear is set
+    pub fn depth_attachment (&self, clear: bool) -> Option<RenderPassDepthStencilAttachment<'_>> {
+        self.depth_view.as_ref().map(|view| RenderPassDepthStencilAttachment {
+            view,
+            depth_ops: Some(Operations {
+                load: if clear { LoadOp::Clear(1.0) } else { LoadOp::Load },
+                store: StoreOp::Store
+            }),
+            stencil_ops: None
+        })
+    }
+}
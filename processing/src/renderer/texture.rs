@@ -0,0 +1,243 @@
+use wgpu::{BindGroup, BindGroupLayout, Device, Extent3d, Queue, Sampler, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages};
+
+use crate::renderer::state::RENDERER_STATE;
+
+// Index into the renderer's texture list
+pub type TextureId = usize;
+
+// What textures show outside of 0 to 1, set with textureWrap()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[repr(C)]
+pub enum PTextureWrap {
+    // The pixels along the edges are stretched out
+    #[default]
+    TextureClamp,
+    // The texture is tiled
+    TextureRepeat
+}
+
+// A texture and how a batch samples it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SampledTexture {
+    pub id: TextureId,
+    pub wrap: PTextureWrap,
+}
+
+impl SampledTexture {
+    // Images and text never go outside of their texture
+    pub fn clamped (id: TextureId) -> Self {
+        Self { id, wrap: PTextureWrap::TextureClamp }
+    }
+}
+
+// RGBA pixels that can be drawn with. They are kept on the CPU, so textures
+// can be made before the window exists (in setup()), and uploaded
+// whenever they have changed before the next frame is drawn
+pub struct TextureSlot {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub dirty: bool,
+    pub gpu: Option<GpuTexture>,
+}
+
+pub struct GpuTexture {
+    pub texture: Texture,
+    // One per PTextureWrap, each with its sampler
+    bind_groups: [BindGroup; 2],
+}
+
+// Shared by every texture bound to a pipeline (group 1).
+// Geometry without a texture is drawn with a white pixel, so
+// the shape shader doesn't need a separate untextured variant.
+// There is a sampler for every PTextureWrap
+pub struct TextureBindings {
+    pub layout: BindGroupLayout,
+    pub samplers: [Sampler; 2],
+    pub white: GpuTexture,
+}
+
+impl TextureBindings {
+    pub fn new (device: &Device, queue: &Queue) -> Self {
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+
+        let samplers = [wgpu::AddressMode::ClampToEdge, wgpu::AddressMode::Repeat].map(|address_mode| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("Texture Sampler"),
+                address_mode_u: address_mode,
+                address_mode_v: address_mode,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                ..Default::default()
+            })
+        });
+
+        let white = GpuTexture::new(device, &layout, &samplers, 1, 1, TextureFormat::Rgba8Unorm);
+        white.write(queue, 1, 1, &[255, 255, 255, 255]);
+
+        Self { layout, samplers, white }
+    }
+}
+
+impl GpuTexture {
+    // The texture starts out empty. Pixels can be written if the format
+    // has 8 bit channels, other textures are filled by copying into them
+    pub fn new (device: &Device, layout: &BindGroupLayout, samplers: &[Sampler; 2], width: u32, height: u32, format: TextureFormat) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Texture"),
+            size: Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[]
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_groups = samplers.each_ref().map(|sampler| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture Bind Group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view)
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler)
+                }
+            ]
+        }));
+
+        Self { texture, bind_groups }
+    }
+
+    pub fn bind_group (&self, wrap: PTextureWrap) -> &BindGroup {
+        &self.bind_groups[wrap as usize]
+    }
+
+    pub fn write (&self, queue: &Queue, width: u32, height: u32, pixels: &[u8]) {
+        queue.write_texture(
+            self.texture.as_image_copy(),
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height)
+            },
+            Extent3d { width, height, depth_or_array_layers: 1 }
+        );
+    }
+}
+
+// Adds a texture and returns its id, in the slot of a freed one if there is one
+fn add_texture (slot: TextureSlot) -> TextureId {
+    let mut state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    match state.free_textures.pop() {
+        Some(id) => {
+            state.textures[id] = slot;
+            id
+        }
+        None => {
+            state.textures.push(slot);
+            state.textures.len() - 1
+        }
+    }
+}
+
+// Adds a texture and returns its id. Pixels are RGBA, row by row
+pub fn create_texture (width: u32, height: u32, pixels: Vec<u8>) -> TextureId {
+    add_texture(TextureSlot {
+        width,
+        height,
+        pixels,
+        dirty: true,
+        gpu: None
+    })
+}
+
+// Adds a texture without pixels, for textures that are rendered into
+// rather than uploaded (like createGraphics()). Whoever renders
+// into it sets its GPU texture, uploads leave it alone
+pub fn create_render_texture (width: u32, height: u32) -> TextureId {
+    add_texture(TextureSlot {
+        width,
+        height,
+        pixels: vec![],
+        dirty: false,
+        gpu: None
+    })
+}
+
+// Frees a texture. The draw list can still use it,
+// so it is only released at the next flush
+pub fn free_texture (id: TextureId) {
+    RENDERER_STATE.try_write().expect("Could not write to RwLock").released_textures.push(id);
+}
+
+// Empties the slots of the textures freed before the last
+// flush, so they can be reused. Called once they are drawn
+pub fn release_textures () {
+    let mut guard = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let state = &mut *guard;
+    for id in state.released_textures.drain(..) {
+        state.textures[id] = TextureSlot { width: 0, height: 0, pixels: vec![], dirty: false, gpu: None };
+        state.free_textures.push(id);
+    }
+}
+
+// Changes the pixels of a texture, they get uploaded before the next frame
+pub fn update_texture<T> (id: TextureId, callback: impl FnOnce(&mut TextureSlot) -> T) -> T {
+    let mut state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let slot = state.textures.get_mut(id).expect("No texture with this id");
+    slot.dirty = true;
+    callback(slot)
+}
+
+// Uploads every texture that changed since the last frame
+pub fn upload_textures () {
+    let mut guard = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let state = &mut *guard;
+    let (Some(device), Some(queue), Some(bindings)) = (
+        state.device.as_ref(),
+        state.queue.as_ref(),
+        state.texture_bindings.as_ref()
+    ) else { return; };
+    let format = state.canvas_format.image_format();
+
+    for slot in state.textures.iter_mut().filter(|slot| slot.dirty) {
+        let size_changed = slot.gpu.as_ref().is_none_or(|gpu| {
+            let size = gpu.texture.size();
+            size.width != slot.width || size.height != slot.height
+        });
+
+        if size_changed {
+            slot.gpu = Some(GpuTexture::new(device, &bindings.layout, &bindings.samplers, slot.width, slot.height, format));
+        }
+        if let Some(gpu) = &slot.gpu {
+            gpu.write(queue, slot.width, slot.height, &slot.pixels);
+        }
+        slot.dirty = false;
+    }
+}
@@ -2,39 +2,81 @@
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
 pub struct Vertex {
     position: [f32; 3],
+    color: [f32; 4],
+    // Position across a stroke (-1 to 1) and the width of its
+    // smoothed edge in the same units. Only used for analytic AA,
+    // the default makes the shader leave the color alone
+    edge: [f32; 2],
+    // Texture coordinates, between 0 and 1
+    uv: [f32; 2],
+    // Which way the surface faces, for lighting in 3D
+    normal: [f32; 3],
 }
 
 impl Vertex {
+    const ATTRIBUTES: [VertexAttribute; 5] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4, 2 => Float32x2, 3 => Float32x2, 4 => Float32x3];
+
     pub fn new (x: f32, y: f32, z: f32) -> Self {
-        let point = Vertex {
-            position: [x, y, z]
-        };
-        println!("bruh: {:?}", point);
-        point
+        Vertex {
+            position: [x, y, z],
+            color: [1.0, 1.0, 1.0, 1.0],
+            edge: [0.0, 1.0],
+            uv: [0.0, 0.0],
+            normal: [0.0, 0.0, 1.0]
+        }
     }
-}
 
+    // Color has to already be in the space the canvas
+    // expects, see PColor::to_shader_color
+    pub fn with_color (mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
 
-macro_rules! normalized_vtx {
+    pub fn with_edge (mut self, across: f32, feather: f32) -> Self {
+        self.edge = [across, feather];
+        self
+    }
 
-    ($x:expr, $y:expr) => {{
-        
-        crate::renderer::vertex::Vertex::new(
-            $x as f32 / crate::renderer::state::get_renderer_state().width.expect("Width of window has not been set") as f32 * 2.0 - 1.0,
-            -($y as f32 / crate::renderer::state::get_renderer_state().height.expect("Height of window has not been set") as f32 * 2.0 - 1.0),
-            0.0
-        )
-    }};
+    pub fn with_uv (mut self, u: f32, v: f32) -> Self {
+        self.uv = [u, v];
+        self
+    }
 
-	($x:expr, $y:expr, $z:expr) => {{
+    pub fn with_normal (mut self, normal: [f32; 3]) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn position (&self) -> [f32; 3] {
+        self.position
+    }
 
-        crate::core::vertex::Vertex::new(
-            $x as f32 / crate::renderer::state::get_renderer_state().width.expect("Width of window has not been set") as f32 * 2.0 - 1.0,
-            -($y as f32 / crate::renderer::state::get_renderer_state().height.expect("Height of window has not been set") as f32 * 2.0 - 1.0),
-            $z as f32
-        ) 
-    }};
+    pub fn color (&self) -> [f32; 4] {
+        self.color
+    }
+
+    pub fn normal (&self) -> [f32; 3] {
+        self.normal
+    }
+
+    // The vertex moved with matrix, for quads that aren't tessellated
+    pub fn transformed (self, matrix: &PMatrix) -> Self {
+        let [x, y, z, _] = transform_point(matrix, self.position);
+        Self { position: [x, y, z], ..self }
+    }
+
+    pub fn layout () -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES
+        }
+    }
 }
 
+
 use bytemuck::{Pod, Zeroable};
-pub(crate) use normalized_vtx;
+use wgpu::{VertexAttribute, VertexBufferLayout};
+
+use crate::math::matrix::{transform_point, PMatrix};
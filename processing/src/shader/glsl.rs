@@ -0,0 +1,297 @@
+use std::collections::BTreeSet;
+
+use naga::{
+    back::wgsl::{self as wgsl_out, WriterFlags},
+    front::glsl::{Frontend, Options},
+    valid::{Capabilities, ValidationFlags, Validator},
+    ShaderStage
+};
+
+use crate::error::{PError, PResult};
+
+// Names Processing gives the projection, bound to the renderer's
+// projection (group 0). Geometry is already transformed on the CPU,
+// so the projection is all of the transform
+const TRANSFORM_NAMES: [&str; 3] = ["transform", "transformMatrix", "projectionMatrix"];
+
+// Locations of the vertex attributes, see Vertex
+const ATTRIBUTES: [(&str, u32); 5] = [("position", 0), ("color", 1), ("texCoord", 3), ("uv", 3), ("normal", 4)];
+
+// Locations of what the vertex stage passes on, matching the ones the
+// shape shader's vertex stage writes. Others get locations from 3 up
+const VARYINGS: [(&str, u32); 2] = [("vertColor", 0), ("vertTexCoord", 2)];
+
+// Shadertoy shaders only define mainImage(), this calls it for every
+// pixel with the coordinates flipped to start at the bottom left
+const SHADERTOY_PREFIX: &str = "\
+uniform vec3 iResolution;
+uniform float iTime;
+uniform vec4 iMouse;
+out vec4 p_shadertoyColor;
+";
+const SHADERTOY_CHANNEL: &str = "uniform sampler2D iChannel0;\n";
+const SHADERTOY_SUFFIX: &str = "
+void main() {
+  mainImage(p_shadertoyColor, vec2(gl_FragCoord.x, iResolution.y - gl_FragCoord.y));
+}
+";
+
+// GLSL unless the file is .wgsl or has WGSL stage attributes in it
+pub fn is_glsl (path: &str, source: &str) -> bool {
+    match std::path::Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some("wgsl") => false,
+        Some("glsl" | "frag" | "vert" | "fs" | "vs" | "fsh" | "vsh") => true,
+        _ => !source.contains("@fragment") && !source.contains("@vertex")
+    }
+}
+
+// A single line declaration outside of a block, like `uniform float time;`
+struct Declaration {
+    qualifier: String,
+    ty: String,
+    // With their array size, if any
+    names: Vec<String>,
+}
+
+fn parse_declaration (line: &str) -> Option<Declaration> {
+    let line = line.split("//").next().unwrap_or_default().trim();
+    if !line.ends_with(';') || line.contains('{') || line.contains('(') || line.starts_with("layout") { return None; }
+
+    let mut words = line.trim_end_matches(';').split_whitespace()
+        .filter(|word| !matches!(*word, "lowp" | "mediump" | "highp" | "smooth" | "flat" | "noperspective"));
+    let qualifier = words.next()?;
+    if !matches!(qualifier, "uniform" | "attribute" | "varying" | "in" | "out") { return None; }
+
+    let ty = words.next()?.to_string();
+    let names = words.collect::<Vec<_>>().join(" ").split(',').map(|name| name.replace(' ', "")).collect();
+    Some(Declaration { qualifier: qualifier.to_string(), ty, names })
+}
+
+// Path (for errors) and source of a stage
+pub type StageFile<'a> = (&'a str, String);
+
+fn has_main (source: &str) -> bool {
+    source.match_indices("main").any(|(index, _)| {
+        let before = source[..index].trim_end();
+        before.ends_with("void") && source[index + 4..].trim_start().starts_with('(')
+    })
+}
+
+fn base_name (name: &str) -> &str {
+    name.split('[').next().unwrap_or(name)
+}
+
+// A GLSL stage taken apart into its declarations
+// and the rest, with declaration lines left empty
+struct Stage<'a> {
+    path: &'a str,
+    stage: ShaderStage,
+    body: String,
+    // Lines added in front of the source, to fix up error line numbers
+    prefix_lines: usize,
+    uniforms: Vec<(String, String)>,
+    transform: Option<String>,
+    samplers: Vec<String>,
+    inputs: Vec<(String, String)>,
+    outputs: Vec<(String, String)>,
+}
+
+impl<'a> Stage<'a> {
+    fn new (path: &'a str, source: &str, stage: ShaderStage) -> Self {
+        let mut source = source.to_string();
+        let mut prefix_lines = 0;
+        let shadertoy = stage == ShaderStage::Fragment && source.contains("mainImage") && !has_main(&source);
+        if shadertoy {
+            let channel = if source.contains("iChannel0") { SHADERTOY_CHANNEL } else { "" };
+            let prefix = format!("{}{}", SHADERTOY_PREFIX, channel);
+            prefix_lines = prefix.lines().count();
+            source = format!("{}{}{}", prefix, source, SHADERTOY_SUFFIX);
+        }
+
+        let mut result = Stage {
+            path,
+            stage,
+            body: String::new(),
+            prefix_lines,
+            uniforms: vec![],
+            transform: None,
+            samplers: vec![],
+            inputs: vec![],
+            outputs: vec![],
+        };
+
+        for line in source.lines() {
+            let trimmed = line.trim();
+            // Everything is compiled as desktop GLSL 4.50
+            if trimmed.starts_with("#version") || trimmed.starts_with("precision ") {
+                result.body.push('\n');
+                continue;
+            }
+            let Some(declaration) = parse_declaration(line) else {
+                result.body.push_str(line);
+                result.body.push('\n');
+                continue;
+            };
+
+            for name in declaration.names {
+                let ty = declaration.ty.clone();
+                match (declaration.qualifier.as_str(), stage) {
+                    ("uniform", _) if ty == "sampler2D" => result.samplers.push(name),
+                    ("uniform", ShaderStage::Vertex) if ty == "mat4" && result.transform.is_none() && TRANSFORM_NAMES.contains(&name.as_str()) => {
+                        result.transform = Some(name);
+                    }
+                    ("uniform", _) => result.uniforms.push((ty, name)),
+                    ("attribute" | "in", ShaderStage::Vertex) | ("varying" | "in", ShaderStage::Fragment) => result.inputs.push((ty, name)),
+                    _ => result.outputs.push((ty, name))
+                }
+            }
+            result.body.push('\n');
+        }
+        result
+    }
+
+    fn error (&self, message: impl Into<String>) -> PError {
+        PError::InvalidShader { path: self.path.to_string(), message: message.into() }
+    }
+
+    // Declarations in Vulkan style GLSL, which naga can read. Every
+    // declaration is on its own line, so errors can give the user's line
+    fn prelude (&self, uniforms: &[(String, String)], varyings: &[String]) -> PResult<String> {
+        let mut lines = vec!["#version 450".to_string(), "#define texture2D texture".to_string()];
+
+        if let Some(name) = &self.transform {
+            lines.push(format!("layout(set = 0, binding = 0) uniform PTransform {{ mat4 {}; }};", name));
+        }
+        if !self.samplers.is_empty() {
+            lines.push("layout(set = 1, binding = 0) uniform texture2D p_texture;".to_string());
+            lines.push("layout(set = 1, binding = 1) uniform sampler p_sampler;".to_string());
+            for name in &self.samplers {
+                lines.push(format!("#define {} sampler2D(p_texture, p_sampler)", name));
+            }
+        }
+        if !uniforms.is_empty() {
+            let members: String = uniforms.iter().map(|(ty, name)| format!(" {} {};", ty, name)).collect();
+            lines.push(format!("layout(set = 2, binding = 0) uniform PUniforms {{{} }};", members));
+        }
+
+        let varying_location = |name: &str| {
+            VARYINGS.iter().find(|(varying, _)| *varying == name).map(|(_, location)| *location)
+                .unwrap_or_else(|| 3 + varyings.iter().position(|varying| varying == name).unwrap_or_default() as u32)
+        };
+
+        for (ty, name) in &self.inputs {
+            let location = match self.stage {
+                ShaderStage::Vertex => ATTRIBUTES.iter().find(|(attribute, _)| *attribute == base_name(name)).map(|(_, location)| *location)
+                    .ok_or_else(|| self.error(format!("Vertices have no attribute \"{}\". They have position, color, texCoord and normal.", name)))?,
+                _ => varying_location(base_name(name))
+            };
+            // Positions are vec3, Processing declares them as vec4
+            if self.stage == ShaderStage::Vertex && location == 0 && ty == "vec4" {
+                lines.push("layout(location = 0) in vec3 p_position;".to_string());
+                lines.push(format!("#define {} vec4(p_position, 1.0)", name));
+            }
+            else {
+                lines.push(format!("layout(location = {}) in {} {};", location, ty, name));
+            }
+        }
+
+        match self.stage {
+            ShaderStage::Vertex => for (ty, name) in &self.outputs {
+                lines.push(format!("layout(location = {}) out {} {};", varying_location(base_name(name)), ty, name));
+            }
+            _ => {
+                if self.outputs.len() > 1 {
+                    return Err(self.error("Fragment shaders can only have one output."));
+                }
+                for (ty, name) in &self.outputs {
+                    lines.push(format!("layout(location = 0) out {} {};", ty, name));
+                }
+                if self.outputs.is_empty() && self.body.contains("gl_FragColor") {
+                    lines.push("layout(location = 0) out vec4 p_FragColor;".to_string());
+                    lines.push("#define gl_FragColor p_FragColor".to_string());
+                }
+            }
+        }
+
+        Ok(lines.join("\n") + "\n")
+    }
+
+    fn to_wgsl (&self, uniforms: &[(String, String)], varyings: &[String]) -> PResult<String> {
+        let prelude = self.prelude(uniforms, varyings)?;
+        let source = prelude.clone() + &self.body;
+        let offset = prelude.lines().count() + self.prefix_lines;
+
+        let module = Frontend::default().parse(&Options::from(self.stage), &source).map_err(|errors| {
+            let messages: Vec<String> = errors.iter().map(|error| {
+                let line = error.meta.location(&source).line_number as usize;
+                if line > offset { format!("line {}: {}", line - offset, error.kind) }
+                else { error.kind.to_string() }
+            }).collect();
+            self.error(messages.join("\n"))
+        })?;
+
+        let info = Validator::new(ValidationFlags::all(), Capabilities::empty())
+            .validate(&module)
+            .map_err(|error| self.error(error.into_inner().to_string()))?;
+        wgsl_out::write_string(&module, &info, WriterFlags::empty()).map_err(|error| self.error(error.to_string()))
+    }
+}
+
+// Translates the stages written in GLSL to WGSL, leaving WGSL ones alone.
+// Processing style GLSL works: uniforms outside of blocks become fields of
+// the uniform block (shared by both stages, so PShader_set() finds them),
+// sampler2D uniforms are the texture being drawn, gl_FragColor and
+// texture2D() work, and attributes and varyings get their locations
+// by name. Shadertoy shaders with only a mainImage() work as well
+pub fn translate<'a> (fragment: Option<StageFile<'a>>, vertex: Option<StageFile<'a>>) -> PResult<(Option<StageFile<'a>>, Option<StageFile<'a>>)> {
+    let glsl = |stage: &Option<StageFile<'a>>, kind| {
+        stage.as_ref().filter(|(path, source)| is_glsl(path, source)).map(|(path, source)| Stage::new(path, source, kind))
+    };
+    let stages = [glsl(&fragment, ShaderStage::Fragment), glsl(&vertex, ShaderStage::Vertex)];
+
+    let mut uniforms: Vec<(String, String)> = vec![];
+    let mut varyings = BTreeSet::new();
+    for stage in stages.iter().flatten() {
+        for (ty, name) in &stage.uniforms {
+            if !uniforms.iter().any(|(_, existing)| existing == name) {
+                uniforms.push((ty.clone(), name.clone()));
+            }
+        }
+        let passed = if stage.stage == ShaderStage::Vertex { &stage.outputs } else { &stage.inputs };
+        varyings.extend(passed.iter().map(|(_, name)| base_name(name).to_string()));
+    }
+    let varyings: Vec<String> = varyings.into_iter().filter(|name| !VARYINGS.iter().any(|(varying, _)| varying == name)).collect();
+
+    let [fragment_stage, vertex_stage] = stages;
+    let fragment = match fragment_stage {
+        Some(stage) => Some((stage.path, stage.to_wgsl(&uniforms, &varyings)?)),
+        None => fragment
+    };
+    let vertex = match vertex_stage {
+        Some(stage) => Some((stage.path, stage.to_wgsl(&uniforms, &varyings)?)),
+        None => vertex
+    };
+    Ok((fragment, vertex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normal_attribute () {
+        let vertex = "
+uniform mat4 transform;
+attribute vec4 position;
+attribute vec3 normal;
+varying vec4 vertColor;
+
+void main() {
+  gl_Position = transform * position;
+  vertColor = vec4(normal, 1.0);
+}
+";
+        let (_, vertex) = translate(None, Some(("lit.vert", vertex.to_string()))).unwrap();
+        assert!(vertex.unwrap().1.contains("@location(4) normal: vec3<f32>"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::ffi::c_char;
+
+use crate::{
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    event::state::get_event_state,
+    renderer::{custom_shader::{add_custom_shader, ShaderFiles}, frame::flush, state::{get_renderer_state, set_renderer_state, RENDERER_STATE}},
+    shader::{glsl::translate, reflect::{reflect, Reflection}, reload::{modified_time, report_shader_error}}
+};
+
+pub mod glsl;
+pub mod reflect;
+pub mod reload;
+
+// Handle to a shader returned by loadShader()
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct PShader {
+    pub id: u32
+}
+
+impl PShader {
+    // Returned by loadShader() when the shader doesn't compile
+    pub const INVALID: PShader = PShader { id: u32::MAX };
+}
+
+// Reads and checks the files for the fragment and vertex stages, either
+// can be left out to use the shape shader's. GLSL is translated to WGSL,
+// see glsl::translate()
+pub(crate) fn compile_shader (fragment: Option<&str>, vertex: Option<&str>) -> PResult<Reflection> {
+    let read = |path: &str| std::fs::read_to_string(path).map_err(|_| PError::ShaderNotFound(path.to_string()));
+    let fragment = match fragment {
+        Some(path) => (path, read(path)?),
+        None => ("shape.wgsl", include_str!("../shaders/shape.wgsl").to_string())
+    };
+    let vertex = match vertex {
+        Some(path) => Some((path, read(path)?)),
+        None => None
+    };
+    let (fragment, vertex) = translate(Some(fragment), vertex)?;
+    let (fragment_path, fragment_source) = fragment.expect("The fragment stage is always given");
+    reflect(fragment_path, fragment_source, vertex)
+}
+
+// Loads a shader from WGSL or GLSL files. It is checked here, so mistakes
+// show up right away rather than the first time the shader is drawn with
+pub(crate) fn load_shader (fragment: Option<&str>, vertex: Option<&str>) -> PResult<PShader> {
+    let reflection = compile_shader(fragment, vertex)?;
+    let mut files = ShaderFiles {
+        fragment: fragment.map(str::to_string),
+        vertex: vertex.map(str::to_string),
+        modified: None
+    };
+    files.modified = modified_time(&files);
+
+    let id = add_custom_shader(
+        format!("Custom Shader {}", fragment.unwrap_or("shape.wgsl")),
+        reflection.vertex,
+        reflection.fragment,
+        files,
+        reflection.uniform_size,
+        reflection.uniforms
+    );
+    Ok(PShader { id: id as u32 })
+}
+
+fn check_shader (shader: PShader) -> PResult<()> {
+    if shader.id as usize >= get_renderer_state().custom_shaders.len() {
+        return Err(PError::UnknownShader(shader.id));
+    }
+    Ok(())
+}
+
+// Everything drawn after this uses the shader, until resetShader()
+pub(crate) fn set_shader (shader: PShader) -> PResult<()> {
+    check_shader(shader)?;
+    set_renderer_state! {
+        shader = Some(shader.id as usize);
+    }
+    Ok(())
+}
+
+pub(crate) fn reset_shader () {
+    set_renderer_state! {
+        shader = None;
+    }
+}
+
+// Sets a field of the shader's uniform block. values has one number per
+// component, matrices are given column by column and arrays element
+// by element. Geometry already drawn with the shader keeps the old value
+pub(crate) fn set_uniform (shader: PShader, name: &str, values: &[f32]) -> PResult<()> {
+    check_shader(shader)?;
+    let id = shader.id as usize;
+
+    let (slots, drawn) = {
+        let renderer_state = get_renderer_state();
+        let slots = renderer_state.custom_shaders[id].uniform_fields
+            .get(name)
+            .cloned()
+            .ok_or_else(|| PError::UnknownUniform(name.to_string()))?;
+        (slots, renderer_state.draw_list.batches.iter().any(|batch| batch.shader == Some(id)))
+    };
+    if slots.len() != values.len() {
+        return Err(PError::InvalidUniformValue { name: name.to_string(), expected: slots.len(), given: values.len() });
+    }
+
+    // There is one uniform buffer per shader, so what has been
+    // drawn with it so far is drawn before the buffer changes
+    if drawn {
+        flush();
+    }
+
+    RENDERER_STATE.try_write().expect("Could not write to RwLock").custom_shaders[id].set_uniform(&slots, values);
+    Ok(())
+}
+
+// Sets the uniforms every frame that shaders commonly read, if a shader
+// has a field with their name: time (seconds since the window opened),
+// resolution (canvas size in pixels) and mouse (in pixels from the top
+// left), and Shadertoy's iTime, iResolution and iMouse (from the bottom left)
+pub(crate) fn update_standard_uniforms () {
+    let (mouse_x, mouse_y) = {
+        let event_state = get_event_state();
+        (event_state.mouse_x, event_state.mouse_y)
+    };
+
+    let mut guard = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let state = &mut *guard;
+    let time = state.start_time.map(|start| start.elapsed().as_secs_f32()).unwrap_or_default();
+    let density = state.pixel_density as f32;
+    let (width, height) = (state.width.unwrap_or_default() * density, state.height.unwrap_or_default() * density);
+    let (mouse_x, mouse_y) = (mouse_x * density, mouse_y * density);
+
+    let values: [(&str, &[f32]); 6] = [
+        ("time", &[time]),
+        ("resolution", &[width, height, 1.0]),
+        ("mouse", &[mouse_x, mouse_y]),
+        ("iTime", &[time]),
+        ("iResolution", &[width, height, 1.0]),
+        ("iMouse", &[mouse_x, height - mouse_y, 0.0, 0.0]),
+    ];
+    for shader in state.custom_shaders.iter_mut() {
+        for (name, values) in values {
+            let Some(slots) = shader.uniform_fields.get(name).cloned() else { continue; };
+            shader.set_uniform(&slots, values);
+        }
+    }
+}
+
+unsafe fn optional_string (string: *const c_char) -> Option<String> {
+    if string.is_null() { return None; }
+    Some(string_from(string))
+}
+
+/// Loads a shader from WGSL or GLSL files. Either path can be null to use
+/// the built-in stage. A shader that can't be loaded gives PShader_INVALID,
+/// the error goes to stderr and to the callback set with onShaderError()
+///
+/// # Safety
+/// `fragment` and `vertex` must be null or point to null terminated UTF-8 strings
+#[no_mangle]
+pub unsafe extern "C" fn loadShader (fragment: *const c_char, vertex: *const c_char) -> PShader {
+    load_shader(optional_string(fragment).as_deref(), optional_string(vertex).as_deref()).unwrap_or_else(|error| {
+        report_shader_error(&error);
+        PShader::INVALID
+    })
+}
+
+// PShader_INVALID is ignored, drawing goes on with the current shader
+#[no_mangle]
+pub extern "C" fn shader (shader: PShader) {
+    if shader == PShader::INVALID { return; }
+    set_shader(shader).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn resetShader () {
+    reset_shader();
+}
+
+/// Sets the uniform block field called `name` to `count` numbers
+///
+/// # Safety
+/// `name` must point to a null terminated UTF-8 string
+/// and `values` to at least `count` floats
+#[no_mangle]
+pub unsafe extern "C" fn PShader_set (shader: PShader, name: *const c_char, values: *const f32, count: usize) {
+    if shader == PShader::INVALID { return; }
+    let name = optional_string(name).unwrap_or_default();
+    let values = if values.is_null() { &[] } else { std::slice::from_raw_parts(values, count) };
+    set_uniform(shader, &name, values).or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::CString, sync::atomic::{AtomicBool, Ordering}};
+
+    use super::*;
+    use crate::{lock_state, shader::reload::onShaderError};
+
+    static REPORTED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn on_error (_message: *const c_char) {
+        REPORTED.store(true, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn load_shader_reports_errors () {
+        let _state = lock_state();
+        onShaderError(on_error);
+        let path = CString::new("missing.frag").unwrap();
+        let shader = unsafe { loadShader(path.as_ptr(), std::ptr::null()) };
+        assert_eq!(shader, PShader::INVALID);
+        assert!(REPORTED.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,294 @@
+use std::collections::BTreeSet;
+
+use naga::{
+    front::wgsl,
+    proc::Layouter,
+    valid::{Capabilities, ValidationFlags, Validator},
+    AddressSpace, ArraySize, Binding, EntryPoint, Function, Handle, Module, ScalarKind, ShaderStage, Type, TypeInner, VectorSize
+};
+
+use crate::{error::{PError, PResult}, renderer::custom_shader::{StageSource, UniformFields, UniformSlot}};
+
+// The uniform block of a custom shader, @group(2) @binding(0).
+// Groups 0 and 1 are the projection and texture every shader gets
+pub(crate) const UNIFORM_GROUP: u32 = 2;
+
+// Inputs of the vertex stage, see Vertex
+const VERTEX_LOCATIONS: [u32; 5] = [0, 1, 2, 3, 4];
+
+// What loadShader() needs to know about a pair of WGSL stages
+pub struct Reflection {
+    pub vertex: StageSource,
+    pub fragment: StageSource,
+    // Size in bytes of the uniform block, 0 if there is none
+    pub uniform_size: u32,
+    pub uniforms: UniformFields,
+}
+
+fn invalid (path: &str, message: impl Into<String>) -> PError {
+    PError::InvalidShader { path: path.to_string(), message: message.into() }
+}
+
+pub(crate) fn parse (path: &str, source: &str) -> PResult<Module> {
+    let module = wgsl::parse_str(source).map_err(|error| invalid(path, error.emit_to_string_with_path(source, path)))?;
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|error| invalid(path, error.emit_to_string_with_path(source, path)))?;
+    Ok(module)
+}
+
+// The entry point for a stage. Prefers the name the built-in shaders
+// use, so one file can be passed for both stages
+fn entry_point<'a> (path: &str, module: &'a Module, stage: ShaderStage, preferred: &str) -> PResult<&'a EntryPoint> {
+    let mut entry_points = module.entry_points.iter().filter(|entry_point| entry_point.stage == stage);
+    let found = entry_points.clone().find(|entry_point| entry_point.name == preferred).or_else(|| entry_points.next());
+    let attribute = if stage == ShaderStage::Vertex { "@vertex" } else { "@fragment" };
+    found.ok_or_else(|| invalid(path, format!("There is no {} function.", attribute)))
+}
+
+// @location()s of a binding, or of the fields of a struct passed in or out
+fn locations (module: &Module, ty: Handle<Type>, binding: Option<&Binding>, found: &mut BTreeSet<u32>) {
+    match binding {
+        Some(Binding::Location { location, .. }) => { found.insert(*location); }
+        Some(Binding::BuiltIn(_)) => {}
+        None => if let TypeInner::Struct { members, .. } = &module.types[ty].inner {
+            for member in members {
+                locations(module, member.ty, member.binding.as_ref(), found);
+            }
+        }
+    }
+}
+
+fn inputs (module: &Module, function: &Function) -> BTreeSet<u32> {
+    let mut found = BTreeSet::new();
+    for argument in &function.arguments {
+        locations(module, argument.ty, argument.binding.as_ref(), &mut found);
+    }
+    found
+}
+
+fn outputs (module: &Module, function: &Function) -> BTreeSet<u32> {
+    let mut found = BTreeSet::new();
+    if let Some(result) = &function.result {
+        locations(module, result.ty, result.binding.as_ref(), &mut found);
+    }
+    found
+}
+
+// Every number in a value of type ty stored at offset, in the order
+// PShader_set() takes them. Matrices are column by column
+fn uniform_slots (module: &Module, ty: Handle<Type>, offset: u32, slots: &mut Vec<UniformSlot>) {
+    match module.types[ty].inner {
+        TypeInner::Scalar { kind, .. } => slots.push(UniformSlot { offset, kind }),
+        TypeInner::Vector { size, kind, .. } => {
+            slots.extend((0..size as u32).map(|index| UniformSlot { offset: offset + index * 4, kind }));
+        }
+        TypeInner::Matrix { columns, rows, .. } => {
+            // Columns are aligned like vectors, a vec3 takes as much room as a vec4
+            let stride = if rows == VectorSize::Bi { 8 } else { 16 };
+            for column in 0..columns as u32 {
+                slots.extend((0..rows as u32).map(|row| UniformSlot { offset: offset + column * stride + row * 4, kind: ScalarKind::Float }));
+            }
+        }
+        TypeInner::Array { base, size: ArraySize::Constant(count), stride } => {
+            for index in 0..count.get() {
+                uniform_slots(module, base, offset + index * stride, slots);
+            }
+        }
+        TypeInner::Struct { ref members, .. } => {
+            for member in members {
+                uniform_slots(module, member.ty, offset + member.offset, slots);
+            }
+        }
+        _ => {}
+    }
+}
+
+// Finds the uniform block and checks that nothing else
+// is bound where the renderer can't provide it
+fn uniform_block (path: &str, module: &Module) -> PResult<Option<(u32, UniformFields)>> {
+    let mut layouter = Layouter::default();
+    layouter.update(module.to_ctx()).map_err(|error| invalid(path, error.to_string()))?;
+
+    let mut block = None;
+    for (_, variable) in module.global_variables.iter() {
+        let Some(binding) = &variable.binding else { continue; };
+        match (binding.group, binding.binding) {
+            (0, 0) | (1, 0) | (1, 1) => {}
+            (UNIFORM_GROUP, 0) if variable.space == AddressSpace::Uniform => {
+                let mut uniforms = UniformFields::new();
+                match &module.types[variable.ty].inner {
+                    TypeInner::Struct { members, .. } => {
+                        for member in members {
+                            let mut slots = vec![];
+                            uniform_slots(module, member.ty, member.offset, &mut slots);
+                            uniforms.insert(member.name.clone().unwrap_or_default(), slots);
+                        }
+                    }
+                    _ => {
+                        let mut slots = vec![];
+                        uniform_slots(module, variable.ty, 0, &mut slots);
+                        uniforms.insert(variable.name.clone().unwrap_or_default(), slots);
+                    }
+                }
+                block = Some((layouter[variable.ty].size, uniforms));
+            }
+            (group, binding) => return Err(invalid(path, format!(
+                "Nothing is bound at @group({}) @binding({}). Uniforms go in a var<uniform> at @group({}) @binding(0).",
+                group, binding, UNIFORM_GROUP
+            )))
+        }
+    }
+    Ok(block)
+}
+
+// The shape shader's vertex stage, writing only the @location()s the
+// fragment stage reads. Stages have to agree on them exactly
+fn default_vertex_source (read: &BTreeSet<u32>) -> String {
+    let outputs = [(0, "color", "vec4<f32>"), (1, "edge", "vec2<f32>"), (2, "uv", "vec2<f32>")];
+    let mut fields = String::new();
+    let mut assignments = String::new();
+    for (location, name, ty) in outputs.into_iter().filter(|(location, ..)| read.contains(location)) {
+        fields += &format!("  @location({}) {}: {},\n", location, name, ty);
+        assignments += &format!("  output.{} = vertex_data.{};\n", name, name);
+    }
+
+    format!(
+"struct VertexInput {{
+  @location(0) position: vec3<f32>,
+  @location(1) color: vec4<f32>,
+  @location(2) edge: vec2<f32>,
+  @location(3) uv: vec2<f32>,
+  @location(4) normal: vec3<f32>,
+}}
+
+struct VertexOutput {{
+  @builtin(position) position: vec4<f32>,
+{}}}
+
+@group(0) @binding(0) var<uniform> projection: mat4x4<f32>;
+
+@vertex
+fn vs_main (vertex_data: VertexInput) -> VertexOutput {{
+  var output: VertexOutput;
+  output.position = projection * vec4<f32>(vertex_data.position, 1.0);
+{}  return output;
+}}
+", fields, assignments)
+}
+
+// Parses and checks both stages. The vertex stage gets Vertex as input and
+// has to write exactly the @location()s the fragment stage reads. Without
+// a vertex stage, the shape shader's is used. Both stages can declare
+// the uniform block, it has to be the same in both
+pub fn reflect (fragment_path: &str, fragment_source: String, vertex: Option<(&str, String)>) -> PResult<Reflection> {
+    let fragment_module = parse(fragment_path, &fragment_source)?;
+    let fragment_entry = entry_point(fragment_path, &fragment_module, ShaderStage::Fragment, "fs_main")?;
+    let read = inputs(&fragment_module, &fragment_entry.function);
+    // Only the shape shader's vertex stage is limited to these,
+    // a vertex stage of the user's can pass on anything
+    if vertex.is_none() {
+        if let Some(location) = read.iter().find(|location| **location > 2) {
+            return Err(invalid(fragment_path, format!(
+                "Nothing is written to @location({}). The vertex stage writes color (0), edge (1) and uv (2).", location
+            )));
+        }
+    }
+
+    let (vertex_path, vertex_source) = vertex.unwrap_or_else(|| ("shape.wgsl", default_vertex_source(&read)));
+    let vertex_module = parse(vertex_path, &vertex_source)?;
+    let vertex_entry = entry_point(vertex_path, &vertex_module, ShaderStage::Vertex, "vs_main")?;
+
+    if let Some(location) = inputs(&vertex_module, &vertex_entry.function).into_iter().find(|location| !VERTEX_LOCATIONS.contains(location)) {
+        return Err(invalid(vertex_path, format!(
+            "Vertices have no @location({}). They have a position (0), color (1), edge (2), uv (3) and normal (4).", location
+        )));
+    }
+    let written = outputs(&vertex_module, &vertex_entry.function);
+    if let Some(location) = read.difference(&written).next() {
+        return Err(invalid(fragment_path, format!("The vertex stage doesn't write @location({}).", location)));
+    }
+    if let Some(location) = written.difference(&read).next() {
+        return Err(invalid(vertex_path, format!("The fragment stage doesn't read @location({}).", location)));
+    }
+
+    let block = match (uniform_block(fragment_path, &fragment_module)?, uniform_block(vertex_path, &vertex_module)?) {
+        (Some(fragment), Some(vertex)) if fragment.0 != vertex.0 || fragment.1 != vertex.1 => {
+            return Err(invalid(fragment_path, "The uniform block is declared differently in the vertex stage."));
+        }
+        (fragment, vertex) => fragment.or(vertex)
+    };
+    let (uniform_size, uniforms) = block.unwrap_or_default();
+
+    Ok(Reflection {
+        vertex: StageSource { entry_point: vertex_entry.name.clone(), source: vertex_source },
+        fragment: StageSource { entry_point: fragment_entry.name.clone(), source: fragment_source },
+        uniform_size,
+        uniforms
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::glsl::translate;
+
+    #[test]
+    fn wgsl_pair_with_custom_location () {
+        let vertex = "
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) color: vec4<f32>,
+  @location(3) height: f32,
+}
+
+@vertex
+fn vs_main (@location(0) position: vec3<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+  var output: VertexOutput;
+  output.position = vec4<f32>(position, 1.0);
+  output.color = color;
+  output.height = position.y;
+  return output;
+}
+";
+        let fragment = "
+@fragment
+fn fs_main (@location(0) color: vec4<f32>, @location(3) height: f32) -> @location(0) vec4<f32> {
+  return color * height;
+}
+";
+        assert!(reflect("custom.frag.wgsl", fragment.to_string(), Some(("custom.vert.wgsl", vertex.to_string()))).is_ok());
+
+        // Without a vertex stage nothing writes @location(3)
+        assert!(reflect("custom.frag.wgsl", fragment.to_string(), None).is_err());
+    }
+
+    #[test]
+    fn glsl_pair_with_custom_varying () {
+        let vertex = "
+uniform mat4 transform;
+attribute vec4 position;
+attribute vec4 color;
+varying vec4 vertColor;
+varying float height;
+
+void main() {
+  gl_Position = transform * position;
+  vertColor = color;
+  height = position.y;
+}
+";
+        let fragment = "
+varying vec4 vertColor;
+varying float height;
+
+void main() {
+  gl_FragColor = vertColor * height;
+}
+";
+        let (fragment, vertex) = translate(Some(("custom.frag", fragment.to_string())), Some(("custom.vert", vertex.to_string()))).unwrap();
+        let (fragment_path, fragment_source) = fragment.unwrap();
+        let reflection = reflect(fragment_path, fragment_source, vertex);
+        assert!(reflection.is_ok(), "{:?}", reflection.err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::{ffi::{c_char, CString}, sync::Arc, time::SystemTime};
+
+use crate::{
+    error::PError,
+    renderer::{custom_shader::{reload_custom_shader, ShaderFiles, ShaderId}, state::{get_renderer_state, set_renderer_state, RENDERER_STATE}},
+    shader::compile_shader
+};
+
+// Called with the error when a changed shader file doesn't compile
+pub type ShaderErrorHandler = Arc<dyn Fn(&PError) + Send + Sync>;
+
+// Type of the function passed to onShaderError(), called with the message
+pub type PShaderErrorCallback = extern "C" fn(*const c_char) -> ();
+
+// When one of the files last changed, None for the built-in stages
+pub(crate) fn modified_time (files: &ShaderFiles) -> Option<SystemTime> {
+    [&files.fragment, &files.vertex]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .max()
+}
+
+pub(crate) fn set_hot_reload (enabled: bool) {
+    set_renderer_state! {
+        shader_hot_reload = enabled;
+    }
+}
+
+pub(crate) fn set_error_handler (handler: ShaderErrorHandler) {
+    set_renderer_state! {
+        shader_error_handler = Some(handler);
+    }
+}
+
+// Compiles shaders again when their files changed, called before every
+// frame while hot reloading is on. This covers shaders loaded with
+// loadShader() and the ones built with ShaderBuilder::from_source().
+// A shader that doesn't compile anymore keeps drawing with what it had,
+// the error goes to stderr and to the handler set with onShaderError().
+// It is tried again on the next change
+pub(crate) fn reload_shaders () {
+    if !get_renderer_state().shader_hot_reload { return; }
+
+    let changed: Vec<(ShaderId, ShaderFiles, SystemTime)> = {
+        let renderer_state = get_renderer_state();
+        renderer_state.custom_shaders.iter().enumerate().filter_map(|(id, shader)| {
+            let modified = modified_time(&shader.files)?;
+            (Some(modified) != shader.files.modified).then(|| (id, shader.files.clone(), modified))
+        }).collect()
+    };
+
+    for (id, files, modified) in changed {
+        RENDERER_STATE.try_write().expect("Could not write to RwLock").custom_shaders[id].files.modified = Some(modified);
+        let path = files.fragment.as_deref().or(files.vertex.as_deref()).unwrap_or_default();
+        let result = compile_shader(files.fragment.as_deref(), files.vertex.as_deref())
+            .and_then(|reflection| reload_custom_shader(id, path, reflection));
+        if let Err(error) = result {
+            report_shader_error(&error);
+        }
+    }
+
+    // Taken out while they are built again, which reads the renderer's state
+    let mut shaders = std::mem::take(&mut RENDERER_STATE.try_write().expect("Could not write to RwLock").shaders);
+    for shader in &mut shaders {
+        let Some(source) = shader.source.as_mut() else { continue; };
+        let modified = std::fs::metadata(&source.path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_none() || modified == source.modified { continue; }
+        source.modified = modified;
+
+        if let Err(error) = shader.rebuild_from_source() {
+            report_shader_error(&error);
+        }
+    }
+    RENDERER_STATE.try_write().expect("Could not write to RwLock").shaders = shaders;
+}
+
+// Sends a shader error to stderr and to the handler set with onShaderError()
+pub(crate) fn report_shader_error (error: &PError) {
+    let handler = get_renderer_state().shader_error_handler.clone();
+    eprintln!("{}", error);
+    if let Some(handler) = handler {
+        handler(error);
+    }
+}
+
+// Shaders loaded from files are compiled again when the files change
+#[no_mangle]
+pub extern "C" fn shaderHotReload (enabled: bool) {
+    set_hot_reload(enabled);
+}
+
+// Calls callback with the message when a shader doesn't compile, both in
+// loadShader() and when a changed one is reloaded. The message is only
+// valid during the call
+#[no_mangle]
+pub extern "C" fn onShaderError (callback: PShaderErrorCallback) {
+    set_error_handler(Arc::new(move |error| {
+        let message = CString::new(error.to_string()).unwrap_or_default();
+        callback(message.as_ptr());
+    }));
+}
@@ -0,0 +1,38 @@
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+
+// Draws a single triangle covering the whole screen,
+// so no vertex buffer is needed
+@vertex
+fn vs_main (@builtin(vertex_index) index: u32) -> VertexOutput {
+  let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+  var output: VertexOutput;
+  output.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+  output.uv = uv;
+  return output;
+}
+
+@fragment
+fn fs_main (fragment_data: VertexOutput) -> @location(0) vec4<f32> {
+  return textureSample(source, source_sampler, fragment_data.uv);
+}
+
+fn linear_to_srgb (value: vec3<f32>) -> vec3<f32> {
+  let clamped = clamp(value, vec3<f32>(0.0), vec3<f32>(1.0));
+  let low = clamped * 12.92;
+  let high = 1.055 * pow(clamped, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, clamped <= vec3<f32>(0.0031308));
+}
+
+// Used when the surface format isn't sRGB
+@fragment
+fn fs_encode_srgb (fragment_data: VertexOutput) -> @location(0) vec4<f32> {
+  let color = textureSample(source, source_sampler, fragment_data.uv);
+  return vec4<f32>(linear_to_srgb(color.rgb), color.a);
+}
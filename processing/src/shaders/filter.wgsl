@@ -0,0 +1,142 @@
+// Filters applied by filter(). The numbers in kind follow PFilter.
+// Colors are filtered sRGB encoded, like pixels[], so the result
+// is the same whatever format the texture has. Keep this in sync
+// with the CPU version in image/filter.rs
+struct FilterParams {
+  kind: u32,
+  // Whether the texture stores linear values that have
+  // to be encoded when read and decoded when written
+  linear: u32,
+  // Threshold level, or number of posterize levels
+  value: f32,
+  // Blur kernel radius in pixels
+  radius: i32,
+  // Blur direction, (1, 0) or (0, 1)
+  direction: vec2<i32>,
+}
+
+const THRESHOLD: u32 = 0u;
+const GRAY: u32 = 1u;
+const INVERT: u32 = 2u;
+const POSTERIZE: u32 = 3u;
+const BLUR: u32 = 4u;
+const ERODE: u32 = 5u;
+const DILATE: u32 = 6u;
+const OPAQUE: u32 = 7u;
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: FilterParams;
+
+@vertex
+fn vs_main (@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+  let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+  return vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+}
+
+fn linear_to_srgb (value: vec3<f32>) -> vec3<f32> {
+  let clamped = clamp(value, vec3<f32>(0.0), vec3<f32>(1.0));
+  let low = clamped * 12.92;
+  let high = 1.055 * pow(clamped, vec3<f32>(1.0 / 2.4)) - 0.055;
+  return select(high, low, clamped <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear (value: vec3<f32>) -> vec3<f32> {
+  let low = value / 12.92;
+  let high = pow((value + 0.055) / 1.055, vec3<f32>(2.4));
+  return select(high, low, value <= vec3<f32>(0.04045));
+}
+
+fn load (coords: vec2<i32>) -> vec4<f32> {
+  let size = vec2<i32>(textureDimensions(source));
+  let color = textureLoad(source, clamp(coords, vec2<i32>(0), size - 1), 0);
+  // Rounded to the 8 bit values loadPixels() would give, so
+  // thresholds and comparisons come out the same as on the CPU
+  if params.linear == 1u {
+    return round(vec4<f32>(linear_to_srgb(color.rgb), clamp(color.a, 0.0, 1.0)) * 255.0) / 255.0;
+  }
+  return color;
+}
+
+fn luminance (color: vec4<f32>) -> f32 {
+  return dot(color.rgb, vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn blur (coords: vec2<i32>) -> vec4<f32> {
+  let size = vec2<i32>(textureDimensions(source));
+  var sum = vec4<f32>(0.0);
+  var total = 0.0;
+
+  // Processing's kernel, weights fall off with the square of the distance.
+  // Pixels outside of the texture are left out rather than repeated
+  for (var offset = 1 - params.radius; offset < params.radius; offset++) {
+    let position = coords + params.direction * offset;
+    if any(position < vec2<i32>(0)) || any(position >= size) { continue; }
+
+    let distance = params.radius - abs(offset);
+    let weight = f32(distance * distance);
+    sum += load(position) * weight;
+    total += weight;
+  }
+
+  return sum / total;
+}
+
+// Picks the darkest (erode) or brightest (dilate)
+// of the pixel and its four neighbours
+fn morph (coords: vec2<i32>, brightest: bool) -> vec4<f32> {
+  var result = load(coords);
+  var result_luminance = luminance(result);
+
+  var offsets = array<vec2<i32>, 4>(vec2<i32>(-1, 0), vec2<i32>(1, 0), vec2<i32>(0, -1), vec2<i32>(0, 1));
+  for (var index = 0; index < 4; index++) {
+    let color = load(coords + offsets[index]);
+    let color_luminance = luminance(color);
+    if (brightest && color_luminance > result_luminance) || (!brightest && color_luminance < result_luminance) {
+      result = color;
+      result_luminance = color_luminance;
+    }
+  }
+
+  return result;
+}
+
+fn apply (coords: vec2<i32>) -> vec4<f32> {
+  switch params.kind {
+    case BLUR: { return blur(coords); }
+    case ERODE: { return morph(coords, false); }
+    case DILATE: { return morph(coords, true); }
+    default: {}
+  }
+
+  let color = load(coords);
+  switch params.kind {
+    case THRESHOLD: {
+      return vec4<f32>(vec3<f32>(select(0.0, 1.0, luminance(color) >= params.value)), color.a);
+    }
+    case GRAY: {
+      return vec4<f32>(vec3<f32>(luminance(color)), color.a);
+    }
+    case INVERT: {
+      return vec4<f32>(1.0 - color.rgb, color.a);
+    }
+    case POSTERIZE: {
+      let levels = params.value;
+      return vec4<f32>(min(floor(color.rgb * levels), vec3<f32>(levels - 1.0)) / (levels - 1.0), color.a);
+    }
+    case OPAQUE: {
+      return vec4<f32>(color.rgb, 1.0);
+    }
+    default: {
+      return color;
+    }
+  }
+}
+
+@fragment
+fn fs_main (@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+  let color = apply(vec2<i32>(floor(position.xy)));
+  if params.linear == 1u {
+    return vec4<f32>(srgb_to_linear(color.rgb), color.a);
+  }
+  return color;
+}
@@ -0,0 +1,115 @@
+// Appended to shape.wgsl for the shape shader, which lights fills while
+// lights are on. Custom shaders get shape.wgsl alone, without group 2
+
+struct LitVertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) color: vec4<f32>,
+  @location(1) edge: vec2<f32>,
+  @location(2) uv: vec2<f32>,
+  @location(3) world_position: vec3<f32>,
+  @location(4) normal: vec3<f32>,
+}
+
+// See LightUniform and LightingUniform
+struct Light {
+  position: vec4<f32>,
+  direction: vec4<f32>,
+  diffuse: vec4<f32>,
+  specular: vec4<f32>,
+  falloff: vec4<f32>,
+  spot: vec4<f32>,
+}
+
+struct Lighting {
+  ambient: vec4<f32>,
+  specular: vec4<f32>,
+  emissive: vec4<f32>,
+  eye: vec4<f32>,
+  shininess: f32,
+  count: u32,
+  lit: u32,
+  fill_ambient: u32,
+  lights: array<Light, 8>,
+}
+
+@group(2) @binding(0) var<uniform> lighting: Lighting;
+
+// Where retained shapes are drawn, projection already includes it.
+// Normals are turned with the normal matrix instead, the inverse
+// transpose of model, so they stay perpendicular when it scales unevenly
+@group(0) @binding(1) var<uniform> model: mat4x4<f32>;
+@group(0) @binding(2) var<uniform> normal_matrix: mat4x4<f32>;
+
+@vertex
+fn vs_lit (vertex_data: VertexInput) -> LitVertexOutput {
+  var output: LitVertexOutput;
+  output.position = projection * vec4<f32>(vertex_data.position, 1.0);
+  output.color = vertex_data.color;
+  output.edge = vertex_data.edge;
+  output.uv = vertex_data.uv;
+  output.world_position = (model * vec4<f32>(vertex_data.position, 1.0)).xyz;
+  output.normal = (normal_matrix * vec4<f32>(vertex_data.normal, 0.0)).xyz;
+  return output;
+}
+
+// Processing's lighting, per fragment. Both sides of a surface are lit,
+// the normal is flipped when it points away from the camera
+fn shade (color: vec4<f32>, position: vec3<f32>, normal: vec3<f32>) -> vec4<f32> {
+  if lighting.lit == 0u {
+    return color;
+  }
+
+  let to_eye = normalize(lighting.eye.xyz - position);
+  var n = normalize(normal);
+  if dot(n, to_eye) < 0.0 {
+    n = -n;
+  }
+  let ambient = select(lighting.ambient.rgb, color.rgb, lighting.fill_ambient == 1u);
+
+  var total = lighting.emissive.rgb;
+  for (var i = 0u; i < lighting.count; i++) {
+    let light = lighting.lights[i];
+    let kind = u32(light.spot.z);
+
+    var to_light = -normalize(light.direction.xyz);
+    var attenuation = 1.0;
+    if kind != 1u {
+      let offset = light.position.xyz - position;
+      let distance = length(offset);
+      to_light = offset / max(distance, 0.0001);
+      attenuation = 1.0 / (light.falloff.x + light.falloff.y * distance + light.falloff.z * distance * distance);
+    }
+    if kind == 3u {
+      let angle = dot(-to_light, normalize(light.direction.xyz));
+      attenuation *= select(0.0, pow(max(angle, 0.0), light.spot.y), angle >= light.spot.x);
+    }
+
+    if kind == 0u {
+      total += light.diffuse.rgb * ambient * attenuation;
+      continue;
+    }
+
+    let diffuse = max(dot(n, to_light), 0.0);
+    let half_way = normalize(to_light + to_eye);
+    let specular = select(0.0, pow(max(dot(n, half_way), 0.0001), lighting.shininess), diffuse > 0.0);
+    total += attenuation * (light.diffuse.rgb * color.rgb * diffuse + light.specular.rgb * lighting.specular.rgb * specular);
+  }
+  return vec4<f32>(total, color.a);
+}
+
+fn lit_color (fragment_data: LitVertexOutput) -> vec4<f32> {
+  let color = fragment_data.color * textureSample(shape_texture, shape_sampler, fragment_data.uv);
+  return cover(shade(color, fragment_data.world_position, fragment_data.normal), fragment_data.edge);
+}
+
+@fragment
+fn fs_lit (fragment_data: LitVertexOutput) -> @location(0) vec4<f32> {
+  return lit_color(fragment_data);
+}
+
+// For blend modes that need the color multiplied by its alpha
+@fragment
+fn fs_lit_premultiplied (fragment_data: LitVertexOutput) -> @location(0) vec4<f32> {
+  let color = lit_color(fragment_data);
+  return vec4<f32>(color.rgb * color.a, color.a);
+}
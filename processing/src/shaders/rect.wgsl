@@ -12,12 +12,13 @@ struct VertexOutput {
 }
 
 // @group(0) @binding(0) var<uniform> uniforms: Uniforms;
+@group(0) @binding(0) var<uniform> projection: mat4x4<f32>;
 
 @vertex
 fn vs_main (vertex_data: VertexInput) -> @builtin(position) vec4<f32> {
 
   var output: VertexOutput;
-  return vec4<f32>(vertex_data.position, 1.0);
+  return projection * vec4<f32>(vertex_data.position, 1.0);
 }
 
 
@@ -0,0 +1,44 @@
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) color: vec4<f32>,
+  @location(2) edge: vec2<f32>,
+  @location(3) uv: vec2<f32>,
+  @location(4) normal: vec3<f32>,
+}
+
+struct VertexOutput {
+  @builtin(position) position: vec4<f32>,
+  @location(0) color: vec4<f32>,
+  @location(1) edge: vec2<f32>,
+  @location(2) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var<uniform> projection: mat4x4<f32>;
+
+// Geometry without a texture gets a white pixel
+@group(1) @binding(0) var shape_texture: texture_2d<f32>;
+@group(1) @binding(1) var shape_sampler: sampler;
+
+@vertex
+fn vs_main (vertex_data: VertexInput) -> VertexOutput {
+  var output: VertexOutput;
+  output.position = projection * vec4<f32>(vertex_data.position, 1.0);
+  output.color = vertex_data.color;
+  output.edge = vertex_data.edge;
+  output.uv = vertex_data.uv;
+  return output;
+}
+
+// edge.x goes from -1 to 1 across a stroke, edge.y is how much of
+// that is the smoothed border. Coverage falls off to 0 over the border,
+// geometry that isn't smoothed has edge = (0, 1) and is fully covered
+fn cover (color: vec4<f32>, edge: vec2<f32>) -> vec4<f32> {
+  let coverage = clamp((1.0 - abs(edge.x)) / edge.y, 0.0, 1.0);
+  return vec4<f32>(color.rgb, color.a * coverage);
+}
+
+@fragment
+fn fs_main (fragment_data: VertexOutput) -> @location(0) vec4<f32> {
+  let color = fragment_data.color * textureSample(shape_texture, shape_sampler, fragment_data.uv);
+  return cover(color, fragment_data.edge);
+}
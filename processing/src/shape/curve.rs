@@ -0,0 +1,184 @@
+use crate::{
+    error::{OrPanic, PError, PResult},
+    math::curve::{bezier_point, bezier_tangent, curve_point, curve_tangent, curve_to_bezier, flatten_bezier, quadratic_to_cubic},
+    record::state::PathSegment,
+    shape::{
+        add_vertex, begin_shape, end_shape, last_vertex, make_vertex, push_vertices,
+        state::{get_shape_state, set_shape_state, PShapeClose, PShapeKind, ShapeCurve, ShapeState, SHAPE_STATE}
+    }
+};
+
+// How far (in points) flattened curves may stray from the real curve
+pub(crate) const FLATTEN_TOLERANCE: f32 = 0.1;
+
+// Adds the curve from the last vertex, flattened. It is
+// kept as segment too, for recordings (see ShapeCurve)
+fn push_bezier (points: [[f32; 2]; 4], detail: u32, segment: PathSegment) -> PResult<()> {
+    let length = |state: &ShapeState| if state.in_contour { state.contours.last().map_or(0, Vec::len) } else { state.vertices.len() };
+    let start = length(&get_shape_state());
+
+    push_vertices(
+        flatten_bezier(points, detail, FLATTEN_TOLERANCE)
+        .into_iter()
+        .map(|[x, y]| make_vertex(x, y, 0.0))
+    )?;
+
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    let end = length(&state);
+    if start > 0 && end > start {
+        let contour = state.in_contour.then(|| state.contours.len() - 1);
+        state.curves.push(ShapeCurve { contour, start: start - 1, end: end - 1, segment });
+    }
+    Ok(())
+}
+
+pub(crate) fn bezier_vertex (cx1: f32, cy1: f32, cx2: f32, cy2: f32, x: f32, y: f32) -> PResult<()> {
+    if get_shape_state().kind.is_none() {
+        return Err(PError::ShapeNotStarted);
+    }
+    let start = last_vertex().ok_or(PError::NoPreviousVertex)?;
+    let detail = get_shape_state().bezier_detail;
+
+    set_shape_state! {
+        curve_vertices.clear();
+    }
+    push_bezier([[start.x, start.y], [cx1, cy1], [cx2, cy2], [x, y]], detail, PathSegment::CubicTo([cx1, cy1], [cx2, cy2], [x, y]))
+}
+
+pub(crate) fn quadratic_vertex (cx: f32, cy: f32, x: f32, y: f32) -> PResult<()> {
+    if get_shape_state().kind.is_none() {
+        return Err(PError::ShapeNotStarted);
+    }
+    let start = last_vertex().ok_or(PError::NoPreviousVertex)?;
+    let detail = get_shape_state().bezier_detail;
+
+    set_shape_state! {
+        curve_vertices.clear();
+    }
+    push_bezier(quadratic_to_cubic([start.x, start.y], [cx, cy], [x, y]), detail, PathSegment::QuadTo([cx, cy], [x, y]))
+}
+
+// The first and last points given to curveVertex() only guide the
+// curve, it is drawn between the ones in between. So every point
+// after the fourth adds one segment to the shape
+pub(crate) fn curve_vertex (x: f32, y: f32) -> PResult<()> {
+    let vertex = make_vertex(x, y, 0.0);
+
+    let (points, is_first_segment, detail, tightness) = {
+        let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+        if state.kind.is_none() {
+            return Err(PError::ShapeNotStarted);
+        }
+
+        state.curve_vertices.push(vertex);
+        let count = state.curve_vertices.len();
+        if count < 4 { return Ok(()); }
+
+        let points: [_; 4] = state.curve_vertices[count - 4..].try_into().expect("Slice has four elements");
+        (points, count == 4, state.curve_detail, state.curve_tightness)
+    };
+
+    let [a, b, c, d] = points;
+    let xs = curve_to_bezier(a.x, b.x, c.x, d.x, tightness);
+    let ys = curve_to_bezier(a.y, b.y, c.y, d.y, tightness);
+
+    if is_first_segment {
+        push_vertices([b])?;
+    }
+    push_bezier(
+        [[xs[0], ys[0]], [xs[1], ys[1]], [xs[2], ys[2]], [xs[3], ys[3]]],
+        detail,
+        PathSegment::CubicTo([xs[1], ys[1]], [xs[2], ys[2]], [xs[3], ys[3]])
+    )
+}
+
+pub(crate) fn draw_bezier (points: [[f32; 2]; 4]) -> PResult<()> {
+    let [start, control1, control2, end] = points;
+    begin_shape(PShapeKind::ShapePolygon)?;
+    add_vertex(start[0], start[1], 0.0)?;
+    bezier_vertex(control1[0], control1[1], control2[0], control2[1], end[0], end[1])?;
+    end_shape(PShapeClose::ShapeOpen)
+}
+
+pub(crate) fn draw_curve (points: [[f32; 2]; 4]) -> PResult<()> {
+    begin_shape(PShapeKind::ShapePolygon)?;
+    for [x, y] in points {
+        curve_vertex(x, y)?;
+    }
+    end_shape(PShapeClose::ShapeOpen)
+}
+
+// Draws a bezier curve from (x1, y1) to (x4, y4),
+// using the points in between as control points
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn bezier (x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    draw_bezier([[x1, y1], [x2, y2], [x3, y3], [x4, y4]]).or_panic()
+}
+
+// Draws a Catmull-Rom curve from (x2, y2) to (x3, y3),
+// shaped by the points on either end
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn curve (x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    draw_curve([[x1, y1], [x2, y2], [x3, y3], [x4, y4]]).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn bezierVertex (cx1: f32, cy1: f32, cx2: f32, cy2: f32, x: f32, y: f32) {
+    bezier_vertex(cx1, cy1, cx2, cy2, x, y).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn quadraticVertex (cx: f32, cy: f32, x: f32, y: f32) {
+    quadratic_vertex(cx, cy, x, y).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn curveVertex (x: f32, y: f32) {
+    curve_vertex(x, y).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn bezierPoint (a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    bezier_point(a, b, c, d, t)
+}
+
+#[no_mangle]
+pub extern "C" fn bezierTangent (a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    bezier_tangent(a, b, c, d, t)
+}
+
+#[no_mangle]
+pub extern "C" fn curvePoint (a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    curve_point(a, b, c, d, t, get_shape_state().curve_tightness)
+}
+
+#[no_mangle]
+pub extern "C" fn curveTangent (a: f32, b: f32, c: f32, d: f32, t: f32) -> f32 {
+    curve_tangent(a, b, c, d, t, get_shape_state().curve_tightness)
+}
+
+// 0 is a Catmull-Rom spline, 1 connects the points with straight lines
+#[no_mangle]
+pub extern "C" fn curveTightness (tightness: f32) {
+    set_shape_state! {
+        curve_tightness = tightness;
+    }
+}
+
+// Number of segments each bezier is drawn with.
+// 0 (the default) picks the number based on how curved it is
+#[no_mangle]
+pub extern "C" fn bezierDetail (detail: u32) {
+    set_shape_state! {
+        bezier_detail = detail;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn curveDetail (detail: u32) {
+    set_shape_state! {
+        curve_detail = detail;
+    }
+}
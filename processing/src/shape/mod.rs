@@ -0,0 +1,540 @@
+use crate::{
+    color::color_state::get_color_state,
+    image::{image_size, image_texture, PImage},
+    error::{OrPanic, PError, PResult},
+    light::lighting_uniform,
+    math::matrix::IDENTITY,
+    record::record_path,
+    renderer::{camera::PRenderer, state::{get_renderer_state, RENDERER_STATE}, texture::{PTextureWrap, SampledTexture}},
+    shape::{state::{get_shape_state, set_shape_state, PShapeClose, PShapeKind, PTextureMode, ShapePath, ShapeStyle, ShapeVertex, SHAPE_STATE}, tessellate::Tessellator}
+};
+
+pub mod curve;
+pub mod retained;
+pub mod solid;
+pub mod state;
+pub mod tessellate;
+pub mod transform;
+
+pub(crate) fn begin_shape (kind: PShapeKind) -> PResult<()> {
+    if get_shape_state().kind.is_some() {
+        return Err(PError::ShapeAlreadyStarted);
+    }
+
+    set_shape_state! {
+        curve_vertices.clear();
+        kind = Some(kind);
+        curves = vec![];
+        in_contour = false;
+        normal = None;
+        texture = None;
+    }
+    Ok(())
+}
+
+// Vertex at the given position with the current colors
+pub(crate) fn make_vertex (x: f32, y: f32, z: f32) -> ShapeVertex {
+    let colors = get_color_state();
+    ShapeVertex {
+        x, y, z,
+        fill: colors.fill.unwrap_or_default(),
+        stroke: colors.stroke.unwrap_or_default(),
+        normal: None,
+        uv: [0.0, 0.0]
+    }
+}
+
+// Adds vertices to the current contour, or the outline if there is none
+pub(crate) fn push_vertices (vertices: impl IntoIterator<Item = ShapeVertex>) -> PResult<()> {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    if state.kind.is_none() {
+        return Err(PError::ShapeNotStarted);
+    }
+
+    let normal = state.normal;
+    let vertices = vertices.into_iter().map(|vertex| ShapeVertex { normal: vertex.normal.or(normal), ..vertex });
+    if state.in_contour {
+        state.contours.last_mut().expect("Contour has not been started").extend(vertices);
+    }
+    else {
+        state.vertices.extend(vertices);
+    }
+    Ok(())
+}
+
+// Last vertex of the current contour (or outline),
+// which is where curves added with bezierVertex() start
+pub(crate) fn last_vertex () -> Option<ShapeVertex> {
+    let state = get_shape_state();
+    if state.in_contour { state.contours.last()?.last().copied() }
+    else { state.vertices.last().copied() }
+}
+
+pub(crate) fn add_vertex (x: f32, y: f32, z: f32) -> PResult<()> {
+    set_shape_state! {
+        curve_vertices.clear();
+    }
+    push_vertices([make_vertex(x, y, z)])
+}
+
+// u and v are in pixels of the texture with textureMode(TextureImage)
+pub(crate) fn add_vertex_uv (x: f32, y: f32, z: f32, u: f32, v: f32) -> PResult<()> {
+    let (texture, mode) = {
+        let state = get_shape_state();
+        (state.texture, state.texture_mode)
+    };
+    let uv = match (texture, mode) {
+        (Some(image), PTextureMode::TextureImage) => {
+            let (width, height) = image_size(image)?;
+            [u / width.max(1) as f32, v / height.max(1) as f32]
+        }
+        _ => [u, v]
+    };
+
+    set_shape_state! {
+        curve_vertices.clear();
+    }
+    push_vertices([ShapeVertex { uv, ..make_vertex(x, y, z) }])
+}
+
+// Fills the current shape with the image instead of the fill color
+pub(crate) fn set_texture (image: PImage) -> PResult<()> {
+    image_size(image)?;
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    if state.kind.is_none() {
+        return Err(PError::ShapeNotStarted);
+    }
+    state.texture = Some(image);
+    Ok(())
+}
+
+pub(crate) fn begin_contour () -> PResult<()> {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    if state.kind != Some(PShapeKind::ShapePolygon) {
+        return Err(PError::ContourOutsidePolygon);
+    }
+    if state.in_contour {
+        return Err(PError::ContourAlreadyStarted);
+    }
+
+    state.contours.push(vec![]);
+    state.curve_vertices.clear();
+    state.in_contour = true;
+    Ok(())
+}
+
+pub(crate) fn end_contour () -> PResult<()> {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    if !state.in_contour {
+        return Err(PError::ContourNotStarted);
+    }
+
+    state.curve_vertices.clear();
+    state.in_contour = false;
+    Ok(())
+}
+
+pub(crate) fn end_shape (close: PShapeClose) -> PResult<()> {
+    let (path, recording) = {
+        let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+        let kind = state.kind.take().ok_or(PError::ShapeNotStarted)?;
+        state.in_contour = false;
+        let path = ShapePath {
+            kind,
+            close,
+            vertices: std::mem::take(&mut state.vertices),
+            contours: std::mem::take(&mut state.contours),
+            curves: std::mem::take(&mut state.curves),
+            texture: state.texture.take(),
+            texture_wrap: state.texture_wrap
+        };
+        (path, state.recording.take())
+    };
+
+    let style = current_style();
+    match recording {
+        Some(shape) => retained::set_path(shape, path, style),
+        None => draw_path(&path, &style)
+    }
+}
+
+// The current colors and stroke weight
+pub(crate) fn current_style () -> ShapeStyle {
+    let colors = get_color_state();
+    ShapeStyle { fill: colors.fill, stroke: colors.stroke, stroke_weight: get_shape_state().stroke_weight }
+}
+
+pub(crate) fn draw_path (path: &ShapePath, style: &ShapeStyle) -> PResult<()> {
+    let texture = path_texture(path)?;
+    let mut tessellator = new_tessellator(style.stroke_weight);
+    if texture.is_some() {
+        tessellator = tessellator.with_textured_fills();
+    }
+    tessellate_path(&mut tessellator, path, style);
+    push_geometry(&tessellator, texture);
+    let model = transform::model_matrix();
+    record_path(path, style, (model != IDENTITY).then_some(&model));
+    Ok(())
+}
+
+// The texture a path is filled with, if it has one
+pub(crate) fn path_texture (path: &ShapePath) -> PResult<Option<SampledTexture>> {
+    match path.texture {
+        Some(image) => Ok(image_texture(image)?.map(|id| SampledTexture { id, wrap: path.texture_wrap })),
+        None => Ok(None)
+    }
+}
+
+// The triangles or quads that shapes made of separate pieces are
+// made of, each one filled and outlined on its own. Empty for the
+// other kinds of shapes
+pub(crate) fn path_pieces (kind: PShapeKind, vertices: &[ShapeVertex]) -> Vec<Vec<ShapeVertex>> {
+    match kind {
+        PShapeKind::ShapeTriangles => vertices.chunks_exact(3).map(|t| t.to_vec()).collect(),
+
+        PShapeKind::ShapeTriangleFan =>
+            (1..vertices.len().saturating_sub(1))
+            .map(|i| vec![vertices[0], vertices[i], vertices[i + 1]])
+            .collect(),
+
+        PShapeKind::ShapeTriangleStrip => vertices.windows(3).map(|t| t.to_vec()).collect(),
+
+        PShapeKind::ShapeQuads => vertices.chunks_exact(4).map(|q| q.to_vec()).collect(),
+
+        // Every new pair of vertices makes a quad with the previous pair
+        PShapeKind::ShapeQuadStrip =>
+            (0..vertices.len().saturating_sub(3))
+            .step_by(2)
+            .map(|i| vec![vertices[i], vertices[i + 1], vertices[i + 3], vertices[i + 2]])
+            .collect(),
+
+        PShapeKind::ShapePolygon | PShapeKind::ShapePoints | PShapeKind::ShapeLines => vec![]
+    }
+}
+
+// Fills and strokes the path if style has a fill and a stroke. The colors
+// are the ones the vertices got, style only turns fills and strokes on
+pub(crate) fn tessellate_path (tessellator: &mut Tessellator, path: &ShapePath, style: &ShapeStyle) {
+    let (fill_enabled, stroke_enabled) = (style.fill.is_some(), style.stroke.is_some());
+    let (vertices, contours) = (&path.vertices, &path.contours);
+
+    match path.kind {
+        PShapeKind::ShapePoints => {
+            if stroke_enabled {
+                vertices.iter().for_each(|vertex| tessellator.point(vertex));
+            }
+        }
+
+        PShapeKind::ShapeLines => {
+            if stroke_enabled {
+                vertices.chunks_exact(2).for_each(|line| tessellator.stroke_path(line, false));
+            }
+        }
+
+        PShapeKind::ShapePolygon => {
+            if fill_enabled {
+                let mut outlines: Vec<&[ShapeVertex]> = vec![vertices];
+                outlines.extend(contours.iter().map(|contour| contour.as_slice()));
+                tessellator.fill_polygon(&outlines);
+            }
+            if stroke_enabled {
+                tessellator.stroke_path(vertices, path.close == PShapeClose::ShapeClose);
+                contours.iter().for_each(|contour| tessellator.stroke_path(contour, true));
+            }
+        }
+
+        kind => {
+            for piece in path_pieces(kind, vertices) {
+                if fill_enabled {
+                    let indices: Vec<u32> = (1..piece.len() as u32 - 1).flat_map(|i| [0, i, i + 1]).collect();
+                    tessellator.fill_triangles(&piece, &indices);
+                }
+                if stroke_enabled {
+                    tessellator.stroke_path(&piece, true);
+                }
+            }
+        }
+    }
+}
+
+// Tessellator for the canvas being drawn into, moving vertices with
+// the current matrix. Like in Processing, strokes are scaled with it
+// in 2D, in 3D they keep their width on the canvas
+pub(crate) fn new_tessellator (stroke_weight: f32) -> Tessellator {
+    let renderer_state = get_renderer_state();
+    let model = transform::model_matrix();
+    let stroke_weight = match renderer_state.renderer {
+        PRenderer::P2D => stroke_weight * (model[0][0] * model[1][1] - model[0][1] * model[1][0]).abs().sqrt(),
+        PRenderer::P3D => stroke_weight
+    };
+    let mut tessellator = Tessellator::new(renderer_state.canvas_format, stroke_weight);
+    if model != IDENTITY {
+        tessellator = tessellator.with_model(model);
+    }
+    if renderer_state.analytic_aa() {
+        tessellator = tessellator.with_analytic_aa(1.0 / renderer_state.pixel_density as f32);
+    }
+    if renderer_state.renderer == PRenderer::P3D {
+        let (width, height) = renderer_state.target_size();
+        tessellator = tessellator.with_screen_projection(renderer_state.view_projection(), width, height);
+    }
+    tessellator
+}
+
+// Adds what was tessellated to the draw list, with fills drawn with
+// texture if there is one. Fills are lit while lights are on
+pub(crate) fn push_geometry (tessellator: &Tessellator, texture: Option<SampledTexture>) {
+    let blend_mode = get_color_state().blend_mode;
+    let shader = get_renderer_state().shader;
+    let lighting = lighting_uniform();
+    let (geometry, strokes) = (&tessellator.geometry, &tessellator.stroke_geometry);
+
+    let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    match lighting {
+        Some(lighting) => renderer_state.draw_list.push_lit(&geometry.vertices, &geometry.indices, blend_mode, texture, shader, &lighting),
+        None => renderer_state.draw_list.push(&geometry.vertices, &geometry.indices, blend_mode, texture, shader, false)
+    }
+    renderer_state.draw_list.push(&strokes.vertices, &strokes.indices, blend_mode, None, shader, tessellator.projects_strokes());
+}
+
+#[no_mangle]
+pub extern "C" fn strokeWeight (weight: f32) {
+    set_shape_state! {
+        stroke_weight = weight;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn beginShape (kind: PShapeKind) {
+    begin_shape(kind).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn vertex (x: f32, y: f32) {
+    add_vertex(x, y, 0.0).or_panic()
+}
+
+// Which way the surface faces at the vertices added after this, for
+// lighting. Without it, every face of the shape gets its own normal
+#[no_mangle]
+pub extern "C" fn normal (nx: f32, ny: f32, nz: f32) {
+    set_shape_state! {
+        normal = Some([nx, ny, nz]);
+    }
+}
+
+// vertex() with a position on the current texture, see textureMode()
+#[no_mangle]
+pub extern "C" fn vertexUV (x: f32, y: f32, u: f32, v: f32) {
+    add_vertex_uv(x, y, 0.0, u, v).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn vertex3DUV (x: f32, y: f32, z: f32, u: f32, v: f32) {
+    add_vertex_uv(x, y, z, u, v).or_panic()
+}
+
+// Maps image onto the current shape, at the u and v of its vertices.
+// Has to be called between beginShape() and endShape()
+#[no_mangle]
+pub extern "C" fn texture (image: PImage) {
+    set_texture(image).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn textureMode (mode: PTextureMode) {
+    set_shape_state! {
+        texture_mode = mode;
+    }
+}
+
+// Whether textures are clamped or tiled outside of 0 to 1
+#[no_mangle]
+pub extern "C" fn textureWrap (wrap: PTextureWrap) {
+    set_shape_state! {
+        texture_wrap = wrap;
+    }
+}
+
+// vertex() with a z coordinate, for shapes in 3D
+#[no_mangle]
+pub extern "C" fn vertex3D (x: f32, y: f32, z: f32) {
+    add_vertex(x, y, z).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn endShape (close: PShapeClose) {
+    end_shape(close).or_panic()
+}
+
+// Starts a hole in the current polygon. Its vertices
+// have to go the opposite way around of the outline
+#[no_mangle]
+pub extern "C" fn beginContour () {
+    begin_contour().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn endContour () {
+    end_contour().or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{color::{color_state::{set_color_state, ColorState, COLOR_STATE}, PColor}, lock_state, math::matrix, renderer::{format::PCanvasFormat, state::set_renderer_state}};
+
+    const RED: PColor = PColor { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+    const BLUE: PColor = PColor { r: 0.0, g: 0.0, b: 1.0, a: 1.0 };
+
+    // Vertices with x set to their index
+    fn vertices (count: usize) -> Vec<ShapeVertex> {
+        let style = ShapeStyle { fill: Some(PColor::WHITE), stroke: None, stroke_weight: 1.0 };
+        (0..count).map(|i| style.vertex(i as f32, 0.0, 0.0)).collect()
+    }
+
+    fn pieces (kind: PShapeKind, count: usize) -> Vec<Vec<f32>> {
+        path_pieces(kind, &vertices(count)).iter().map(|piece| piece.iter().map(|vertex| vertex.x).collect()).collect()
+    }
+
+    #[test]
+    fn shapes_are_split_into_pieces () {
+        // Left over vertices are ignored
+        assert_eq!(pieces(PShapeKind::ShapeTriangles, 7), [[0.0, 1.0, 2.0], [3.0, 4.0, 5.0]]);
+        assert_eq!(pieces(PShapeKind::ShapeTriangleStrip, 5), [[0.0, 1.0, 2.0], [1.0, 2.0, 3.0], [2.0, 3.0, 4.0]]);
+        assert_eq!(pieces(PShapeKind::ShapeTriangleFan, 5), [[0.0, 1.0, 2.0], [0.0, 2.0, 3.0], [0.0, 3.0, 4.0]]);
+        assert_eq!(pieces(PShapeKind::ShapeQuads, 9), [[0.0, 1.0, 2.0, 3.0], [4.0, 5.0, 6.0, 7.0]]);
+        // Quads of a strip go around, so the second pair is reversed
+        assert_eq!(pieces(PShapeKind::ShapeQuadStrip, 7), [[0.0, 1.0, 3.0, 2.0], [2.0, 3.0, 5.0, 4.0]]);
+
+        for kind in [PShapeKind::ShapeTriangles, PShapeKind::ShapeTriangleStrip, PShapeKind::ShapeTriangleFan] {
+            assert!(pieces(kind, 2).is_empty());
+        }
+        assert!(pieces(PShapeKind::ShapeQuadStrip, 3).is_empty());
+        assert!(pieces(PShapeKind::ShapePolygon, 4).is_empty());
+    }
+
+    #[test]
+    fn pieces_are_filled_with_two_triangles_per_quad () {
+        let style = ShapeStyle { fill: Some(PColor::WHITE), stroke: None, stroke_weight: 1.0 };
+        let triangles = |kind, count| {
+            let path = ShapePath {
+                kind,
+                close: PShapeClose::ShapeOpen,
+                vertices: vertices(count),
+                contours: vec![],
+                curves: vec![],
+                texture: None,
+                texture_wrap: PTextureWrap::TextureClamp
+            };
+            let mut tessellator = Tessellator::new(PCanvasFormat::CanvasSrgb, 1.0);
+            tessellate_path(&mut tessellator, &path, &style);
+            tessellator.geometry.indices.len() / 3
+        };
+        assert_eq!(triangles(PShapeKind::ShapeTriangles, 6), 2);
+        assert_eq!(triangles(PShapeKind::ShapeTriangleStrip, 6), 4);
+        assert_eq!(triangles(PShapeKind::ShapeTriangleFan, 6), 4);
+        assert_eq!(triangles(PShapeKind::ShapeQuads, 8), 4);
+        assert_eq!(triangles(PShapeKind::ShapeQuadStrip, 8), 6);
+    }
+
+    // Draws a shape with the current styles and returns its
+    // triangles, as the positions and colors of their corners
+    fn draw (shape: impl FnOnce()) -> Vec<[([f32; 3], [f32; 4]); 3]> {
+        set_renderer_state! {
+            renderer = PRenderer::P2D;
+            draw_list.clear();
+        }
+        shape();
+        let state = get_renderer_state();
+        let list = &state.draw_list;
+        let triangles = list.indices.chunks_exact(3).map(|triangle| {
+            let corner = |index: u32| (list.vertices[index as usize].position(), list.vertices[index as usize].color());
+            [corner(triangle[0]), corner(triangle[1]), corner(triangle[2])]
+        }).collect();
+        drop(state);
+        set_renderer_state! {
+            draw_list.clear();
+        }
+        triangles
+    }
+
+    fn area (triangles: &[[([f32; 3], [f32; 4]); 3]]) -> f32 {
+        triangles.iter().map(|[(a, _), (b, _), (c, _)]| ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0).sum()
+    }
+
+    #[test]
+    fn contours_are_holes () {
+        let _state = lock_state();
+        *COLOR_STATE.try_write().unwrap() = ColorState { stroke: None, ..ColorState::default() };
+
+        let triangles = draw(|| {
+            begin_shape(PShapeKind::ShapePolygon).unwrap();
+            for (x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)] {
+                add_vertex(x, y, 0.0).unwrap();
+            }
+            begin_contour().unwrap();
+            for (x, y) in [(3.0, 3.0), (3.0, 7.0), (7.0, 7.0), (7.0, 3.0)] {
+                add_vertex(x, y, 0.0).unwrap();
+            }
+            end_contour().unwrap();
+            end_shape(PShapeClose::ShapeClose).unwrap();
+        });
+        assert!((area(&triangles) - 84.0).abs() < 1e-3);
+
+        *COLOR_STATE.try_write().unwrap() = ColorState::default();
+    }
+
+    #[test]
+    fn shapes_are_moved_with_the_matrix () {
+        let _state = lock_state();
+        *COLOR_STATE.try_write().unwrap() = ColorState { stroke: None, ..ColorState::default() };
+        let square = || draw(|| {
+            begin_shape(PShapeKind::ShapePolygon).unwrap();
+            for (x, y) in [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)] {
+                add_vertex(x, y, 0.0).unwrap();
+            }
+            end_shape(PShapeClose::ShapeClose).unwrap();
+        });
+        let left = |triangles: &[[([f32; 3], [f32; 4]); 3]]| triangles.iter().flatten().map(|(position, _)| position[0]).fold(f32::MAX, f32::min);
+
+        transform::push_matrix().unwrap();
+        transform::apply_matrix(matrix::translation(100.0, 0.0, 0.0));
+        transform::apply_matrix(matrix::scaling(2.0, 2.0, 1.0));
+        let moved = square();
+        assert!((area(&moved) - 400.0).abs() < 1e-3);
+        assert_eq!(left(&moved), 100.0);
+
+        transform::pop_matrix().unwrap();
+        let square = square();
+        assert!((area(&square) - 100.0).abs() < 1e-3);
+        assert_eq!(left(&square), 0.0);
+
+        *COLOR_STATE.try_write().unwrap() = ColorState::default();
+    }
+
+    #[test]
+    fn vertices_keep_the_fill_they_were_given () {
+        let _state = lock_state();
+        *COLOR_STATE.try_write().unwrap() = ColorState { stroke: None, ..ColorState::default() };
+
+        let triangles = draw(|| {
+            begin_shape(PShapeKind::ShapeTriangles).unwrap();
+            set_color_state! {
+                fill = Some(RED);
+            }
+            add_vertex(0.0, 0.0, 0.0).unwrap();
+            add_vertex(10.0, 0.0, 0.0).unwrap();
+            set_color_state! {
+                fill = Some(BLUE);
+            }
+            add_vertex(0.0, 10.0, 0.0).unwrap();
+            end_shape(PShapeClose::ShapeOpen).unwrap();
+        });
+        let format = get_renderer_state().canvas_format;
+        let colors: Vec<_> = triangles[0].iter().map(|(_, color)| *color).collect();
+        assert_eq!(colors, [RED, RED, BLUE].map(|color| color.to_shader_color(format)));
+
+        *COLOR_STATE.try_write().unwrap() = ColorState::default();
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use crate::{
+    color::color_state::get_color_state,
+    error::{OrPanic, PResult},
+    image::image_texture,
+    light::lighting_uniform,
+    math::matrix::{self, multiply, PMatrix},
+    record::record_path,
+    renderer::{camera::PRenderer, draw_list::{RetainedDraw, RetainedGeometry}, state::{get_renderer_state, RENDERER_STATE}, texture::SampledTexture},
+    shape::{
+        retained::{state::{get_retained_state, RetainedState, ShapeCache, ShapeCacheKey, ShapeGeometry, RETAINED_STATE}, with_shape, PShape},
+        solid::{tessellate_box, tessellate_sphere},
+        state::ShapeStyle,
+        tessellate::Tessellator,
+        tessellate_path,
+        transform
+    }
+};
+
+// Shapes that aren't groups, with the matrix they are drawn with
+fn collect_leaves (state: &RetainedState, shape: PShape, parent: &PMatrix, leaves: &mut Vec<(PShape, PMatrix)>) {
+    let data = &state.shapes[shape.id as usize];
+    let model = multiply(parent, &data.matrix);
+    match &data.geometry {
+        ShapeGeometry::Group(children) => children.iter().for_each(|child| collect_leaves(state, *child, &model, leaves)),
+        _ => leaves.push((shape, model))
+    }
+}
+
+fn tessellate_geometry (tessellator: &mut Tessellator, geometry: &ShapeGeometry, style: &ShapeStyle) {
+    match geometry {
+        ShapeGeometry::Path(Some(path)) => tessellate_path(tessellator, path, style),
+        ShapeGeometry::Box(size) => tessellate_box(tessellator, *size, style),
+        ShapeGeometry::Sphere { radius, detail } => tessellate_sphere(tessellator, *radius, *detail, style),
+        ShapeGeometry::Path(None) | ShapeGeometry::Group(_) => {}
+    }
+}
+
+// Tessellator for what is being drawn into, without the camera
+fn tessellator (key: &ShapeCacheKey, stroke_weight: f32) -> Tessellator {
+    let tessellator = Tessellator::new(key.format, stroke_weight);
+    match key.pixel_size {
+        Some(pixel_size) => tessellator.with_analytic_aa(pixel_size),
+        None => tessellator
+    }
+}
+
+fn cache_key () -> ShapeCacheKey {
+    let renderer_state = get_renderer_state();
+    ShapeCacheKey {
+        format: renderer_state.canvas_format,
+        pixel_size: renderer_state.analytic_aa().then(|| 1.0 / renderer_state.pixel_density as f32),
+        renderer: renderer_state.renderer
+    }
+}
+
+// The cached geometry of a shape, tessellated first if it has none
+// or it was tessellated for something else
+fn shape_cache (shape: PShape) -> ShapeCache {
+    let key = cache_key();
+    let cache = {
+        let state = get_retained_state();
+        let data = &state.shapes[shape.id as usize];
+        if let Some(cache) = data.cache.as_ref().filter(|cache| cache.key == key) {
+            return cache.clone();
+        }
+
+        let mut tessellator = tessellator(&key, data.style.stroke_weight);
+        if matches!(&data.geometry, ShapeGeometry::Path(Some(path)) if path.texture.is_some()) {
+            tessellator = tessellator.with_textured_fills();
+        }
+        let style = match key.renderer {
+            PRenderer::P2D => data.style,
+            PRenderer::P3D => ShapeStyle { stroke: None, ..data.style }
+        };
+        tessellate_geometry(&mut tessellator, &data.geometry, &style);
+
+        // Strokes that had to be kept apart go after the fills
+        let (fills, strokes) = (tessellator.geometry, tessellator.stroke_geometry);
+        let (offset, fills_end) = (fills.vertices.len() as u32, fills.indices.len() as u32);
+        let mut vertices = fills.vertices;
+        vertices.extend(strokes.vertices);
+        let mut indices = fills.indices;
+        indices.extend(strokes.indices.iter().map(|index| index + offset));
+        let end = indices.len() as u32;
+
+        ShapeCache { key, geometry: Arc::new(RetainedGeometry::new(vertices, indices)), fills: 0..fills_end, strokes: fills_end..end }
+    };
+
+    RETAINED_STATE.try_write().expect("Could not write to RwLock").shapes[shape.id as usize].cache = Some(cache.clone());
+    cache
+}
+
+// Strokes of a shape in 3D, projected onto the canvas like the ones of other shapes
+fn project_strokes (shape: PShape, key: &ShapeCacheKey, style: &ShapeStyle, model: &PMatrix) -> Tessellator {
+    let mut tessellator = {
+        let renderer_state = get_renderer_state();
+        let (width, height) = renderer_state.target_size();
+        tessellator(key, style.stroke_weight).with_screen_projection(multiply(&renderer_state.view_projection(), model), width, height)
+    };
+    let state = get_retained_state();
+    tessellate_geometry(&mut tessellator, &state.shapes[shape.id as usize].geometry, &ShapeStyle { fill: None, ..*style });
+    tessellator
+}
+
+fn draw_leaf (shape: PShape, model: PMatrix) -> PResult<()> {
+    let cache = shape_cache(shape);
+    let (style, texture) = with_shape(shape, |data| (data.style, match &data.geometry {
+        ShapeGeometry::Path(Some(path)) => {
+            record_path(path, &data.style, Some(&model));
+            path.texture.map(|image| (image, path.texture_wrap))
+        }
+        _ => None
+    }))?;
+    let texture = match texture {
+        Some((image, wrap)) => image_texture(image)?.map(|id| SampledTexture { id, wrap }),
+        None => None
+    };
+    let strokes = (cache.key.renderer == PRenderer::P3D && style.stroke.is_some())
+        .then(|| project_strokes(shape, &cache.key, &style, &model));
+
+    let blend_mode = get_color_state().blend_mode;
+    let lighting = lighting_uniform();
+    let mut renderer_state = RENDERER_STATE.try_write().expect("Could not write to RwLock");
+    let shader = renderer_state.shader;
+    let draw = || RetainedDraw { geometry: cache.geometry.clone(), model };
+    renderer_state.draw_list.push_retained(draw(), cache.fills.clone(), blend_mode, texture, shader, lighting.as_ref());
+    renderer_state.draw_list.push_retained(draw(), cache.strokes.clone(), blend_mode, None, shader, None);
+    if let Some(strokes) = strokes {
+        let strokes = &strokes.stroke_geometry;
+        renderer_state.draw_list.push(&strokes.vertices, &strokes.indices, blend_mode, None, shader, true);
+    }
+    Ok(())
+}
+
+// Draws a shape and its children with its origin at x, y, moved with
+// the current matrix. Geometry is only tessellated the first time
+// and after the shape changes
+pub(crate) fn draw_shape (shape: PShape, x: f32, y: f32) -> PResult<()> {
+    with_shape(shape, |_| ())?;
+    let mut leaves = vec![];
+    let origin = multiply(&transform::model_matrix(), &matrix::translation(x, y, 0.0));
+    collect_leaves(&get_retained_state(), shape, &origin, &mut leaves);
+    leaves.into_iter().try_for_each(|(shape, model)| draw_leaf(shape, model))
+}
+
+#[no_mangle]
+pub extern "C" fn shape (shape: PShape, x: f32, y: f32) {
+    draw_shape(shape, x, y).or_panic()
+}
@@ -0,0 +1,440 @@
+use std::{ffi::c_char, path::Path};
+
+use crate::{
+    color::PColor,
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    math::{matrix::{self, multiply, PMatrix, IDENTITY}, vector::PVector},
+    renderer::camera::check_3d,
+    shape::{
+        add_vertex, begin_shape, current_style, end_shape,
+        retained::state::{get_retained_state, RetainedState, ShapeData, ShapeGeometry, RETAINED_STATE},
+        state::{get_shape_state, set_shape_state, PShapeClose, PShapeKind, ShapePath, ShapeStyle, ShapeVertex}
+    }
+};
+
+pub mod draw;
+pub mod obj;
+pub mod state;
+pub mod svg;
+
+// Handle to a shape returned by createShape() and the like. Its
+// geometry is kept, and only tessellated again when it changes
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct PShape {
+    pub id: u32
+}
+
+// Shapes createShapePrimitive() can make, centered on the origin
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PShapePrimitive {
+    // A width, height and depth, or one size for a cube
+    PrimitiveBox,
+    // A radius, with the current sphereDetail()
+    PrimitiveSphere
+}
+
+pub(crate) fn add_shape (data: ShapeData) -> PShape {
+    let mut state = RETAINED_STATE.try_write().expect("Could not write to RwLock");
+    state.shapes.push(data);
+    PShape { id: state.shapes.len() as u32 - 1 }
+}
+
+// Runs callback with the data of a shape
+pub(crate) fn with_shape<T> (shape: PShape, callback: impl FnOnce(&ShapeData) -> T) -> PResult<T> {
+    let state = get_retained_state();
+    let data = state.shapes.get(shape.id as usize).ok_or(PError::UnknownShape(shape.id))?;
+    Ok(callback(data))
+}
+
+// Changes a shape. Its cached geometry is dropped unless only the matrix changed
+fn update_shape<T> (shape: PShape, keep_cache: bool, callback: impl FnOnce(&mut ShapeData) -> PResult<T>) -> PResult<T> {
+    let mut state = RETAINED_STATE.try_write().expect("Could not write to RwLock");
+    let data = state.shapes.get_mut(shape.id as usize).ok_or(PError::UnknownShape(shape.id))?;
+    if !keep_cache {
+        data.cache = None;
+    }
+    callback(data)
+}
+
+// A shape for PShape_beginShape() and PShape_endShape(),
+// with the styles that are current at PShape_endShape()
+pub(crate) fn create_shape () -> PShape {
+    add_shape(ShapeData::new(ShapeGeometry::Path(None), current_style()))
+}
+
+pub(crate) fn create_shape_group () -> PShape {
+    add_shape(ShapeData::new(ShapeGeometry::Group(vec![]), current_style()))
+}
+
+// Only 3D primitives exist so far. They get the current styles
+pub(crate) fn create_shape_primitive (kind: PShapePrimitive, params: &[f32]) -> PResult<PShape> {
+    check_3d()?;
+    let geometry = match (kind, params) {
+        (PShapePrimitive::PrimitiveBox, &[size]) => ShapeGeometry::Box([size; 3]),
+        (PShapePrimitive::PrimitiveBox, &[width, height, depth]) => ShapeGeometry::Box([width, height, depth]),
+        (PShapePrimitive::PrimitiveSphere, &[radius]) => ShapeGeometry::Sphere { radius, detail: get_shape_state().sphere_detail },
+        _ => return Err(PError::InvalidShapeParams { kind, given: params.len() })
+    };
+    Ok(add_shape(ShapeData::new(geometry, current_style())))
+}
+
+// True if shape is target or has it among its children
+fn contains (shape: PShape, target: PShape) -> bool {
+    if shape == target { return true; }
+    let state = get_retained_state();
+    let Some(ShapeGeometry::Group(children)) = state.shapes.get(shape.id as usize).map(|data| &data.geometry) else { return false; };
+    let children = children.clone();
+    drop(state);
+    children.into_iter().any(|child| contains(child, target))
+}
+
+pub(crate) fn add_child (group: PShape, child: PShape) -> PResult<()> {
+    with_shape(child, |_| ())?;
+    if contains(child, group) {
+        return Err(PError::ShapeContainsItself);
+    }
+
+    update_shape(group, true, |data| match &mut data.geometry {
+        ShapeGeometry::Group(children) => {
+            children.push(child);
+            Ok(())
+        }
+        _ => Err(PError::NotShapeGroup(group.id))
+    })
+}
+
+pub(crate) fn child_count (shape: PShape) -> PResult<u32> {
+    with_shape(shape, |data| match &data.geometry {
+        ShapeGeometry::Group(children) => children.len() as u32,
+        _ => 0
+    })
+}
+
+pub(crate) fn child (shape: PShape, index: u32) -> PResult<PShape> {
+    with_shape(shape, |data| match &data.geometry {
+        ShapeGeometry::Group(children) => children.get(index as usize).copied()
+            .ok_or(PError::InvalidChildIndex { index, count: children.len() as u32 }),
+        _ => Err(PError::InvalidChildIndex { index, count: 0 })
+    })?
+}
+
+// The first shape called name among the children of shape,
+// their children and so on. SVG elements are named after their id
+pub(crate) fn child_by_name (shape: PShape, name: &str) -> PResult<PShape> {
+    fn find (state: &RetainedState, shape: PShape, name: &str) -> Option<PShape> {
+        let ShapeGeometry::Group(children) = &state.shapes.get(shape.id as usize)?.geometry else { return None; };
+        children.iter().find(|child| state.shapes[child.id as usize].name.as_deref() == Some(name)).copied()
+            .or_else(|| children.iter().find_map(|child| find(state, *child, name)))
+    }
+
+    with_shape(shape, |_| ())?;
+    find(&get_retained_state(), shape, name).ok_or(PError::UnknownChild(name.to_string()))
+}
+
+// Loads a shape from an SVG or OBJ file
+pub(crate) fn load_shape (path: &str) -> PResult<PShape> {
+    let file = Path::new(path);
+    if !file.is_file() {
+        return Err(PError::ShapeNotFound(path.to_string()));
+    }
+    match file.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("svg") => svg::load_svg(path),
+        Some("obj") => obj::load_obj(path),
+        _ => Err(PError::UnsupportedShapeFile(path.to_string()))
+    }
+}
+
+// Starts recording vertices into shape. vertex() and the other vertex
+// functions add to it until PShape_endShape(), which keeps them
+// in the shape instead of drawing them
+pub(crate) fn begin_recording (shape: PShape, kind: PShapeKind) -> PResult<()> {
+    if !with_shape(shape, |data| matches!(data.geometry, ShapeGeometry::Path(_)))? {
+        return Err(PError::NotPathShape(shape.id));
+    }
+    begin_shape(kind)?;
+    set_shape_state! {
+        recording = Some(shape);
+    }
+    Ok(())
+}
+
+fn check_recording (shape: PShape) -> PResult<()> {
+    if get_shape_state().recording != Some(shape) {
+        return Err(PError::ShapeNotRecording(shape.id));
+    }
+    Ok(())
+}
+
+pub(crate) fn record_vertex (shape: PShape, x: f32, y: f32, z: f32) -> PResult<()> {
+    check_recording(shape)?;
+    add_vertex(x, y, z)
+}
+
+pub(crate) fn end_recording (shape: PShape, close: PShapeClose) -> PResult<()> {
+    check_recording(shape)?;
+    end_shape(close)
+}
+
+// Called by endShape() while recording
+pub(crate) fn set_path (shape: PShape, path: ShapePath, style: ShapeStyle) -> PResult<()> {
+    update_shape(shape, false, |data| {
+        data.geometry = ShapeGeometry::Path(Some(path));
+        data.style = style;
+        Ok(())
+    })
+}
+
+// Changes the style of a shape and everything in it. change gets
+// the style, and every vertex of a path after it
+fn restyle (shape: PShape, change: &impl Fn(&mut ShapeStyle, Option<&mut ShapeVertex>)) -> PResult<()> {
+    let children = update_shape(shape, false, |data| {
+        change(&mut data.style, None);
+        match &mut data.geometry {
+            ShapeGeometry::Group(children) => return Ok(children.clone()),
+            ShapeGeometry::Path(Some(path)) => {
+                for vertex in path.vertices.iter_mut().chain(path.contours.iter_mut().flatten()) {
+                    change(&mut data.style, Some(vertex));
+                }
+            }
+            _ => {}
+        }
+        Ok(vec![])
+    })?;
+    children.into_iter().try_for_each(|child| restyle(child, change))
+}
+
+// None turns fills off, like noFill()
+pub(crate) fn set_fill (shape: PShape, fill: Option<PColor>) -> PResult<()> {
+    restyle(shape, &|style, vertex| match vertex {
+        Some(vertex) => vertex.fill = fill.unwrap_or(vertex.fill),
+        None => style.fill = fill
+    })
+}
+
+pub(crate) fn set_stroke (shape: PShape, stroke: Option<PColor>) -> PResult<()> {
+    restyle(shape, &|style, vertex| match vertex {
+        Some(vertex) => vertex.stroke = stroke.unwrap_or(vertex.stroke),
+        None => style.stroke = stroke
+    })
+}
+
+pub(crate) fn set_stroke_weight (shape: PShape, weight: f32) -> PResult<()> {
+    restyle(shape, &|style, _| style.stroke_weight = weight)
+}
+
+// Applies transform after the shape's current matrix, so transforms
+// happen in the order they are called. Cached geometry is kept
+pub(crate) fn transform_shape (shape: PShape, transform: PMatrix) -> PResult<()> {
+    update_shape(shape, true, |data| {
+        data.matrix = multiply(&transform, &data.matrix);
+        Ok(())
+    })
+}
+
+pub(crate) fn reset_matrix (shape: PShape) -> PResult<()> {
+    update_shape(shape, true, |data| {
+        data.matrix = IDENTITY;
+        Ok(())
+    })
+}
+
+// Vertices of a path, the outline first and then its contours
+fn path_vertices (data: &ShapeData) -> Vec<ShapeVertex> {
+    match &data.geometry {
+        ShapeGeometry::Path(Some(path)) => path.vertices.iter().chain(path.contours.iter().flatten()).copied().collect(),
+        _ => vec![]
+    }
+}
+
+pub(crate) fn vertex_count (shape: PShape) -> PResult<u32> {
+    with_shape(shape, |data| path_vertices(data).len() as u32)
+}
+
+pub(crate) fn get_vertex (shape: PShape, index: u32) -> PResult<PVector> {
+    let vertices = with_shape(shape, path_vertices)?;
+    let vertex = vertices.get(index as usize).ok_or(PError::InvalidVertexIndex { index, count: vertices.len() as u32 })?;
+    Ok(PVector { x: vertex.x, y: vertex.y, z: vertex.z })
+}
+
+pub(crate) fn set_vertex (shape: PShape, index: u32, position: PVector) -> PResult<()> {
+    update_shape(shape, false, |data| {
+        let ShapeGeometry::Path(Some(path)) = &mut data.geometry else {
+            return Err(PError::InvalidVertexIndex { index, count: 0 });
+        };
+        let count = (path.vertices.len() + path.contours.iter().map(Vec::len).sum::<usize>()) as u32;
+        let vertex = path.vertices.iter_mut().chain(path.contours.iter_mut().flatten())
+            .nth(index as usize)
+            .ok_or(PError::InvalidVertexIndex { index, count })?;
+        (vertex.x, vertex.y, vertex.z) = (position.x, position.y, position.z);
+        // Recordings write the vertices as they are now, not the curves
+        path.curves.clear();
+        Ok(())
+    })
+}
+
+/// Loads an SVG or OBJ file as a group of shapes. OBJ files only load in 3D
+///
+/// # Safety
+/// `path` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn loadShape (path: *const c_char) -> PShape {
+    load_shape(&string_from(path)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn createShape () -> PShape {
+    create_shape()
+}
+
+#[no_mangle]
+pub extern "C" fn createShapeGroup () -> PShape {
+    create_shape_group()
+}
+
+/// Makes a box or a sphere from `count` parameters, see PShapePrimitive
+///
+/// # Safety
+/// `params` must point to at least `count` floats
+#[no_mangle]
+pub unsafe extern "C" fn createShapePrimitive (kind: PShapePrimitive, params: *const f32, count: usize) -> PShape {
+    let params = if params.is_null() { &[] } else { std::slice::from_raw_parts(params, count) };
+    create_shape_primitive(kind, params).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_addChild (group: PShape, child: PShape) {
+    add_child(group, child).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_getChildCount (shape: PShape) -> u32 {
+    child_count(shape).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_getChild (shape: PShape, index: u32) -> PShape {
+    child(shape, index).or_panic()
+}
+
+/// Finds a child of the shape, or a child of one of its children, by name
+///
+/// # Safety
+/// `name` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn PShape_getChildByName (shape: PShape, name: *const c_char) -> PShape {
+    child_by_name(shape, &string_from(name)).or_panic()
+}
+
+// Vertices can also be added with the other vertex functions (vertexUV(),
+// bezierVertex(), curveVertex() and so on), along with normal(), texture()
+// and contours, until PShape_endShape()
+#[no_mangle]
+pub extern "C" fn PShape_beginShape (shape: PShape, kind: PShapeKind) {
+    begin_recording(shape, kind).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_vertex (shape: PShape, x: f32, y: f32) {
+    record_vertex(shape, x, y, 0.0).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_vertex3D (shape: PShape, x: f32, y: f32, z: f32) {
+    record_vertex(shape, x, y, z).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_endShape (shape: PShape, close: PShapeClose) {
+    end_recording(shape, close).or_panic()
+}
+
+// Recolors every vertex of the shape and its children
+#[no_mangle]
+pub extern "C" fn PShape_setFill (shape: PShape, r: f32, g: f32, b: f32, a: f32) {
+    set_fill(shape, Some(PColor::from_rgba(r, g, b, a))).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_noFill (shape: PShape) {
+    set_fill(shape, None).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_setStroke (shape: PShape, r: f32, g: f32, b: f32, a: f32) {
+    set_stroke(shape, Some(PColor::from_rgba(r, g, b, a))).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_noStroke (shape: PShape) {
+    set_stroke(shape, None).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_setStrokeWeight (shape: PShape, weight: f32) {
+    set_stroke_weight(shape, weight).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_translate (shape: PShape, x: f32, y: f32) {
+    transform_shape(shape, matrix::translation(x, y, 0.0)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_translate3D (shape: PShape, x: f32, y: f32, z: f32) {
+    transform_shape(shape, matrix::translation(x, y, z)).or_panic()
+}
+
+// Angles are in radians. rotate() turns around the z axis, like in 2D
+#[no_mangle]
+pub extern "C" fn PShape_rotate (shape: PShape, angle: f32) {
+    transform_shape(shape, matrix::rotation_z(angle)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_rotateX (shape: PShape, angle: f32) {
+    transform_shape(shape, matrix::rotation_x(angle)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_rotateY (shape: PShape, angle: f32) {
+    transform_shape(shape, matrix::rotation_y(angle)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_rotateZ (shape: PShape, angle: f32) {
+    transform_shape(shape, matrix::rotation_z(angle)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_scale (shape: PShape, x: f32, y: f32) {
+    transform_shape(shape, matrix::scaling(x, y, 1.0)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_scale3D (shape: PShape, x: f32, y: f32, z: f32) {
+    transform_shape(shape, matrix::scaling(x, y, z)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_resetMatrix (shape: PShape) {
+    reset_matrix(shape).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_getVertexCount (shape: PShape) -> u32 {
+    vertex_count(shape).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn PShape_getVertex (shape: PShape, index: u32) -> PVector {
+    get_vertex(shape, index).or_panic()
+}
+
+// Moves a vertex, the shape is tessellated again the next time it is drawn
+#[no_mangle]
+pub extern "C" fn PShape_setVertex (shape: PShape, index: u32, x: f32, y: f32, z: f32) {
+    set_vertex(shape, index, PVector { x, y, z }).or_panic()
+}
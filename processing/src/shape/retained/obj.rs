@@ -0,0 +1,327 @@
+use std::{collections::HashMap, fs, path::Path, str::SplitWhitespace};
+
+use crate::{
+    color::PColor,
+    error::{PError, PResult},
+    image::{create::load_image, free_image, PImage},
+    renderer::{camera::check_3d, texture::PTextureWrap},
+    shape::{
+        retained::{add_shape, state::{ShapeData, ShapeGeometry}, PShape},
+        state::{PShapeClose, PShapeKind, ShapePath, ShapeStyle, ShapeVertex}
+    }
+};
+
+// What the 3D pipeline can draw of an MTL material
+struct Material {
+    // The diffuse color, with the opacity of the material
+    color: PColor,
+    texture: Option<PImage>,
+}
+
+// A corner of a face, as indices into the positions, uvs and normals of the file
+#[derive(Copy, Clone)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+// Faces in the same object or group with the same material, which become one shape
+struct Part {
+    name: Option<String>,
+    material: Option<String>,
+    triangles: Vec<Corner>,
+}
+
+#[derive(Default)]
+struct Obj {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    materials: HashMap<String, Material>,
+    parts: Vec<Part>,
+}
+
+fn numbers<const N: usize> (words: &mut SplitWhitespace, keyword: &str) -> Result<[f32; N], String> {
+    let mut numbers = [0.0; N];
+    for number in numbers.iter_mut() {
+        let word = words.next().ok_or_else(|| format!("\"{}\" takes {} numbers.", keyword, N))?;
+        *number = word.parse().map_err(|_| format!("\"{}\" is not a number.", word))?;
+    }
+    Ok(numbers)
+}
+
+// u, and v, which is 0 when left out. w is ignored
+fn uv (words: &mut SplitWhitespace) -> Result<[f32; 2], String> {
+    let [u] = numbers(words, "vt")?;
+    let v = match words.next() {
+        Some(word) => word.parse().map_err(|_| format!("\"{}\" is not a number.", word))?,
+        None => 0.0
+    };
+    Ok([u, v])
+}
+
+// Indices start at 1, negative ones count back from the last element defined
+fn index (word: &str, count: usize, kind: &str) -> Result<usize, String> {
+    let given: i64 = word.parse().map_err(|_| format!("\"{}\" is not a {} index.", word, kind))?;
+    let index = if given < 0 { count as i64 + given } else { given - 1 };
+    if index < 0 || index >= count as i64 {
+        return Err(format!("The face uses {} {}, but only {} have been defined so far.", kind, given, count));
+    }
+    Ok(index as usize)
+}
+
+impl Obj {
+    // A corner written as v, v/vt, v//vn or v/vt/vn
+    fn corner (&self, word: &str) -> Result<Corner, String> {
+        let mut indices = word.split('/');
+        let position = index(indices.next().unwrap_or(""), self.positions.len(), "vertex")?;
+        let uv = match indices.next() {
+            None | Some("") => None,
+            Some(uv) => Some(index(uv, self.uvs.len(), "texture coordinate")?)
+        };
+        let normal = match indices.next() {
+            None | Some("") => None,
+            Some(normal) => Some(index(normal, self.normals.len(), "normal")?)
+        };
+        Ok(Corner { position, uv, normal })
+    }
+
+    // Faces with more than 3 corners are split into a fan of triangles
+    fn add_face (&mut self, words: SplitWhitespace, name: &Option<String>, material: &Option<String>) -> Result<(), String> {
+        let corners = words.map(|word| self.corner(word)).collect::<Result<Vec<_>, _>>()?;
+        if corners.len() < 3 {
+            return Err(format!("A face needs at least 3 corners, it has {}.", corners.len()));
+        }
+
+        if !matches!(self.parts.last(), Some(part) if part.name == *name && part.material == *material) {
+            self.parts.push(Part { name: name.clone(), material: material.clone(), triangles: vec![] });
+        }
+        let part = self.parts.last_mut().unwrap();
+        for i in 1..corners.len() - 1 {
+            part.triangles.extend([corners[0], corners[i], corners[i + 1]]);
+        }
+        Ok(())
+    }
+
+    // Adds the materials of an MTL file. Textures are found next to it
+    fn load_materials (&mut self, path: &Path) -> PResult<()> {
+        let invalid = |message: String| PError::InvalidShape { path: path.display().to_string(), message };
+        let text = fs::read_to_string(path).map_err(|_| PError::ShapeNotFound(path.display().to_string()))?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+
+        let mut current: Option<String> = None;
+        for (line, text) in text.lines().enumerate() {
+            let mut words = text.split_whitespace();
+            let Some(keyword) = words.next() else { continue; };
+            let at_line = |message: String| invalid(format!("Line {}: {}", line + 1, message));
+            if keyword == "newmtl" {
+                let name = words.collect::<Vec<_>>().join(" ");
+                self.materials.insert(name.clone(), Material { color: PColor::WHITE, texture: None });
+                current = Some(name);
+                continue;
+            }
+            if !matches!(keyword, "Kd" | "d" | "Tr" | "map_Kd") {
+                continue;
+            }
+
+            let material = current.as_ref().and_then(|name| self.materials.get_mut(name))
+                .ok_or_else(|| at_line(format!("\"{}\" comes before any \"newmtl\".", keyword)))?;
+            match keyword {
+                "Kd" => {
+                    let [r, g, b] = numbers(&mut words, keyword).map_err(at_line)?;
+                    material.color = PColor { r, g, b, ..material.color };
+                }
+                "d" => material.color.a = numbers::<1>(&mut words, keyword).map_err(at_line)?[0],
+                "Tr" => material.color.a = 1.0 - numbers::<1>(&mut words, keyword).map_err(at_line)?[0],
+                // Options like -s come before the file name, which is last
+                _ => {
+                    let file = words.last().ok_or_else(|| at_line("\"map_Kd\" needs an image file.".to_string()))?;
+                    material.texture = Some(load_image(&directory.join(file).to_string_lossy())?);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn part_shape (&self, part: &Part) -> PShape {
+        let material = part.material.as_ref().and_then(|name| self.materials.get(name));
+        let style = ShapeStyle {
+            fill: Some(material.map_or(PColor::WHITE, |material| material.color)),
+            stroke: None,
+            stroke_weight: 1.0
+        };
+        // Rows of textures go up in OBJ files
+        let vertices = part.triangles.iter().map(|corner| {
+            let [x, y, z] = self.positions[corner.position];
+            ShapeVertex {
+                normal: corner.normal.map(|normal| self.normals[normal]),
+                uv: corner.uv.map_or([0.0, 0.0], |uv| [self.uvs[uv][0], 1.0 - self.uvs[uv][1]]),
+                ..style.vertex(x, y, z)
+            }
+        }).collect();
+
+        let path = ShapePath {
+            kind: PShapeKind::ShapeTriangles,
+            close: PShapeClose::ShapeOpen,
+            vertices,
+            contours: vec![],
+            curves: vec![],
+            texture: material.and_then(|material| material.texture),
+            texture_wrap: PTextureWrap::TextureRepeat
+        };
+        add_shape(ShapeData { name: part.name.clone(), ..ShapeData::new(ShapeGeometry::Path(Some(path)), style) })
+    }
+}
+
+// Turns an OBJ file into a group with a shape for every object or group
+// in it and material they use, named after the object or group. Faces
+// are filled with the diffuse color and texture of their material,
+// or white, and are not stroked. MTL files that can't be found are
+// skipped, materials that may have been in them are white too.
+// Only works in 3D
+pub(crate) fn load_obj (path: &str) -> PResult<PShape> {
+    check_3d()?;
+    // Textures of the materials are loaded while reading the file,
+    // they are freed again when it turns out to be invalid
+    let mut obj = Obj::default();
+    read_obj(path, &mut obj).inspect_err(|_| {
+        for texture in obj.materials.values().filter_map(|material| material.texture) {
+            free_image(texture).ok();
+        }
+    })
+}
+
+fn read_obj (path: &str, obj: &mut Obj) -> PResult<PShape> {
+    let invalid = |message: String| PError::InvalidShape { path: path.to_string(), message };
+    let text = fs::read_to_string(path).map_err(|_| PError::ShapeNotFound(path.to_string()))?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let (mut name, mut material) = (None, None);
+    // MTL files that couldn't be found. Materials that aren't in the
+    // ones that loaded may be in them, those get the default material
+    let mut missing: Vec<String> = vec![];
+    for (line, text) in text.lines().enumerate() {
+        let mut words = text.split_whitespace();
+        let Some(keyword) = words.next() else { continue; };
+        let at_line = |message: String| invalid(format!("Line {}: {}", line + 1, message));
+        match keyword {
+            "v" => obj.positions.push(numbers(&mut words, keyword).map_err(at_line)?),
+            "vt" => obj.uvs.push(uv(&mut words).map_err(at_line)?),
+            "vn" => obj.normals.push(numbers(&mut words, keyword).map_err(at_line)?),
+            "f" => obj.add_face(words, &name, &material).map_err(at_line)?,
+            "o" | "g" => name = Some(words.collect::<Vec<_>>().join(" ")).filter(|name| !name.is_empty()),
+            "mtllib" => {
+                for file in words {
+                    match obj.load_materials(&directory.join(file)) {
+                        Err(PError::ShapeNotFound(file)) => missing.push(file),
+                        result => result?
+                    }
+                }
+            }
+            "usemtl" => {
+                let used = words.collect::<Vec<_>>().join(" ");
+                if !obj.materials.contains_key(&used) && missing.is_empty() {
+                    return Err(at_line(format!("No material called \"{}\" has been loaded with \"mtllib\".", used)));
+                }
+                material = Some(used);
+            }
+            // Lines, points, smoothing groups and the like
+            _ => {}
+        }
+    }
+
+    let children = obj.parts.iter().map(|part| obj.part_shape(part)).collect();
+    let style = ShapeStyle { fill: Some(PColor::WHITE), stroke: None, stroke_weight: 1.0 };
+    Ok(add_shape(ShapeData::new(ShapeGeometry::Group(children), style)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture, image::state::get_image_state, lock_state, renderer::{camera::PRenderer, state::set_renderer_state}, shape::retained::state::get_retained_state};
+
+    // The paths of the children of a loaded group
+    fn children (shape: PShape) -> Vec<(Option<String>, ShapePath)> {
+        let state = get_retained_state();
+        let ShapeGeometry::Group(children) = &state.shapes[shape.id as usize].geometry else { panic!("Not a group"); };
+        children.iter().map(|child| {
+            let data = &state.shapes[child.id as usize];
+            let ShapeGeometry::Path(Some(path)) = &data.geometry else { panic!("Not a path"); };
+            (data.name.clone(), path.clone())
+        }).collect()
+    }
+
+    fn load (name: &str) -> PResult<PShape> {
+        set_renderer_state! {
+            renderer = PRenderer::P3D;
+        }
+        load_obj(&fixture(name))
+    }
+
+    #[test]
+    fn faces_quads_and_materials () {
+        let _state = lock_state();
+        let parts = children(load("quad.obj").unwrap());
+        assert_eq!(parts.len(), 2);
+
+        // The quad is split into two triangles
+        let (name, quad) = &parts[0];
+        assert_eq!(name.as_deref(), Some("Quad"));
+        assert_eq!(quad.vertices.len(), 6);
+        let positions: Vec<_> = quad.vertices.iter().map(|vertex| [vertex.x, vertex.y, vertex.z]).collect();
+        assert_eq!(positions, [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]);
+        // Rows go up in OBJ files, v is flipped
+        assert_eq!(quad.vertices[2].uv, [1.0, 0.0]);
+        assert_eq!(quad.vertices[0].normal, Some([0.0, 0.0, 1.0]));
+        assert_eq!(quad.vertices[0].fill, PColor { r: 1.0, g: 0.0, b: 0.0, a: 1.0 });
+
+        // Negative indices count back from the last one, a lone u gets v = 0
+        let (name, triangle) = &parts[1];
+        assert_eq!(name.as_deref(), Some("Triangle"));
+        let positions: Vec<_> = triangle.vertices.iter().map(|vertex| [vertex.x, vertex.y, vertex.z]).collect();
+        assert_eq!(positions, [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+        assert_eq!(triangle.vertices[0].uv, [0.5, 1.0]);
+        assert_eq!(triangle.vertices[1].uv, [0.0, 0.0]);
+        assert_eq!(triangle.vertices[2].normal, Some([0.0, 0.0, 1.0]));
+        assert_eq!(triangle.vertices[0].fill, PColor { r: 0.0, g: 0.0, b: 1.0, a: 0.5 });
+    }
+
+    #[test]
+    fn missing_mtl_uses_the_default_material () {
+        let _state = lock_state();
+        let parts = children(load("missing_mtl.obj").unwrap());
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].1.vertices[0].fill, PColor::WHITE);
+        assert_eq!(parts[0].1.texture, None);
+
+        // Only materials that aren't in the MTL files that loaded get the default
+        let parts = children(load("partly_missing_mtl.obj").unwrap());
+        assert_eq!(parts[0].1.vertices[0].fill, PColor { r: 1.0, g: 0.0, b: 0.0, a: 1.0 });
+        assert_eq!(parts[1].1.vertices[0].fill, PColor::WHITE);
+    }
+
+    #[test]
+    fn unknown_materials_are_errors () {
+        let _state = lock_state();
+        let error = |name| match load(name) {
+            Err(PError::InvalidShape { message, .. }) => message,
+            result => panic!("{:?}", result)
+        };
+        assert_eq!(error("unknown_material.obj"), "Line 8: No material called \"green\" has been loaded with \"mtllib\".");
+        // A missing MTL file named after the material is used can't have it
+        assert_eq!(error("missing_mtl_after_use.obj"), "Line 9: No material called \"other\" has been loaded with \"mtllib\".");
+    }
+
+    #[test]
+    fn textures_are_freed_on_errors () {
+        let _state = lock_state();
+        let images = || {
+            let state = get_image_state();
+            state.images.len() - state.free_images.len()
+        };
+        let before = images();
+        assert!(matches!(load("invalid_after_texture.obj"), Err(PError::InvalidShape { .. })));
+        assert_eq!(images(), before);
+    }
+}
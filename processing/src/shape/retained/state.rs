@@ -0,0 +1,70 @@
+use lazy_static::lazy_static;
+use std::{ops::Range, sync::{Arc, RwLock, RwLockReadGuard}};
+
+use crate::{
+    math::matrix::{PMatrix, IDENTITY},
+    renderer::{camera::PRenderer, draw_list::RetainedGeometry, format::PCanvasFormat},
+    shape::{retained::PShape, state::{ShapePath, ShapeStyle}}
+};
+
+// What a retained shape is made of
+#[derive(Debug, Clone)]
+pub enum ShapeGeometry {
+    // Shapes drawn along with it, each with its own matrix and style
+    Group(Vec<PShape>),
+    // Made with createShape(), empty until PShape_endShape()
+    Path(Option<ShapePath>),
+    Box([f32; 3]),
+    Sphere { radius: f32, detail: (u32, u32) },
+}
+
+// What the cached geometry of a shape was tessellated for. It is
+// tessellated again when drawn into something that doesn't match
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeCacheKey {
+    pub format: PCanvasFormat,
+    // See Tessellator::with_analytic_aa
+    pub pixel_size: Option<f32>,
+    pub renderer: PRenderer,
+}
+
+// Tessellated fills of a shape, and its strokes in 2D. In 3D
+// strokes keep their width on the canvas, so they are
+// tessellated again every time the shape is drawn
+#[derive(Clone)]
+pub struct ShapeCache {
+    pub key: ShapeCacheKey,
+    pub geometry: Arc<RetainedGeometry>,
+    pub fills: Range<u32>,
+    pub strokes: Range<u32>,
+}
+
+pub struct ShapeData {
+    // The id of the element it was loaded from, see PShape_getChildByName()
+    pub name: Option<String>,
+    pub geometry: ShapeGeometry,
+    pub style: ShapeStyle,
+    // Applied to the shape before its parents' matrices and shape()
+    pub matrix: PMatrix,
+    // Dropped whenever the shape changes (its matrix aside)
+    pub cache: Option<ShapeCache>,
+}
+
+impl ShapeData {
+    pub fn new (geometry: ShapeGeometry, style: ShapeStyle) -> Self {
+        Self { name: None, geometry, style, matrix: IDENTITY, cache: None }
+    }
+}
+
+#[derive(Default)]
+pub struct RetainedState {
+    pub shapes: Vec<ShapeData>,
+}
+
+lazy_static! {
+    pub static ref RETAINED_STATE: Arc<RwLock<RetainedState>> = Arc::new(RwLock::new(RetainedState::default()));
+}
+
+pub fn get_retained_state () -> RwLockReadGuard<'static, RetainedState> {
+    RETAINED_STATE.try_read().unwrap()
+}
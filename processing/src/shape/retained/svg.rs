@@ -0,0 +1,653 @@
+use std::{f32::consts::{FRAC_PI_2, PI, SQRT_2}, fs, str::FromStr};
+
+use roxmltree::{Document, Node};
+use svgtypes::{Align, AspectRatio, Color, Length, LengthUnit, Paint, PaintFallback, PointsParser, SimplePathSegment, SimplifyingPathParser, Transform, ViewBox};
+
+use crate::{
+    color::PColor,
+    error::{PError, PResult},
+    math::{curve::{flatten_bezier, quadratic_to_cubic, KAPPA}, matrix::{self, multiply, PMatrix, IDENTITY}},
+    record::state::PathSegment,
+    renderer::texture::PTextureWrap,
+    shape::{
+        curve::FLATTEN_TOLERANCE,
+        retained::{add_shape, state::{get_retained_state, ShapeData, ShapeGeometry, RETAINED_STATE}, PShape},
+        state::{PShapeClose, PShapeKind, ShapeCurve, ShapePath, ShapeStyle, ShapeVertex}
+    }
+};
+
+// Styles an element gets from its parents, and passes on to its children
+#[derive(Copy, Clone)]
+struct SvgStyle {
+    fill: Option<PColor>,
+    stroke: Option<PColor>,
+    stroke_weight: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    // Multiplied with the opacity of every parent
+    opacity: f32,
+    // What currentColor stands for
+    color: PColor,
+    // Size of the nearest <svg>'s viewport, percentages are of it
+    viewport: [f32; 2],
+}
+
+impl Default for SvgStyle {
+    fn default () -> Self {
+        Self {
+            fill: Some(PColor::BLACK),
+            stroke: None,
+            stroke_weight: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            color: PColor::BLACK,
+            viewport: [0.0, 0.0]
+        }
+    }
+}
+
+impl SvgStyle {
+    // The style of node, with what it doesn't set taken from self
+    fn of (&self, node: Node) -> Self {
+        let length = |name| property(node, name).and_then(|value| Length::from_str(value).ok());
+        // Opacities can be given as percentages too
+        let number = |name| length(name).map(|length| match length.unit {
+            LengthUnit::Percent => length.number as f32 / 100.0,
+            _ => length.number as f32
+        });
+        let color = property(node, "color").and_then(|value| Color::from_str(value).ok()).map_or(self.color, to_color);
+        let paint = |name, inherited| property(node, name).map_or(inherited, |value| paint(value, color, inherited));
+        Self {
+            fill: paint("fill", self.fill),
+            stroke: paint("stroke", self.stroke),
+            stroke_weight: length("stroke-width").map_or(self.stroke_weight, |width| user_units(width, diagonal(self.viewport))),
+            fill_opacity: number("fill-opacity").unwrap_or(self.fill_opacity),
+            stroke_opacity: number("stroke-opacity").unwrap_or(self.stroke_opacity),
+            opacity: self.opacity * number("opacity").unwrap_or(1.0),
+            color,
+            viewport: self.viewport
+        }
+    }
+
+    fn shape_style (&self) -> ShapeStyle {
+        let faded = |color: PColor, opacity: f32| PColor { a: color.a * opacity * self.opacity, ..color };
+        ShapeStyle {
+            fill: self.fill.map(|fill| faded(fill, self.fill_opacity)),
+            stroke: self.stroke.map(|stroke| faded(stroke, self.stroke_opacity)),
+            stroke_weight: self.stroke_weight
+        }
+    }
+}
+
+fn to_color (color: Color) -> PColor {
+    PColor::from_rgba(color.red as f32, color.green as f32, color.blue as f32, color.alpha as f32)
+}
+
+// Gradients and patterns aren't supported, their fallback color is used
+// if they have one. Values that can't be read are ignored
+fn paint (value: &str, color: PColor, inherited: Option<PColor>) -> Option<PColor> {
+    match Paint::from_str(value) {
+        Ok(Paint::None) => None,
+        Ok(Paint::Color(paint)) => Some(to_color(paint)),
+        Ok(Paint::CurrentColor) => Some(color),
+        Ok(Paint::FuncIRI(_, Some(PaintFallback::Color(fallback)))) => Some(to_color(fallback)),
+        Ok(Paint::FuncIRI(_, Some(PaintFallback::CurrentColor))) => Some(color),
+        Ok(Paint::FuncIRI(_, Some(PaintFallback::None))) => None,
+        _ => inherited
+    }
+}
+
+// A presentation attribute, or the same property in the
+// style attribute, which wins when both are there
+fn property<'a> (node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    let declared = node.attribute("style").and_then(|style| style
+        .split(';')
+        .filter_map(|declaration| declaration.split_once(':'))
+        .find(|(property, _)| property.trim() == name)
+        .map(|(_, value)| value.trim()));
+    declared.or_else(|| node.attribute(name)).filter(|value| *value != "inherit")
+}
+
+// Size of em and ex, as there is no font to take it from
+const FONT_SIZE: f32 = 16.0;
+
+// A length in user units (pixels), at 96 pixels per inch like
+// browsers. Percentages are of reference
+fn user_units (length: Length, reference: f32) -> f32 {
+    length.number as f32 * match length.unit {
+        LengthUnit::None | LengthUnit::Px => 1.0,
+        LengthUnit::In => 96.0,
+        LengthUnit::Cm => 96.0 / 2.54,
+        LengthUnit::Mm => 96.0 / 25.4,
+        LengthUnit::Pt => 96.0 / 72.0,
+        LengthUnit::Pc => 16.0,
+        LengthUnit::Em => FONT_SIZE,
+        LengthUnit::Ex => FONT_SIZE / 2.0,
+        LengthUnit::Percent => reference / 100.0
+    }
+}
+
+// What lengths that aren't along x or y are percentages of
+fn diagonal ([width, height]: [f32; 2]) -> f32 {
+    (width * width + height * height).sqrt() / SQRT_2
+}
+
+// Attribute name of node in user units, 0 when it isn't there. Percentages
+// are of the viewport's width or height, or its diagonal for radii
+fn length (node: Node, name: &str, viewport: [f32; 2]) -> f32 {
+    let reference = match name {
+        "x" | "cx" | "x1" | "x2" | "width" | "rx" => viewport[0],
+        "y" | "cy" | "y1" | "y2" | "height" | "ry" => viewport[1],
+        _ => diagonal(viewport)
+    };
+    node.attribute(name).and_then(|value| Length::from_str(value).ok()).map_or(0.0, |length| user_units(length, reference))
+}
+
+// Points of a subpath, and whether it goes back to its start. Curves
+// are flattened into the points, and kept for recordings like the
+// ones drawn with bezierVertex() (their contour is set by path_geometry())
+struct Outline {
+    points: Vec<[f32; 2]>,
+    curves: Vec<ShapeCurve>,
+    closed: bool,
+}
+
+// A subpath being read
+#[derive(Default)]
+struct Subpath {
+    points: Vec<[f32; 2]>,
+    curves: Vec<ShapeCurve>,
+}
+
+impl Subpath {
+    fn starting_at (point: [f32; 2]) -> Self {
+        Self { points: vec![point], curves: vec![] }
+    }
+
+    // Repeated points are dropped, they would give strokes nowhere to point
+    fn line_to (&mut self, point: [f32; 2]) {
+        if self.points.last() != Some(&point) {
+            self.points.push(point);
+        }
+    }
+
+    // The cubic curve from the last point, flattened
+    fn cubic_to (&mut self, control1: [f32; 2], control2: [f32; 2], end: [f32; 2], segment: PathSegment) {
+        let Some(&first) = self.points.last() else { return; };
+        let start = self.points.len() - 1;
+        for point in flatten_bezier([first, control1, control2, end], 0, FLATTEN_TOLERANCE) {
+            self.line_to(point);
+        }
+        let end = self.points.len() - 1;
+        if end > start {
+            self.curves.push(ShapeCurve { contour: None, start, end, segment });
+        }
+    }
+
+    // Curves around a shape only get back to where they started give or
+    // take rounding, this puts the last point right on the first one
+    fn end_at_start (&mut self) {
+        let Some(&first) = self.points.first() else { return; };
+        if let Some(last) = self.points.last_mut() {
+            *last = first;
+        }
+    }
+
+    // A closed subpath that ends on its first point doesn't repeat it, a
+    // curve that ended there ends on the point before (closing it draws
+    // nothing more). Subpaths of a single point are dropped altogether
+    fn finish (mut self, closed: bool) -> Option<Outline> {
+        if closed && self.points.len() > 1 && self.points.first() == self.points.last() {
+            self.points.pop();
+            let last = self.points.len() - 1;
+            self.curves.iter_mut().for_each(|curve| curve.end = curve.end.min(last));
+            self.curves.retain(|curve| curve.end > curve.start);
+        }
+        (self.points.len() > 1).then_some(Outline { points: self.points, curves: self.curves, closed })
+    }
+}
+
+fn path_outlines (data: &str) -> Result<Vec<Outline>, String> {
+    let mut outlines = vec![];
+    let mut subpath = Subpath::default();
+    for segment in SimplifyingPathParser::from(data) {
+        match segment.map_err(|error| format!("Invalid path data: {}", error))? {
+            SimplePathSegment::MoveTo { x, y } => {
+                outlines.extend(std::mem::replace(&mut subpath, Subpath::starting_at([x as f32, y as f32])).finish(false));
+            }
+            SimplePathSegment::LineTo { x, y } => subpath.line_to([x as f32, y as f32]),
+            SimplePathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+                let (control1, control2, end) = ([x1 as f32, y1 as f32], [x2 as f32, y2 as f32], [x as f32, y as f32]);
+                subpath.cubic_to(control1, control2, end, PathSegment::CubicTo(control1, control2, end));
+            }
+            SimplePathSegment::Quadratic { x1, y1, x, y } => if let Some(&start) = subpath.points.last() {
+                let (control, end) = ([x1 as f32, y1 as f32], [x as f32, y as f32]);
+                let [_, control1, control2, _] = quadratic_to_cubic(start, control, end);
+                subpath.cubic_to(control1, control2, end, PathSegment::QuadTo(control, end));
+            }
+            SimplePathSegment::ClosePath => outlines.extend(std::mem::take(&mut subpath).finish(true)),
+        }
+    }
+    outlines.extend(subpath.finish(false));
+    Ok(outlines)
+}
+
+// Adds a quarter of an ellipse going from angle to angle + PI / 2
+fn add_quarter (subpath: &mut Subpath, [cx, cy]: [f32; 2], [rx, ry]: [f32; 2], angle: f32) {
+    let point = |angle: f32| [cx + rx * angle.cos(), cy + ry * angle.sin()];
+    let tangent = |angle: f32| [-rx * angle.sin() * KAPPA, ry * angle.cos() * KAPPA];
+    let (start, end) = (point(angle), point(angle + FRAC_PI_2));
+    let (start_tangent, end_tangent) = (tangent(angle), tangent(angle + FRAC_PI_2));
+    let control1 = [start[0] + start_tangent[0], start[1] + start_tangent[1]];
+    let control2 = [end[0] - end_tangent[0], end[1] - end_tangent[1]];
+    subpath.cubic_to(control1, control2, end, PathSegment::CubicTo(control1, control2, end));
+}
+
+fn ellipse_outline (center: [f32; 2], radii: [f32; 2]) -> Option<Outline> {
+    let mut subpath = Subpath::starting_at([center[0] + radii[0], center[1]]);
+    for quarter in 0..4 {
+        add_quarter(&mut subpath, center, radii, quarter as f32 * FRAC_PI_2);
+    }
+    subpath.end_at_start();
+    subpath.finish(true)
+}
+
+// Corners are rounded with rx and ry, when only one of them is given it is used for both
+fn rect_outline (node: Node, viewport: [f32; 2]) -> Option<Outline> {
+    let length = |node, name| length(node, name, viewport);
+    let (x, y, width, height) = (length(node, "x"), length(node, "y"), length(node, "width"), length(node, "height"));
+    let (rx, ry) = match (node.has_attribute("rx"), node.has_attribute("ry")) {
+        (true, false) => (length(node, "rx"), length(node, "rx")),
+        (false, true) => (length(node, "ry"), length(node, "ry")),
+        _ => (length(node, "rx"), length(node, "ry"))
+    };
+    let radii = [rx.clamp(0.0, width / 2.0), ry.clamp(0.0, height / 2.0)];
+    if width <= 0.0 || height <= 0.0 {
+        return None;
+    }
+
+    let (left, top, right, bottom) = (x + radii[0], y + radii[1], x + width - radii[0], y + height - radii[1]);
+    let mut subpath = Subpath::starting_at([left, y]);
+    subpath.line_to([right, y]);
+    if radii[0] > 0.0 && radii[1] > 0.0 {
+        add_quarter(&mut subpath, [right, top], radii, -FRAC_PI_2);
+        subpath.line_to([x + width, bottom]);
+        add_quarter(&mut subpath, [right, bottom], radii, 0.0);
+        subpath.line_to([left, y + height]);
+        add_quarter(&mut subpath, [left, bottom], radii, FRAC_PI_2);
+        subpath.line_to([x, top]);
+        add_quarter(&mut subpath, [left, top], radii, PI);
+        subpath.end_at_start();
+    }
+    else {
+        subpath.line_to([x + width, y + height]);
+        subpath.line_to([x, y + height]);
+    }
+    subpath.finish(true)
+}
+
+fn element_outlines (node: Node, viewport: [f32; 2]) -> Result<Vec<Outline>, String> {
+    let length = |node, name| length(node, name, viewport);
+    let points = |closed| {
+        let mut subpath = Subpath::default();
+        PointsParser::from(node.attribute("points").unwrap_or("")).for_each(|(x, y)| subpath.line_to([x as f32, y as f32]));
+        subpath.finish(closed)
+    };
+    let line = |[x1, y1, x2, y2]: [f32; 4]| {
+        let mut subpath = Subpath::starting_at([x1, y1]);
+        subpath.line_to([x2, y2]);
+        subpath.finish(false)
+    };
+    let outlines = match node.tag_name().name() {
+        "path" => return path_outlines(node.attribute("d").unwrap_or("")),
+        "rect" => rect_outline(node, viewport),
+        "circle" => {
+            let radius = length(node, "r");
+            ellipse_outline([length(node, "cx"), length(node, "cy")], [radius, radius])
+        }
+        "ellipse" => ellipse_outline([length(node, "cx"), length(node, "cy")], [length(node, "rx"), length(node, "ry")]),
+        "line" => line([length(node, "x1"), length(node, "y1"), length(node, "x2"), length(node, "y2")]),
+        "polyline" => points(false),
+        "polygon" => points(true),
+        _ => None
+    };
+    Ok(outlines.into_iter().collect())
+}
+
+// One path with the other subpaths as its contours, so the nonzero fill
+// rule of SVG makes holes of them. Paths with open subpaths after the
+// first one are split into a group instead, so those aren't stroked closed
+fn path_geometry (outlines: &[Outline], style: &ShapeStyle) -> ShapeGeometry {
+    let vertices = |outline: &Outline| -> Vec<ShapeVertex> {
+        outline.points.iter().map(|&[x, y]| style.vertex(x, y, 0.0)).collect()
+    };
+    // The curves of the outline, and of the other subpaths as contours
+    let curves = |outlines: &[Outline]| -> Vec<ShapeCurve> {
+        outlines.iter().enumerate().flat_map(|(index, outline)| outline.curves.iter().map(move |curve| ShapeCurve {
+            contour: index.checked_sub(1),
+            ..*curve
+        })).collect()
+    };
+    let path = |outlines: &[Outline]| ShapePath {
+        kind: PShapeKind::ShapePolygon,
+        close: if outlines[0].closed { PShapeClose::ShapeClose } else { PShapeClose::ShapeOpen },
+        vertices: vertices(&outlines[0]),
+        contours: outlines[1..].iter().map(vertices).collect(),
+        curves: curves(outlines),
+        texture: None,
+        texture_wrap: PTextureWrap::default()
+    };
+
+    if outlines[1..].iter().all(|outline| outline.closed) {
+        ShapeGeometry::Path(Some(path(outlines)))
+    }
+    else {
+        ShapeGeometry::Group(outlines.iter()
+            .map(|outline| add_shape(ShapeData::new(ShapeGeometry::Path(Some(path(std::slice::from_ref(outline)))), *style)))
+            .collect())
+    }
+}
+
+fn transform_matrix (value: &str) -> Result<PMatrix, String> {
+    let Transform { a, b, c, d, e, f } = Transform::from_str(value).map_err(|error| format!("Invalid transform \"{}\": {}", value, error))?;
+    Ok([
+        [a as f32, b as f32, 0.0, 0.0],
+        [c as f32, d as f32, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [e as f32, f as f32, 0.0, 1.0],
+    ])
+}
+
+// Where the viewBox of an <svg> goes in its parent, scaled to fit its
+// width and height as preserveAspectRatio says (centered without
+// distortion by default), and the size of its viewport. Nested ones
+// are placed at their x and y. Without a viewBox nothing is scaled
+fn viewport_matrix (node: Node, parent_viewport: [f32; 2]) -> (PMatrix, [f32; 2]) {
+    let view_box = node.attribute("viewBox")
+        .and_then(|value| ViewBox::from_str(value).ok())
+        .filter(|view_box| view_box.w > 0.0 && view_box.h > 0.0);
+    let nested = node.parent_element().is_some();
+    let origin = match nested {
+        true => matrix::translation(length(node, "x", parent_viewport), length(node, "y", parent_viewport), 0.0),
+        false => IDENTITY
+    };
+    // The root's width and height have nothing to be percentages of but its viewBox
+    let parent_viewport = match (nested, view_box) {
+        (false, Some(view_box)) => [view_box.w as f32, view_box.h as f32],
+        _ => parent_viewport
+    };
+    let size = |name, index: usize, fallback| node.attribute(name)
+        .and_then(|value| Length::from_str(value).ok())
+        .map_or(fallback, |length| user_units(length, parent_viewport[index]));
+
+    let Some(view_box) = view_box else {
+        return (origin, [size("width", 0, parent_viewport[0]), size("height", 1, parent_viewport[1])]);
+    };
+    let [x, y, view_width, view_height] = [view_box.x, view_box.y, view_box.w, view_box.h].map(|value| value as f32);
+    let (width, height) = (size("width", 0, view_width), size("height", 1, view_height));
+
+    let ratio = node.attribute("preserveAspectRatio").and_then(|value| AspectRatio::from_str(value).ok()).unwrap_or_default();
+    let (scale_x, scale_y) = (width / view_width, height / view_height);
+    let (scale_x, scale_y) = match (ratio.align, ratio.slice) {
+        (Align::None, _) => (scale_x, scale_y),
+        (_, false) => (scale_x.min(scale_y), scale_x.min(scale_y)),
+        (_, true) => (scale_x.max(scale_y), scale_x.max(scale_y))
+    };
+    // How far along the space left over the viewBox is placed
+    let (align_x, align_y) = match ratio.align {
+        Align::None | Align::XMinYMin => (0.0, 0.0),
+        Align::XMidYMin => (0.5, 0.0),
+        Align::XMaxYMin => (1.0, 0.0),
+        Align::XMinYMid => (0.0, 0.5),
+        Align::XMidYMid => (0.5, 0.5),
+        Align::XMaxYMid => (1.0, 0.5),
+        Align::XMinYMax => (0.0, 1.0),
+        Align::XMidYMax => (0.5, 1.0),
+        Align::XMaxYMax => (1.0, 1.0)
+    };
+    let offset_x = (width - view_width * scale_x) * align_x - x * scale_x;
+    let offset_y = (height - view_height * scale_y) * align_y - y * scale_y;
+    let placed = multiply(&matrix::translation(offset_x, offset_y, 0.0), &matrix::scaling(scale_x, scale_y, 1.0));
+    (multiply(&origin, &placed), [view_width, view_height])
+}
+
+// The shape for an element and its children. Elements that draw
+// nothing (like <defs>, <text> or empty paths) have none
+fn element_shape (node: Node, parent_style: &SvgStyle) -> Result<Option<PShape>, String> {
+    if property(node, "display") == Some("none") {
+        return Ok(None);
+    }
+    let mut style = parent_style.of(node);
+    let viewport = (node.tag_name().name() == "svg").then(|| viewport_matrix(node, parent_style.viewport));
+    if let Some((_, size)) = viewport {
+        style.viewport = size;
+    }
+    let geometry = match node.tag_name().name() {
+        "svg" | "g" | "a" => {
+            let mut children = vec![];
+            for child in node.children().filter(Node::is_element) {
+                children.extend(element_shape(child, &style)?);
+            }
+            ShapeGeometry::Group(children)
+        }
+        _ => {
+            let outlines = element_outlines(node, style.viewport)?;
+            if outlines.is_empty() {
+                return Ok(None);
+            }
+            path_geometry(&outlines, &style.shape_style())
+        }
+    };
+    let matrix = node.attribute("transform").map_or(Ok(IDENTITY), transform_matrix)?;
+    let matrix = viewport.map_or(matrix, |(placed, _)| multiply(&matrix, &placed));
+
+    Ok(Some(add_shape(ShapeData {
+        name: node.attribute("id").map(str::to_string),
+        matrix,
+        ..ShapeData::new(geometry, style.shape_style())
+    })))
+}
+
+// Turns an SVG file into a group with a shape for every element, named
+// after its id. Paths, basic shapes, groups, transforms, fills and
+// strokes are read, text, images, gradients and <use> are not. Lengths
+// are in pixels, and the root's viewBox is scaled to its width and height
+pub(crate) fn load_svg (path: &str) -> PResult<PShape> {
+    let invalid = |message: String| PError::InvalidShape { path: path.to_string(), message };
+    let text = fs::read_to_string(path).map_err(|_| PError::ShapeNotFound(path.to_string()))?;
+    let document = Document::parse(&text).map_err(|error| invalid(error.to_string()))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" {
+        return Err(invalid(format!("The root element is <{}> instead of <svg>.", root.tag_name().name())));
+    }
+
+    // Shapes added for the elements before an invalid one are dropped again
+    let style = SvgStyle::default();
+    let count = get_retained_state().shapes.len();
+    let shape = element_shape(root, &style).map_err(|message| {
+        RETAINED_STATE.try_write().expect("Could not write to RwLock").shapes.truncate(count);
+        invalid(message)
+    })?;
+    Ok(shape.unwrap_or_else(|| add_shape(ShapeData::new(ShapeGeometry::Group(vec![]), style.shape_style()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fixture, lock_state, renderer::format::PCanvasFormat, shape::{retained::child_by_name, tessellate::Tessellator}};
+
+    #[test]
+    fn invalid_files_leave_no_shapes_behind () {
+        let _state = lock_state();
+        let before = get_retained_state().shapes.len();
+        assert!(matches!(load_svg(&fixture("invalid_transform.svg")), Err(PError::InvalidShape { .. })));
+        assert_eq!(get_retained_state().shapes.len(), before);
+    }
+
+    // The paths of the children of a group, in order
+    fn paths (group: PShape) -> Vec<ShapePath> {
+        let state = get_retained_state();
+        let ShapeGeometry::Group(children) = &state.shapes[group.id as usize].geometry else { panic!("Not a group"); };
+        children.iter().map(|child| match &state.shapes[child.id as usize].geometry {
+            ShapeGeometry::Path(Some(path)) => path.clone(),
+            _ => panic!("Not a path")
+        }).collect()
+    }
+
+    fn styles (group: PShape) -> Vec<ShapeStyle> {
+        let state = get_retained_state();
+        let ShapeGeometry::Group(children) = &state.shapes[group.id as usize].geometry else { panic!("Not a group"); };
+        children.iter().map(|child| state.shapes[child.id as usize].style).collect()
+    }
+
+    fn segments (path: &ShapePath) -> Vec<PathSegment> {
+        path.curves.iter().map(|curve| curve.segment).collect()
+    }
+
+    #[test]
+    fn curves_are_kept_for_recordings () {
+        let _state = lock_state();
+        let paths = paths(load_svg(&fixture("curves.svg")).unwrap());
+
+        let closed = &paths[0];
+        assert_eq!(segments(closed), [
+            PathSegment::CubicTo([20.0, 0.0], [80.0, 0.0], [90.0, 10.0]),
+            PathSegment::QuadTo([90.0, 90.0], [10.0, 90.0])
+        ]);
+        // The vertices a curve was flattened into end on its last point
+        let cubic = closed.curves[0];
+        assert_eq!((cubic.start, cubic.contour), (0, None));
+        let end = closed.vertices[cubic.end];
+        assert_eq!([end.x, end.y], [90.0, 10.0]);
+        assert_eq!([closed.vertices[cubic.end + 1].x, closed.vertices[cubic.end + 1].y], [90.0, 50.0]);
+
+        let open = &paths[1];
+        assert_eq!(segments(open), [PathSegment::CubicTo([18.333, 60.0], [35.0, 51.667], [50.0, 60.0])]);
+        assert_eq!(open.curves[0].end, open.vertices.len() - 1);
+    }
+
+    fn root_matrix (shape: PShape) -> PMatrix {
+        get_retained_state().shapes[shape.id as usize].matrix
+    }
+
+    fn corners (path: &ShapePath) -> Vec<[f32; 2]> {
+        path.vertices.iter().map(|vertex| [vertex.x, vertex.y]).collect()
+    }
+
+    fn assert_close (a: [f32; 4], b: [f32; 4]) {
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-3), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn lengths_are_converted_to_pixels () {
+        let _state = lock_state();
+        let shape = load_svg(&fixture("viewbox.svg")).unwrap();
+        let rect = &paths(shape)[0];
+        // Half of the viewBox wide, 1cm high at 96 pixels per inch
+        let height = 96.0 / 2.54;
+        assert_eq!(corners(rect), [[0.0, 0.0], [50.0, 0.0], [50.0, height], [0.0, height]]);
+        assert_eq!(styles(shape)[0].stroke_weight, 4.0);
+    }
+
+    #[test]
+    fn the_view_box_is_fitted_into_the_root () {
+        let _state = lock_state();
+        // 100 x 100 into 192 x 96, scaled by 0.96 and centered
+        let matrix = root_matrix(load_svg(&fixture("viewbox.svg")).unwrap());
+        assert_close(matrix::transform_point(&matrix, [0.0, 0.0, 0.0]), [48.0, 0.0, 0.0, 1.0]);
+        assert_close(matrix::transform_point(&matrix, [100.0, 100.0, 0.0]), [144.0, 96.0, 0.0, 1.0]);
+
+        // Stretched with preserveAspectRatio="none", from its own origin
+        let matrix = root_matrix(load_svg(&fixture("viewbox_stretched.svg")).unwrap());
+        assert_close(matrix::transform_point(&matrix, [-10.0, 0.0, 0.0]), [0.0, 0.0, 0.0, 1.0]);
+        assert_close(matrix::transform_point(&matrix, [90.0, 100.0, 0.0]), [200.0, 50.0, 0.0, 1.0]);
+
+        // The view box matches the size, nothing moves
+        assert_eq!(root_matrix(load_svg(&fixture("curves.svg")).unwrap()), IDENTITY);
+    }
+
+    // The data of the element with id in shapes.svg
+    fn element (id: &str) -> ShapeData {
+        let shape = child_by_name(load_svg(&fixture("shapes.svg")).unwrap(), id).unwrap();
+        let data = &get_retained_state().shapes[shape.id as usize];
+        ShapeData { name: data.name.clone(), geometry: data.geometry.clone(), cache: None, ..*data }
+    }
+
+    fn element_path (id: &str) -> ShapePath {
+        match element(id).geometry {
+            ShapeGeometry::Path(Some(path)) => path,
+            _ => panic!("{} is not a path", id)
+        }
+    }
+
+    #[test]
+    fn elements_are_found_by_id () {
+        let _state = lock_state();
+        let shape = load_svg(&fixture("shapes.svg")).unwrap();
+        assert_eq!(element("inner").name.as_deref(), Some("inner"));
+        assert_eq!(element("hole").name.as_deref(), Some("hole"));
+        assert_eq!(child_by_name(shape, "missing"), Err(PError::UnknownChild("missing".to_string())));
+    }
+
+    #[test]
+    fn nested_groups_keep_their_transforms () {
+        let _state = lock_state();
+        let (outer, inner) = (element("outer").matrix, element("inner").matrix);
+        assert_eq!(outer, matrix::translation(10.0, 20.0, 0.0));
+        assert_eq!(inner, matrix::scaling(2.0, 2.0, 1.0));
+        // Drawn with the inner transform first
+        assert_eq!(matrix::transform_point(&multiply(&outer, &inner), [5.0, 5.0, 0.0]), [20.0, 30.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn styles_are_inherited_and_overridden () {
+        let _state = lock_state();
+        let inherited = element("inherited").style;
+        assert_eq!(inherited.fill, Some(PColor { r: 1.0, g: 0.0, b: 0.0, a: 0.5 }));
+        assert_eq!(inherited.stroke, Some(PColor { r: 0.0, g: 0.0, b: 1.0, a: 0.5 }));
+        assert_eq!(inherited.stroke_weight, 2.0);
+
+        // The style attribute wins over the fill and stroke attributes,
+        // opacity adds up with the group's
+        let styled = element("styled");
+        assert_eq!(styled.style.fill, Some(PColor { r: 0.0, g: 1.0, b: 0.0, a: 0.25 }));
+        assert_eq!(styled.style.stroke, None);
+        let ShapeGeometry::Path(Some(path)) = &styled.geometry else { panic!("Not a path"); };
+        assert_eq!(path.vertices[0].fill, PColor { r: 0.0, g: 1.0, b: 0.0, a: 0.25 });
+    }
+
+    #[test]
+    fn rounded_rects_and_circles_are_curved () {
+        let _state = lock_state();
+        let rounded = element_path("rounded");
+        assert_eq!([rounded.vertices[0].x, rounded.vertices[0].y], [5.0, 0.0]);
+        assert!(rounded.vertices.iter().all(|vertex| (0.0..=40.0).contains(&vertex.x) && (0.0..=20.0).contains(&vertex.y)));
+        // The corners are cut off
+        assert!(!corners(&rounded).contains(&[0.0, 0.0]));
+        assert_eq!(rounded.curves.len(), 4);
+
+        let circle = element_path("circle");
+        assert!(circle.vertices.iter().all(|vertex| ((vertex.x - 50.0).hypot(vertex.y - 50.0) - 10.0).abs() < FLATTEN_TOLERANCE));
+        assert_eq!(circle.close, PShapeClose::ShapeClose);
+        assert_eq!(circle.curves.len(), 4);
+        assert_eq!(circle.curves[3].end, circle.vertices.len() - 1);
+    }
+
+    #[test]
+    fn subpaths_make_holes () {
+        let _state = lock_state();
+        let hole = element_path("hole");
+        assert_eq!(hole.contours.len(), 1);
+        assert_eq!(corners(&hole), [[0.0, 0.0], [30.0, 0.0], [30.0, 30.0], [0.0, 30.0]]);
+
+        let mut tessellator = Tessellator::new(PCanvasFormat::CanvasSrgb, 1.0);
+        let outlines: Vec<&[ShapeVertex]> = [hole.vertices.as_slice()].into_iter().chain(hole.contours.iter().map(Vec::as_slice)).collect();
+        tessellator.fill_polygon(&outlines);
+        let area: f32 = tessellator.geometry.indices.chunks_exact(3).map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| tessellator.geometry.vertices[triangle[i] as usize].position());
+            ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+        }).sum();
+        assert!((area - 800.0).abs() < 1e-3);
+    }
+}
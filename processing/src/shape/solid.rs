@@ -0,0 +1,122 @@
+use std::f32::consts::PI;
+
+use crate::{
+    error::{OrPanic, PResult},
+    renderer::camera::check_3d,
+    shape::{current_style, new_tessellator, push_geometry, state::{get_shape_state, set_shape_state, ShapeStyle, ShapeVertex}, tessellate::Tessellator}
+};
+
+// Faces of a box as quads, going around the same way seen from outside
+const BOX_FACES: [[usize; 4]; 6] = [
+    [0, 1, 2, 3], [5, 4, 7, 6], [4, 0, 3, 7],
+    [1, 5, 6, 2], [4, 5, 1, 0], [3, 2, 6, 7]
+];
+
+// Box centered on the origin. Every face is filled on its own
+// (and gets its own normal), and only the 12 edges are stroked
+pub(crate) fn draw_box (width: f32, height: f32, depth: f32) -> PResult<()> {
+    check_3d()?;
+    let style = current_style();
+    let mut tessellator = new_tessellator(style.stroke_weight);
+    tessellate_box(&mut tessellator, [width, height, depth], &style);
+    push_geometry(&tessellator, None);
+    Ok(())
+}
+
+pub(crate) fn tessellate_box (tessellator: &mut Tessellator, [width, height, depth]: [f32; 3], style: &ShapeStyle) {
+    let (x, y, z) = (width / 2.0, height / 2.0, depth / 2.0);
+    let corners: Vec<ShapeVertex> = [
+        [-x, -y, z], [x, -y, z], [x, y, z], [-x, y, z],
+        [-x, -y, -z], [x, -y, -z], [x, y, -z], [-x, y, -z]
+    ].into_iter().map(|[x, y, z]| style.vertex(x, y, z)).collect();
+
+    if style.fill.is_some() {
+        for face in BOX_FACES {
+            let vertices = face.map(|corner| corners[corner]);
+            tessellator.fill_triangles(&vertices, &[0, 1, 2, 0, 2, 3]);
+        }
+    }
+    if style.stroke.is_some() {
+        tessellator.stroke_path(&corners[0..4], true);
+        tessellator.stroke_path(&corners[4..8], true);
+        for corner in 0..4 {
+            tessellator.stroke_path(&[corners[corner], corners[corner + 4]], false);
+        }
+    }
+}
+
+// Sphere centered on the origin, made of sphereDetail() segments around
+// and from pole to pole. Strokes follow the rings and meridians
+pub(crate) fn draw_sphere (radius: f32) -> PResult<()> {
+    check_3d()?;
+    let style = current_style();
+    let detail = get_shape_state().sphere_detail;
+    let mut tessellator = new_tessellator(style.stroke_weight);
+    tessellate_sphere(&mut tessellator, radius, detail, &style);
+    push_geometry(&tessellator, None);
+    Ok(())
+}
+
+pub(crate) fn tessellate_sphere (tessellator: &mut Tessellator, radius: f32, (around, down): (u32, u32), style: &ShapeStyle) {
+    let (around, down) = (around as usize, down as usize);
+
+    // Rows of around + 1 vertices from the top pole (y = -radius) to the
+    // bottom one, the last vertex of a row is the first one again
+    let vertex = |row: usize, column: usize| {
+        let latitude = PI * row as f32 / down as f32;
+        let longitude = 2.0 * PI * column as f32 / around as f32;
+        let normal = [latitude.sin() * longitude.sin(), -latitude.cos(), latitude.sin() * longitude.cos()];
+        ShapeVertex {
+            normal: Some(normal),
+            ..style.vertex(radius * normal[0], radius * normal[1], radius * normal[2])
+        }
+    };
+    let rows: Vec<Vec<ShapeVertex>> = (0..=down)
+        .map(|row| (0..=around).map(|column| vertex(row, column)).collect())
+        .collect();
+
+    if style.fill.is_some() {
+        let vertices: Vec<ShapeVertex> = rows.concat();
+        let stride = around as u32 + 1;
+        let indices: Vec<u32> = (0..down as u32).flat_map(|row| (0..around as u32).flat_map(move |column| {
+            let top = row * stride + column;
+            let bottom = top + stride;
+            [top, bottom, bottom + 1, top, bottom + 1, top + 1]
+        })).collect();
+        tessellator.fill_triangles(&vertices, &indices);
+    }
+    if style.stroke.is_some() {
+        for row in &rows[1..down] {
+            tessellator.stroke_path(&row[..around], true);
+        }
+        for column in 0..around {
+            let meridian: Vec<ShapeVertex> = rows.iter().map(|row| row[column]).collect();
+            tessellator.stroke_path(&meridian, false);
+        }
+    }
+}
+
+// Processing's box(), box is a keyword in Rust
+#[export_name = "box"]
+pub extern "C" fn box_shape (width: f32, height: f32, depth: f32) {
+    draw_box(width, height, depth).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn sphere (radius: f32) {
+    draw_sphere(radius).or_panic()
+}
+
+// Number of segments spheres are made of around and from pole to pole.
+// 30 by default, at least 3
+#[no_mangle]
+pub extern "C" fn sphereDetail (resolution: u32) {
+    sphereDetailUV(resolution, resolution);
+}
+
+#[no_mangle]
+pub extern "C" fn sphereDetailUV (around: u32, down: u32) {
+    set_shape_state! {
+        sphere_detail = (around.max(3), down.max(3));
+    }
+}
@@ -0,0 +1,219 @@
+use lazy_static::lazy_static;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::{color::PColor, image::PImage, math::matrix::{PMatrix, IDENTITY}, record::state::PathSegment, renderer::texture::PTextureWrap, shape::retained::PShape};
+
+// Kinds of shapes that can be passed to beginShape()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PShapeKind {
+    // Arbitrary polygon, can be concave and have holes
+    #[default]
+    ShapePolygon,
+    ShapePoints,
+    ShapeLines,
+    ShapeTriangles,
+    ShapeTriangleFan,
+    ShapeTriangleStrip,
+    ShapeQuads,
+    ShapeQuadStrip
+}
+
+// Whether endShape() connects the last vertex to the first
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PShapeClose {
+    #[default]
+    ShapeOpen,
+    ShapeClose
+}
+
+// How the u and v given to vertexUV() are measured
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PTextureMode {
+    // In pixels of the texture
+    #[default]
+    TextureImage,
+    // From 0 to 1 across the texture
+    TextureNormal
+}
+
+// A vertex along with the colors that were
+// active when vertex() was called
+#[derive(Debug, Copy, Clone)]
+pub struct ShapeVertex {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub fill: PColor,
+    pub stroke: PColor,
+    // Set with normal(), the face's normal is used otherwise
+    pub normal: Option<[f32; 3]>,
+    // Where the vertex is on the texture, from 0 to 1
+    pub uv: [f32; 2],
+}
+
+// A curve added with bezierVertex(), quadraticVertex() or curveVertex().
+// The vertices from start to end (of the outline, or a contour) were
+// flattened from it, recordings write segment in their place
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeCurve {
+    pub contour: Option<usize>,
+    pub start: usize,
+    pub end: usize,
+    pub segment: PathSegment,
+}
+
+// What endShape() takes from the shape state, drawn right
+// away or kept in a PShape to be drawn with shape()
+#[derive(Debug, Clone)]
+pub struct ShapePath {
+    pub kind: PShapeKind,
+    pub close: PShapeClose,
+    pub vertices: Vec<ShapeVertex>,
+    pub contours: Vec<Vec<ShapeVertex>>,
+    pub curves: Vec<ShapeCurve>,
+    pub texture: Option<PImage>,
+    pub texture_wrap: PTextureWrap,
+}
+
+// Whether a shape is filled and stroked, and how thick its strokes are.
+// The colors of a path are in its vertices, the ones here
+// are given to the vertices of boxes and spheres
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ShapeStyle {
+    pub fill: Option<PColor>,
+    pub stroke: Option<PColor>,
+    pub stroke_weight: f32,
+}
+
+impl ShapeStyle {
+    pub fn vertex (&self, x: f32, y: f32, z: f32) -> ShapeVertex {
+        ShapeVertex {
+            x, y, z,
+            fill: self.fill.unwrap_or_default(),
+            stroke: self.stroke.unwrap_or_default(),
+            normal: None,
+            uv: [0.0, 0.0]
+        }
+    }
+}
+
+pub struct ShapeState {
+    // Some while between beginShape() and endShape()
+    pub kind: Option<PShapeKind>,
+    pub vertices: Vec<ShapeVertex>,
+
+    // Holes added with beginContour()/endContour()
+    pub contours: Vec<Vec<ShapeVertex>>,
+    pub in_contour: bool,
+
+    // Curves the vertices were flattened from
+    pub curves: Vec<ShapeCurve>,
+
+    // Given to vertices added after normal() is called, until the shape ends
+    pub normal: Option<[f32; 3]>,
+
+    // Set with texture() for the current shape only
+    pub texture: Option<PImage>,
+    pub texture_mode: PTextureMode,
+    pub texture_wrap: PTextureWrap,
+
+    pub stroke_weight: f32,
+
+    // Set by PShape_beginShape(), endShape() then
+    // keeps the shape in it instead of drawing it
+    pub recording: Option<PShape>,
+
+    // Points given to curveVertex() so far. Segments are
+    // only added once there are four of them to work with
+    pub curve_vertices: Vec<ShapeVertex>,
+
+    // Number of segments curves are split into,
+    // 0 flattens them adaptively
+    pub bezier_detail: u32,
+    pub curve_detail: u32,
+    pub curve_tightness: f32,
+
+    // Segments around and from pole to pole of spheres
+    pub sphere_detail: (u32, u32),
+
+    // Set with translate(), rotate() and scale(), vertices are
+    // moved with it. pushMatrix() saves it on the stack
+    pub matrix: PMatrix,
+    pub matrix_stack: Vec<PMatrix>,
+}
+
+impl Default for ShapeState {
+    fn default () -> Self {
+        Self {
+            kind: None,
+            vertices: vec![],
+            contours: vec![],
+            in_contour: false,
+            curves: vec![],
+            normal: None,
+            texture: None,
+            texture_mode: PTextureMode::TextureImage,
+            texture_wrap: PTextureWrap::TextureClamp,
+            stroke_weight: 1.0,
+            recording: None,
+            curve_vertices: vec![],
+            bezier_detail: 0,
+            curve_detail: 0,
+            curve_tightness: 0.0,
+            sphere_detail: (30, 30),
+            matrix: IDENTITY,
+            matrix_stack: vec![]
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref SHAPE_STATE: Arc<RwLock<ShapeState>> = Arc::new(RwLock::new(ShapeState::default()));
+}
+
+pub fn get_shape_state () -> RwLockReadGuard<'static, ShapeState> {
+    SHAPE_STATE.try_read().unwrap()
+}
+
+macro_rules! set_shape_state {
+
+    // base cases
+    ($var:ident$(.$var2:ident)* = $value:expr;) => {
+        {
+            crate::shape::state::SHAPE_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)* = $value;
+        }
+    };
+    ($var:ident$(.$var2:ident)*($($value:expr),*);) => {
+        {
+            crate::shape::state::SHAPE_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)*($($value),*);
+        }
+    };
+
+    // expr - expr
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_shape_state!{ $var$(.$var2)* = $value; };
+        set_shape_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+
+    // fn - expr
+    ($var:ident$(.$var2:ident)*($($value:expr),*); $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_shape_state!{ $var$(.$var2)*($($value),*); };
+        set_shape_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+
+    // expr - fn
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)*($($value2:expr),*);)*) => {
+        set_shape_state!{ $var$(.$var2)* = $value; };
+        set_shape_state!{ $($var3$(.$var4)*($($value2),*);)* };
+    };  
+
+    // fn - fn
+    ($var:ident$(.$var2:ident)*($($value:expr),*); $($var3:ident$(.$var4:ident)*($($value2:expr),*);)*) => {
+        set_shape_state!{ $var$(.$var2)*($($value),*); };
+        set_shape_state!{ $($var3$(.$var4)*($($value2),*);)* };
+    };  
+}
+pub(crate) use set_shape_state;
\ No newline at end of file
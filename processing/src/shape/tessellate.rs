@@ -0,0 +1,391 @@
+use lyon::{
+    math::point,
+    path::Path,
+    tessellation::{
+        BuffersBuilder, FillOptions, FillRule, FillTessellator, FillVertex, LineCap, LineJoin,
+        Side, StrokeOptions, StrokeTessellator, StrokeVertex, VertexBuffers
+    }
+};
+
+use std::borrow::Cow;
+
+use crate::{
+    color::PColor,
+    math::matrix::{normal_matrix, normalize, transform_point, PMatrix},
+    renderer::{format::PCanvasFormat, vertex::Vertex},
+    shape::state::ShapeVertex
+};
+
+// Strokes are moved this much closer to the camera than
+// the fills they outline, so they aren't hidden by them
+const STROKE_DEPTH_BIAS: f32 = 0.00001;
+
+// Turns shapes into triangles that can be pushed to the draw list.
+// Colors and z are passed to lyon as custom attributes, so they
+// get interpolated at any new vertices it has to create
+pub struct Tessellator {
+    pub geometry: VertexBuffers<Vertex, u32>,
+    // Strokes and points, when they are drawn apart from the fills: in 3D,
+    // where they are already projected onto the canvas and drawn with
+    // the screen projection (see Projection), and when fills are textured
+    pub stroke_geometry: VertexBuffers<Vertex, u32>,
+    format: PCanvasFormat,
+    stroke_weight: f32,
+    // Size of a canvas pixel in points when strokes
+    // are smoothed in the shader instead of by MSAA
+    feather: Option<f32>,
+    // The camera and the canvas size in points, in 3D
+    screen: Option<(PMatrix, f32, f32)>,
+    // Moves vertices before anything else, with the matrix for their normals
+    model: Option<(PMatrix, PMatrix)>,
+    textured: bool,
+}
+
+// Attributes are the color, then the position, normal and uv of the vertex
+fn vertex_from_attributes (x: f32, y: f32, attributes: &[f32]) -> Vertex {
+    Vertex::new(x, y, attributes[6])
+        .with_color([attributes[0], attributes[1], attributes[2], attributes[3]])
+        .with_normal([attributes[7], attributes[8], attributes[9]])
+        .with_uv(attributes[10], attributes[11])
+}
+
+// Normal of a polygon with Newell's method, which works
+// for concave polygons and ones that aren't quite flat
+fn face_normal (vertices: &[ShapeVertex]) -> [f32; 3] {
+    let mut normal = [0.0f32; 3];
+    for (i, current) in vertices.iter().enumerate() {
+        let next = &vertices[(i + 1) % vertices.len()];
+        normal[0] += (current.y - next.y) * (current.z + next.z);
+        normal[1] += (current.z - next.z) * (current.x + next.x);
+        normal[2] += (current.x - next.x) * (current.y + next.y);
+    }
+    let length = normal.iter().map(|n| n * n).sum::<f32>().sqrt();
+    if length == 0.0 { [0.0, 0.0, 1.0] } else { normal.map(|n| n / length) }
+}
+
+// Which two coordinates to tessellate a polygon in. A polygon in 3D
+// is flattened onto the axis plane it faces the most. In 2D that is always x and y
+fn polygon_plane (normal: [f32; 3]) -> fn(&ShapeVertex) -> (f32, f32) {
+    let [x, y, z] = normal.map(f32::abs);
+    if z >= x && z >= y { |vertex| (vertex.x, vertex.y) }
+    else if x >= y { |vertex| (vertex.y, vertex.z) }
+    else { |vertex| (vertex.z, vertex.x) }
+}
+
+impl Tessellator {
+    pub fn new (format: PCanvasFormat, stroke_weight: f32) -> Self {
+        Self {
+            geometry: VertexBuffers::new(),
+            stroke_geometry: VertexBuffers::new(),
+            format,
+            stroke_weight,
+            feather: None,
+            screen: None,
+            model: None,
+            textured: false
+        }
+    }
+
+    // Keeps strokes out of the fills' geometry, which is drawn with a texture.
+    // Like in Processing, the texture replaces the fill color instead of being tinted by it
+    pub fn with_textured_fills (mut self) -> Self {
+        self.textured = true;
+        self
+    }
+
+    // Whether strokes are projected onto the canvas already, see with_screen_projection()
+    pub fn projects_strokes (&self) -> bool {
+        self.screen.is_some()
+    }
+
+    fn stroke_target (&mut self) -> &mut VertexBuffers<Vertex, u32> {
+        if self.screen.is_some() || self.textured { &mut self.stroke_geometry } else { &mut self.geometry }
+    }
+
+    // Strokes keep their width on the canvas in 3D, they are projected
+    // with matrix first and then outlined like in 2D
+    pub fn with_screen_projection (mut self, matrix: PMatrix, width: f32, height: f32) -> Self {
+        self.screen = Some((matrix, width, height));
+        self
+    }
+
+    // Vertices are moved with matrix (set with translate(), rotate(), etc)
+    // before they are filled, outlined or projected
+    pub fn with_model (mut self, matrix: PMatrix) -> Self {
+        self.model = Some((matrix, normal_matrix(&matrix)));
+        self
+    }
+
+    fn transform<'a> (&self, vertices: &'a [ShapeVertex]) -> Cow<'a, [ShapeVertex]> {
+        let Some((model, normals)) = self.model else { return Cow::Borrowed(vertices); };
+        vertices.iter().map(|vertex| {
+            let [x, y, z, _] = transform_point(&model, [vertex.x, vertex.y, vertex.z]);
+            let normal = vertex.normal.map(|normal| {
+                let [x, y, z, _] = transform_point(&normals, normal);
+                normalize([x, y, z])
+            });
+            ShapeVertex { x, y, z, normal, ..*vertex }
+        }).collect()
+    }
+
+    // Where vertices end up on the canvas in 3D, with z as their depth.
+    // None if one of them is behind the camera
+    fn project<'a> (&self, vertices: &'a [ShapeVertex]) -> Option<Cow<'a, [ShapeVertex]>> {
+        let Some((matrix, width, height)) = self.screen else { return Some(Cow::Borrowed(vertices)); };
+        vertices.iter().map(|vertex| {
+            let [x, y, z, w] = transform_point(&matrix, [vertex.x, vertex.y, vertex.z]);
+            (w > 0.0).then(|| ShapeVertex {
+                x: (x / w + 1.0) / 2.0 * width,
+                y: (1.0 - y / w) / 2.0 * height,
+                z: z / w - STROKE_DEPTH_BIAS,
+                ..*vertex
+            })
+        }).collect()
+    }
+
+    // Smooths stroke edges over one pixel (given in points) in the shader.
+    // Used when the canvas can't be multisampled
+    pub fn with_analytic_aa (mut self, pixel_size: f32) -> Self {
+        self.feather = Some(pixel_size);
+        self
+    }
+
+    fn build_path (
+        &self,
+        contours: &[&[ShapeVertex]],
+        closed: bool,
+        plane: fn(&ShapeVertex) -> (f32, f32),
+        normal: [f32; 3],
+        color: impl Fn(&ShapeVertex) -> PColor
+    ) -> Path {
+        let at = |vertex: &ShapeVertex| {
+            let (x, y) = plane(vertex);
+            point(x, y)
+        };
+        let attributes = |vertex: &ShapeVertex| {
+            let [r, g, b, a] = color(vertex).to_shader_color(self.format);
+            let [nx, ny, nz] = vertex.normal.unwrap_or(normal);
+            let [u, v] = vertex.uv;
+            [r, g, b, a, vertex.x, vertex.y, vertex.z, nx, ny, nz, u, v]
+        };
+        let mut builder = Path::builder_with_attributes(12);
+        for contour in contours {
+            let Some((first, rest)) = contour.split_first() else { continue; };
+
+            builder.begin(at(first), &attributes(first));
+            for vertex in rest {
+                builder.line_to(at(vertex), &attributes(vertex));
+            }
+            builder.end(closed);
+        }
+        builder.build()
+    }
+
+    // Fills the area enclosed by the outline (the first contour), minus any holes.
+    // Uses the non-zero rule like Processing, so holes have to
+    // wind the opposite way of the outline
+    pub fn fill_polygon (&mut self, contours: &[&[ShapeVertex]]) {
+        let contours: Vec<Cow<[ShapeVertex]>> = contours.iter().map(|contour| self.transform(contour)).collect();
+        let contours: Vec<&[ShapeVertex]> = contours.iter().map(|contour| contour.as_ref()).collect();
+        let Some(outline) = contours.first() else { return; };
+        let normal = face_normal(outline);
+        let textured = self.textured;
+        let path = self.build_path(&contours, true, polygon_plane(normal), normal, |vertex| if textured { PColor::WHITE } else { vertex.fill });
+
+        // Degenerate polygons (all points on a line, etc) fail
+        // to tessellate, there is nothing to draw for them anyway
+        let _ = FillTessellator::new().tessellate_path(
+            &path,
+            &FillOptions::default().with_fill_rule(FillRule::NonZero),
+            &mut BuffersBuilder::new(&mut self.geometry, |mut vertex: FillVertex| {
+                let attributes = vertex.interpolated_attributes();
+                vertex_from_attributes(attributes[4], attributes[5], attributes)
+            })
+        );
+    }
+
+    // Outlines a line strip with the current stroke weight
+    pub fn stroke_path (&mut self, vertices: &[ShapeVertex], closed: bool) {
+        if vertices.len() < 2 { return; }
+
+        let vertices = self.transform(vertices);
+        let Some(vertices) = self.project(&vertices) else { return; };
+        let path = self.build_path(&[&vertices], closed, |vertex| (vertex.x, vertex.y), [0.0, 0.0, 1.0], |vertex| vertex.stroke);
+
+        // With analytic AA the outline is made a pixel wider, and the
+        // shader fades it out over that pixel. Strokes thinner than a
+        // pixel are drawn a pixel wide and made more transparent instead
+        let (line_width, feather_ratio, alpha_scale) = match self.feather {
+            Some(feather) => {
+                let line_width = self.stroke_weight.max(feather) + feather;
+                (line_width, 2.0 * feather / line_width, (self.stroke_weight / feather).min(1.0))
+            }
+            None => (self.stroke_weight, 0.0, 1.0)
+        };
+        let smoothed = self.feather.is_some();
+        let geometry = self.stroke_target();
+
+        let options =
+            StrokeOptions::default()
+            .with_line_width(line_width)
+            .with_line_join(LineJoin::Miter)
+            .with_line_cap(LineCap::Round);
+
+        let _ = StrokeTessellator::new().tessellate_path(
+            &path,
+            &options,
+            &mut BuffersBuilder::new(geometry, |mut vertex: StrokeVertex| {
+                let position = vertex.position();
+                let side = vertex.side();
+                let attributes = vertex.interpolated_attributes();
+                let stroke = vertex_from_attributes(position.x, position.y, attributes);
+
+                if smoothed {
+                    let across = if side == Side::Negative { -1.0 } else { 1.0 };
+                    stroke
+                        .with_color([attributes[0], attributes[1], attributes[2], attributes[3] * alpha_scale])
+                        .with_edge(across, feather_ratio)
+                }
+                else { stroke }
+            })
+        );
+    }
+
+    // Triangles that don't need tessellating. Indices are relative to vertices.
+    // Vertices without a normal get the one of the polygon they make up
+    pub fn fill_triangles (&mut self, vertices: &[ShapeVertex], indices: &[u32]) {
+        let vertices = self.transform(vertices);
+        let offset = self.geometry.vertices.len() as u32;
+        let normal = if vertices.iter().any(|vertex| vertex.normal.is_none()) { face_normal(&vertices) } else { [0.0, 0.0, 1.0] };
+        self.geometry.vertices.extend(vertices.iter().map(|vertex| {
+            Vertex::new(vertex.x, vertex.y, vertex.z)
+                .with_color(if self.textured { PColor::WHITE } else { vertex.fill }.to_shader_color(self.format))
+                .with_normal(vertex.normal.unwrap_or(normal))
+                .with_uv(vertex.uv[0], vertex.uv[1])
+        }));
+        self.geometry.indices.extend(indices.iter().map(|index| index + offset));
+    }
+
+    // Points are circles with the stroke weight as their diameter
+    pub fn point (&mut self, vertex: &ShapeVertex) {
+        let color = vertex.stroke.to_shader_color(self.format);
+        let transformed = self.transform(std::slice::from_ref(vertex));
+        let Some(projected) = self.project(&transformed) else { return; };
+        let vertex = projected[0];
+        let radius = self.stroke_weight / 2.0;
+        let geometry = self.stroke_target();
+
+        let _ = FillTessellator::new().tessellate_circle(
+            point(vertex.x, vertex.y),
+            radius,
+            &FillOptions::default(),
+            &mut BuffersBuilder::new(geometry, |fill_vertex: FillVertex| {
+                let position = fill_vertex.position();
+                Vertex::new(position.x, position.y, vertex.z).with_color(color)
+            })
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+    use crate::math::matrix::{multiply, rotation_y, translation};
+
+    const FORMAT: PCanvasFormat = PCanvasFormat::CanvasSrgb;
+
+    fn vertex (x: f32, y: f32, fill: PColor) -> ShapeVertex {
+        ShapeVertex { x, y, z: 0.0, fill, stroke: PColor::BLACK, normal: None, uv: [0.0, 0.0] }
+    }
+
+    fn square (size: f32, offset: f32, clockwise: bool) -> Vec<ShapeVertex> {
+        let mut corners = vec![
+            vertex(offset, offset, PColor::WHITE),
+            vertex(offset + size, offset, PColor::WHITE),
+            vertex(offset + size, offset + size, PColor::WHITE),
+            vertex(offset, offset + size, PColor::WHITE)
+        ];
+        if !clockwise { corners.reverse(); }
+        corners
+    }
+
+    // Area covered by the triangles of the geometry
+    fn area (geometry: &VertexBuffers<Vertex, u32>) -> f32 {
+        geometry.indices.chunks_exact(3).map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|i| geometry.vertices[triangle[i] as usize].position());
+            ((b[0] - a[0]) * (c[1] - a[1]) - (c[0] - a[0]) * (b[1] - a[1])).abs() / 2.0
+        }).sum()
+    }
+
+    #[test]
+    fn polygons_are_filled () {
+        let mut tessellator = Tessellator::new(FORMAT, 1.0);
+        tessellator.fill_polygon(&[&square(10.0, 0.0, true)]);
+        assert!((area(&tessellator.geometry) - 100.0).abs() < 1e-3);
+        assert!(tessellator.stroke_geometry.vertices.is_empty());
+    }
+
+    #[test]
+    fn contours_winding_the_other_way_are_holes () {
+        let mut tessellator = Tessellator::new(FORMAT, 1.0);
+        tessellator.fill_polygon(&[&square(10.0, 0.0, true), &square(4.0, 3.0, false)]);
+        assert!((area(&tessellator.geometry) - 84.0).abs() < 1e-3);
+
+        // The same way round they add up with the non-zero rule
+        let mut tessellator = Tessellator::new(FORMAT, 1.0);
+        tessellator.fill_polygon(&[&square(10.0, 0.0, true), &square(4.0, 3.0, true)]);
+        assert!((area(&tessellator.geometry) - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn fills_keep_the_colors_of_their_vertices () {
+        let (red, blue) = (PColor { r: 1.0, g: 0.0, b: 0.0, a: 1.0 }, PColor { r: 0.0, g: 0.0, b: 1.0, a: 1.0 });
+        let triangle = [vertex(0.0, 0.0, red), vertex(10.0, 0.0, blue), vertex(0.0, 10.0, red)];
+
+        let mut tessellator = Tessellator::new(FORMAT, 1.0);
+        tessellator.fill_triangles(&triangle, &[0, 1, 2]);
+        let colors: Vec<_> = tessellator.geometry.vertices.iter().map(Vertex::color).collect();
+        assert_eq!(colors, [red, blue, red].map(|color| color.to_shader_color(FORMAT)));
+
+        let mut tessellator = Tessellator::new(FORMAT, 1.0);
+        tessellator.fill_polygon(&[&triangle]);
+        for vertex in &tessellator.geometry.vertices {
+            let expected = if vertex.position()[0] == 10.0 { blue } else { red };
+            assert_eq!(vertex.color(), expected.to_shader_color(FORMAT));
+        }
+
+        // Textured fills are white, the texture gives them their color
+        let mut tessellator = Tessellator::new(FORMAT, 1.0).with_textured_fills();
+        tessellator.fill_triangles(&triangle, &[0, 1, 2]);
+        assert!(tessellator.geometry.vertices.iter().all(|vertex| vertex.color() == PColor::WHITE.to_shader_color(FORMAT)));
+    }
+
+    #[test]
+    fn strokes_go_apart_from_textured_fills () {
+        let mut tessellator = Tessellator::new(FORMAT, 2.0);
+        tessellator.stroke_path(&square(10.0, 0.0, true), true);
+        assert!(!tessellator.geometry.indices.is_empty());
+        assert!(tessellator.stroke_geometry.indices.is_empty());
+
+        let mut tessellator = Tessellator::new(FORMAT, 2.0).with_textured_fills();
+        tessellator.stroke_path(&square(10.0, 0.0, true), true);
+        assert!(tessellator.geometry.indices.is_empty());
+        // A closed outline 2 wide around a square 10 wide
+        assert!((area(&tessellator.stroke_geometry) - (12.0 * 12.0 - 8.0 * 8.0)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn vertices_and_normals_are_moved_with_the_model () {
+        let model = multiply(&translation(0.0, 0.0, 5.0), &rotation_y(FRAC_PI_2));
+        let mut tessellator = Tessellator::new(FORMAT, 1.0).with_model(model);
+        let triangle = [vertex(0.0, 0.0, PColor::WHITE), vertex(10.0, 0.0, PColor::WHITE), vertex(0.0, 10.0, PColor::WHITE)];
+        tessellator.fill_triangles(&triangle, &[0, 1, 2]);
+
+        // Turned to face along x, around the y axis
+        let [x, y, z] = tessellator.geometry.vertices[1].position();
+        assert!((x - 0.0).abs() < 1e-4 && y == 0.0 && (z - -5.0).abs() < 1e-4, "{:?}", [x, y, z]);
+        let [nx, ny, nz] = tessellator.geometry.vertices[0].normal();
+        assert!((nx.abs() - 1.0).abs() < 1e-4 && ny.abs() < 1e-4 && nz.abs() < 1e-4);
+    }
+}
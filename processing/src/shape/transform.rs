@@ -0,0 +1,146 @@
+use crate::{
+    error::{OrPanic, PError, PResult},
+    math::matrix::{self, multiply, PMatrix, IDENTITY},
+    shape::state::{get_shape_state, SHAPE_STATE}
+};
+
+// Like in Processing, pushMatrix() can only be nested this deep
+pub(crate) const MAX_MATRIX_STACK: usize = 32;
+
+// The matrix everything drawn is moved with
+pub(crate) fn model_matrix () -> PMatrix {
+    get_shape_state().matrix
+}
+
+// Applies transform before the current matrix, so the
+// transform called last is the first to move vertices
+pub(crate) fn apply_matrix (transform: PMatrix) {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    state.matrix = multiply(&state.matrix, &transform);
+}
+
+pub(crate) fn push_matrix () -> PResult<()> {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    if state.matrix_stack.len() >= MAX_MATRIX_STACK {
+        return Err(PError::MatrixStackFull);
+    }
+    let matrix = state.matrix;
+    state.matrix_stack.push(matrix);
+    Ok(())
+}
+
+pub(crate) fn pop_matrix () -> PResult<()> {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    state.matrix = state.matrix_stack.pop().ok_or(PError::MatrixStackEmpty)?;
+    Ok(())
+}
+
+// Back to no transform. The stack is left alone, like in Processing
+pub(crate) fn reset_matrix () {
+    SHAPE_STATE.try_write().expect("Could not write to RwLock").matrix = IDENTITY;
+}
+
+// No transform and an empty stack, at the start of every
+// frame and whenever drawing into a graphics starts
+pub(crate) fn reset_transforms () {
+    let mut state = SHAPE_STATE.try_write().expect("Could not write to RwLock");
+    state.matrix = IDENTITY;
+    state.matrix_stack.clear();
+}
+
+#[no_mangle]
+pub extern "C" fn translate (x: f32, y: f32) {
+    apply_matrix(matrix::translation(x, y, 0.0));
+}
+
+#[no_mangle]
+pub extern "C" fn translate3D (x: f32, y: f32, z: f32) {
+    apply_matrix(matrix::translation(x, y, z));
+}
+
+// Angles are in radians. rotate() turns around the z axis, like in 2D
+#[no_mangle]
+pub extern "C" fn rotate (angle: f32) {
+    apply_matrix(matrix::rotation_z(angle));
+}
+
+#[no_mangle]
+pub extern "C" fn rotateX (angle: f32) {
+    apply_matrix(matrix::rotation_x(angle));
+}
+
+#[no_mangle]
+pub extern "C" fn rotateY (angle: f32) {
+    apply_matrix(matrix::rotation_y(angle));
+}
+
+#[no_mangle]
+pub extern "C" fn rotateZ (angle: f32) {
+    apply_matrix(matrix::rotation_z(angle));
+}
+
+#[no_mangle]
+pub extern "C" fn scale (x: f32, y: f32) {
+    apply_matrix(matrix::scaling(x, y, 1.0));
+}
+
+#[no_mangle]
+pub extern "C" fn scale3D (x: f32, y: f32, z: f32) {
+    apply_matrix(matrix::scaling(x, y, z));
+}
+
+#[no_mangle]
+pub extern "C" fn pushMatrix () {
+    push_matrix().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn popMatrix () {
+    pop_matrix().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn resetMatrix () {
+    reset_matrix();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lock_state, math::matrix::transform_point};
+
+    #[test]
+    fn transforms_apply_in_reverse_order () {
+        let _state = lock_state();
+        reset_transforms();
+
+        // Scaled first, then moved
+        apply_matrix(matrix::translation(10.0, 0.0, 0.0));
+        apply_matrix(matrix::scaling(2.0, 2.0, 2.0));
+        assert_eq!(transform_point(&model_matrix(), [1.0, 1.0, 0.0]), [12.0, 2.0, 0.0, 1.0]);
+
+        reset_matrix();
+        assert_eq!(model_matrix(), IDENTITY);
+    }
+
+    #[test]
+    fn pushed_matrices_are_popped () {
+        let _state = lock_state();
+        reset_transforms();
+
+        apply_matrix(matrix::translation(5.0, 0.0, 0.0));
+        push_matrix().unwrap();
+        apply_matrix(matrix::rotation_z(1.0));
+        pop_matrix().unwrap();
+        assert_eq!(model_matrix(), matrix::translation(5.0, 0.0, 0.0));
+        assert_eq!(pop_matrix(), Err(PError::MatrixStackEmpty));
+
+        for _ in 0..MAX_MATRIX_STACK {
+            push_matrix().unwrap();
+        }
+        assert_eq!(push_matrix(), Err(PError::MatrixStackFull));
+
+        reset_transforms();
+        assert!(get_shape_state().matrix_stack.is_empty());
+    }
+}
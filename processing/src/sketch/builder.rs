@@ -0,0 +1,95 @@
+use crate::error::PResult;
+
+use super::{ctx::Ctx, Sketch};
+
+type Callback = Box<dyn FnMut(&mut Ctx) -> PResult<()>>;
+
+// Sketch made out of closures, for when defining a
+// struct and implementing Sketch for it is overkill.
+// Closures can capture (and mutate) any state they need
+#[derive(Default)]
+pub struct SketchBuilder {
+    setup: Option<Callback>,
+    draw: Option<Callback>,
+    mouse_pressed: Option<Callback>,
+    mouse_released: Option<Callback>,
+    mouse_moved: Option<Callback>,
+    key_pressed: Option<Callback>,
+    key_released: Option<Callback>,
+    key_typed: Option<Callback>,
+    window_resized: Option<Callback>,
+}
+
+impl SketchBuilder {
+    pub fn new () -> Self {
+        Self::default()
+    }
+
+    pub fn with_setup (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.setup = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_draw (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.draw = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_mouse_pressed (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.mouse_pressed = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_mouse_released (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.mouse_released = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_mouse_moved (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.mouse_moved = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_key_pressed (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.key_pressed = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_key_released (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.key_released = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_key_typed (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.key_typed = Some(Box::new(callback));
+        self
+    }
+
+    pub fn with_window_resized (mut self, callback: impl FnMut(&mut Ctx) -> PResult<()> + 'static) -> Self {
+        self.window_resized = Some(Box::new(callback));
+        self
+    }
+
+    pub fn run (self) {
+        super::run(self)
+    }
+}
+
+fn call (callback: &mut Option<Callback>, ctx: &mut Ctx) -> PResult<()> {
+    match callback {
+        Some(callback) => callback(ctx),
+        None => Ok(())
+    }
+}
+
+impl Sketch for SketchBuilder {
+    fn setup (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.setup, ctx) }
+    fn draw (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.draw, ctx) }
+    fn mouse_pressed (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.mouse_pressed, ctx) }
+    fn mouse_released (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.mouse_released, ctx) }
+    fn mouse_moved (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.mouse_moved, ctx) }
+    fn key_pressed (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.key_pressed, ctx) }
+    fn key_released (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.key_released, ctx) }
+    fn key_typed (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.key_typed, ctx) }
+    fn window_resized (&mut self, ctx: &mut Ctx) -> PResult<()> { call(&mut self.window_resized, ctx) }
+}
@@ -0,0 +1,689 @@
+use std::sync::Arc;
+
+use crate::{
+    color::{self, blend::PBlendMode, PColor},
+    core::window::{self, PCursor},
+    error::{PError, PResult},
+    event::state::{get_event_state, PMouseButton},
+    light::{self, state::PLightKind},
+    image::{self, create, edit::{self, PRegion}, filter::{self, PFilter}, graphics::{self, PGraphics}, pixels, state::IMAGE_STATE, PImage, PImageFormat},
+    renderer::{camera::{self, PRenderer}, format::PCanvasFormat, state::get_renderer_state, texture::PTextureWrap},
+    record::{self, PRecordFormat},
+    shader::{self, PShader},
+    math::{matrix, vector::PVector},
+    shape::{self, curve, retained::{self, PShape, PShapePrimitive}, solid, state::{PShapeClose, PShapeKind, PTextureMode}, transform},
+    text::{self, PAlignX, PAlignY, PFont}
+};
+
+// Handle passed to every Sketch callback.
+// All methods go through the same renderer and event state
+// as the C functions, so both APIs always agree with each other
+pub struct Ctx {
+    _private: ()
+}
+
+impl Ctx {
+    pub(crate) fn new () -> Self {
+        Self { _private: () }
+    }
+
+    pub fn create_window (&mut self, width: f32, height: f32) -> PResult<()> {
+        window::create_window(width, height)
+    }
+
+    // Like create_window(), with P3D to draw in 3D
+    pub fn create_window_with_renderer (&mut self, width: f32, height: f32, renderer: PRenderer) -> PResult<()> {
+        self.create_window(width, height)?;
+        camera::set_renderer(renderer);
+        Ok(())
+    }
+
+    pub fn width (&self) -> PResult<f32> {
+        get_renderer_state().width.ok_or(PError::WindowNotCreated)
+    }
+
+    pub fn height (&self) -> PResult<f32> {
+        get_renderer_state().height.ok_or(PError::WindowNotCreated)
+    }
+
+    pub fn window_title (&mut self, title: impl Into<String>) {
+        window::set_window_title(title.into());
+    }
+
+    pub fn window_resizable (&mut self, resizable: bool) {
+        window::windowResizable(resizable);
+    }
+
+    pub fn window_move (&mut self, x: i32, y: i32) {
+        window::windowMove(x, y);
+    }
+
+    // Only has an effect in setup(), see fullScreen()
+    pub fn full_screen (&mut self, display: u32) {
+        window::fullScreen(display);
+    }
+
+    pub fn pixel_density (&mut self, density: u32) -> PResult<()> {
+        window::set_pixel_density(density)
+    }
+
+    // Only has an effect in setup(), see smooth()
+    pub fn smooth (&mut self, level: u32) -> PResult<()> {
+        window::set_smooth(level)
+    }
+
+    pub fn no_smooth (&mut self) {
+        window::noSmooth();
+    }
+
+    // Only has an effect in setup(), see canvasFormat()
+    pub fn canvas_format (&mut self, format: PCanvasFormat) {
+        window::canvasFormat(format);
+    }
+
+    pub fn display_density (&self) -> f32 {
+        window::displayDensity()
+    }
+
+    pub fn cursor (&mut self, cursor: PCursor) {
+        window::cursor(cursor);
+    }
+
+    pub fn no_cursor (&mut self) {
+        window::noCursor();
+    }
+
+    // Color components go from 0 to 255
+    pub fn fill (&mut self, r: f32, g: f32, b: f32, a: f32) {
+        color::fill(r, g, b, a);
+    }
+
+    pub fn no_fill (&mut self) {
+        color::noFill();
+    }
+
+    pub fn stroke (&mut self, r: f32, g: f32, b: f32, a: f32) {
+        color::stroke(r, g, b, a);
+    }
+
+    pub fn no_stroke (&mut self) {
+        color::noStroke();
+    }
+
+    pub fn stroke_weight (&mut self, weight: f32) {
+        shape::strokeWeight(weight);
+    }
+
+    pub fn blend_mode (&mut self, mode: PBlendMode) {
+        color::blendMode(mode);
+    }
+
+    pub fn background (&mut self, r: f32, g: f32, b: f32, a: f32) {
+        color::background(r, g, b, a);
+    }
+
+    pub fn begin_shape (&mut self, kind: PShapeKind) -> PResult<()> {
+        shape::begin_shape(kind)
+    }
+
+    pub fn vertex (&mut self, x: f32, y: f32) -> PResult<()> {
+        shape::add_vertex(x, y, 0.0)
+    }
+
+    pub fn vertex_3d (&mut self, x: f32, y: f32, z: f32) -> PResult<()> {
+        shape::add_vertex(x, y, z)
+    }
+
+    pub fn normal (&mut self, nx: f32, ny: f32, nz: f32) {
+        shape::normal(nx, ny, nz);
+    }
+
+    pub fn vertex_uv (&mut self, x: f32, y: f32, u: f32, v: f32) -> PResult<()> {
+        shape::add_vertex_uv(x, y, 0.0, u, v)
+    }
+
+    pub fn vertex_3d_uv (&mut self, x: f32, y: f32, z: f32, u: f32, v: f32) -> PResult<()> {
+        shape::add_vertex_uv(x, y, z, u, v)
+    }
+
+    pub fn texture (&mut self, image: PImage) -> PResult<()> {
+        shape::set_texture(image)
+    }
+
+    pub fn texture_mode (&mut self, mode: PTextureMode) {
+        shape::textureMode(mode);
+    }
+
+    pub fn texture_wrap (&mut self, wrap: PTextureWrap) {
+        shape::textureWrap(wrap);
+    }
+
+    pub fn end_shape (&mut self, close: PShapeClose) -> PResult<()> {
+        shape::end_shape(close)
+    }
+
+    pub fn begin_contour (&mut self) -> PResult<()> {
+        shape::begin_contour()
+    }
+
+    pub fn end_contour (&mut self) -> PResult<()> {
+        shape::end_contour()
+    }
+
+    // Points are the start, two control points and the end
+    pub fn bezier (&mut self, points: [[f32; 2]; 4]) -> PResult<()> {
+        curve::draw_bezier(points)
+    }
+
+    // Draws between the middle two points, the outer ones shape the curve
+    pub fn curve (&mut self, points: [[f32; 2]; 4]) -> PResult<()> {
+        curve::draw_curve(points)
+    }
+
+    pub fn bezier_vertex (&mut self, cx1: f32, cy1: f32, cx2: f32, cy2: f32, x: f32, y: f32) -> PResult<()> {
+        curve::bezier_vertex(cx1, cy1, cx2, cy2, x, y)
+    }
+
+    pub fn quadratic_vertex (&mut self, cx: f32, cy: f32, x: f32, y: f32) -> PResult<()> {
+        curve::quadratic_vertex(cx, cy, x, y)
+    }
+
+    pub fn curve_vertex (&mut self, x: f32, y: f32) -> PResult<()> {
+        curve::curve_vertex(x, y)
+    }
+
+    pub fn bezier_detail (&mut self, detail: u32) {
+        curve::bezierDetail(detail);
+    }
+
+    pub fn curve_detail (&mut self, detail: u32) {
+        curve::curveDetail(detail);
+    }
+
+    pub fn curve_tightness (&mut self, tightness: f32) {
+        curve::curveTightness(tightness);
+    }
+
+    pub fn camera (&mut self, eye: [f32; 3], center: [f32; 3], up: [f32; 3]) -> PResult<()> {
+        camera::set_camera(eye, center, up)
+    }
+
+    pub fn reset_camera (&mut self) -> PResult<()> {
+        camera::reset_camera()
+    }
+
+    pub fn perspective (&mut self, fov: f32, aspect: f32, near: f32, far: f32) -> PResult<()> {
+        camera::set_perspective(fov, aspect, near, far)
+    }
+
+    pub fn reset_perspective (&mut self) -> PResult<()> {
+        camera::reset_perspective()
+    }
+
+    pub fn ortho (&mut self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> PResult<()> {
+        camera::set_ortho(left, right, bottom, top, near, far)
+    }
+
+    pub fn reset_ortho (&mut self) -> PResult<()> {
+        camera::reset_ortho()
+    }
+
+    pub fn frustum (&mut self, left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> PResult<()> {
+        camera::set_frustum(left, right, bottom, top, near, far)
+    }
+
+    pub fn translate (&mut self, x: f32, y: f32, z: f32) {
+        transform::apply_matrix(matrix::translation(x, y, z));
+    }
+
+    // Around the z axis, like in 2D
+    pub fn rotate (&mut self, angle: f32) {
+        transform::apply_matrix(matrix::rotation_z(angle));
+    }
+
+    pub fn rotate_x (&mut self, angle: f32) {
+        transform::apply_matrix(matrix::rotation_x(angle));
+    }
+
+    pub fn rotate_y (&mut self, angle: f32) {
+        transform::apply_matrix(matrix::rotation_y(angle));
+    }
+
+    pub fn scale (&mut self, x: f32, y: f32, z: f32) {
+        transform::apply_matrix(matrix::scaling(x, y, z));
+    }
+
+    pub fn push_matrix (&mut self) -> PResult<()> {
+        transform::push_matrix()
+    }
+
+    pub fn pop_matrix (&mut self) -> PResult<()> {
+        transform::pop_matrix()
+    }
+
+    pub fn reset_matrix (&mut self) {
+        transform::reset_matrix();
+    }
+
+    pub fn box_shape (&mut self, width: f32, height: f32, depth: f32) -> PResult<()> {
+        solid::draw_box(width, height, depth)
+    }
+
+    pub fn sphere (&mut self, radius: f32) -> PResult<()> {
+        solid::draw_sphere(radius)
+    }
+
+    pub fn sphere_detail (&mut self, around: u32, down: u32) {
+        solid::sphereDetailUV(around, down);
+    }
+
+    pub fn load_shape (&mut self, path: &str) -> PResult<PShape> {
+        retained::load_shape(path)
+    }
+
+    pub fn create_shape (&mut self) -> PShape {
+        retained::create_shape()
+    }
+
+    pub fn create_shape_group (&mut self) -> PShape {
+        retained::create_shape_group()
+    }
+
+    pub fn create_shape_primitive (&mut self, kind: PShapePrimitive, params: &[f32]) -> PResult<PShape> {
+        retained::create_shape_primitive(kind, params)
+    }
+
+    pub fn shape (&mut self, shape: PShape, x: f32, y: f32) -> PResult<()> {
+        retained::draw::draw_shape(shape, x, y)
+    }
+
+    pub fn shape_add_child (&mut self, group: PShape, child: PShape) -> PResult<()> {
+        retained::add_child(group, child)
+    }
+
+    pub fn shape_child_count (&self, shape: PShape) -> PResult<u32> {
+        retained::child_count(shape)
+    }
+
+    pub fn shape_child (&self, shape: PShape, index: u32) -> PResult<PShape> {
+        retained::child(shape, index)
+    }
+
+    // Searches the children of the children too
+    pub fn shape_child_by_name (&self, shape: PShape, name: &str) -> PResult<PShape> {
+        retained::child_by_name(shape, name)
+    }
+
+    // The other vertex functions add to the shape too, until shape_end_shape()
+    pub fn shape_begin_shape (&mut self, shape: PShape, kind: PShapeKind) -> PResult<()> {
+        retained::begin_recording(shape, kind)
+    }
+
+    pub fn shape_vertex (&mut self, shape: PShape, x: f32, y: f32) -> PResult<()> {
+        retained::record_vertex(shape, x, y, 0.0)
+    }
+
+    pub fn shape_vertex_3d (&mut self, shape: PShape, x: f32, y: f32, z: f32) -> PResult<()> {
+        retained::record_vertex(shape, x, y, z)
+    }
+
+    pub fn shape_end_shape (&mut self, shape: PShape, close: PShapeClose) -> PResult<()> {
+        retained::end_recording(shape, close)
+    }
+
+    pub fn shape_set_fill (&mut self, shape: PShape, r: f32, g: f32, b: f32, a: f32) -> PResult<()> {
+        retained::set_fill(shape, Some(PColor::from_rgba(r, g, b, a)))
+    }
+
+    pub fn shape_no_fill (&mut self, shape: PShape) -> PResult<()> {
+        retained::set_fill(shape, None)
+    }
+
+    pub fn shape_set_stroke (&mut self, shape: PShape, r: f32, g: f32, b: f32, a: f32) -> PResult<()> {
+        retained::set_stroke(shape, Some(PColor::from_rgba(r, g, b, a)))
+    }
+
+    pub fn shape_no_stroke (&mut self, shape: PShape) -> PResult<()> {
+        retained::set_stroke(shape, None)
+    }
+
+    pub fn shape_set_stroke_weight (&mut self, shape: PShape, weight: f32) -> PResult<()> {
+        retained::set_stroke_weight(shape, weight)
+    }
+
+    pub fn shape_translate (&mut self, shape: PShape, x: f32, y: f32, z: f32) -> PResult<()> {
+        retained::transform_shape(shape, matrix::translation(x, y, z))
+    }
+
+    // Around the z axis, like rotate() in 2D
+    pub fn shape_rotate (&mut self, shape: PShape, angle: f32) -> PResult<()> {
+        retained::transform_shape(shape, matrix::rotation_z(angle))
+    }
+
+    pub fn shape_rotate_x (&mut self, shape: PShape, angle: f32) -> PResult<()> {
+        retained::transform_shape(shape, matrix::rotation_x(angle))
+    }
+
+    pub fn shape_rotate_y (&mut self, shape: PShape, angle: f32) -> PResult<()> {
+        retained::transform_shape(shape, matrix::rotation_y(angle))
+    }
+
+    pub fn shape_scale (&mut self, shape: PShape, x: f32, y: f32, z: f32) -> PResult<()> {
+        retained::transform_shape(shape, matrix::scaling(x, y, z))
+    }
+
+    pub fn shape_reset_matrix (&mut self, shape: PShape) -> PResult<()> {
+        retained::reset_matrix(shape)
+    }
+
+    pub fn shape_vertex_count (&self, shape: PShape) -> PResult<u32> {
+        retained::vertex_count(shape)
+    }
+
+    pub fn shape_get_vertex (&self, shape: PShape, index: u32) -> PResult<PVector> {
+        retained::get_vertex(shape, index)
+    }
+
+    pub fn shape_set_vertex (&mut self, shape: PShape, index: u32, position: PVector) -> PResult<()> {
+        retained::set_vertex(shape, index, position)
+    }
+
+    pub fn lights (&mut self) -> PResult<()> {
+        light::default_lights()
+    }
+
+    pub fn no_lights (&mut self) {
+        light::reset_lights();
+    }
+
+    pub fn ambient_light (&mut self, [r, g, b]: [f32; 3]) -> PResult<()> {
+        light::add_light(PLightKind::Ambient, PColor::from_rgba(r, g, b, 255.0), [0.0; 3], [0.0; 3], 0.0, 0.0)
+    }
+
+    pub fn directional_light (&mut self, [r, g, b]: [f32; 3], direction: [f32; 3]) -> PResult<()> {
+        light::add_light(PLightKind::Directional, PColor::from_rgba(r, g, b, 255.0), [0.0; 3], direction, 0.0, 0.0)
+    }
+
+    pub fn point_light (&mut self, [r, g, b]: [f32; 3], position: [f32; 3]) -> PResult<()> {
+        light::add_light(PLightKind::Point, PColor::from_rgba(r, g, b, 255.0), position, [0.0; 3], 0.0, 0.0)
+    }
+
+    pub fn spot_light (&mut self, [r, g, b]: [f32; 3], position: [f32; 3], direction: [f32; 3], angle: f32, concentration: f32) -> PResult<()> {
+        light::add_light(PLightKind::Spot, PColor::from_rgba(r, g, b, 255.0), position, direction, angle.clamp(0.0, std::f32::consts::PI), concentration)
+    }
+
+    pub fn light_falloff (&mut self, constant: f32, linear: f32, quadratic: f32) {
+        light::lightFalloff(constant, linear, quadratic);
+    }
+
+    pub fn light_specular (&mut self, r: f32, g: f32, b: f32) {
+        light::lightSpecular(r, g, b);
+    }
+
+    pub fn ambient (&mut self, r: f32, g: f32, b: f32) {
+        light::ambient(r, g, b);
+    }
+
+    pub fn specular (&mut self, r: f32, g: f32, b: f32) {
+        light::specular(r, g, b);
+    }
+
+    pub fn emissive (&mut self, r: f32, g: f32, b: f32) {
+        light::emissive(r, g, b);
+    }
+
+    pub fn shininess (&mut self, shine: f32) {
+        light::shininess(shine);
+    }
+
+    pub fn load_font (&mut self, path: &str) -> PResult<PFont> {
+        text::load_font(path)
+    }
+
+    pub fn create_font (&mut self, name: &str, size: f32) -> PResult<PFont> {
+        text::create_font(name, size)
+    }
+
+    pub fn text_font (&mut self, font: PFont) -> PResult<()> {
+        text::text_font(font)
+    }
+
+    pub fn text (&mut self, string: &str, x: f32, y: f32) -> PResult<()> {
+        text::draw_text(string, x, y)
+    }
+
+    pub fn text_box (&mut self, string: &str, x: f32, y: f32, width: f32, height: f32) -> PResult<()> {
+        text::draw_text_box(string, x, y, width, height)
+    }
+
+    pub fn text_size (&mut self, size: f32) {
+        text::textSize(size);
+    }
+
+    pub fn text_align (&mut self, align_x: PAlignX, align_y: PAlignY) {
+        text::textAlign(align_x, align_y);
+    }
+
+    pub fn text_leading (&mut self, leading: f32) {
+        text::textLeading(leading);
+    }
+
+    pub fn text_width (&self, string: &str) -> PResult<f32> {
+        text::text_width(string)
+    }
+
+    pub fn text_ascent (&self) -> PResult<f32> {
+        text::text_ascent()
+    }
+
+    pub fn text_descent (&self) -> PResult<f32> {
+        text::text_descent()
+    }
+
+    pub fn load_pixels (&mut self) {
+        pixels::load_pixels();
+    }
+
+    // Gives access to the buffer filled by load_pixels().
+    // Call update_pixels() afterwards to write changes back
+    pub fn with_pixels<T> (&mut self, callback: impl FnOnce(&mut [u32]) -> T) -> T {
+        let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+        callback(&mut state.pixels)
+    }
+
+    pub fn update_pixels (&mut self) {
+        pixels::update_pixels();
+    }
+
+    pub fn get (&self, x: i32, y: i32) -> u32 {
+        pixels::get_pixel(x, y)
+    }
+
+    pub fn get_region (&self, x: i32, y: i32, width: i32, height: i32) -> PResult<PImage> {
+        pixels::get_region(x, y, width, height)
+    }
+
+    pub fn set (&mut self, x: i32, y: i32, color: u32) {
+        pixels::set_pixel(x, y, color);
+    }
+
+    pub fn image_size (&self, image: PImage) -> PResult<(u32, u32)> {
+        image::image_size(image)
+    }
+
+    // Width and height are in pixels
+    pub fn create_image (&mut self, width: u32, height: u32, format: PImageFormat) -> PResult<PImage> {
+        create::create_image(width, height, format)
+    }
+
+    pub fn load_image (&mut self, path: &str) -> PResult<PImage> {
+        create::load_image(path)
+    }
+
+    // The image can't be used after this, its id goes to the next one made
+    pub fn free_image (&mut self, image: PImage) -> PResult<()> {
+        image::free_image(image)
+    }
+
+    // Graphics copy their pixels from the GPU, other images always have theirs
+    pub fn image_load_pixels (&mut self, image: PImage) -> PResult<()> {
+        pixels::load_image_pixels(image)
+    }
+
+    // Gives access to the pixels of an image. Call
+    // image_update_pixels() afterwards to apply changes
+    pub fn with_image_pixels<T> (&mut self, image: PImage, callback: impl FnOnce(&mut [u32]) -> T) -> PResult<T> {
+        let mut state = IMAGE_STATE.try_write().expect("Could not write to RwLock");
+        let drawing = state.drawing == Some(image);
+        let data = state.images.get_mut(image.id as usize).ok_or(PError::UnknownImage(image.id))?;
+        if drawing { Ok(callback(&mut state.pixels)) } else { Ok(callback(&mut data.pixels)) }
+    }
+
+    pub fn image_update_pixels (&mut self, image: PImage) -> PResult<()> {
+        pixels::update_image_pixels(image)
+    }
+
+    // Width and height are in pixels. If one of them is 0 the aspect ratio is kept
+    pub fn image_resize (&mut self, image: PImage, width: u32, height: u32) -> PResult<()> {
+        edit::resize_image(image, width, height)
+    }
+
+    pub fn image_copy (&mut self, source: PImage, source_region: PRegion, destination: PImage, destination_region: PRegion) -> PResult<()> {
+        edit::copy_image(source, source_region, destination, destination_region)
+    }
+
+    pub fn image_blend (
+        &mut self,
+        source: PImage,
+        source_region: PRegion,
+        destination: PImage,
+        destination_region: PRegion,
+        mode: PBlendMode
+    ) -> PResult<()> {
+        edit::blend_image(source, source_region, destination, destination_region, mode)
+    }
+
+    pub fn image_mask (&mut self, image: PImage, mask: PImage) -> PResult<()> {
+        edit::mask_image(image, mask)
+    }
+
+    pub fn image_get (&self, image: PImage, x: i32, y: i32) -> PResult<u32> {
+        edit::get_image_pixel(image, x, y)
+    }
+
+    pub fn image_set (&mut self, image: PImage, x: i32, y: i32, color: u32) -> PResult<()> {
+        edit::set_image_pixel(image, x, y, color)
+    }
+
+    // param is the threshold level, the number of posterize levels
+    // or the blur radius. None uses the default, posterize needs one
+    pub fn filter (&mut self, filter: PFilter, param: Option<f32>) -> PResult<()> {
+        filter::filter_canvas(filter, param)
+    }
+
+    pub fn image_filter (&mut self, image: PImage, filter: PFilter, param: Option<f32>) -> PResult<()> {
+        filter::filter_image(image, filter, param)
+    }
+
+    pub fn image (&mut self, image: PImage, x: f32, y: f32) -> PResult<()> {
+        image::draw_image(image, x, y, None)
+    }
+
+    pub fn image_rect (&mut self, image: PImage, x: f32, y: f32, width: f32, height: f32) -> PResult<()> {
+        image::draw_image(image, x, y, Some((width, height)))
+    }
+
+    pub fn create_graphics (&mut self, width: u32, height: u32) -> PResult<PGraphics> {
+        graphics::create_graphics(width, height)
+    }
+
+    // Everything drawn until end_draw() goes into the graphics
+    pub fn begin_draw (&mut self, graphics: PGraphics) -> PResult<()> {
+        graphics::begin_draw(graphics)
+    }
+
+    pub fn end_draw (&mut self, graphics: PGraphics) -> PResult<()> {
+        graphics::end_draw(graphics)
+    }
+
+    // Graphics that write what is drawn into them to a file at every end_draw()
+    pub fn create_graphics_with_recorder (&mut self, width: u32, height: u32, format: PRecordFormat, path: &str) -> PResult<PGraphics> {
+        graphics::create_graphics_with_recorder(width, height, format, path)
+    }
+
+    // Everything drawn until end_record() is also written to a file
+    pub fn begin_record (&mut self, format: PRecordFormat, path: &str) -> PResult<()> {
+        record::begin_record(format, path)
+    }
+
+    pub fn end_record (&mut self) -> PResult<()> {
+        record::end_record()
+    }
+
+    // Only for PDF recordings
+    pub fn next_page (&mut self) -> PResult<()> {
+        record::next_page()
+    }
+
+    // WGSL or GLSL files for each stage, None uses the built-in one
+    pub fn load_shader (&mut self, fragment: Option<&str>, vertex: Option<&str>) -> PResult<PShader> {
+        shader::load_shader(fragment, vertex)
+    }
+
+    pub fn shader (&mut self, shader: PShader) -> PResult<()> {
+        shader::set_shader(shader)
+    }
+
+    pub fn reset_shader (&mut self) {
+        shader::reset_shader();
+    }
+
+    // Shaders loaded from files are compiled again when the files change
+    pub fn shader_hot_reload (&mut self, enabled: bool) {
+        shader::reload::set_hot_reload(enabled);
+    }
+
+    // Called with the error when a changed shader doesn't compile
+    pub fn on_shader_error (&mut self, handler: impl Fn(&PError) + Send + Sync + 'static) {
+        shader::reload::set_error_handler(Arc::new(handler));
+    }
+
+    // One value per component of the uniform field
+    pub fn shader_set (&mut self, shader: PShader, name: &str, values: &[f32]) -> PResult<()> {
+        shader::set_uniform(shader, name, values)
+    }
+
+    pub fn mouse_x (&self) -> f32 {
+        get_event_state().mouse_x
+    }
+
+    pub fn mouse_y (&self) -> f32 {
+        get_event_state().mouse_y
+    }
+
+    pub fn mouse_button (&self) -> PMouseButton {
+        get_event_state().mouse_button
+    }
+
+    // See the constants in event::keyboard for keys without a character
+    pub fn key_code (&self) -> u32 {
+        get_event_state().key_code
+    }
+
+    // Last typed character, only valid inside key_typed()
+    pub fn key (&self) -> Option<char> {
+        char::from_u32(get_event_state().key)
+    }
+
+    pub fn ctrl_pressed (&self) -> bool {
+        get_event_state().ctrl_pressed
+    }
+
+    pub fn alt_pressed (&self) -> bool {
+        get_event_state().alt_pressed
+    }
+
+    pub fn shift_pressed (&self) -> bool {
+        get_event_state().shift_pressed
+    }
+}
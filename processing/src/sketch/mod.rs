@@ -0,0 +1,74 @@
+use std::cell::RefCell;
+
+use crate::{core::{p_init, p_on, p_run}, error::PResult, event::state::PEvent};
+
+pub mod ctx;
+pub mod builder;
+
+pub use ctx::Ctx;
+pub use builder::SketchBuilder;
+
+// Safe Rust front end for the library.
+// Implement the callbacks you need, every one of them
+// defaults to doing nothing. Returning an error from a
+// callback prints it and ends the sketch
+pub trait Sketch {
+    fn setup (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+    fn draw (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+
+    fn mouse_pressed (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+    fn mouse_released (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+    fn mouse_moved (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+
+    fn key_pressed (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+    fn key_released (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+    fn key_typed (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+
+    fn window_resized (&mut self, _ctx: &mut Ctx) -> PResult<()> { Ok(()) }
+}
+
+// The event loop only knows about extern "C" callbacks,
+// so the running sketch is kept here and the trampolines
+// below forward to it. Everything runs on the main thread
+thread_local! {
+    static SKETCH: RefCell<Option<Box<dyn Sketch>>> = RefCell::new(None);
+}
+
+fn with_sketch (callback: impl FnOnce(&mut dyn Sketch, &mut Ctx) -> PResult<()>) {
+    let result = SKETCH.with(|sketch| {
+        let mut sketch = sketch.borrow_mut();
+        let sketch = sketch.as_mut().expect("No sketch is running. Call sketch::run() first.");
+        callback(sketch.as_mut(), &mut Ctx::new())
+    });
+
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+extern "C" fn sketch_setup () { with_sketch(|s, ctx| s.setup(ctx)) }
+extern "C" fn sketch_draw () { with_sketch(|s, ctx| s.draw(ctx)) }
+extern "C" fn sketch_mouse_pressed () { with_sketch(|s, ctx| s.mouse_pressed(ctx)) }
+extern "C" fn sketch_mouse_released () { with_sketch(|s, ctx| s.mouse_released(ctx)) }
+extern "C" fn sketch_mouse_moved () { with_sketch(|s, ctx| s.mouse_moved(ctx)) }
+extern "C" fn sketch_key_pressed () { with_sketch(|s, ctx| s.key_pressed(ctx)) }
+extern "C" fn sketch_key_released () { with_sketch(|s, ctx| s.key_released(ctx)) }
+extern "C" fn sketch_key_typed () { with_sketch(|s, ctx| s.key_typed(ctx)) }
+extern "C" fn sketch_window_resized () { with_sketch(|s, ctx| s.window_resized(ctx)) }
+
+// Runs the sketch. Like p_run(), this only returns
+// once the window has been closed
+pub fn run (sketch: impl Sketch + 'static) {
+    SKETCH.with(|s| *s.borrow_mut() = Some(Box::new(sketch)));
+
+    p_init(sketch_setup, sketch_draw);
+    p_on(PEvent::PMousePressed, sketch_mouse_pressed);
+    p_on(PEvent::PMouseReleased, sketch_mouse_released);
+    p_on(PEvent::PMouseMoved, sketch_mouse_moved);
+    p_on(PEvent::PKeyPressed, sketch_key_pressed);
+    p_on(PEvent::PKeyReleased, sketch_key_released);
+    p_on(PEvent::PKeyTyped, sketch_key_typed);
+    p_on(PEvent::PWindowResized, sketch_window_resized);
+    p_run();
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use ab_glyph::{point, Font as _, FontVec, GlyphId, OutlineCurve, PxScale, ScaleFont};
+
+use crate::{math::curve::quadratic_to_cubic, record::state::PathSegment, renderer::texture::{create_texture, update_texture, TextureId}};
+
+// Glyphs are packed into pages of this size, a new
+// page is started when the current one is full
+pub(crate) const ATLAS_SIZE: u32 = 1024;
+const GLYPH_PADDING: u32 = 1;
+
+// Where a rasterized glyph is in the atlas, and where it goes
+// relative to the pen position on the baseline (in pixels)
+#[derive(Debug, Copy, Clone)]
+pub struct AtlasGlyph {
+    pub page: TextureId,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub left: f32,
+    pub top: f32,
+}
+
+struct AtlasPage {
+    texture: TextureId,
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+}
+
+// A TTF/OTF font and the glyphs rasterized from it so far.
+// Glyphs are cached per size, so drawing text at many
+// different sizes fills up the atlas faster
+pub struct Font {
+    font: FontVec,
+    // Size given to createFont(), textFont() switches to it
+    pub size: f32,
+    pages: Vec<AtlasPage>,
+    // None for glyphs with nothing to draw (spaces) or
+    // that are too big to fit in a page
+    glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+}
+
+impl Font {
+    pub fn from_bytes (bytes: Vec<u8>, size: f32) -> Option<Self> {
+        Some(Self {
+            font: FontVec::try_from_vec(bytes).ok()?,
+            size,
+            pages: vec![],
+            glyphs: HashMap::new()
+        })
+    }
+
+    // Text sizes are em sizes like in Processing, ab_glyph
+    // scales by the height from ascent to descent instead
+    fn scale (&self, size: f32) -> PxScale {
+        let units_per_em = self.font.units_per_em().unwrap_or(1.0);
+        PxScale::from(size * self.font.height_unscaled() / units_per_em)
+    }
+
+    pub fn ascent (&self, size: f32) -> f32 {
+        self.font.as_scaled(self.scale(size)).ascent()
+    }
+
+    // Positive, like textDescent() in Processing
+    pub fn descent (&self, size: f32) -> f32 {
+        -self.font.as_scaled(self.scale(size)).descent()
+    }
+
+    // Glyphs of a line of text and their offsets from the start of the line,
+    // along with the width of the whole line
+    pub fn layout_line (&self, line: &str, size: f32) -> (Vec<(GlyphId, f32)>, f32) {
+        let scaled = self.font.as_scaled(self.scale(size));
+        let mut glyphs = Vec::with_capacity(line.len());
+        let mut pen = 0.0;
+        let mut previous: Option<GlyphId> = None;
+
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                pen += scaled.kern(previous, id);
+            }
+            glyphs.push((id, pen));
+            pen += scaled.h_advance(id);
+            previous = Some(id);
+        }
+
+        (glyphs, pen)
+    }
+
+    pub fn line_width (&self, line: &str, size: f32) -> f32 {
+        self.layout_line(line, size).1
+    }
+
+    // Outline of a glyph with its pen position at origin, on the baseline.
+    // A new subpath starts wherever a curve doesn't start where the last one ended
+    pub fn outline (&self, id: GlyphId, size: f32, [x, y]: [f32; 2]) -> Vec<PathSegment> {
+        let Some(outline) = self.font.outline(id) else { return vec![]; };
+        let scaled = self.font.as_scaled(self.scale(size));
+        let (h_scale, v_scale) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+        // Font units go up from the baseline
+        let place = |p: ab_glyph::Point| [x + p.x * h_scale, y - p.y * v_scale];
+
+        let mut segments = vec![];
+        let mut end: Option<[f32; 2]> = None;
+        for curve in outline.curves {
+            let (start, segment) = match curve {
+                OutlineCurve::Line(from, to) => (place(from), PathSegment::LineTo(place(to))),
+                OutlineCurve::Quad(from, control, to) => {
+                    let [from, control1, control2, to] = quadratic_to_cubic(place(from), place(control), place(to));
+                    (from, PathSegment::CubicTo(control1, control2, to))
+                }
+                OutlineCurve::Cubic(from, control1, control2, to) => (place(from), PathSegment::CubicTo(place(control1), place(control2), place(to)))
+            };
+            if end != Some(start) {
+                if end.is_some() {
+                    segments.push(PathSegment::Close);
+                }
+                segments.push(PathSegment::MoveTo(start));
+            }
+            end = Some(match segment {
+                PathSegment::LineTo(to) | PathSegment::CubicTo(_, _, to) => to,
+                _ => start
+            });
+            segments.push(segment);
+        }
+        if end.is_some() {
+            segments.push(PathSegment::Close);
+        }
+        segments
+    }
+
+    // Rasterizes a glyph at the given size in pixels the first
+    // time it is asked for, and returns where it is in the atlas
+    pub fn glyph (&mut self, id: GlyphId, pixel_size: f32) -> Option<AtlasGlyph> {
+        // Sizes are rounded to a quarter pixel so that
+        // slightly different sizes share their glyphs
+        let key = (id, (pixel_size * 4.0).round() as u32);
+        if let Some(glyph) = self.glyphs.get(&key) {
+            return *glyph;
+        }
+
+        let glyph = self.rasterize(id, key.1 as f32 / 4.0);
+        self.glyphs.insert(key, glyph);
+        glyph
+    }
+
+    fn rasterize (&mut self, id: GlyphId, pixel_size: f32) -> Option<AtlasGlyph> {
+        let outline = self.font.outline_glyph(id.with_scale_and_position(self.scale(pixel_size), point(0.0, 0.0)))?;
+        let bounds = outline.px_bounds();
+        let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+        if width == 0 || height == 0 { return None; }
+
+        let (page, x, y) = self.allocate(width, height)?;
+
+        update_texture(page, |slot| {
+            outline.draw(|glyph_x, glyph_y, coverage| {
+                if glyph_x >= width || glyph_y >= height { return; }
+                let index = (((y + glyph_y) * slot.width + x + glyph_x) * 4 + 3) as usize;
+                slot.pixels[index] = (coverage.clamp(0.0, 1.0) * 255.0).round() as u8;
+            });
+        });
+
+        Some(AtlasGlyph {
+            page,
+            x,
+            y,
+            width,
+            height,
+            left: bounds.min.x,
+            top: bounds.min.y
+        })
+    }
+
+    // Finds room for a glyph, row by row. Glyphs are
+    // padded so that filtering doesn't bleed into neighbours
+    fn allocate (&mut self, width: u32, height: u32) -> Option<(TextureId, u32, u32)> {
+        let (padded_width, padded_height) = (width + GLYPH_PADDING * 2, height + GLYPH_PADDING * 2);
+        if padded_width > ATLAS_SIZE || padded_height > ATLAS_SIZE { return None; }
+
+        let needs_page = match self.pages.last_mut() {
+            Some(page) => {
+                if page.cursor_x + padded_width > ATLAS_SIZE {
+                    page.cursor_x = 0;
+                    page.cursor_y += page.row_height;
+                    page.row_height = 0;
+                }
+                page.cursor_y + padded_height > ATLAS_SIZE
+            }
+            None => true
+        };
+
+        if needs_page {
+            // Transparent white, so filtered edges don't get darker
+            let pixels = [255, 255, 255, 0].repeat((ATLAS_SIZE * ATLAS_SIZE) as usize);
+            self.pages.push(AtlasPage {
+                texture: create_texture(ATLAS_SIZE, ATLAS_SIZE, pixels),
+                cursor_x: 0,
+                cursor_y: 0,
+                row_height: 0
+            });
+        }
+
+        let page = self.pages.last_mut()?;
+        let position = (page.texture, page.cursor_x + GLYPH_PADDING, page.cursor_y + GLYPH_PADDING);
+        page.cursor_x += padded_width;
+        page.row_height = page.row_height.max(padded_height);
+        Some(position)
+    }
+}
@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+
+// Looks up the file of a system font by name ("DejaVu Sans", "serif",
+// "Noto Sans:bold", any fontconfig pattern works). Fontconfig always
+// picks the closest font it has, so this only fails if there are none
+#[cfg(target_os = "linux")]
+pub fn find_font (name: &str) -> Option<PathBuf> {
+    use std::ffi::{CStr, CString};
+
+    let name = CString::new(name).ok()?;
+
+    unsafe {
+        let config = ffi::FcInitLoadConfigAndFonts();
+        if config.is_null() { return None; }
+
+        let pattern = ffi::FcNameParse(name.as_ptr() as *const u8);
+        if pattern.is_null() {
+            ffi::FcConfigDestroy(config);
+            return None;
+        }
+
+        ffi::FcConfigSubstitute(config, pattern, ffi::FC_MATCH_PATTERN);
+        ffi::FcDefaultSubstitute(pattern);
+
+        let mut result = ffi::FC_RESULT_MATCH;
+        let font = ffi::FcFontMatch(config, pattern, &mut result);
+
+        // The file name belongs to the matched pattern,
+        // so it has to be copied before that is destroyed
+        let mut path = None;
+        if !font.is_null() {
+            let mut file: *mut u8 = std::ptr::null_mut();
+            if ffi::FcPatternGetString(font, ffi::FC_FILE.as_ptr(), 0, &mut file) == ffi::FC_RESULT_MATCH && !file.is_null() {
+                path = Some(PathBuf::from(CStr::from_ptr(file as *const _).to_string_lossy().into_owned()));
+            }
+            ffi::FcPatternDestroy(font);
+        }
+
+        ffi::FcPatternDestroy(pattern);
+        ffi::FcConfigDestroy(config);
+        path
+    }
+}
+
+// Fontconfig is only used on Linux. Elsewhere fonts
+// have to be given as paths to TTF/OTF files
+#[cfg(not(target_os = "linux"))]
+pub fn find_font (_name: &str) -> Option<PathBuf> {
+    None
+}
+
+// Kept out of bindings.h, where it would clash with fontconfig.h
+/// cbindgen:ignore
+#[cfg(target_os = "linux")]
+mod ffi {
+    use std::ffi::{c_int, c_void};
+
+    pub type FcConfig = c_void;
+    pub type FcPattern = c_void;
+
+    pub const FC_MATCH_PATTERN: c_int = 0;
+    pub const FC_RESULT_MATCH: c_int = 0;
+    pub const FC_FILE: &[u8] = b"file\0";
+
+    #[link(name = "fontconfig")]
+    extern "C" {
+        pub fn FcInitLoadConfigAndFonts () -> *mut FcConfig;
+        pub fn FcConfigDestroy (config: *mut FcConfig);
+        pub fn FcNameParse (name: *const u8) -> *mut FcPattern;
+        pub fn FcConfigSubstitute (config: *mut FcConfig, pattern: *mut FcPattern, kind: c_int) -> c_int;
+        pub fn FcDefaultSubstitute (pattern: *mut FcPattern);
+        pub fn FcFontMatch (config: *mut FcConfig, pattern: *mut FcPattern, result: *mut c_int) -> *mut FcPattern;
+        pub fn FcPatternGetString (pattern: *const FcPattern, object: *const u8, n: c_int, value: *mut *mut u8) -> c_int;
+        pub fn FcPatternDestroy (pattern: *mut FcPattern);
+    }
+}
@@ -0,0 +1,482 @@
+use std::{collections::BTreeMap, ffi::c_char};
+
+use crate::{
+    color::color_state::get_color_state,
+    core::string_from,
+    error::{OrPanic, PError, PResult},
+    math::matrix::IDENTITY,
+    record::{is_recording, record_text},
+    renderer::{state::{get_renderer_state, set_renderer_state}, texture::{SampledTexture, TextureId}, vertex::Vertex},
+    shape::transform::model_matrix,
+    text::{font::{Font, ATLAS_SIZE}, state::{get_text_state, set_text_state, TEXT_STATE}}
+};
+
+pub mod font;
+pub mod fontconfig;
+pub mod state;
+
+// Font used when text is drawn before textFont() is called
+const DEFAULT_FONT: &str = "sans-serif";
+
+// Processing's default leading, relative to ascent + descent
+const LEADING_RATIO: f32 = 1.275;
+
+// Handle to a font returned by loadFont() and createFont()
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub struct PFont {
+    pub id: u32
+}
+
+// Horizontal alignment for textAlign()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PAlignX {
+    #[default]
+    AlignLeft,
+    AlignCenter,
+    AlignRight
+}
+
+// Vertical alignment for textAlign()
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(C)]
+pub enum PAlignY {
+    AlignTop,
+    AlignMiddle,
+    #[default]
+    AlignBaseline,
+    AlignBottom
+}
+
+// Settings text is laid out with, copied out of the text state
+#[derive(Copy, Clone)]
+struct TextStyle {
+    size: f32,
+    leading: f32,
+    ascent: f32,
+    descent: f32,
+    align_x: PAlignX,
+    align_y: PAlignY,
+}
+
+fn add_font (font: Font) -> PFont {
+    let mut state = TEXT_STATE.try_write().expect("Could not write to RwLock");
+    state.fonts.push(font);
+    PFont { id: state.fonts.len() as u32 - 1 }
+}
+
+pub(crate) fn load_font (path: &str) -> PResult<PFont> {
+    let bytes = std::fs::read(path).map_err(|_| PError::FontNotFound(path.to_string()))?;
+    let font = Font::from_bytes(bytes, get_text_state().size).ok_or_else(|| PError::InvalidFont(path.to_string()))?;
+    Ok(add_font(font))
+}
+
+// Like Processing, the name can either be a path to a
+// font file or the name of a font installed on the system
+pub(crate) fn create_font (name: &str, size: f32) -> PResult<PFont> {
+    let path =
+        if std::path::Path::new(name).is_file() { Some(name.into()) }
+        else { fontconfig::find_font(name) }
+        .ok_or_else(|| PError::FontNotFound(name.to_string()))?;
+
+    let bytes = std::fs::read(&path).map_err(|_| PError::FontNotFound(name.to_string()))?;
+    let font = Font::from_bytes(bytes, size).ok_or_else(|| PError::InvalidFont(name.to_string()))?;
+    Ok(add_font(font))
+}
+
+pub(crate) fn text_font (font: PFont) -> PResult<()> {
+    let size = get_text_state().fonts.get(font.id as usize).ok_or(PError::UnknownFont(font.id))?.size;
+    set_text_state! {
+        font = Some(font.id as usize);
+        size = size;
+        leading = None;
+    }
+    Ok(())
+}
+
+// Runs callback with the current font, loading the default
+// one first if no font has been set with textFont()
+fn with_font<T> (callback: impl FnOnce(&mut Font, TextStyle) -> T) -> PResult<T> {
+    if get_text_state().font.is_none() {
+        let size = get_text_state().size;
+        let font = create_font(DEFAULT_FONT, size)?;
+        set_text_state! {
+            font = Some(font.id as usize);
+        }
+    }
+
+    let mut state = TEXT_STATE.try_write().expect("Could not write to RwLock");
+    let (size, leading, align_x, align_y) = (state.size, state.leading, state.align_x, state.align_y);
+    let id = state.font.unwrap_or_default();
+    let font = state.fonts.get_mut(id).ok_or(PError::UnknownFont(id as u32))?;
+
+    let (ascent, descent) = (font.ascent(size), font.descent(size));
+    let style = TextStyle {
+        size,
+        leading: leading.unwrap_or((ascent + descent) * LEADING_RATIO),
+        ascent,
+        descent,
+        align_x,
+        align_y
+    };
+    Ok(callback(font, style))
+}
+
+fn align_offset (align_x: PAlignX, width: f32) -> f32 {
+    match align_x {
+        PAlignX::AlignLeft => 0.0,
+        PAlignX::AlignCenter => -width / 2.0,
+        PAlignX::AlignRight => -width
+    }
+}
+
+// Turns lines of text into quads, one batch per atlas page.
+// x is where lines are aligned to, baseline is the baseline of the first line
+fn push_lines (font: &mut Font, style: TextStyle, lines: &[&str], x: f32, baseline: f32) {
+    let Some(fill) = get_color_state().fill else { return; };
+    let (format, density, blend_mode) = {
+        let renderer_state = get_renderer_state();
+        (renderer_state.canvas_format, renderer_state.pixel_density as f32, get_color_state().blend_mode)
+    };
+    let color = fill.to_shader_color(format);
+    let atlas_size = ATLAS_SIZE as f32;
+
+    let recording = is_recording();
+    let mut outlines = vec![];
+    let mut pages: BTreeMap<TextureId, (Vec<Vertex>, Vec<u32>)> = BTreeMap::new();
+    for (index, line) in lines.iter().enumerate() {
+        let (glyphs, width) = font.layout_line(line, style.size);
+        let line_x = x + align_offset(style.align_x, width);
+        let line_y = baseline + index as f32 * style.leading;
+
+        for (id, offset) in glyphs {
+            if recording {
+                outlines.extend(font.outline(id, style.size, [line_x + offset, line_y]));
+            }
+            let Some(glyph) = font.glyph(id, style.size * density) else { continue; };
+
+            // Glyphs are placed on whole pixels of the canvas so they stay sharp
+            let left = ((line_x + offset) * density).round() + glyph.left;
+            let top = (line_y * density).round() + glyph.top;
+            let (x0, y0) = (left / density, top / density);
+            let (x1, y1) = ((left + glyph.width as f32) / density, (top + glyph.height as f32) / density);
+            let (u0, v0) = (glyph.x as f32 / atlas_size, glyph.y as f32 / atlas_size);
+            let (u1, v1) = ((glyph.x + glyph.width) as f32 / atlas_size, (glyph.y + glyph.height) as f32 / atlas_size);
+
+            let (vertices, indices) = pages.entry(glyph.page).or_default();
+            let start = vertices.len() as u32;
+            vertices.extend([
+                Vertex::new(x0, y0, 0.0).with_color(color).with_uv(u0, v0),
+                Vertex::new(x1, y0, 0.0).with_color(color).with_uv(u1, v0),
+                Vertex::new(x1, y1, 0.0).with_color(color).with_uv(u1, v1),
+                Vertex::new(x0, y1, 0.0).with_color(color).with_uv(u0, v1)
+            ]);
+            indices.extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+        }
+    }
+
+    let model = model_matrix();
+    record_text(outlines, fill, (model != IDENTITY).then_some(&model));
+
+    let shader = get_renderer_state().shader;
+    for (page, (mut vertices, indices)) in pages {
+        if model != IDENTITY {
+            vertices.iter_mut().for_each(|vertex| *vertex = vertex.transformed(&model));
+        }
+        set_renderer_state! {
+            draw_list.push(&vertices, &indices, blend_mode, Some(SampledTexture::clamped(page)), shader, false);
+        }
+    }
+}
+
+pub(crate) fn draw_text (string: &str, x: f32, y: f32) -> PResult<()> {
+    with_font(|font, style| {
+        let lines: Vec<&str> = string.lines().collect();
+        push_lines(font, style, &lines, x, text_baseline(style, y, lines.len()));
+    })
+}
+
+// Baseline of the first of count lines drawn at y
+fn text_baseline (style: TextStyle, y: f32, count: usize) -> f32 {
+    let extra_lines = count.saturating_sub(1) as f32 * style.leading;
+    match style.align_y {
+        PAlignY::AlignBaseline => y,
+        PAlignY::AlignTop => y + style.ascent,
+        PAlignY::AlignMiddle => y + (style.ascent - style.descent - extra_lines) / 2.0,
+        PAlignY::AlignBottom => y - style.descent - extra_lines
+    }
+}
+
+// Breaks text into lines that fit in width. Words are split
+// on spaces, words wider than the box are split between characters
+fn wrap_lines (font: &Font, string: &str, width: f32, size: f32) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in string.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+            if font.line_width(&candidate, size) <= width {
+                line = candidate;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if font.line_width(&line, size) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+// Text wrapped inside a box. Lines that don't fit in the height are left out
+pub(crate) fn draw_text_box (string: &str, x: f32, y: f32, width: f32, height: f32) -> PResult<()> {
+    with_font(|font, style| {
+        let lines = box_lines(font, style, string, width, height);
+        let (anchor, baseline) = box_anchor(style, [x, y, width, height], lines.len());
+
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        push_lines(font, style, &lines, anchor, baseline);
+    })
+}
+
+// The wrapped lines of a text box that fit in its height
+fn box_lines (font: &Font, style: TextStyle, string: &str, width: f32, height: f32) -> Vec<String> {
+    let mut lines = wrap_lines(font, string, width, style.size);
+    let line_height = style.ascent + style.descent;
+    let max_lines = if height < line_height { 0 } else { ((height - line_height) / style.leading) as usize + 1 };
+    lines.truncate(max_lines);
+    lines
+}
+
+// Where count lines in a box are aligned to, and the baseline of the first one
+fn box_anchor (style: TextStyle, [x, y, width, height]: [f32; 4], count: usize) -> (f32, f32) {
+    let line_height = style.ascent + style.descent;
+    let extra_lines = count.saturating_sub(1) as f32 * style.leading;
+    let baseline = match style.align_y {
+        PAlignY::AlignTop | PAlignY::AlignBaseline => y + style.ascent,
+        PAlignY::AlignMiddle => y + (height - line_height - extra_lines) / 2.0 + style.ascent,
+        PAlignY::AlignBottom => y + height - style.descent - extra_lines
+    };
+    let anchor = match style.align_x {
+        PAlignX::AlignLeft => x,
+        PAlignX::AlignCenter => x + width / 2.0,
+        PAlignX::AlignRight => x + width
+    };
+    (anchor, baseline)
+}
+
+// Width of the widest line
+pub(crate) fn text_width (string: &str) -> PResult<f32> {
+    with_font(|font, style| {
+        string
+            .lines()
+            .map(|line| font.line_width(line, style.size))
+            .fold(0.0, f32::max)
+    })
+}
+
+pub(crate) fn text_ascent () -> PResult<f32> {
+    with_font(|_, style| style.ascent)
+}
+
+pub(crate) fn text_descent () -> PResult<f32> {
+    with_font(|_, style| style.descent)
+}
+
+/// Loads a TTF or OTF font file
+///
+/// # Safety
+/// `path` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn loadFont (path: *const c_char) -> PFont {
+    load_font(&string_from(path)).or_panic()
+}
+
+/// Loads a font by file or system font name, to be drawn at size
+///
+/// # Safety
+/// `name` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn createFont (name: *const c_char, size: f32) -> PFont {
+    create_font(&string_from(name), size).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn textFont (font: PFont) {
+    text_font(font).or_panic();
+}
+
+/// Draws text with the fill color. Lines are split on '\n'
+///
+/// # Safety
+/// `string` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn text (string: *const c_char, x: f32, y: f32) {
+    draw_text(&string_from(string), x, y).or_panic();
+}
+
+/// Draws text wrapped inside the box at x, y
+///
+/// # Safety
+/// `string` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn textBox (string: *const c_char, x: f32, y: f32, width: f32, height: f32) {
+    draw_text_box(&string_from(string), x, y, width, height).or_panic();
+}
+
+// Also resets the leading to the default for the new size
+#[no_mangle]
+pub extern "C" fn textSize (size: f32) {
+    set_text_state! {
+        size = size;
+        leading = None;
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn textAlign (align_x: PAlignX, align_y: PAlignY) {
+    set_text_state! {
+        align_x = align_x;
+        align_y = align_y;
+    }
+}
+
+// Distance between the baselines of two lines
+#[no_mangle]
+pub extern "C" fn textLeading (leading: f32) {
+    set_text_state! {
+        leading = Some(leading);
+    }
+}
+
+/// Width of the widest line of the string with the current font and size
+///
+/// # Safety
+/// `string` must be null or point to a null terminated UTF-8 string
+#[no_mangle]
+pub unsafe extern "C" fn textWidth (string: *const c_char) -> f32 {
+    text_width(&string_from(string)).or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn textAscent () -> f32 {
+    text_ascent().or_panic()
+}
+
+#[no_mangle]
+pub extern "C" fn textDescent () -> f32 {
+    text_descent().or_panic()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixture;
+
+    const SIZE: f32 = 20.0;
+
+    fn font () -> Font {
+        Font::from_bytes(std::fs::read(fixture("Cantarell-Regular.ttf")).unwrap(), SIZE).unwrap()
+    }
+
+    fn style (align_x: PAlignX, align_y: PAlignY) -> TextStyle {
+        TextStyle { size: SIZE, leading: 24.0, ascent: 16.0, descent: 4.0, align_x, align_y }
+    }
+
+    #[test]
+    fn layout_advances_and_measures_lines () {
+        let font = font();
+        let (glyphs, width) = font.layout_line("abc", SIZE);
+        assert_eq!(glyphs.len(), 3);
+        assert_eq!(glyphs[0].1, 0.0);
+        assert!(glyphs[0].1 < glyphs[1].1 && glyphs[1].1 < glyphs[2].1 && glyphs[2].1 < width);
+        assert_eq!(font.line_width("", SIZE), 0.0);
+        assert!((font.line_width("abc", SIZE * 2.0) - width * 2.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn text_width_is_the_widest_line () {
+        let _state = crate::lock_state();
+        let font = load_font(&fixture("Cantarell-Regular.ttf")).unwrap();
+        text_font(font).unwrap();
+        set_text_state! {
+            size = SIZE;
+        }
+
+        assert_eq!(text_width("ab\nabcdef\nabc").unwrap(), self::font().line_width("abcdef", SIZE));
+    }
+
+    #[test]
+    fn wrap_lines_breaks_between_words () {
+        let font = font();
+        let width = font.line_width("one two", SIZE) + 1.0;
+        let lines = wrap_lines(&font, "one two three four", width, SIZE);
+        assert_eq!(lines, ["one two", "three", "four"]);
+        assert!(lines.iter().all(|line| font.line_width(line, SIZE) <= width));
+    }
+
+    #[test]
+    fn wrap_lines_keeps_paragraphs_and_splits_long_words () {
+        let font = font();
+        let width = font.line_width("abc", SIZE) + 1.0;
+        assert_eq!(wrap_lines(&font, "ab\nab", width, SIZE), ["ab", "ab"]);
+
+        let lines = wrap_lines(&font, "abcabcab", width, SIZE);
+        assert_eq!(lines.concat(), "abcabcab");
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| font.line_width(line, SIZE) <= width));
+
+        // A single character wider than the box still gets a line
+        assert_eq!(wrap_lines(&font, "w", 1.0, SIZE), ["w"]);
+    }
+
+    #[test]
+    fn box_lines_fit_in_the_height () {
+        let font = font();
+        let style = style(PAlignX::AlignLeft, PAlignY::AlignTop);
+        let width = font.line_width("one", SIZE) + 1.0;
+        assert_eq!(box_lines(&font, style, "one one one", width, 19.0).len(), 0);
+        assert_eq!(box_lines(&font, style, "one one one", width, 20.0), ["one"]);
+        assert_eq!(box_lines(&font, style, "one one one", width, 44.0), ["one", "one"]);
+        assert_eq!(box_lines(&font, style, "one one one", width, 1000.0).len(), 3);
+    }
+
+    #[test]
+    fn lines_are_aligned_horizontally () {
+        assert_eq!(align_offset(PAlignX::AlignLeft, 10.0), 0.0);
+        assert_eq!(align_offset(PAlignX::AlignCenter, 10.0), -5.0);
+        assert_eq!(align_offset(PAlignX::AlignRight, 10.0), -10.0);
+
+        let bounds = [10.0, 20.0, 100.0, 200.0];
+        assert_eq!(box_anchor(style(PAlignX::AlignLeft, PAlignY::AlignTop), bounds, 1).0, 10.0);
+        assert_eq!(box_anchor(style(PAlignX::AlignCenter, PAlignY::AlignTop), bounds, 1).0, 60.0);
+        assert_eq!(box_anchor(style(PAlignX::AlignRight, PAlignY::AlignTop), bounds, 1).0, 110.0);
+    }
+
+    #[test]
+    fn lines_are_aligned_vertically () {
+        let text = |align_y| text_baseline(style(PAlignX::AlignLeft, align_y), 100.0, 3);
+        assert_eq!(text(PAlignY::AlignBaseline), 100.0);
+        assert_eq!(text(PAlignY::AlignTop), 116.0);
+        // Three lines are 48 between the first and last baseline
+        assert_eq!(text(PAlignY::AlignMiddle), 100.0 + (16.0 - 4.0 - 48.0) / 2.0);
+        assert_eq!(text(PAlignY::AlignBottom), 100.0 - 4.0 - 48.0);
+
+        let bounds = [0.0, 100.0, 50.0, 200.0];
+        let boxed = |align_y| box_anchor(style(PAlignX::AlignLeft, align_y), bounds, 3).1;
+        assert_eq!(boxed(PAlignY::AlignTop), 116.0);
+        assert_eq!(boxed(PAlignY::AlignBaseline), 116.0);
+        assert_eq!(boxed(PAlignY::AlignMiddle), 100.0 + (200.0 - 20.0 - 48.0) / 2.0 + 16.0);
+        // The last baseline sits a descent above the bottom of the box
+        assert_eq!(boxed(PAlignY::AlignBottom) + 48.0, 300.0 - 4.0);
+    }
+}
@@ -0,0 +1,56 @@
+use lazy_static::lazy_static;
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+
+use crate::text::{font::Font, PAlignX, PAlignY};
+
+// Fonts that have been loaded and the settings text() is drawn with
+pub struct TextState {
+    pub fonts: Vec<Font>,
+    // Index into fonts. The default font is
+    // loaded the first time text is drawn without one
+    pub font: Option<usize>,
+    pub size: f32,
+    // None means the default for the font and size,
+    // textSize() goes back to it like in Processing
+    pub leading: Option<f32>,
+    pub align_x: PAlignX,
+    pub align_y: PAlignY,
+}
+
+impl Default for TextState {
+    fn default () -> Self {
+        Self {
+            fonts: vec![],
+            font: None,
+            size: 12.0,
+            leading: None,
+            align_x: PAlignX::AlignLeft,
+            align_y: PAlignY::AlignBaseline
+        }
+    }
+}
+
+lazy_static! {
+    pub static ref TEXT_STATE: Arc<RwLock<TextState>> = Arc::new(RwLock::new(TextState::default()));
+}
+
+pub fn get_text_state () -> RwLockReadGuard<'static, TextState> {
+    TEXT_STATE.try_read().unwrap()
+}
+
+macro_rules! set_text_state {
+
+    // base cases
+    ($var:ident$(.$var2:ident)* = $value:expr;) => {
+        {
+            crate::text::state::TEXT_STATE.try_write().expect("Could not write to RwLock").$var$(.$var2)* = $value;
+        }
+    };
+
+    // expr - expr
+    ($var:ident$(.$var2:ident)* = $value:expr; $($var3:ident$(.$var4:ident)* = $value2:expr;)*) => {
+        set_text_state!{ $var$(.$var2)* = $value; };
+        set_text_state!{ $($var3$(.$var4)* = $value2;)* };
+    };
+}
+pub(crate) use set_text_state;
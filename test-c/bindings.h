@@ -3,6 +3,99 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+#define P_ABI_VERSION 1
+
+#define BACKSPACE 8
+
+#define TAB 9
+
+#define ENTER 10
+
+#define SHIFT 16
+
+#define CONTROL 17
+
+#define ALT 18
+
+#define ESC 27
+
+#define LEFT 37
+
+#define UP 38
+
+#define RIGHT 39
+
+#define DOWN 40
+
+#define DELETE 127
+
+typedef enum PAlignX {
+  AlignLeft,
+  AlignCenter,
+  AlignRight,
+} PAlignX;
+
+typedef enum PAlignY {
+  AlignTop,
+  AlignMiddle,
+  AlignBaseline,
+  AlignBottom,
+} PAlignY;
+
+typedef enum PBlendMode {
+  BlendBlend,
+  BlendAdd,
+  BlendSubtract,
+  BlendMultiply,
+  BlendScreen,
+  BlendLightest,
+  BlendDarkest,
+  BlendReplace,
+} PBlendMode;
+
+typedef enum PCanvasFormat {
+  CanvasSrgb,
+  CanvasLinear,
+  CanvasFloat,
+} PCanvasFormat;
+
+typedef enum PCursor {
+  CursorArrow,
+  CursorCross,
+  CursorHand,
+  CursorMove,
+  CursorText,
+  CursorWait,
+} PCursor;
+
+typedef enum PEvent {
+  PMousePressed,
+  PMouseReleased,
+  PMouseMoved,
+  NoEvent,
+  PKeyPressed,
+  PKeyReleased,
+  PKeyTyped,
+  PWindowResized,
+} PEvent;
+
+typedef enum PFilter {
+  FilterThreshold,
+  FilterGray,
+  FilterInvert,
+  FilterPosterize,
+  FilterBlur,
+  FilterErode,
+  FilterDilate,
+  FilterOpaque,
+} PFilter;
+
+typedef enum PImageFormat {
+  ImageRgb,
+  ImageArgb,
+  ImageAlpha,
+} PImageFormat;
+
 typedef enum PMouseButton {
   LeftButton,
   RightButton,
@@ -10,14 +103,544 @@ typedef enum PMouseButton {
   NoButton,
 } PMouseButton;
 
+typedef enum PRecordFormat {
+  RecordSvg,
+  RecordPdf,
+} PRecordFormat;
+
+typedef enum PRenderer {
+  P2D,
+  P3D,
+} PRenderer;
+
+typedef enum PShapeClose {
+  ShapeOpen,
+  ShapeClose,
+} PShapeClose;
+
+typedef enum PShapeKind {
+  ShapePolygon,
+  ShapePoints,
+  ShapeLines,
+  ShapeTriangles,
+  ShapeTriangleFan,
+  ShapeTriangleStrip,
+  ShapeQuads,
+  ShapeQuadStrip,
+} PShapeKind;
+
+typedef enum PShapePrimitive {
+  PrimitiveBox,
+  PrimitiveSphere,
+} PShapePrimitive;
+
+typedef enum PTextureMode {
+  TextureImage,
+  TextureNormal,
+} PTextureMode;
+
+typedef enum PTextureWrap {
+  TextureClamp,
+  TextureRepeat,
+} PTextureWrap;
+
+typedef void (*PEventCallback)(void);
+
+typedef struct PImage {
+  uint32_t id;
+} PImage;
+
+typedef struct PShape {
+  uint32_t id;
+} PShape;
+
+typedef struct PVector {
+  float x;
+  float y;
+  float z;
+} PVector;
+
+typedef struct PFont {
+  uint32_t id;
+} PFont;
+
+typedef struct PRegion {
+  int32_t x;
+  int32_t y;
+  int32_t width;
+  int32_t height;
+} PRegion;
+
+typedef struct PImage PGraphics;
+
+typedef struct PPixels {
+  uint32_t *data;
+  uintptr_t length;
+} PPixels;
+
+typedef struct PShader {
+  uint32_t id;
+} PShader;
+#define PShader_INVALID (PShader){ .id = UINT32_MAX }
+
+typedef void (*PShaderErrorCallback)(const char*);
+
+
+
+void p_init(PEventCallback setup, PEventCallback draw);
+
+void p_on(enum PEvent event, PEventCallback callback);
+
+void p_run(void);
+
 void createWindow(float width, float height);
 
 float width(void);
 
 float height(void);
 
+/**
+ * Sets the title of the window
+ *
+ * # Safety
+ * `title` must be null or point to a null terminated UTF-8 string
+ */
+void windowTitle(const char *title);
+
+void windowResizable(bool resizable);
+
+void windowMove(int32_t x, int32_t y);
+
+void fullScreen(uint32_t display);
+
+void pixelDensity(uint32_t density);
+
+void smooth(uint32_t level);
+
+void noSmooth(void);
+
+void canvasFormat(enum PCanvasFormat format);
+
+float displayDensity(void);
+
+uint32_t pixelWidth(void);
+
+uint32_t pixelHeight(void);
+
+void noCursor(void);
+
+void cursor(enum PCursor cursor);
+
+uint32_t p_version(void);
+
+void createWindowWithRenderer(float width, float height, enum PRenderer renderer);
+
+void camera(float eye_x,
+            float eye_y,
+            float eye_z,
+            float center_x,
+            float center_y,
+            float center_z,
+            float up_x,
+            float up_y,
+            float up_z);
+
+void resetCamera(void);
+
+void perspective(float fov, float aspect, float near, float far);
+
+void resetPerspective(void);
+
+void ortho(float left, float right, float bottom, float top, float near, float far);
+
+void resetOrtho(void);
+
+void frustum(float left, float right, float bottom, float top, float near, float far);
+
+void fill(float r, float g, float b, float a);
+
+void noFill(void);
+
+void stroke(float r, float g, float b, float a);
+
+void noStroke(void);
+
+void blendMode(enum PBlendMode mode);
+
+void background(float r, float g, float b, float a);
+
 float mouseX(void);
 
 float mouseY(void);
 
 enum PMouseButton mouseButton(void);
+
+uint32_t keyCode(void);
+
+uint32_t key(void);
+
+void strokeWeight(float weight);
+
+void beginShape(enum PShapeKind kind);
+
+void vertex(float x, float y);
+
+void normal(float nx, float ny, float nz);
+
+void vertexUV(float x, float y, float u, float v);
+
+void vertex3DUV(float x, float y, float z, float u, float v);
+
+void texture(struct PImage image);
+
+void textureMode(enum PTextureMode mode);
+
+void textureWrap(enum PTextureWrap wrap);
+
+void vertex3D(float x, float y, float z);
+
+void endShape(enum PShapeClose close);
+
+void beginContour(void);
+
+void endContour(void);
+
+void bezier(float x1, float y1, float x2, float y2, float x3, float y3, float x4, float y4);
+
+void curve(float x1, float y1, float x2, float y2, float x3, float y3, float x4, float y4);
+
+void bezierVertex(float cx1, float cy1, float cx2, float cy2, float x, float y);
+
+void quadraticVertex(float cx, float cy, float x, float y);
+
+void curveVertex(float x, float y);
+
+float bezierPoint(float a, float b, float c, float d, float t);
+
+float bezierTangent(float a, float b, float c, float d, float t);
+
+float curvePoint(float a, float b, float c, float d, float t);
+
+float curveTangent(float a, float b, float c, float d, float t);
+
+void curveTightness(float tightness);
+
+void bezierDetail(uint32_t detail);
+
+void curveDetail(uint32_t detail);
+
+/**
+ * Loads an SVG or OBJ file as a group of shapes. OBJ files only load in 3D
+ *
+ * # Safety
+ * `path` must be null or point to a null terminated UTF-8 string
+ */
+struct PShape loadShape(const char *path);
+
+struct PShape createShape(void);
+
+struct PShape createShapeGroup(void);
+
+/**
+ * Makes a box or a sphere from `count` parameters, see PShapePrimitive
+ *
+ * # Safety
+ * `params` must point to at least `count` floats
+ */
+struct PShape createShapePrimitive(enum PShapePrimitive kind, const float *params, uintptr_t count);
+
+void PShape_addChild(struct PShape group, struct PShape child);
+
+uint32_t PShape_getChildCount(struct PShape shape);
+
+struct PShape PShape_getChild(struct PShape shape, uint32_t index);
+
+/**
+ * Finds a child of the shape, or a child of one of its children, by name
+ *
+ * # Safety
+ * `name` must be null or point to a null terminated UTF-8 string
+ */
+struct PShape PShape_getChildByName(struct PShape shape, const char *name);
+
+void PShape_beginShape(struct PShape shape, enum PShapeKind kind);
+
+void PShape_vertex(struct PShape shape, float x, float y);
+
+void PShape_vertex3D(struct PShape shape, float x, float y, float z);
+
+void PShape_endShape(struct PShape shape, enum PShapeClose close);
+
+void PShape_setFill(struct PShape shape, float r, float g, float b, float a);
+
+void PShape_noFill(struct PShape shape);
+
+void PShape_setStroke(struct PShape shape, float r, float g, float b, float a);
+
+void PShape_noStroke(struct PShape shape);
+
+void PShape_setStrokeWeight(struct PShape shape, float weight);
+
+void PShape_translate(struct PShape shape, float x, float y);
+
+void PShape_translate3D(struct PShape shape, float x, float y, float z);
+
+void PShape_rotate(struct PShape shape, float angle);
+
+void PShape_rotateX(struct PShape shape, float angle);
+
+void PShape_rotateY(struct PShape shape, float angle);
+
+void PShape_rotateZ(struct PShape shape, float angle);
+
+void PShape_scale(struct PShape shape, float x, float y);
+
+void PShape_scale3D(struct PShape shape, float x, float y, float z);
+
+void PShape_resetMatrix(struct PShape shape);
+
+uint32_t PShape_getVertexCount(struct PShape shape);
+
+struct PVector PShape_getVertex(struct PShape shape, uint32_t index);
+
+void PShape_setVertex(struct PShape shape, uint32_t index, float x, float y, float z);
+
+void shape(struct PShape shape, float x, float y);
+
+void box(float width, float height, float depth);
+
+void sphere(float radius);
+
+void sphereDetail(uint32_t resolution);
+
+void sphereDetailUV(uint32_t around, uint32_t down);
+
+void translate(float x, float y);
+
+void translate3D(float x, float y, float z);
+
+void rotate(float angle);
+
+void rotateX(float angle);
+
+void rotateY(float angle);
+
+void rotateZ(float angle);
+
+void scale(float x, float y);
+
+void scale3D(float x, float y, float z);
+
+void pushMatrix(void);
+
+void popMatrix(void);
+
+void resetMatrix(void);
+
+void lights(void);
+
+void noLights(void);
+
+void ambientLight(float r, float g, float b);
+
+void directionalLight(float r, float g, float b, float nx, float ny, float nz);
+
+void pointLight(float r, float g, float b, float x, float y, float z);
+
+void spotLight(float r,
+               float g,
+               float b,
+               float x,
+               float y,
+               float z,
+               float nx,
+               float ny,
+               float nz,
+               float angle,
+               float concentration);
+
+void lightFalloff(float constant, float linear, float quadratic);
+
+void lightSpecular(float r, float g, float b);
+
+void ambient(float r, float g, float b);
+
+void specular(float r, float g, float b);
+
+void emissive(float r, float g, float b);
+
+void shininess(float shine);
+
+/**
+ * Loads a TTF or OTF font file
+ *
+ * # Safety
+ * `path` must be null or point to a null terminated UTF-8 string
+ */
+struct PFont loadFont(const char *path);
+
+/**
+ * Loads a font by file or system font name, to be drawn at size
+ *
+ * # Safety
+ * `name` must be null or point to a null terminated UTF-8 string
+ */
+struct PFont createFont(const char *name, float size);
+
+void textFont(struct PFont font);
+
+/**
+ * Draws text with the fill color. Lines are split on '\n'
+ *
+ * # Safety
+ * `string` must be null or point to a null terminated UTF-8 string
+ */
+void text(const char *string, float x, float y);
+
+/**
+ * Draws text wrapped inside the box at x, y
+ *
+ * # Safety
+ * `string` must be null or point to a null terminated UTF-8 string
+ */
+void textBox(const char *string, float x, float y, float width, float height);
+
+void textSize(float size);
+
+void textAlign(enum PAlignX align_x, enum PAlignY align_y);
+
+void textLeading(float leading);
+
+/**
+ * Width of the widest line of the string with the current font and size
+ *
+ * # Safety
+ * `string` must be null or point to a null terminated UTF-8 string
+ */
+float textWidth(const char *string);
+
+float textAscent(void);
+
+float textDescent(void);
+
+void image(struct PImage image, float x, float y);
+
+void imageRect(struct PImage image, float x, float y, float width, float height);
+
+uint32_t imageWidth(struct PImage image);
+
+uint32_t imageHeight(struct PImage image);
+
+void freeImage(struct PImage image);
+
+struct PImage createImage(uint32_t width, uint32_t height, enum PImageFormat format);
+
+/**
+ * Loads a PNG or JPEG image
+ *
+ * # Safety
+ * `path` must be null or point to a null terminated UTF-8 string
+ */
+struct PImage loadImage(const char *path);
+
+void imageResize(struct PImage image, uint32_t width, uint32_t height);
+
+void imageCopy(struct PImage source,
+               struct PRegion source_region,
+               struct PImage destination,
+               struct PRegion destination_region);
+
+void imageBlend(struct PImage source,
+                struct PRegion source_region,
+                struct PImage destination,
+                struct PRegion destination_region,
+                enum PBlendMode mode);
+
+void imageMask(struct PImage image, struct PImage mask);
+
+uint32_t imageGet(struct PImage image, int32_t x, int32_t y);
+
+void imageSet(struct PImage image, int32_t x, int32_t y, uint32_t color);
+
+void filter(enum PFilter filter);
+
+void filterParam(enum PFilter filter, float param);
+
+void imageFilter(struct PImage image, enum PFilter filter);
+
+void imageFilterParam(struct PImage image, enum PFilter filter, float param);
+
+PGraphics createGraphics(uint32_t width, uint32_t height);
+
+/**
+ * Graphics the size of width x height points, that write what is drawn
+ * into them to the file at path at every endDraw()
+ *
+ * # Safety
+ * `path` must be null or point to a null terminated UTF-8 string
+ */
+PGraphics createGraphicsWithRecorder(uint32_t width,
+                                     uint32_t height,
+                                     enum PRecordFormat format,
+                                     const char *path);
+
+void beginDraw(PGraphics graphics);
+
+void endDraw(PGraphics graphics);
+
+void loadPixels(void);
+
+void updatePixels(void);
+
+struct PPixels pixels(void);
+
+uint32_t get(int32_t x, int32_t y);
+
+struct PImage getRegion(int32_t x, int32_t y, int32_t width, int32_t height);
+
+void set(int32_t x, int32_t y, uint32_t color);
+
+void imageLoadPixels(struct PImage image);
+
+void imageUpdatePixels(struct PImage image);
+
+struct PPixels imagePixels(struct PImage image);
+
+/**
+ * Loads a shader from WGSL or GLSL files. Either path can be null to use
+ * the built-in stage. A shader that can't be loaded gives PShader_INVALID,
+ * the error goes to stderr and to the callback set with onShaderError()
+ *
+ * # Safety
+ * `fragment` and `vertex` must be null or point to null terminated UTF-8 strings
+ */
+struct PShader loadShader(const char *fragment, const char *vertex);
+
+void shader(struct PShader shader);
+
+void resetShader(void);
+
+/**
+ * Sets the uniform block field called `name` to `count` numbers
+ *
+ * # Safety
+ * `name` must point to a null terminated UTF-8 string
+ * and `values` to at least `count` floats
+ */
+void PShader_set(struct PShader shader, const char *name, const float *values, uintptr_t count);
+
+void shaderHotReload(bool enabled);
+
+void onShaderError(PShaderErrorCallback callback);
+
+/**
+ * Starts writing everything that is drawn to a file as well, until endRecord()
+ *
+ * # Safety
+ * `path` must be null or point to a null terminated UTF-8 string
+ */
+void beginRecord(enum PRecordFormat format, const char *path);
+
+void endRecord(void);
+
+void nextPage(void);
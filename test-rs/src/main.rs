@@ -1,24 +1,54 @@
-use processing::{core::{*, window::createWindow}, event::mouse::{mouseX, mouseY}};
+use processing::{error::PResult, sketch::{self, Ctx, Sketch}, shape::state::{PShapeClose, PShapeKind}};
 
-pub extern "C" fn setup () {
-    createWindow(800.0, 800.0);
+#[derive(Default)]
+struct Test {
+    clicks: u32
 }
 
-pub extern "C" fn draw () {
-}
+impl Sketch for Test {
+    fn setup (&mut self, ctx: &mut Ctx) -> PResult<()> {
+        ctx.create_window(800.0, 800.0)
+    }
 
-pub extern "C" fn callback () {
-    // println!("({:?}, {:?})", mouseX(), mouseY());
-}
+    fn draw (&mut self, ctx: &mut Ctx) -> PResult<()> {
+        ctx.background(26.0, 51.0, 77.0, 255.0);
+
+        // A square with a square hole
+        ctx.fill(255.0, 0.0, 0.0, 255.0);
+        ctx.stroke(255.0, 255.0, 255.0, 255.0);
+        ctx.stroke_weight(4.0);
+        ctx.begin_shape(PShapeKind::ShapePolygon)?;
+        ctx.vertex(100.0, 100.0)?;
+        ctx.vertex(300.0, 100.0)?;
+        ctx.vertex(300.0, 300.0)?;
+        ctx.vertex(100.0, 300.0)?;
+        ctx.begin_contour()?;
+        ctx.vertex(150.0, 150.0)?;
+        ctx.vertex(150.0, 250.0)?;
+        ctx.vertex(250.0, 250.0)?;
+        ctx.vertex(250.0, 150.0)?;
+        ctx.end_contour()?;
+        ctx.end_shape(PShapeClose::ShapeClose)?;
 
+        // Per vertex colors follow the mouse
+        ctx.no_stroke();
+        ctx.begin_shape(PShapeKind::ShapeTriangles)?;
+        ctx.fill(255.0, 0.0, 0.0, 255.0);
+        ctx.vertex(400.0, 400.0)?;
+        ctx.fill(0.0, 255.0, 0.0, 255.0);
+        ctx.vertex(600.0, 400.0)?;
+        ctx.fill(0.0, 0.0, 255.0, 255.0);
+        ctx.vertex(ctx.mouse_x(), ctx.mouse_y())?;
+        ctx.end_shape(PShapeClose::ShapeOpen)
+    }
 
-pub extern "C" fn callback2 () {
-    println!("Clicked");
+    fn mouse_pressed (&mut self, _ctx: &mut Ctx) -> PResult<()> {
+        self.clicks += 1;
+        println!("Clicked {} times", self.clicks);
+        Ok(())
+    }
 }
 
 fn main () {
-    p_init(setup, draw);
-    p_on(processing::event::state::PEvent::PMouseMoved, callback);
-    p_on(processing::event::state::PEvent::PMousePressed, callback2);
-    p_run();
+    sketch::run(Test::default());
 }